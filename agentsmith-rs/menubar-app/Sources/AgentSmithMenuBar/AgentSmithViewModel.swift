@@ -394,6 +394,7 @@ final class AgentSmithViewModel: ObservableObject {
     @Published var daemonStateHint: String = "launchd: 状态未知"
     @Published var records: [DenialRecord] = [] // UI 列表使用，只保留最新的 500 条
     @Published var policy: SecurityPolicy = .empty
+    @Published var pendingOverrides: [PendingOverrideRequest] = []
     @Published var lastDenial: String = "暂无最新拦截记录"
     @Published var overrideMessage: String = ""
     @Published var overrideSuccess: Bool = false
@@ -428,6 +429,7 @@ final class AgentSmithViewModel: ObservableObject {
     private var policyLockPath: String { "\(policyPath).lock" }
     private var denialsPath: String { "\(homeDir)/.agentsmith-rs/guard/denials.jsonl" }
     private var lastDenialPath: String { "\(homeDir)/.agentsmith-rs/guard/last_denial.txt" }
+    private var pendingOverridesPath: String { "\(homeDir)/.agentsmith-rs/guard/override-pending.json" }
     private let logPath = "/tmp/agentsmith-rs.log"
     private let daemonServiceTarget = "system/dev.agentsmith-rs"
     private let daemonPlistPath = "/Library/LaunchDaemons/dev.agentsmith-rs.plist"
@@ -435,6 +437,8 @@ final class AgentSmithViewModel: ObservableObject {
     // Services
     private let fileMonitor = FileMonitorService()
     private let logTailer = LogTailService()
+    // Follows rotated denials.jsonl segments; only touched on recordsLoadQueue.
+    private lazy var denialsReader = GuardLogReader(path: denialsPath)
     private let daemonControlQueue = DispatchQueue(
         label: "dev.agentsmith-rs.daemon-control",
         qos: .userInitiated
@@ -474,6 +478,7 @@ final class AgentSmithViewModel: ObservableObject {
         checkGuardRunning()
         loadRecords()
         loadPolicy()
+        loadPendingOverrides()
         loadLastDenial()
         loadLatestCacheMetrics()
     }
@@ -676,6 +681,10 @@ final class AgentSmithViewModel: ObservableObject {
         fileMonitor.watch(path: lastDenialPath) { [weak self] in
             Task { @MainActor in self?.loadLastDenial() }
         }
+
+        fileMonitor.watch(path: pendingOverridesPath) { [weak self] in
+            Task { @MainActor in self?.loadPendingOverrides() }
+        }
         
         logTailer.startTailing(path: logPath, homeDir: homeDir) { [weak self] newLines in
             DispatchQueue.main.async {
@@ -694,12 +703,12 @@ final class AgentSmithViewModel: ObservableObject {
     private func loadRecords() {
         recordsLoadToken += 1
         let token = recordsLoadToken
-        let path = denialsPath
+        let reader = denialsReader
         let homeDir = self.homeDir
         let suppressNoise = self.suppressNoiseRecords
 
         recordsLoadQueue.async {
-            guard let data = try? Data(contentsOf: URL(fileURLWithPath: path)) else {
+            guard let data = reader.readAll() else {
                 DispatchQueue.main.async { [weak self] in
                     guard let self = self else { return }
                     guard token == self.recordsLoadToken else { return }
@@ -768,6 +777,15 @@ final class AgentSmithViewModel: ObservableObject {
         self.policy = policy
     }
     
+    private func loadPendingOverrides() {
+        guard let data = try? Data(contentsOf: URL(fileURLWithPath: pendingOverridesPath)),
+              let pending = try? JSONDecoder().decode([PendingOverrideRequest].self, from: data) else {
+            self.pendingOverrides = []
+            return
+        }
+        self.pendingOverrides = pending
+    }
+
     private func loadLastDenial() {
         if let text = try? String(contentsOfFile: lastDenialPath, encoding: .utf8), !text.isEmpty {
             self.lastDenial = text.trimmingCharacters(in: .whitespacesAndNewlines)
@@ -1107,6 +1125,22 @@ final class AgentSmithViewModel: ObservableObject {
         )
     }
 
+    func approvePendingOverride(_ request: PendingOverrideRequest) {
+        submitOverrideCommand(
+            arguments: ["--approve", request.id],
+            successMessage: "已批准放行：\(URL(fileURLWithPath: request.path ?? "").lastPathComponent)",
+            failurePrefix: "批准失败"
+        )
+    }
+
+    func rejectPendingOverride(_ request: PendingOverrideRequest) {
+        submitOverrideCommand(
+            arguments: ["--reject", request.id],
+            successMessage: "已拒绝放行申请：\(request.id)",
+            failurePrefix: "拒绝失败"
+        )
+    }
+
     private func submitOverrideCommand(
         arguments: [String],
         successMessage: String,
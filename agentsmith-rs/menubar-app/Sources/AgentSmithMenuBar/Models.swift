@@ -127,6 +127,38 @@ struct DenialRecord: Codable, Identifiable {
     }
 }
 
+struct PendingOverrideRequest: Codable, Identifiable, Hashable {
+    let id: String
+    let action: String
+    let path: String?
+    let minutes: Int?
+    let requesterPid: Int
+    let requesterProcess: String
+    let createdAt: Int
+    let deadline: Int
+    let relatedDenial: RelatedDenial?
+
+    struct RelatedDenial: Codable, Hashable {
+        let op: String
+        let path: String
+        let reason: String?
+    }
+
+    var isSensitiveRead: Bool { action == "grant-sensitive-read" }
+
+    var remainingSeconds: Int {
+        max(0, deadline - Int(Date().timeIntervalSince1970))
+    }
+
+    enum CodingKeys: String, CodingKey {
+        case id, action, path, minutes, deadline
+        case requesterPid = "requester_pid"
+        case requesterProcess = "requester_process"
+        case createdAt = "created_at"
+        case relatedDenial = "related_denial"
+    }
+}
+
 struct SecurityPolicy: Codable {
     var protectedZones: [String]
     var temporaryOverrides: [TemporaryOverride]
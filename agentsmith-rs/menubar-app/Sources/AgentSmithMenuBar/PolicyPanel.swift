@@ -71,6 +71,47 @@ struct PolicyPanel: View {
                     }
                 }
                 
+                if !viewModel.pendingOverrides.isEmpty {
+                    Section(header: Text("待审批放行 (\(viewModel.pendingOverrides.count))")) {
+                        ForEach(viewModel.pendingOverrides) { request in
+                            HStack {
+                                VStack(alignment: .leading, spacing: 2) {
+                                    Text(request.path ?? "-")
+                                        .font(.system(.caption, design: .monospaced))
+                                        .foregroundColor(ApplePalette.warning)
+
+                                    TimelineView(.periodic(from: .now, by: 1.0)) { _ in
+                                        Text(pendingStatusText(request))
+                                            .font(.caption2)
+                                            .foregroundColor(.secondary)
+                                    }
+
+                                    if let denial = request.relatedDenial {
+                                        Text("相关拦截: \(denial.op) \(denial.path)")
+                                            .font(.caption2)
+                                            .foregroundColor(.secondary)
+                                    }
+                                }
+                                Spacer()
+                                Button(action: {
+                                    viewModel.approvePendingOverride(request)
+                                }) {
+                                    Image(systemName: "checkmark.circle.fill")
+                                        .foregroundColor(ApplePalette.success)
+                                }
+                                .buttonStyle(PlainButtonStyle())
+                                Button(action: {
+                                    viewModel.rejectPendingOverride(request)
+                                }) {
+                                    Image(systemName: "xmark.circle.fill")
+                                        .foregroundColor(ApplePalette.danger)
+                                }
+                                .buttonStyle(PlainButtonStyle())
+                            }
+                        }
+                    }
+                }
+
                 Section(header: HStack {
                     Text("临时放行路径 (\(viewModel.policy.temporaryOverrides.count))")
                     Spacer()
@@ -420,6 +461,12 @@ struct PolicyPanel: View {
         return "到期时间 \(formatter.string(from: date))"
     }
 
+    private func pendingStatusText(_ request: PendingOverrideRequest) -> String {
+        let kind = request.isSensitiveRead ? "敏感读取" : "放行"
+        let minutes = request.minutes.map { "\($0) 分钟" } ?? "默认时长"
+        return "\(request.requesterProcess) (pid \(request.requesterPid)) 申请\(kind) \(minutes)，剩余 \(request.remainingSeconds) 秒审批"
+    }
+
     private func overrideMetaText(_ override: TemporaryOverride) -> String? {
         let createdBy = override.createdBy?.trimmingCharacters(in: .whitespacesAndNewlines)
         let createdAt = override.createdAt
@@ -0,0 +1,80 @@
+import Foundation
+
+// Rotated segments of a guard log (`denials.jsonl.20261019T120000Z`, or `.gz`
+// once compressed), oldest first. A segment present both plain and gzipped is
+// listed once, as the `.gz`; `.tmp` leftovers are skipped.
+func rotatedLogSegmentNames(_ names: [String], logName: String) -> [String] {
+    let prefix = logName + "."
+    var gzippedByStem: [String: Bool] = [:]
+    for name in names where name.hasPrefix(prefix) && !name.hasSuffix(".tmp") {
+        let stamp = String(name.dropFirst(prefix.count))
+        guard !stamp.isEmpty else { continue }
+        if stamp.hasSuffix(".gz") {
+            gzippedByStem[String(stamp.dropLast(3))] = true
+        } else if gzippedByStem[stamp] == nil {
+            gzippedByStem[stamp] = false
+        }
+    }
+    return gzippedByStem.keys.sorted().map { stem in
+        prefix + stem + (gzippedByStem[stem] == true ? ".gz" : "")
+    }
+}
+
+// Reads a guard log together with its rotated segments. Rotated segments never
+// change, so they are cached by name and only the live file is re-read.
+// Not thread-safe: use from a single serial queue.
+final class GuardLogReader {
+    private let path: String
+    private var segmentCache: [String: Data] = [:]
+
+    init(path: String) {
+        self.path = path
+    }
+
+    func readAll() -> Data? {
+        let url = URL(fileURLWithPath: path)
+        let directory = url.deletingLastPathComponent()
+        let names = (try? FileManager.default.contentsOfDirectory(atPath: directory.path)) ?? []
+        let segments = rotatedLogSegmentNames(names, logName: url.lastPathComponent)
+        segmentCache = segmentCache.filter { segments.contains($0.key) }
+
+        var data = Data()
+        for name in segments {
+            if segmentCache[name] == nil {
+                segmentCache[name] = readSegment(at: directory.appendingPathComponent(name).path)
+            }
+            guard let segment = segmentCache[name] else { continue }
+            data.append(segment)
+            if let last = data.last, last != UInt8(ascii: "\n") {
+                data.append(UInt8(ascii: "\n"))
+            }
+        }
+
+        guard let current = try? Data(contentsOf: url) else {
+            return segments.isEmpty ? nil : data
+        }
+        data.append(current)
+        return data
+    }
+
+    private func readSegment(at path: String) -> Data? {
+        guard path.hasSuffix(".gz") else {
+            return try? Data(contentsOf: URL(fileURLWithPath: path))
+        }
+        let task = Process()
+        task.executableURL = URL(fileURLWithPath: "/usr/bin/gzip")
+        task.arguments = ["-dc", path]
+        let stdoutPipe = Pipe()
+        task.standardOutput = stdoutPipe
+        task.standardError = FileHandle.nullDevice
+        do {
+            try task.run()
+        } catch {
+            return nil
+        }
+        // Drain before waiting so a large segment cannot fill the pipe and stall gzip.
+        let data = stdoutPipe.fileHandleForReading.readDataToEndOfFile()
+        task.waitUntilExit()
+        return task.terminationStatus == 0 ? data : nil
+    }
+}
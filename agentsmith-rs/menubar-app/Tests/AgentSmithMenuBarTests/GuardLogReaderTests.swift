@@ -0,0 +1,33 @@
+import XCTest
+@testable import AgentSmithMenuBar
+
+final class GuardLogReaderTests: XCTestCase {
+    func testRotatedSegmentsAreOrderedOldestFirst() {
+        let names = [
+            "denials.jsonl",
+            "denials.jsonl.20261019T120500Z.gz",
+            "denials.jsonl.20261019T120000Z",
+            "last_denial.txt",
+            "taint-marks.jsonl.20261019T110000Z",
+        ]
+
+        XCTAssertEqual(
+            rotatedLogSegmentNames(names, logName: "denials.jsonl"),
+            ["denials.jsonl.20261019T120000Z", "denials.jsonl.20261019T120500Z.gz"]
+        )
+    }
+
+    func testInterruptedCompressionLeftoversAreSkipped() {
+        let names = [
+            "denials.jsonl.20261019T120000Z",
+            "denials.jsonl.20261019T120000Z.gz",
+            "denials.jsonl.20261019T120500Z.gz.tmp",
+            "denials.jsonl.20261019T120500Z",
+        ]
+
+        XCTAssertEqual(
+            rotatedLogSegmentNames(names, logName: "denials.jsonl"),
+            ["denials.jsonl.20261019T120000Z.gz", "denials.jsonl.20261019T120500Z"]
+        )
+    }
+}
@@ -0,0 +1,1085 @@
+//! `agentsmith-override`: ask the root daemon for a short-lived override.
+//!
+//! Speaks the protocol from `protocol.rs`: the control socket first, where the
+//! daemon takes the requester from the kernel's peer credentials, and the file
+//! queue only when the socket is not there. The file queue validates the
+//! requester by pid, so this process must stay alive (and named
+//! `agentsmith-override`) until the response arrives.
+
+#[path = "../protocol.rs"]
+mod protocol;
+#[path = "../request_queue.rs"]
+mod request_queue;
+
+use protocol::{
+    control_socket_path, normalize_absolute_path, override_request_dir, send_control_request, ControlRequest,
+    ControlResponse, OverrideRequest, OverrideResponse, CONTROL_PROTOCOL_VERSION, MAX_OVERRIDE_BATCH_PATHS,
+    MAX_OVERRIDE_PATH_LEN, MAX_OVERRIDE_USES, OVERRIDE_DEFAULT_MINUTES, OVERRIDE_MAX_MINUTES, OVERRIDE_OPS,
+};
+use request_queue::{make_request_id, submit_request, wait_for_response};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const EXIT_OK: i32 = 0;
+const EXIT_ERROR: i32 = 1;
+const EXIT_INVALID: i32 = 2;
+const EXIT_DENIED: i32 = 3;
+const EXIT_TIMEOUT: i32 = 4;
+const DEFAULT_WAIT_SECS: u64 = 8;
+const POLICY_RELOAD_WAIT_SECS: u64 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Grant,
+    GrantSensitiveRead,
+    Remove,
+    Clear,
+    List,
+    Pending,
+    Approve,
+    Reject,
+}
+
+impl Action {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Grant => "grant",
+            Self::GrantSensitiveRead => "grant-sensitive-read",
+            Self::Remove => "remove",
+            Self::Clear => "clear",
+            Self::List => "list",
+            Self::Pending => "pending",
+            Self::Approve => "approve",
+            Self::Reject => "reject",
+        }
+    }
+
+    fn is_grant(self) -> bool {
+        matches!(self, Self::Grant | Self::GrantSensitiveRead)
+    }
+}
+
+#[derive(Debug)]
+struct Options {
+    action: Action,
+    target: Option<String>,
+    paths: Vec<String>,
+    glob: Option<String>,
+    minutes: Option<u64>,
+    approval_id: Option<String>,
+    scope_pid: Option<i32>,
+    scope_session: Option<String>,
+    ops: Vec<String>,
+    rename_to: Option<String>,
+    max_uses: Option<u32>,
+    pin: bool,
+    json: bool,
+}
+
+fn usage(home: &str) -> String {
+    let mut text = String::from(
+        "Usage:\n\
+         \x20 agentsmith-override [--minutes N] <path>...\n\
+         \x20 agentsmith-override --glob '<dir>/<pattern>' [<path>...]   (granted, approved or rejected together)\n\
+         \x20 agentsmith-override --sensitive-read [--minutes N] <path>\n\
+         \x20 agentsmith-override --session <id> | --scope-pid <pid> [--minutes N] <path>\n\
+         \x20 agentsmith-override --op unlink|rename|truncate [--rename-to <dest>] [--once | --uses N] <path>\n\
+         \x20 agentsmith-override --pin <file>   (only while the file keeps its current content)\n\
+         \x20 agentsmith-override --remove <path>\n\
+         \x20 agentsmith-override --clear\n\
+         \x20 agentsmith-override --list\n\
+         \x20 agentsmith-override --pending\n\
+         \x20 agentsmith-override --approve <id> | --reject <id>\n\
+         \x20 (add --json for machine-readable output)\n\
+         \n\
+         Exit codes: 0 ok, 2 invalid request, 3 denied by the daemon, 4 no response.\n\
+         Tip: safer first step is agentsmith-quarantine <path> (move to ./temp).\n",
+    );
+    let feedback = Path::new(home).join(".agentsmith-rs/guard/last_denial.txt");
+    if let Ok(last) = fs::read_to_string(feedback) {
+        text.push_str("\nLast denial:\n");
+        text.push_str(&last);
+    }
+    text
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut action = Action::Grant;
+    let mut target = None;
+    let mut paths = Vec::new();
+    let mut glob = None;
+    let mut minutes = None;
+    let mut approval_id = None;
+    let mut scope_pid = None;
+    let mut scope_session = None;
+    let mut ops = Vec::new();
+    let mut rename_to = None;
+    let mut max_uses = None;
+    let mut pin = false;
+    let mut json = false;
+    let mut index = 0;
+    while let Some(arg) = args.get(index) {
+        match arg.as_str() {
+            "--minutes" | "-m" => {
+                let value = args.get(index + 1).ok_or("--minutes requires a value")?;
+                minutes = Some(
+                    value
+                        .parse::<u64>()
+                        .map_err(|_| "minutes must be a non-negative integer".to_string())?,
+                );
+                index += 1;
+            },
+            "--no-expire" => minutes = Some(0),
+            "--scope-pid" => {
+                let value = args.get(index + 1).ok_or("--scope-pid requires a pid")?;
+                scope_pid = Some(
+                    value
+                        .parse::<i32>()
+                        .map_err(|_| "scope pid must be an integer".to_string())?,
+                );
+                index += 1;
+            },
+            "--session" => {
+                let value = args.get(index + 1).ok_or("--session requires a session id")?;
+                scope_session = Some(value.clone());
+                index += 1;
+            },
+            "--op" => {
+                let value = args.get(index + 1).ok_or("--op requires an operation")?;
+                if !OVERRIDE_OPS.contains(&value.as_str()) {
+                    return Err(format!("--op must be one of {}", OVERRIDE_OPS.join(", ")));
+                }
+                ops.push(value.clone());
+                index += 1;
+            },
+            "--rename-to" => {
+                let value = args.get(index + 1).ok_or("--rename-to requires a destination")?;
+                rename_to = Some(value.clone());
+                index += 1;
+            },
+            "--uses" => {
+                let value = args.get(index + 1).ok_or("--uses requires a count")?;
+                max_uses = Some(
+                    value
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|uses| (1..=MAX_OVERRIDE_USES).contains(uses))
+                        .ok_or(format!("uses must be between 1 and {}", MAX_OVERRIDE_USES))?,
+                );
+                index += 1;
+            },
+            "--once" => max_uses = Some(1),
+            "--pin" => pin = true,
+            "--glob" => {
+                let value = args.get(index + 1).ok_or("--glob requires a pattern")?;
+                glob = Some(value.clone());
+                index += 1;
+            },
+            "--sensitive-read" => action = Action::GrantSensitiveRead,
+            "--remove" => {
+                let value = args.get(index + 1).ok_or("--remove requires a path")?;
+                action = Action::Remove;
+                target = Some(value.clone());
+                index += 1;
+            },
+            "--clear" => action = Action::Clear,
+            "--list" => action = Action::List,
+            "--pending" => action = Action::Pending,
+            "--approve" | "--reject" => {
+                let value = args.get(index + 1).ok_or(format!("{} requires a request id", arg))?;
+                action = if arg == "--approve" {
+                    Action::Approve
+                } else {
+                    Action::Reject
+                };
+                approval_id = Some(value.clone());
+                index += 1;
+            },
+            "--json" => json = true,
+            other if other.starts_with('-') && other != "-" => return Err(format!("unknown option: {}", other)),
+            other if target.is_some() => paths.push(other.to_string()),
+            other => target = Some(other.to_string()),
+        }
+        index += 1;
+    }
+
+    match action {
+        Action::Clear | Action::List | Action::Pending | Action::Approve | Action::Reject if target.is_some() => {
+            return Err(format!("--{} does not take a target path", action.as_str()));
+        },
+        Action::Grant | Action::GrantSensitiveRead if target.is_none() && glob.is_none() => {
+            return Err("missing target path".to_string());
+        },
+        Action::Remove if target.is_none() => {
+            return Err("missing target path".to_string());
+        },
+        _ => {},
+    }
+    if !action.is_grant() && (!paths.is_empty() || glob.is_some()) {
+        return Err("several paths and --glob are only valid for grant/sensitive-read mode".to_string());
+    }
+    if paths.len() >= MAX_OVERRIDE_BATCH_PATHS {
+        return Err(format!(
+            "too many target paths (max {})",
+            MAX_OVERRIDE_BATCH_PATHS
+        ));
+    }
+    if rename_to.is_some() && (!paths.is_empty() || glob.is_some()) {
+        return Err("--rename-to needs a single target path".to_string());
+    }
+    if !action.is_grant() && minutes.is_some() {
+        return Err("--minutes/--no-expire is only valid for grant/sensitive-read mode".to_string());
+    }
+    if action != Action::Grant && (scope_pid.is_some() || scope_session.is_some()) {
+        return Err("--session/--scope-pid is only valid for grant mode".to_string());
+    }
+    if scope_pid.is_some() && scope_session.is_some() {
+        return Err("--session and --scope-pid are mutually exclusive".to_string());
+    }
+    if action != Action::Grant && (!ops.is_empty() || rename_to.is_some() || max_uses.is_some() || pin) {
+        return Err("--op/--rename-to/--once/--uses/--pin are only valid for grant mode".to_string());
+    }
+    if rename_to.is_some() && !ops.is_empty() && !ops.iter().any(|op| op == "rename") {
+        return Err("--rename-to requires --op rename".to_string());
+    }
+    Ok(Options {
+        action,
+        target,
+        paths,
+        glob,
+        minutes,
+        approval_id,
+        scope_pid,
+        scope_session,
+        ops,
+        rename_to,
+        max_uses,
+        pin,
+        json,
+    })
+}
+
+fn env_u64(name: &str, default: u64) -> Result<u64, String> {
+    match std::env::var(name) {
+        Ok(value) => value
+            .parse::<u64>()
+            .map_err(|_| format!("{} must be a non-negative integer", name)),
+        Err(_) => Ok(default),
+    }
+}
+
+fn grant_minutes(requested: Option<u64>) -> Result<u64, String> {
+    let minutes = match requested {
+        Some(minutes) => minutes,
+        None => env_u64("AGENTSMITH_OVERRIDE_MINUTES", OVERRIDE_DEFAULT_MINUTES)?,
+    };
+    let max_minutes = env_u64("AGENTSMITH_OVERRIDE_MAX_MINUTES", OVERRIDE_MAX_MINUTES)?.min(OVERRIDE_MAX_MINUTES);
+    if minutes == 0 {
+        return Err(format!(
+            "no-expire override is disabled; please use a short TTL (1-{} minutes)",
+            max_minutes
+        ));
+    }
+    if minutes > max_minutes {
+        return Err(format!("minutes exceeds max allowed ({})", max_minutes));
+    }
+    Ok(minutes)
+}
+
+/// Absolute, lexically normalized the same way the daemon does, with the
+/// parent directory resolved (`/tmp` -> `/private/tmp`) so the override matches
+/// the path Endpoint Security reports. The last component is kept as-is, so
+/// overriding a symlink targets the link rather than what it points to.
+fn resolve_target(raw: &str, cwd: &Path) -> Result<String, String> {
+    let absolute = if raw.starts_with('/') {
+        raw.to_string()
+    } else {
+        format!("{}/{}", cwd.to_string_lossy(), raw)
+    };
+    let normalized = normalize_absolute_path(&absolute).ok_or("target must resolve to an absolute path")?;
+    let path = Path::new(&normalized);
+    let resolved = match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => match fs::canonicalize(parent) {
+            Ok(parent) => normalize_absolute_path(&parent.join(name).to_string_lossy()).unwrap_or(normalized),
+            Err(_) => normalized,
+        },
+        _ => normalized,
+    };
+    if resolved == "/" {
+        return Err("refusing to override root path '/'".to_string());
+    }
+    if resolved.len() > MAX_OVERRIDE_PATH_LEN {
+        return Err(format!(
+            "target path too long (max {} chars)",
+            MAX_OVERRIDE_PATH_LEN
+        ));
+    }
+    Ok(resolved)
+}
+
+fn now_ts() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Answer to `id` from the file queue; a response carrying another id is an
+/// error, not a reply.
+fn wait_for_override_response(request_dir: &Path, id: &str, timeout: Duration) -> Option<OverrideResponse> {
+    let response: OverrideResponse = wait_for_response(request_dir, id, timeout)?;
+    if response.id == id {
+        return Some(response);
+    }
+    Some(OverrideResponse {
+        id: id.to_string(),
+        status: "error".to_string(),
+        message: "response id mismatch".to_string(),
+        expires_at: None,
+        explanation: Vec::new(),
+        paths: Vec::new(),
+    })
+}
+
+/// Submit to the file queue and wait for the answer. A grant parked for
+/// approval keeps this process alive (the daemon checks its pid) until a human
+/// decides or the deadline passes.
+fn request_over_file_queue(
+    request_dir: &Path,
+    request: &OverrideRequest,
+    wait_secs: u64,
+) -> Result<OverrideResponse, i32> {
+    let request_path = match submit_request(request_dir, &request.id, request) {
+        Ok(path) => path,
+        Err(err) => {
+            eprintln!(
+                "Error: cannot write request in {}: {}",
+                request_dir.display(),
+                err
+            );
+            return Err(EXIT_ERROR);
+        },
+    };
+    let Some(mut response) = wait_for_override_response(request_dir, &request.id, Duration::from_secs(wait_secs))
+    else {
+        let _ = fs::remove_file(&request_path);
+        eprintln!("Error: override helper timeout. Ensure agentsmith-rs daemon is running.");
+        return Err(EXIT_TIMEOUT);
+    };
+    while response.status == "pending" {
+        eprintln!("Pending: {}", response.message);
+        let deadline = response.expires_at.unwrap_or_default();
+        let wait = deadline.saturating_sub(now_ts()) + DEFAULT_WAIT_SECS;
+        let Some(next) = wait_for_override_response(request_dir, &request.id, Duration::from_secs(wait)) else {
+            eprintln!(
+                "Error: no decision on override request {} before its deadline.",
+                request.id
+            );
+            return Err(EXIT_TIMEOUT);
+        };
+        response = next;
+    }
+    Ok(response)
+}
+
+/// Nobody is listening on the control socket (daemon down, or one that could
+/// not bind it): the request was never sent, so the file queue may take it.
+fn control_socket_unavailable(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused
+    )
+}
+
+fn control_request_for(request: &OverrideRequest) -> ControlRequest {
+    ControlRequest {
+        version: CONTROL_PROTOCOL_VERSION,
+        id: Some(request.id.clone()),
+        command: format!("override.{}", request.action),
+        path: request.path.clone(),
+        minutes: request.minutes,
+        approval_id: request.approval_id.clone(),
+        scope_pid: request.scope_pid,
+        scope_session: request.scope_session.clone(),
+        ops: request.ops.clone(),
+        rename_to: request.rename_to.clone(),
+        max_uses: request.max_uses,
+        pin: request.pin,
+        paths: request.paths.clone(),
+        glob: request.glob.clone(),
+    }
+}
+
+/// The socket carries a grant's expanded targets under `data.paths`.
+fn override_response_from_control(response: ControlResponse) -> OverrideResponse {
+    let paths = response
+        .data
+        .as_ref()
+        .and_then(|data| data.get("paths"))
+        .and_then(|paths| paths.as_array())
+        .map(|paths| {
+            paths
+                .iter()
+                .filter_map(|path| path.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+    OverrideResponse {
+        id: response.id,
+        status: response.status,
+        message: response.message,
+        expires_at: response.expires_at,
+        explanation: response.explanation,
+        paths,
+    }
+}
+
+fn request_over_socket(socket_path: &Path, request: &OverrideRequest) -> io::Result<OverrideResponse> {
+    send_control_request(socket_path, &control_request_for(request), |pending| {
+        eprintln!("Pending: {}", pending.message);
+    })
+    .map(override_response_from_control)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct ActiveOverride {
+    path: String,
+    expires_at: Option<u64>,
+    created_by: Option<String>,
+}
+
+/// Overrides from the daemon's mirror in `policy.json`, skipping expired ones.
+fn active_overrides(policy: &serde_json::Value, now: u64) -> Vec<ActiveOverride> {
+    policy
+        .get("temporary_overrides")
+        .and_then(|value| value.as_array())
+        .map(|entries| {
+            entries
+                .iter()
+                .filter_map(|entry| match entry {
+                    serde_json::Value::String(path) => Some(ActiveOverride {
+                        path: path.clone(),
+                        expires_at: None,
+                        created_by: None,
+                    }),
+                    serde_json::Value::Object(rule) => Some(ActiveOverride {
+                        path: rule.get("path")?.as_str()?.to_string(),
+                        expires_at: rule.get("expires_at").and_then(|value| value.as_u64()),
+                        created_by: rule
+                            .get("created_by")
+                            .and_then(|value| value.as_str())
+                            .map(str::to_string),
+                    }),
+                    _ => None,
+                })
+                .filter(|entry| entry.expires_at.is_none_or(|exp| exp > now))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn load_policy_json(home: &str) -> serde_json::Value {
+    let path = Path::new(home).join(".agentsmith-rs/policy.json");
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or(serde_json::Value::Null)
+}
+
+fn format_remaining(secs: u64) -> String {
+    if secs >= 60 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+fn print_list(home: &str, json: bool) -> i32 {
+    let now = now_ts();
+    let overrides = active_overrides(&load_policy_json(home), now);
+    if json {
+        let entries: Vec<serde_json::Value> = overrides
+            .iter()
+            .map(|entry| {
+                serde_json::json!({
+                    "path": entry.path,
+                    "expires_at": entry.expires_at,
+                    "remaining_secs": entry.expires_at.map(|exp| exp.saturating_sub(now)),
+                    "created_by": entry.created_by,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::Value::Array(entries));
+        return EXIT_OK;
+    }
+    if overrides.is_empty() {
+        println!("No active overrides");
+    }
+    for entry in overrides {
+        let remaining = match entry.expires_at {
+            Some(exp) => format!("expires in {}", format_remaining(exp.saturating_sub(now))),
+            None => "no expiry".to_string(),
+        };
+        println!("{}  ({})", entry.path, remaining);
+    }
+    EXIT_OK
+}
+
+/// Grants waiting for a human, from the daemon's `override-pending.json`.
+fn print_pending(home: &str, json: bool) -> i32 {
+    let path = Path::new(home).join(".agentsmith-rs/guard/override-pending.json");
+    let pending = fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+        .unwrap_or_else(|| serde_json::json!([]));
+    if json {
+        println!("{}", pending);
+        return EXIT_OK;
+    }
+    let now = now_ts();
+    let entries = pending.as_array().cloned().unwrap_or_default();
+    if entries.is_empty() {
+        println!("No pending override requests");
+    }
+    for entry in entries {
+        println!(
+            "{}  {} {} for {} minute(s) by {} (pid {}), decide within {}",
+            entry["id"].as_str().unwrap_or_default(),
+            entry["action"].as_str().unwrap_or_default(),
+            entry["path"].as_str().unwrap_or_default(),
+            entry["minutes"].as_u64().unwrap_or_default(),
+            entry["requester_process"].as_str().unwrap_or_default(),
+            entry["requester_pid"].as_i64().unwrap_or_default(),
+            format_remaining(entry["deadline"].as_u64().unwrap_or_default().saturating_sub(now)),
+        );
+        for path in entry["paths"].as_array().into_iter().flatten() {
+            println!("    + {}", path.as_str().unwrap_or_default());
+        }
+        if let Some(denial) = entry.get("related_denial").filter(|denial| !denial.is_null()) {
+            println!(
+                "    last denial: {} {} ({})",
+                denial["op"].as_str().unwrap_or_default(),
+                denial["path"].as_str().unwrap_or_default(),
+                denial["reason"].as_str().unwrap_or_default(),
+            );
+        }
+    }
+    EXIT_OK
+}
+
+/// The daemon answers before it mirrors the new override into `policy.json`;
+/// wait for the mirror so "Ready" means the retry will be allowed.
+fn wait_for_policy_mirror(home: &str, path: &str) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(POLICY_RELOAD_WAIT_SECS);
+    while Instant::now() < deadline {
+        if active_overrides(&load_policy_json(home), now_ts())
+            .iter()
+            .any(|entry| entry.path == path)
+        {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    false
+}
+
+fn run(args: &[String]) -> i32 {
+    let home = std::env::var("HOME").unwrap_or_else(|_| "/root".into());
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        print!("{}", usage(&home));
+        return EXIT_OK;
+    }
+    let options = match parse_args(args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("Error: {}\n\n{}", err, usage(&home));
+            return EXIT_INVALID;
+        },
+    };
+    if options.action == Action::List {
+        return print_list(&home, options.json);
+    }
+    if options.action == Action::Pending {
+        return print_pending(&home, options.json);
+    }
+
+    let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/"));
+    let target = match options
+        .target
+        .as_deref()
+        .map(|raw| resolve_target(raw, &cwd))
+        .transpose()
+    {
+        Ok(target) => target,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            return EXIT_INVALID;
+        },
+    };
+    let paths = match options
+        .paths
+        .iter()
+        .map(|raw| resolve_target(raw, &cwd))
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(paths) => paths,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            return EXIT_INVALID;
+        },
+    };
+    // Wildcards stay in the last component, so only the directory is resolved.
+    let glob = match options.glob.as_deref().map(|raw| resolve_target(raw, &cwd)).transpose() {
+        Ok(glob) => glob,
+        Err(err) => {
+            eprintln!("Error: --glob: {}", err);
+            return EXIT_INVALID;
+        },
+    };
+    let rename_to = match options
+        .rename_to
+        .as_deref()
+        .map(|raw| resolve_target(raw, &cwd))
+        .transpose()
+    {
+        Ok(rename_to) => rename_to,
+        Err(err) => {
+            eprintln!("Error: --rename-to: {}", err);
+            return EXIT_INVALID;
+        },
+    };
+    let minutes = if options.action.is_grant() {
+        match grant_minutes(options.minutes) {
+            Ok(minutes) => Some(minutes),
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                return EXIT_INVALID;
+            },
+        }
+    } else {
+        None
+    };
+    let wait_secs = match env_u64("AGENTSMITH_OVERRIDE_WAIT_SECS", DEFAULT_WAIT_SECS) {
+        Ok(secs) => secs,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            return EXIT_INVALID;
+        },
+    };
+
+    let request = OverrideRequest {
+        id: make_request_id(),
+        action: options.action.as_str().to_string(),
+        path: target.clone(),
+        minutes,
+        requester_pid: Some(std::process::id() as i32),
+        approval_id: options.approval_id.clone(),
+        scope_pid: options.scope_pid,
+        scope_session: options.scope_session.clone(),
+        ops: options.ops.clone(),
+        rename_to: rename_to.clone(),
+        max_uses: options.max_uses,
+        pin: options.pin,
+        paths,
+        glob,
+    };
+    let socket_path = control_socket_path();
+    let response = match request_over_socket(&socket_path, &request) {
+        Ok(response) => response,
+        Err(err) if control_socket_unavailable(&err) => {
+            match request_over_file_queue(&override_request_dir(&home), &request, wait_secs) {
+                Ok(response) => response,
+                Err(code) => return code,
+            }
+        },
+        Err(err)
+            if matches!(
+                err.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ) =>
+        {
+            eprintln!(
+                "Error: no answer on {} for override request {}.",
+                socket_path.display(),
+                request.id
+            );
+            return EXIT_TIMEOUT;
+        },
+        Err(err) => {
+            eprintln!("Error: control socket {}: {}", socket_path.display(), err);
+            return EXIT_ERROR;
+        },
+    };
+
+    let ok = response.status == "ok";
+    if ok && options.action.is_grant() {
+        if let Some(target) = response.paths.last().or(target.as_ref()) {
+            if !wait_for_policy_mirror(&home, target) && !options.json {
+                eprintln!("Warning: override granted but not yet visible in policy.json");
+            }
+        }
+    }
+    if options.json {
+        println!("{}", serde_json::to_string(&response).unwrap_or_default());
+    } else if !ok {
+        eprintln!("Error: {}", response.message);
+        for line in &response.explanation {
+            eprintln!("  - {}", line);
+        }
+    } else {
+        let target = target.unwrap_or_default();
+        match options.action {
+            Action::Grant | Action::GrantSensitiveRead => {
+                let kind = if options.action == Action::Grant {
+                    "Override"
+                } else {
+                    "Sensitive-read override"
+                };
+                if response.paths.is_empty() {
+                    println!(
+                        "{} granted for {} minute(s): {}",
+                        kind,
+                        minutes.unwrap_or_default(),
+                        target
+                    );
+                } else {
+                    println!(
+                        "{} granted for {} minute(s) on {} path(s):",
+                        kind,
+                        minutes.unwrap_or_default(),
+                        response.paths.len()
+                    );
+                    for path in &response.paths {
+                        println!("  {}", path);
+                    }
+                }
+                if let Some(expires_at) = response.expires_at {
+                    println!("Expires at: {}", expires_at);
+                }
+                if let Some(session) = &options.scope_session {
+                    println!("Only for session: {}", session);
+                } else if let Some(pid) = options.scope_pid {
+                    println!("Only for pid {} and its descendants", pid);
+                }
+                if !options.ops.is_empty() || rename_to.is_some() {
+                    let ops = if options.ops.is_empty() {
+                        "rename".to_string()
+                    } else {
+                        options.ops.join(", ")
+                    };
+                    match &rename_to {
+                        Some(dest) => println!("Only for: {} (rename to {})", ops, dest),
+                        None => println!("Only for: {}", ops),
+                    }
+                }
+                if let Some(uses) = options.max_uses {
+                    println!("Removed after {} allowed operation(s)", uses);
+                }
+                if options.pin {
+                    println!("Pinned: only while the file keeps its current content");
+                }
+                for line in &response.explanation {
+                    println!("Policy: {}", line);
+                }
+                println!("Ready. You can now retry the operation.");
+            },
+            Action::Remove => println!("Override removed (if present): {}", target),
+            Action::Clear => println!("All overrides cleared"),
+            Action::Approve | Action::Reject => println!("{}", response.message),
+            Action::List | Action::Pending => {},
+        }
+    }
+    exit_code_for_status(&response.status)
+}
+
+/// `invalid` means the daemon could not take the request as written; any
+/// other refusal is a denial.
+fn exit_code_for_status(status: &str) -> i32 {
+    match status {
+        "ok" => EXIT_OK,
+        "invalid" => EXIT_INVALID,
+        _ => EXIT_DENIED,
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    std::process::exit(run(&args));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_args_matches_shell_helper_interface() {
+        let options = parse_args(&args(&["--minutes", "5", "build"])).expect("grant");
+        assert_eq!(options.action, Action::Grant);
+        assert_eq!(options.minutes, Some(5));
+        assert_eq!(options.target.as_deref(), Some("build"));
+
+        let options = parse_args(&args(&["--remove", "/tmp/x", "--json"])).expect("remove");
+        assert_eq!(options.action, Action::Remove);
+        assert!(options.json);
+
+        assert!(parse_args(&args(&["--clear", "/tmp/x"])).is_err());
+        let options = parse_args(&args(&["a", "b", "c"])).expect("multi-path grant");
+        assert_eq!(options.target.as_deref(), Some("a"));
+        assert_eq!(options.paths, vec!["b".to_string(), "c".to_string()]);
+        assert!(parse_args(&args(&["--glob", "gen/*.pb.go"]))
+            .expect("glob grant")
+            .glob
+            .is_some());
+        assert!(parse_args(&args(&["--remove", "a", "b"])).is_err());
+        assert!(parse_args(&args(&["--rename-to", "/tmp/x", "a", "b"])).is_err());
+        assert!(parse_args(&args(&["--remove", "/tmp/x", "--minutes", "3"])).is_err());
+        assert!(parse_args(&args(&[])).is_err());
+        assert!(parse_args(&args(&["--bogus"])).is_err());
+        assert_eq!(
+            parse_args(&args(&["--list"])).expect("list").action,
+            Action::List
+        );
+        let options = parse_args(&args(&["--approve", "1700000000-42-abcd"])).expect("approve");
+        assert_eq!(options.action, Action::Approve);
+        assert_eq!(options.approval_id.as_deref(), Some("1700000000-42-abcd"));
+        assert!(parse_args(&args(&["--reject"])).is_err());
+        assert!(parse_args(&args(&["--pending", "/tmp/x"])).is_err());
+        let options = parse_args(&args(&["--session", "claude-4242-1700000000", "build"])).expect("scoped grant");
+        assert_eq!(
+            options.scope_session.as_deref(),
+            Some("claude-4242-1700000000")
+        );
+        assert!(parse_args(&args(&["--session", "s", "--scope-pid", "42", "build"])).is_err());
+        assert!(parse_args(&args(&["--sensitive-read", "--scope-pid", "42", "build"])).is_err());
+        let options = parse_args(&args(&["--op", "unlink", "--once", "build/out.o"])).expect("single-use grant");
+        assert_eq!(options.ops, vec!["unlink".to_string()]);
+        assert_eq!(options.max_uses, Some(1));
+        assert!(parse_args(&args(&["--op", "chmod", "build"])).is_err());
+        assert!(parse_args(&args(&["--uses", "0", "build"])).is_err());
+        assert!(parse_args(&args(&["--op", "unlink", "--rename-to", "/tmp/x", "build"])).is_err());
+        assert!(parse_args(&args(&["--remove", "build", "--once"])).is_err());
+        assert!(parse_args(&args(&["--pin", "build/out.o"])).expect("pinned grant").pin);
+        assert!(parse_args(&args(&["--sensitive-read", "--pin", "secret"])).is_err());
+    }
+
+    #[test]
+    fn grant_minutes_rejects_no_expire_and_long_ttl() {
+        assert_eq!(grant_minutes(Some(5)), Ok(5));
+        assert!(grant_minutes(Some(0)).expect_err("no-expire").contains("disabled"));
+        assert!(grant_minutes(Some(OVERRIDE_MAX_MINUTES + 1))
+            .expect_err("too long")
+            .contains("exceeds max"));
+    }
+
+    #[test]
+    fn resolve_target_normalizes_like_the_daemon() {
+        let cwd = Path::new("/nonexistent-agentsmith/project");
+        assert_eq!(
+            resolve_target("./build/../out//", cwd).as_deref(),
+            Ok("/nonexistent-agentsmith/project/out")
+        );
+        assert!(resolve_target("../..", cwd).is_err());
+        assert!(resolve_target(&format!("/{}", "a".repeat(MAX_OVERRIDE_PATH_LEN)), cwd).is_err());
+
+        let dir = std::env::temp_dir().join(format!("agentsmith-override-bin-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("create temp dir");
+        let canonical_dir = fs::canonicalize(&dir).expect("canonical temp dir");
+        let link = dir.join("link");
+        let _ = fs::remove_file(&link);
+        std::os::unix::fs::symlink("/etc/hosts", &link).expect("create symlink");
+        assert_eq!(
+            resolve_target(&link.to_string_lossy(), cwd),
+            Ok(canonical_dir.join("link").to_string_lossy().into_owned())
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn active_overrides_skip_expired_entries() {
+        let policy = serde_json::json!({
+            "temporary_overrides": [
+                "/Users/jqwang/project/legacy",
+                {"path": "/Users/jqwang/project/build", "expires_at": 1_180, "created_by": "agentsmith-helper"},
+                {"path": "/Users/jqwang/project/old", "expires_at": 900}
+            ]
+        });
+        let active = active_overrides(&policy, 1_000);
+        assert_eq!(active.len(), 2);
+        assert_eq!(active[0].expires_at, None);
+        assert_eq!(active[1].path, "/Users/jqwang/project/build");
+        assert_eq!(active[1].created_by.as_deref(), Some("agentsmith-helper"));
+        assert_eq!(format_remaining(180), "3m00s");
+        assert_eq!(format_remaining(42), "42s");
+        assert!(active_overrides(&serde_json::Value::Null, 0).is_empty());
+    }
+
+    #[test]
+    fn submitted_request_round_trips_through_the_shared_protocol() {
+        let dir = std::env::temp_dir().join(format!("agentsmith-override-queue-{}", std::process::id()));
+        let request = OverrideRequest {
+            id: make_request_id(),
+            action: "grant".to_string(),
+            path: Some("/Users/jqwang/project/build".to_string()),
+            minutes: Some(3),
+            requester_pid: Some(4242),
+            approval_id: None,
+            scope_pid: None,
+            scope_session: None,
+            ops: vec!["unlink".to_string()],
+            rename_to: None,
+            max_uses: Some(1),
+            pin: false,
+            paths: Vec::new(),
+            glob: None,
+        };
+        let path = submit_request(&dir, &request.id, &request).expect("submit request");
+        let parsed: OverrideRequest =
+            serde_json::from_str(&fs::read_to_string(&path).expect("read request")).expect("parse request");
+        assert_eq!(parsed.id, request.id);
+        assert_eq!(parsed.minutes, Some(3));
+        assert_eq!(parsed.ops, vec!["unlink".to_string()]);
+        assert_eq!(parsed.max_uses, Some(1));
+
+        let response = OverrideResponse {
+            id: request.id.clone(),
+            status: "ok".to_string(),
+            message: "override granted for 3 minute(s)".to_string(),
+            expires_at: Some(1_180),
+            explanation: Vec::new(),
+            paths: Vec::new(),
+        };
+        fs::write(
+            dir.join(format!("{}.response.json", request.id)),
+            serde_json::to_vec(&response).expect("serialize response"),
+        )
+        .expect("write response");
+        let received = wait_for_override_response(&dir, &request.id, Duration::from_secs(1)).expect("response");
+        assert_eq!(received.expires_at, Some(1_180));
+        assert!(wait_for_override_response(&dir, "missing", Duration::from_millis(50)).is_none());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn grant_goes_over_the_control_socket_and_waits_out_pending() {
+        let dir = std::env::temp_dir().join(format!("agentsmith-override-socket-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("create socket dir");
+        let socket = dir.join("control.sock");
+        let listener = std::os::unix::net::UnixListener::bind(&socket).expect("bind control socket");
+        let daemon = std::thread::spawn(move || {
+            use std::io::{BufRead, Write};
+            let (mut stream, _) = listener.accept().expect("accept");
+            let mut line = String::new();
+            io::BufReader::new(stream.try_clone().expect("clone stream"))
+                .read_line(&mut line)
+                .expect("read request");
+            let request: ControlRequest = serde_json::from_str(&line).expect("parse request");
+            assert_eq!(request.version, CONTROL_PROTOCOL_VERSION);
+            assert_eq!(request.command, "override.grant");
+            assert_eq!(
+                request.glob.as_deref(),
+                Some("/Users/jqwang/project/gen/*.pb.go")
+            );
+            let id = request.id.expect("client sends its request id");
+            for (status, data) in [
+                ("pending", None),
+                (
+                    "ok",
+                    Some(serde_json::json!({ "paths": ["/Users/jqwang/project/gen/a.pb.go"] })),
+                ),
+            ] {
+                let response = ControlResponse {
+                    version: CONTROL_PROTOCOL_VERSION,
+                    id: id.clone(),
+                    status: status.to_string(),
+                    message: format!("{} for {}", status, id),
+                    expires_at: Some(now_ts()),
+                    data,
+                    explanation: Vec::new(),
+                };
+                let mut reply = serde_json::to_vec(&response).expect("serialize response");
+                reply.push(b'\n');
+                stream.write_all(&reply).expect("write response");
+            }
+        });
+
+        let request = OverrideRequest {
+            id: make_request_id(),
+            action: "grant".to_string(),
+            path: None,
+            minutes: Some(3),
+            requester_pid: Some(std::process::id() as i32),
+            approval_id: None,
+            scope_pid: None,
+            scope_session: None,
+            ops: Vec::new(),
+            rename_to: None,
+            max_uses: None,
+            pin: false,
+            paths: Vec::new(),
+            glob: Some("/Users/jqwang/project/gen/*.pb.go".to_string()),
+        };
+        let response = request_over_socket(&socket, &request).expect("socket response");
+        daemon.join().expect("daemon thread");
+        assert_eq!(response.id, request.id);
+        assert_eq!(response.status, "ok");
+        assert_eq!(
+            response.paths,
+            vec!["/Users/jqwang/project/gen/a.pb.go".to_string()]
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn missing_control_socket_falls_back_to_the_file_queue() {
+        let request = OverrideRequest {
+            id: make_request_id(),
+            action: "clear".to_string(),
+            path: None,
+            minutes: None,
+            requester_pid: Some(std::process::id() as i32),
+            approval_id: None,
+            scope_pid: None,
+            scope_session: None,
+            ops: Vec::new(),
+            rename_to: None,
+            max_uses: None,
+            pin: false,
+            paths: Vec::new(),
+            glob: None,
+        };
+        let missing = std::env::temp_dir().join(format!(
+            "agentsmith-override-nosock-{}.sock",
+            std::process::id()
+        ));
+        let err = request_over_socket(&missing, &request).expect_err("nothing listens there");
+        assert!(control_socket_unavailable(&err));
+        assert!(!control_socket_unavailable(&io::Error::from(
+            io::ErrorKind::TimedOut
+        )));
+    }
+
+    #[test]
+    fn daemon_validation_errors_exit_invalid_not_denied() {
+        assert_eq!(exit_code_for_status("ok"), EXIT_OK);
+        assert_eq!(exit_code_for_status("invalid"), EXIT_INVALID);
+        assert_eq!(exit_code_for_status("error"), EXIT_DENIED);
+
+        let response = override_response_from_control(ControlResponse {
+            version: CONTROL_PROTOCOL_VERSION,
+            id: "req-1".to_string(),
+            status: "invalid".to_string(),
+            message: "scope_pid and scope_session are mutually exclusive".to_string(),
+            expires_at: None,
+            data: None,
+            explanation: Vec::new(),
+        });
+        assert_eq!(exit_code_for_status(&response.status), EXIT_INVALID);
+    }
+}
@@ -0,0 +1,256 @@
+//! `agentsmith-run`: start an AI agent as a registered agentsmith-rs session.
+//!
+//! The session (pid, start time, agent, cwd) is registered with the daemon
+//! through the file queue from `session_protocol.rs` before exec, so the whole
+//! process tree is treated as AI context regardless of `ai_agent_patterns`.
+//! The daemon checks that the requesting pid runs `agentsmith-run`; the agent
+//! keeps that pid because it is exec'd in place.
+
+#[path = "../request_queue.rs"]
+mod request_queue;
+#[path = "../session_protocol.rs"]
+mod session_protocol;
+
+use request_queue::{make_request_id, submit_request, wait_for_response};
+use session_protocol::{session_request_dir, SessionRequest, SessionResponse};
+use std::fs;
+use std::io;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+const EXIT_ERROR: i32 = 1;
+const EXIT_CANNOT_EXEC: i32 = 126;
+const EXIT_NOT_FOUND: i32 = 127;
+const DEFAULT_WAIT_SECS: u64 = 5;
+const MAX_AGENT_LEN: usize = 64;
+
+fn usage() -> &'static str {
+    "Usage:\n\
+     \x20 agentsmith-run [--agent NAME] -- <command> [args...]\n\
+     \n\
+     NAME defaults to the command's basename.\n"
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Options {
+    agent: String,
+    command: Vec<String>,
+}
+
+/// `None` asks for the usage text.
+fn parse_args(args: &[String]) -> Result<Option<Options>, String> {
+    let mut agent = None;
+    let mut index = 0;
+    while index < args.len() {
+        match args[index].as_str() {
+            "--agent" | "-a" => {
+                index += 1;
+                let value = args.get(index).ok_or_else(|| "--agent requires a value".to_string())?;
+                agent = Some(value.clone());
+            },
+            "--help" | "-h" => return Ok(None),
+            "--" => {
+                index += 1;
+                break;
+            },
+            flag if flag.starts_with('-') => return Err(format!("unknown option: {}", flag)),
+            _ => break,
+        }
+        index += 1;
+    }
+    let command = args[index..].to_vec();
+    let Some(program) = command.first() else {
+        return Err("missing command".to_string());
+    };
+    let agent = agent.unwrap_or_else(|| {
+        Path::new(program)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| program.clone())
+    });
+    if agent.chars().count() > MAX_AGENT_LEN {
+        return Err(format!("agent name too long (max {} chars)", MAX_AGENT_LEN));
+    }
+    Ok(Some(Options { agent, command }))
+}
+
+fn wait_secs() -> Result<u64, String> {
+    match std::env::var("AGENTSMITH_RUN_WAIT_SECS") {
+        Ok(value) => value.trim().parse().map_err(|_| {
+            format!(
+                "AGENTSMITH_RUN_WAIT_SECS must be a number of seconds, got {:?}",
+                value
+            )
+        }),
+        Err(_) => Ok(DEFAULT_WAIT_SECS),
+    }
+}
+
+/// Register this process as the session root and return the session id.
+fn register_session(request_dir: &Path, agent: &str, cwd: &Path, wait: Duration) -> Result<String, String> {
+    let id = make_request_id();
+    let request = SessionRequest {
+        pid: Some(std::process::id() as i32),
+        agent: Some(agent.to_string()),
+        cwd: Some(cwd.to_string_lossy().to_string()),
+    };
+    let request_path = submit_request(request_dir, &id, &request)
+        .map_err(|err| format!("cannot write request in {}: {}", request_dir.display(), err))?;
+    let Some(response) = wait_for_response::<SessionResponse>(request_dir, &id, wait) else {
+        let _ = fs::remove_file(&request_path);
+        return Err("session registration timeout. Ensure agentsmith-rs daemon is running.".to_string());
+    };
+    if response.id != id {
+        return Err("response id mismatch".to_string());
+    }
+    if response.status != "ok" {
+        return Err(if response.message.is_empty() {
+            "session registration rejected".to_string()
+        } else {
+            response.message
+        });
+    }
+    response
+        .session_id
+        .ok_or_else(|| "daemon did not return a session id".to_string())
+}
+
+fn run(args: &[String]) -> i32 {
+    let options = match parse_args(args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print!("{}", usage());
+            return 0;
+        },
+        Err(err) => {
+            eprintln!("Error: {}\n{}", err, usage());
+            return EXIT_ERROR;
+        },
+    };
+    let wait = match wait_secs() {
+        Ok(secs) => Duration::from_secs(secs),
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            return EXIT_ERROR;
+        },
+    };
+    let home = std::env::var("HOME").unwrap_or_else(|_| "/root".into());
+    let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/"));
+    let session_id = match register_session(&session_request_dir(&home), &options.agent, &cwd, wait) {
+        Ok(session_id) => session_id,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            return EXIT_ERROR;
+        },
+    };
+
+    let err = Command::new(&options.command[0])
+        .args(&options.command[1..])
+        .env("AGENTSMITH_SESSION_ID", &session_id)
+        .exec();
+    eprintln!("Error: cannot run {}: {}", options.command[0], err);
+    if err.kind() == io::ErrorKind::NotFound {
+        EXIT_NOT_FOUND
+    } else {
+        EXIT_CANNOT_EXEC
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    std::process::exit(run(&args));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_args_matches_the_shell_launcher_interface() {
+        let options = parse_args(&args(&["--agent", "claude", "--", "claude", "--resume"]))
+            .expect("valid args")
+            .expect("not help");
+        assert_eq!(options.agent, "claude");
+        assert_eq!(options.command, args(&["claude", "--resume"]));
+
+        let options = parse_args(&args(&["/opt/homebrew/bin/codex", "-q"]))
+            .expect("valid args")
+            .expect("not help");
+        assert_eq!(options.agent, "codex");
+        assert_eq!(options.command, args(&["/opt/homebrew/bin/codex", "-q"]));
+
+        assert_eq!(parse_args(&args(&["-h"])), Ok(None));
+        assert!(parse_args(&args(&["--agent"])).is_err());
+        assert!(parse_args(&args(&["--agent", "claude", "--"])).is_err());
+        assert!(parse_args(&args(&["--verbose", "--", "claude"])).is_err());
+        let long = "a".repeat(MAX_AGENT_LEN + 1);
+        assert!(parse_args(&args(&["--agent", &long, "--", "claude"])).is_err());
+    }
+
+    #[test]
+    fn register_session_reads_the_daemon_answer() {
+        let dir = std::env::temp_dir().join(format!("agentsmith-run-queue-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let answer_dir = dir.clone();
+        let daemon = std::thread::spawn(move || {
+            for _ in 0..100 {
+                let request = fs::read_dir(&answer_dir).ok().and_then(|entries| {
+                    entries
+                        .filter_map(Result::ok)
+                        .map(|entry| entry.file_name().to_string_lossy().to_string())
+                        .find_map(|name| name.strip_suffix(".request.json").map(str::to_string))
+                });
+                if let Some(id) = request {
+                    let path = answer_dir.join(format!("{}.request.json", id));
+                    let request: SessionRequest =
+                        serde_json::from_str(&fs::read_to_string(&path).expect("read request")).expect("parse request");
+                    assert_eq!(request.pid, Some(std::process::id() as i32));
+                    assert_eq!(request.agent.as_deref(), Some("codex"));
+                    assert_eq!(request.cwd.as_deref(), Some("/Users/jqwang/project"));
+                    fs::remove_file(&path).expect("consume request");
+                    let response = SessionResponse {
+                        id: id.clone(),
+                        status: "ok".to_string(),
+                        message: "session registered".to_string(),
+                        session_id: Some("codex-4242-1770000000".to_string()),
+                    };
+                    fs::write(
+                        answer_dir.join(format!("{}.response.json", id)),
+                        serde_json::to_vec(&response).expect("serialize response"),
+                    )
+                    .expect("write response");
+                    return;
+                }
+                std::thread::sleep(Duration::from_millis(20));
+            }
+            panic!("no session request was submitted");
+        });
+        let session_id = register_session(
+            &dir,
+            "codex",
+            Path::new("/Users/jqwang/project"),
+            Duration::from_secs(5),
+        )
+        .expect("registered");
+        daemon.join().expect("daemon thread");
+        assert_eq!(session_id, "codex-4242-1770000000");
+
+        let err = register_session(
+            &dir,
+            "codex",
+            Path::new("/Users/jqwang/project"),
+            Duration::from_millis(50),
+        )
+        .expect_err("nobody answers");
+        assert!(err.contains("timeout"));
+        let leftovers = fs::read_dir(&dir).expect("queue dir").count();
+        assert_eq!(leftovers, 0, "a timed-out request is withdrawn");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
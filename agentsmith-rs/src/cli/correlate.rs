@@ -0,0 +1,604 @@
+//! `agentsmith-rs correlate`: match denials to the agent tool calls behind them.
+
+use crate::{exe_name, format_utc_ts, now_ts, path_prefix_match, read_guard_log};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const CORRELATE_DEFAULT_WINDOW_SECS: u64 = 120;
+const CORRELATE_DEFAULT_SINCE_SECS: u64 = 24 * 60 * 60;
+/// Transcript and daemon timestamps come from different clocks and rounding.
+const CORRELATE_CLOCK_SLACK_SECS: u64 = 2;
+const CORRELATE_MAX_COMMAND_CHARS: usize = 2000;
+
+/// One tool call recorded in an agent transcript.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct TranscriptToolCall {
+    agent: &'static str,
+    transcript: String,
+    transcript_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_use_id: Option<String>,
+    ts: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cwd: Option<String>,
+    command: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct DenialCorrelation {
+    confidence: &'static str,
+    score: u32,
+    #[serde(flatten)]
+    call: TranscriptToolCall,
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Seconds since the epoch for an RFC 3339 timestamp (`2026-03-04T05:06:07.890Z`).
+fn parse_rfc3339_secs(value: &str) -> Option<u64> {
+    let number = |range: std::ops::Range<usize>| value.get(range)?.parse::<i64>().ok();
+    if value.len() < 19 || value.as_bytes()[10] != b'T' && value.as_bytes()[10] != b' ' {
+        return None;
+    }
+    let days = days_from_civil(number(0..4)?, number(5..7)?, number(8..10)?);
+    let mut secs = days * 86_400 + number(11..13)? * 3600 + number(14..16)? * 60 + number(17..19)?;
+    let zone = value[19..].trim_start_matches(|ch: char| ch == '.' || ch.is_ascii_digit());
+    if let Some(sign) = zone.chars().next().filter(|ch| *ch == '+' || *ch == '-') {
+        let offset = zone.get(1..3)?.parse::<i64>().ok()? * 3600 + zone.get(4..6)?.parse::<i64>().ok()? * 60;
+        secs -= if sign == '+' { offset } else { -offset };
+    }
+    u64::try_from(secs).ok()
+}
+
+fn truncate_command(command: String) -> String {
+    if command.chars().count() <= CORRELATE_MAX_COMMAND_CHARS {
+        return command;
+    }
+    let mut truncated: String = command.chars().take(CORRELATE_MAX_COMMAND_CHARS).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Tool calls from a Claude Code session transcript (`~/.claude/projects/<project>/<session>.jsonl`).
+fn claude_transcript_tool_calls(transcript: &str, content: &str) -> Vec<TranscriptToolCall> {
+    let mut calls = Vec::new();
+    for line in content.lines() {
+        let Ok(entry) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        if entry.get("type").and_then(|kind| kind.as_str()) != Some("assistant") {
+            continue;
+        }
+        let Some(ts) = entry
+            .get("timestamp")
+            .and_then(|ts| ts.as_str())
+            .and_then(parse_rfc3339_secs)
+        else {
+            continue;
+        };
+        let Some(items) = entry.pointer("/message/content").and_then(|items| items.as_array()) else {
+            continue;
+        };
+        for item in items {
+            if item.get("type").and_then(|kind| kind.as_str()) != Some("tool_use") {
+                continue;
+            }
+            let name = item.get("name").and_then(|name| name.as_str()).unwrap_or("tool");
+            let input = item.get("input");
+            let command = match input
+                .and_then(|input| input.get("command"))
+                .and_then(|command| command.as_str())
+            {
+                Some(command) => command.to_string(),
+                // File tools (Write, Edit, ...) name their target instead of a command.
+                None => match input
+                    .and_then(|input| input.get("file_path").or_else(|| input.get("notebook_path")))
+                    .and_then(|path| path.as_str())
+                {
+                    Some(path) => format!("{} {}", name, path),
+                    None => continue,
+                },
+            };
+            calls.push(TranscriptToolCall {
+                agent: "claude",
+                transcript: transcript.to_string(),
+                transcript_id: entry
+                    .get("sessionId")
+                    .and_then(|id| id.as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+                tool_use_id: item.get("id").and_then(|id| id.as_str()).map(str::to_string),
+                ts,
+                cwd: entry.get("cwd").and_then(|cwd| cwd.as_str()).map(str::to_string),
+                command: truncate_command(command),
+            });
+        }
+    }
+    calls
+}
+
+/// Shell text for a Codex `command` argument: `["bash", "-lc", script]` yields the script.
+fn codex_command_text(command: &serde_json::Value) -> Option<String> {
+    match command {
+        serde_json::Value::String(command) => Some(command.clone()),
+        serde_json::Value::Array(argv) => {
+            let argv: Vec<&str> = argv.iter().filter_map(|arg| arg.as_str()).collect();
+            match argv.as_slice() {
+                [shell, flag, script, ..]
+                    if matches!(exe_name(shell), "bash" | "sh" | "zsh")
+                        && flag.starts_with('-')
+                        && flag.contains('c') =>
+                {
+                    Some(script.to_string())
+                },
+                [] => None,
+                _ => Some(argv.join(" ")),
+            }
+        },
+        _ => None,
+    }
+}
+
+/// Tool calls from a Codex rollout log (`~/.codex/sessions/YYYY/MM/DD/rollout-*.jsonl`).
+fn codex_transcript_tool_calls(transcript: &str, content: &str) -> Vec<TranscriptToolCall> {
+    let mut calls = Vec::new();
+    let mut session_id = String::new();
+    let mut session_cwd: Option<String> = None;
+    for line in content.lines() {
+        let Ok(entry) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        let Some(payload) = entry.get("payload") else {
+            continue;
+        };
+        match entry.get("type").and_then(|kind| kind.as_str()) {
+            Some("session_meta") => {
+                if let Some(id) = payload.get("id").and_then(|id| id.as_str()) {
+                    session_id = id.to_string();
+                }
+                if let Some(cwd) = payload.get("cwd").and_then(|cwd| cwd.as_str()) {
+                    session_cwd = Some(cwd.to_string());
+                }
+                continue;
+            },
+            Some("turn_context") => {
+                if let Some(cwd) = payload.get("cwd").and_then(|cwd| cwd.as_str()) {
+                    session_cwd = Some(cwd.to_string());
+                }
+                continue;
+            },
+            Some("response_item") => {},
+            _ => continue,
+        }
+        let Some(ts) = entry
+            .get("timestamp")
+            .and_then(|ts| ts.as_str())
+            .and_then(parse_rfc3339_secs)
+        else {
+            continue;
+        };
+        let (command, cwd) = match payload.get("type").and_then(|kind| kind.as_str()) {
+            Some("function_call") => {
+                let Some(arguments) = payload
+                    .get("arguments")
+                    .and_then(|arguments| arguments.as_str())
+                    .and_then(|arguments| serde_json::from_str::<serde_json::Value>(arguments).ok())
+                else {
+                    continue;
+                };
+                let command = arguments
+                    .get("command")
+                    .or_else(|| arguments.get("cmd"))
+                    .and_then(codex_command_text);
+                let cwd = arguments
+                    .get("workdir")
+                    .and_then(|cwd| cwd.as_str())
+                    .map(str::to_string);
+                (command, cwd)
+            },
+            Some("local_shell_call") => {
+                let action = payload.get("action");
+                let command = action
+                    .and_then(|action| action.get("command"))
+                    .and_then(codex_command_text);
+                let cwd = action
+                    .and_then(|action| action.get("working_directory"))
+                    .and_then(|cwd| cwd.as_str())
+                    .map(str::to_string);
+                (command, cwd)
+            },
+            // apply_patch: the patch text names every file it touches.
+            Some("custom_tool_call") => (
+                payload
+                    .get("input")
+                    .and_then(|input| input.as_str())
+                    .map(str::to_string),
+                None,
+            ),
+            _ => continue,
+        };
+        let Some(command) = command else {
+            continue;
+        };
+        calls.push(TranscriptToolCall {
+            agent: "codex",
+            transcript: transcript.to_string(),
+            transcript_id: session_id.clone(),
+            tool_use_id: payload.get("call_id").and_then(|id| id.as_str()).map(str::to_string),
+            ts,
+            cwd: cwd.or_else(|| session_cwd.clone()),
+            command: truncate_command(command),
+        });
+    }
+    calls
+}
+
+type TranscriptParser = fn(&str, &str) -> Vec<TranscriptToolCall>;
+
+/// `*.jsonl` files under `dir` modified at or after `min_mtime`.
+fn collect_transcript_files(dir: &Path, depth: usize, min_mtime: u64, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        let Ok(meta) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if meta.is_dir() {
+            if depth > 0 {
+                collect_transcript_files(&path, depth - 1, min_mtime, out);
+            }
+            continue;
+        }
+        let modified = meta
+            .modified()
+            .ok()
+            .and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok())
+            .map(|delta| delta.as_secs())
+            .unwrap_or(0);
+        if meta.is_file() && modified >= min_mtime && path.extension().is_some_and(|ext| ext == "jsonl") {
+            out.push(path);
+        }
+    }
+}
+
+/// Tool calls from every Claude Code and Codex transcript written since `min_mtime`.
+fn load_transcript_tool_calls(home: &str, min_mtime: u64) -> Vec<TranscriptToolCall> {
+    let claude_root = std::env::var("CLAUDE_CONFIG_DIR").unwrap_or_else(|_| format!("{}/.claude", home));
+    let codex_root = std::env::var("CODEX_HOME").unwrap_or_else(|_| format!("{}/.codex", home));
+    let sources: [(PathBuf, usize, TranscriptParser); 2] = [
+        (
+            Path::new(&claude_root).join("projects"),
+            3,
+            claude_transcript_tool_calls,
+        ),
+        (
+            Path::new(&codex_root).join("sessions"),
+            4,
+            codex_transcript_tool_calls,
+        ),
+    ];
+    let mut calls = Vec::new();
+    for (root, depth, parse) in sources {
+        let mut files = Vec::new();
+        collect_transcript_files(&root, depth, min_mtime, &mut files);
+        for file in files {
+            if let Ok(content) = fs::read_to_string(&file) {
+                calls.extend(parse(&file.to_string_lossy(), &content));
+            }
+        }
+    }
+    calls
+}
+
+fn json_str<'a>(value: &'a serde_json::Value, pointer: &str) -> Option<&'a str> {
+    value.pointer(pointer).and_then(|value| value.as_str())
+}
+
+/// How strongly `call` explains `denial`; `None` when it cannot have caused it.
+fn correlation_score(denial: &serde_json::Value, call: &TranscriptToolCall, window: u64) -> Option<u32> {
+    let denial_ts = denial.get("ts").and_then(|ts| ts.as_u64())?;
+    if call.ts > denial_ts + CORRELATE_CLOCK_SLACK_SECS || denial_ts > call.ts + window {
+        return None;
+    }
+    // The daemon's session or AI ancestor label names the agent when it knows it.
+    let hint = json_str(denial, "/session/agent")
+        .or_else(|| json_str(denial, "/ancestor"))
+        .unwrap_or_default();
+    let other = if call.agent == "claude" { "codex" } else { "claude" };
+    if hint.contains(other) && !hint.contains(call.agent) {
+        return None;
+    }
+
+    let path = json_str(denial, "/path").unwrap_or_default();
+    let mut score = 0;
+    for target in [Some(path), json_str(denial, "/dest")].into_iter().flatten() {
+        if target.is_empty() {
+            continue;
+        }
+        let name = exe_name(target);
+        if call.command.contains(target) {
+            score += 4;
+        } else if name.len() >= 3 && call.command.contains(name) {
+            score += 2;
+        }
+    }
+    if let Some(cwd) = &call.cwd {
+        if !path.is_empty() && path_prefix_match(path, cwd) {
+            score += 2;
+        }
+        if json_str(denial, "/session/cwd") == Some(cwd.as_str()) {
+            score += 2;
+        }
+    }
+    let process = json_str(denial, "/process").unwrap_or_default();
+    if !process.is_empty()
+        && call
+            .command
+            .split(|ch: char| ch.is_whitespace() || ch == ';' || ch == '&' || ch == '|' || ch == '(')
+            .any(|word| exe_name(word) == process)
+    {
+        score += 1;
+    }
+    (score > 0).then_some(score)
+}
+
+/// Best-scoring tool call for a denial; ties go to the call closest to it.
+fn correlate_denial(
+    denial: &serde_json::Value,
+    calls: &[TranscriptToolCall],
+    window: u64,
+) -> Option<DenialCorrelation> {
+    calls
+        .iter()
+        .filter_map(|call| Some((correlation_score(denial, call, window)?, call)))
+        .max_by_key(|(score, call)| (*score, call.ts))
+        .map(|(score, call)| DenialCorrelation {
+            confidence: match score {
+                6.. => "high",
+                3..=5 => "medium",
+                _ => "low",
+            },
+            score,
+            call: call.clone(),
+        })
+}
+
+fn correlate_usage() -> &'static str {
+    "Usage: agentsmith-rs correlate [--since SECONDS] [--window SECONDS] [--denials FILE] [--json]\n\
+     Match recent denials to the Claude Code / Codex tool call that triggered them.\n\
+     --since   only denials from the last SECONDS (default 86400)\n\
+     --window  how long before a denial its tool call may start (default 120)\n\
+     --json    print each denial record with a `correlation` field (JSON lines)\n"
+}
+
+/// `agentsmith-rs correlate`: report which transcript tool call caused each denial.
+pub fn run_correlate(args: &[String]) -> i32 {
+    let home = std::env::var("HOME").unwrap_or_else(|_| "/root".into());
+    let mut since = CORRELATE_DEFAULT_SINCE_SECS;
+    let mut window = CORRELATE_DEFAULT_WINDOW_SECS;
+    let mut denials_path = PathBuf::from(&home)
+        .join(".agentsmith-rs")
+        .join("guard")
+        .join("denials.jsonl");
+    let mut json = false;
+    let mut index = 0;
+    while let Some(arg) = args.get(index) {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--help" | "-h" => {
+                print!("{}", correlate_usage());
+                return 0;
+            },
+            "--since" | "--window" | "--denials" => {
+                index += 1;
+                let value = args.get(index);
+                let parsed = match (arg.as_str(), value) {
+                    ("--denials", Some(value)) => {
+                        denials_path = PathBuf::from(value);
+                        true
+                    },
+                    ("--since", Some(value)) => value.parse().map(|secs| since = secs).is_ok(),
+                    ("--window", Some(value)) => value.parse().map(|secs| window = secs).is_ok(),
+                    _ => false,
+                };
+                if !parsed {
+                    eprintln!(
+                        "[correlate] invalid value for {}\n{}",
+                        arg,
+                        correlate_usage()
+                    );
+                    return 1;
+                }
+            },
+            other => {
+                eprintln!(
+                    "[correlate] unknown argument: {}\n{}",
+                    other,
+                    correlate_usage()
+                );
+                return 1;
+            },
+        }
+        index += 1;
+    }
+
+    let cutoff = now_ts().saturating_sub(since);
+    let content = match read_guard_log(&denials_path, cutoff) {
+        Ok(content) => content,
+        Err(err) => {
+            eprintln!(
+                "[correlate] cannot read {}: {}",
+                denials_path.display(),
+                err
+            );
+            return 1;
+        },
+    };
+    let denials: Vec<serde_json::Value> = content
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .filter(|denial| {
+            denial
+                .get("ts")
+                .and_then(|ts| ts.as_u64())
+                .is_some_and(|ts| ts >= cutoff)
+        })
+        .collect();
+    let Some(oldest) = denials.iter().filter_map(|denial| denial.get("ts")?.as_u64()).min() else {
+        if !json {
+            println!("No denials in the selected period.");
+        }
+        return 0;
+    };
+    let calls = load_transcript_tool_calls(&home, oldest.saturating_sub(window));
+
+    let total = denials.len();
+    let mut matched = 0;
+    for mut denial in denials {
+        let correlation = correlate_denial(&denial, &calls, window);
+        matched += usize::from(correlation.is_some());
+        if json {
+            if let Some(object) = denial.as_object_mut() {
+                object.insert(
+                    "correlation".to_string(),
+                    serde_json::to_value(&correlation).unwrap_or(serde_json::Value::Null),
+                );
+            }
+            println!("{}", denial);
+            continue;
+        }
+        println!(
+            "{} {} {} {}{}",
+            format_utc_ts(denial.get("ts").and_then(|ts| ts.as_u64()).unwrap_or_default()),
+            json_str(&denial, "/op").unwrap_or("?"),
+            json_str(&denial, "/reason").unwrap_or("?"),
+            json_str(&denial, "/path").unwrap_or("?"),
+            json_str(&denial, "/dest")
+                .map(|dest| format!(" -> {}", dest))
+                .unwrap_or_default()
+        );
+        match correlation {
+            Some(correlation) => {
+                println!(
+                    "  {} session {} call {} ({} confidence, {})",
+                    correlation.call.agent,
+                    correlation.call.transcript_id,
+                    correlation.call.tool_use_id.as_deref().unwrap_or("-"),
+                    correlation.confidence,
+                    format_utc_ts(correlation.call.ts)
+                );
+                println!(
+                    "  command: {}",
+                    correlation.call.command.lines().next().unwrap_or_default()
+                );
+                println!("  transcript: {}", correlation.call.transcript);
+            },
+            None => println!("  no matching tool call found"),
+        }
+    }
+    if !json {
+        println!(
+            "\n{} of {} denials matched ({} tool calls scanned).",
+            matched,
+            total,
+            calls.len()
+        );
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rfc3339_timestamps_round_trip_through_utc_format() {
+        assert_eq!(parse_rfc3339_secs("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(
+            parse_rfc3339_secs("2026-03-04T05:06:07.890Z"),
+            Some(1_772_600_767)
+        );
+        assert_eq!(
+            parse_rfc3339_secs("2026-03-04T13:06:07+08:00"),
+            Some(1_772_600_767)
+        );
+        assert_eq!(parse_rfc3339_secs("not a timestamp"), None);
+        assert_eq!(format_utc_ts(1_772_600_767), "2026-03-04T05:06:07Z");
+        assert_eq!(format_utc_ts(951_782_400), "2000-02-29T00:00:00Z");
+    }
+
+    #[test]
+    fn transcript_parsers_extract_tool_calls() {
+        let claude = concat!(
+            r#"{"type":"user","timestamp":"2026-03-04T05:06:00Z","message":{"content":"clean up"}}"#,
+            "\n",
+            r#"{"type":"assistant","timestamp":"2026-03-04T05:06:07Z","sessionId":"abc","cwd":"/Users/jqwang/project","message":{"content":[{"type":"text","text":"ok"},{"type":"tool_use","id":"toolu_1","name":"Bash","input":{"command":"rm -rf build"}},{"type":"tool_use","id":"toolu_2","name":"Write","input":{"file_path":"/Users/jqwang/project/a.txt"}}]}}"#,
+        );
+        let calls = claude_transcript_tool_calls("/t/abc.jsonl", claude);
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].transcript_id, "abc");
+        assert_eq!(calls[0].tool_use_id.as_deref(), Some("toolu_1"));
+        assert_eq!(calls[0].command, "rm -rf build");
+        assert_eq!(calls[0].cwd.as_deref(), Some("/Users/jqwang/project"));
+        assert_eq!(calls[1].command, "Write /Users/jqwang/project/a.txt");
+
+        let codex = concat!(
+            r#"{"timestamp":"2026-03-04T05:00:00Z","type":"session_meta","payload":{"id":"rollout-1","cwd":"/Users/jqwang/project"}}"#,
+            "\n",
+            r#"{"timestamp":"2026-03-04T05:06:07Z","type":"response_item","payload":{"type":"function_call","name":"shell","call_id":"call_1","arguments":"{\"command\":[\"bash\",\"-lc\",\"git clean -fdx\"],\"workdir\":\"/Users/jqwang/project/sub\"}"}}"#,
+            "\n",
+            r#"{"timestamp":"2026-03-04T05:06:09Z","type":"response_item","payload":{"type":"message","role":"assistant","content":[]}}"#,
+        );
+        let calls = codex_transcript_tool_calls("/t/rollout.jsonl", codex);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].agent, "codex");
+        assert_eq!(calls[0].transcript_id, "rollout-1");
+        assert_eq!(calls[0].tool_use_id.as_deref(), Some("call_1"));
+        assert_eq!(calls[0].command, "git clean -fdx");
+        assert_eq!(calls[0].cwd.as_deref(), Some("/Users/jqwang/project/sub"));
+    }
+
+    #[test]
+    fn correlate_denial_prefers_call_naming_the_denied_path() {
+        let call = |agent: &'static str, id: &str, ts: u64, command: &str| TranscriptToolCall {
+            agent,
+            transcript: format!("/t/{}.jsonl", id),
+            transcript_id: id.to_string(),
+            tool_use_id: Some(format!("{}-call", id)),
+            ts,
+            cwd: Some("/Users/jqwang/project".to_string()),
+            command: command.to_string(),
+        };
+        let calls = vec![
+            call("claude", "early", 800, "rm -rf /Users/jqwang/project/build"),
+            call("claude", "other", 995, "ls -la"),
+            call("claude", "match", 990, "rm -rf build"),
+            call("codex", "codex", 999, "rm -rf /Users/jqwang/project/build"),
+        ];
+        let denial = serde_json::json!({
+            "ts": 1000,
+            "op": "unlink",
+            "path": "/Users/jqwang/project/build",
+            "process": "rm",
+            "ancestor": "claude",
+        });
+
+        let correlation = correlate_denial(&denial, &calls, 120).expect("correlated call");
+        assert_eq!(correlation.call.transcript_id, "match");
+        assert_eq!(correlation.confidence, "medium");
+
+        let late = serde_json::json!({"ts": 2000, "path": "/Users/jqwang/project/build", "ancestor": "claude"});
+        assert!(correlate_denial(&late, &calls, 120).is_none());
+    }
+}
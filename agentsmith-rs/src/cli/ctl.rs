@@ -0,0 +1,176 @@
+//! `agentsmith-rs ctl`: client for the daemon's control socket.
+
+use crate::join_path_component;
+use crate::protocol::{control_socket_path, send_control_request, ControlRequest, CONTROL_PROTOCOL_VERSION};
+use std::path::PathBuf;
+
+fn ctl_usage() -> &'static str {
+    "Usage: agentsmith-rs ctl COMMAND [--json] [--socket PATH]\n\
+     Commands:\n\
+     \x20 status                      daemon state and cache sizes\n\
+     \x20 reload                      re-read policy.json and runtime overrides now\n\
+     \x20 cache-dump                  AI processes, taint marks, trust and signature caches\n\
+     \x20 list                        active overrides with remaining TTL\n\
+     \x20 grant PATH... [--minutes N] short override (human terminal only)\n\
+     \x20     [--glob 'DIR/PATTERN']   add the entries of DIR matching PATTERN; approved as one unit\n\
+     \x20     [--scope-pid PID | --session ID]  limit it to one process tree or AI session\n\
+     \x20     [--op OP]... [--rename-to DEST] [--once | --uses N]  limit it to operations / uses\n\
+     \x20     [--pin]                   only while the file keeps its current content\n\
+     \x20 remove PATH                 drop overrides for PATH\n\
+     \x20 clear                       drop all overrides\n\
+     \x20 pending                     grants waiting for human approval\n\
+     \x20 approve ID                  approve a pending grant\n\
+     \x20 reject ID                   reject a pending grant\n"
+}
+
+fn absolute_from_cwd(path: &str) -> String {
+    if path.starts_with('/') {
+        return path.to_string();
+    }
+    let cwd = std::env::current_dir()
+        .map(|dir| dir.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    join_path_component(&cwd, path)
+}
+
+pub fn run_ctl(args: &[String]) -> i32 {
+    let mut positional = Vec::new();
+    let mut json = false;
+    let mut minutes = None;
+    let mut scope_pid = None;
+    let mut scope_session = None;
+    let mut ops = Vec::new();
+    let mut rename_to = None;
+    let mut max_uses = None;
+    let mut pin = false;
+    let mut glob = None;
+    let mut socket = control_socket_path();
+    let mut index = 0;
+    while let Some(arg) = args.get(index) {
+        match arg.as_str() {
+            "--help" | "-h" => {
+                print!("{}", ctl_usage());
+                return 0;
+            },
+            "--json" => json = true,
+            "--once" => max_uses = Some(1),
+            "--pin" => pin = true,
+            "--minutes" | "--socket" | "--scope-pid" | "--session" | "--op" | "--rename-to" | "--uses" | "--glob" => {
+                let Some(value) = args.get(index + 1) else {
+                    eprintln!("[ctl] missing value for {}\n{}", arg, ctl_usage());
+                    return 1;
+                };
+                match arg.as_str() {
+                    "--socket" => socket = PathBuf::from(value),
+                    "--session" => scope_session = Some(value.clone()),
+                    "--op" => ops.push(value.clone()),
+                    "--rename-to" => rename_to = Some(absolute_from_cwd(value)),
+                    "--glob" => glob = Some(absolute_from_cwd(value)),
+                    "--uses" => match value.parse::<u32>() {
+                        Ok(value) => max_uses = Some(value),
+                        Err(_) => {
+                            eprintln!("[ctl] invalid use count: {}", value);
+                            return 1;
+                        },
+                    },
+                    "--scope-pid" => match value.parse::<i32>() {
+                        Ok(value) => scope_pid = Some(value),
+                        Err(_) => {
+                            eprintln!("[ctl] invalid pid: {}", value);
+                            return 1;
+                        },
+                    },
+                    _ => match value.parse::<u64>() {
+                        Ok(value) => minutes = Some(value),
+                        Err(_) => {
+                            eprintln!("[ctl] invalid minutes: {}", value);
+                            return 1;
+                        },
+                    },
+                }
+                index += 1;
+            },
+            other => positional.push(other.to_string()),
+        }
+        index += 1;
+    }
+
+    let (command, operand) = match positional.first().map(String::as_str) {
+        Some("status") => ("status", None),
+        Some("reload") => ("reload", None),
+        Some("cache-dump") => ("cache.dump", None),
+        Some("list") => ("override.list", None),
+        Some("grant") => ("override.grant", Some("a path")),
+        Some("remove") => ("override.remove", Some("a path")),
+        Some("clear") => ("override.clear", None),
+        Some("pending") => ("override.pending", None),
+        Some("approve") => ("override.approve", Some("a request id")),
+        Some("reject") => ("override.reject", Some("a request id")),
+        _ => {
+            eprint!("{}", ctl_usage());
+            return 1;
+        },
+    };
+    let needs_id = operand == Some("a request id");
+    let approval_id = positional.get(1).filter(|_| needs_id).cloned();
+    let path = match (operand, positional.get(1)) {
+        (Some(_), Some(_)) if needs_id => None,
+        (Some(_), Some(path)) => Some(absolute_from_cwd(path)),
+        (Some(_), None) if command == "override.grant" && glob.is_some() => None,
+        (Some(operand), None) => {
+            eprintln!("[ctl] {} needs {}\n{}", positional[0], operand, ctl_usage());
+            return 1;
+        },
+        (None, _) => None,
+    };
+    let paths: Vec<String> = if command == "override.grant" {
+        positional.iter().skip(2).map(|path| absolute_from_cwd(path)).collect()
+    } else {
+        Vec::new()
+    };
+
+    let request = ControlRequest {
+        version: CONTROL_PROTOCOL_VERSION,
+        id: None,
+        command: command.to_string(),
+        path,
+        minutes,
+        approval_id,
+        scope_pid,
+        scope_session,
+        ops,
+        rename_to,
+        max_uses,
+        pin,
+        paths,
+        glob,
+    };
+    let response = match send_control_request(&socket, &request, |pending| {
+        eprintln!("[ctl] {}", pending.message);
+    }) {
+        Ok(response) => response,
+        Err(err) => {
+            eprintln!("[ctl] cannot reach daemon at {}: {}", socket.display(), err);
+            return 1;
+        },
+    };
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&response).unwrap_or_default()
+        );
+    } else {
+        println!("[{}] {}", response.status, response.message);
+        for line in &response.explanation {
+            println!("  - {}", line);
+        }
+        if let Some(data) = &response.data {
+            println!("{}", serde_json::to_string_pretty(data).unwrap_or_default());
+        }
+    }
+    if response.status == "ok" {
+        0
+    } else {
+        1
+    }
+}
@@ -0,0 +1,605 @@
+//! `agentsmith-rs mcp`: stdio MCP server exposing the guard to agents.
+
+use super::load_user_policy;
+use crate::preflight::{preflight_command, preflight_feedback, PreflightReport};
+use crate::protocol::normalize_absolute_path;
+use crate::{
+    auto_home_digit_zones, get_process_info, is_system_temp, join_path_component, now_ts, sanitize_component,
+    SecurityPolicy, MAX_ANCESTRY_DEPTH,
+};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const MCP_PROTOCOL_VERSIONS: [&str; 3] = ["2025-06-18", "2025-03-26", "2024-11-05"];
+const MCP_MAX_DENIALS: u64 = 50;
+const MCP_DEFAULT_OVERRIDE_MINUTES: u64 = 3;
+
+fn mcp_tools() -> serde_json::Value {
+    serde_json::json!([
+        {
+            "name": "get_last_denial",
+            "description": "Most recent agentsmith denial for this agent (per-session feedback), with the reason and suggested next steps.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "count": {"type": "integer", "minimum": 1, "maximum": MCP_MAX_DENIALS, "description": "Number of recent denial records to return (default 1)."},
+                    "session": {"type": "string", "description": "Session ID to read instead of the caller's own."}
+                }
+            }
+        },
+        {
+            "name": "list_protected_zones",
+            "description": "Protected zones, sensitive zones and active temporary overrides from the live policy.",
+            "inputSchema": {"type": "object", "properties": {}}
+        },
+        {
+            "name": "check_path",
+            "description": "Whether a path is protected or sensitive, and whether deleting, moving or truncating it would be denied.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "path": {"type": "string"},
+                    "cwd": {"type": "string", "description": "Directory relative paths are resolved against."}
+                },
+                "required": ["path"]
+            }
+        },
+        {
+            "name": "check_command",
+            "description": "Statically check a shell command for destructive operations the guard would deny (same analysis as `agentsmith-rs preflight`).",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "command": {"type": "string"},
+                    "cwd": {"type": "string"}
+                },
+                "required": ["command"]
+            }
+        },
+        {
+            "name": "quarantine_path",
+            "description": "Move a path into <cwd>/temp instead of deleting it (runs agentsmith-quarantine).",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "path": {"type": "string"},
+                    "cwd": {"type": "string", "description": "Directory whose ./temp receives the path."}
+                },
+                "required": ["path"]
+            }
+        },
+        {
+            "name": "request_override",
+            "description": "Request a temporary override through agentsmith-override. Overrides are human-gated: requests originating from an agent are rejected, and the result explains what the user has to run.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "path": {"type": "string"},
+                    "minutes": {"type": "integer", "minimum": 1, "description": "Override lifetime (default 3)."},
+                    "sensitive_read": {"type": "boolean", "description": "Request a sensitive-read override instead of a delete/move override."},
+                    "cwd": {"type": "string"}
+                },
+                "required": ["path"]
+            }
+        }
+    ])
+}
+
+struct McpToolOutput {
+    text: String,
+    is_error: bool,
+}
+
+impl McpToolOutput {
+    fn json(value: &serde_json::Value) -> Self {
+        Self {
+            text: serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string()),
+            is_error: false,
+        }
+    }
+
+    fn error(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            is_error: true,
+        }
+    }
+}
+
+/// Most recently written feedback directory for a session rooted at `root_pid`
+/// (session IDs embed the root pid as `<agent>-<pid>-<start>`).
+fn newest_session_feedback_dir(feedback_root: &Path, root_pid: i32) -> Option<PathBuf> {
+    let needle = format!("-{}-", root_pid);
+    fs::read_dir(feedback_root)
+        .ok()?
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_name().to_string_lossy().contains(&needle))
+        .filter_map(|entry| {
+            let modified = fs::symlink_metadata(entry.path().join("last_denial.json"))
+                .and_then(|meta| meta.modified())
+                .ok()?;
+            Some((modified, entry.path()))
+        })
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path)
+}
+
+/// Feedback directory for the calling agent: an explicit session, then
+/// `$AGENTSMITH_SESSION_ID`, then the closest ancestor that owns a session or
+/// `pid-<pid>` directory. Same lookup as `agentsmith-feedback`.
+fn caller_feedback_dir(feedback_root: &Path, session: Option<&str>, start_pid: i32) -> Option<PathBuf> {
+    let session = session
+        .map(str::to_string)
+        .or_else(|| std::env::var("AGENTSMITH_SESSION_ID").ok())
+        .filter(|session| !session.is_empty());
+    if let Some(session) = session {
+        let dir = feedback_root.join(sanitize_component(&session));
+        return dir.join("last_denial.json").is_file().then_some(dir);
+    }
+
+    let mut pid = start_pid;
+    for _ in 0..MAX_ANCESTRY_DEPTH {
+        if pid <= 1 {
+            break;
+        }
+        if let Some(dir) = newest_session_feedback_dir(feedback_root, pid) {
+            return Some(dir);
+        }
+        let dir = feedback_root.join(format!("pid-{}", pid));
+        if dir.join("last_denial.json").is_file() {
+            return Some(dir);
+        }
+        pid = get_process_info(pid)?.0;
+    }
+    None
+}
+
+fn mcp_get_last_denial(home: &str, args: &serde_json::Value) -> McpToolOutput {
+    let count = args
+        .get("count")
+        .and_then(|count| count.as_u64())
+        .unwrap_or(1)
+        .clamp(1, MCP_MAX_DENIALS) as usize;
+    let session = args.get("session").and_then(|session| session.as_str());
+    let guard_dir = PathBuf::from(home).join(".agentsmith-rs").join("guard");
+    let (dir, scope) = match caller_feedback_dir(
+        &guard_dir.join("feedback"),
+        session,
+        std::process::id() as i32,
+    ) {
+        Some(dir) => (dir, "session"),
+        None if session.is_some() => {
+            return McpToolOutput::error(format!(
+                "No denials recorded for session {}.",
+                session.unwrap_or_default()
+            ));
+        },
+        // Shared files: may belong to another agent on this machine.
+        None => (guard_dir, "global"),
+    };
+
+    let Ok(feedback) = fs::read_to_string(dir.join("last_denial.txt")) else {
+        return McpToolOutput {
+            text: "No denials recorded for this agent.".to_string(),
+            is_error: false,
+        };
+    };
+    let history = fs::read_to_string(dir.join("denials.jsonl")).unwrap_or_default();
+    let lines: Vec<&str> = history.lines().filter(|line| !line.trim().is_empty()).collect();
+    let denials: Vec<serde_json::Value> = lines[lines.len().saturating_sub(count)..]
+        .iter()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    McpToolOutput::json(&serde_json::json!({
+        "scope": scope,
+        "feedback_dir": dir.to_string_lossy(),
+        "feedback": feedback,
+        "denials": denials,
+    }))
+}
+
+fn mcp_list_protected_zones(home: &str, policy: &SecurityPolicy) -> McpToolOutput {
+    let now = now_ts();
+    let overrides: Vec<serde_json::Value> = policy
+        .temporary_overrides
+        .iter()
+        .filter(|entry| !entry.is_expired(now))
+        .map(|entry| {
+            serde_json::json!({
+                "path": entry.path(),
+                "expires_at": entry.expires_at(),
+                "sensitive_read": entry.is_sensitive_read_only(),
+            })
+        })
+        .collect();
+    let auto_zones = if policy.auto_protect_home_digit_children {
+        auto_home_digit_zones(home)
+    } else {
+        Vec::new()
+    };
+    McpToolOutput::json(&serde_json::json!({
+        "protected_zones": policy.protected_zones,
+        "auto_protect_home_digit_children": policy.auto_protect_home_digit_children,
+        "auto_home_digit_zones": auto_zones,
+        "sensitive_zones": policy.sensitive_zones,
+        "sensitive_export_allow_zones": policy.sensitive_export_allow_zones,
+        "active_overrides": overrides,
+        "audit_only_mode": policy.audit_only_mode,
+    }))
+}
+
+fn mcp_cwd(args: &serde_json::Value, home: &str) -> String {
+    args.get("cwd")
+        .and_then(|cwd| cwd.as_str())
+        .map(str::to_string)
+        .or_else(|| {
+            std::env::current_dir()
+                .ok()
+                .map(|dir| dir.to_string_lossy().into_owned())
+        })
+        .and_then(|cwd| normalize_absolute_path(&cwd))
+        .unwrap_or_else(|| home.to_string())
+}
+
+/// Absolute path for a tool argument, expanding `~` and resolving against `cwd`.
+fn mcp_resolve_path(raw: &str, cwd: &str, home: &str) -> Option<String> {
+    let expanded = match raw.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => format!("{}{}", home, rest),
+        _ => raw.to_string(),
+    };
+    if expanded.starts_with('/') {
+        normalize_absolute_path(&expanded)
+    } else {
+        normalize_absolute_path(&join_path_component(cwd, &expanded))
+    }
+}
+
+fn mcp_path_arg(args: &serde_json::Value, home: &str) -> Result<(String, String), McpToolOutput> {
+    let cwd = mcp_cwd(args, home);
+    let raw = args
+        .get("path")
+        .and_then(|path| path.as_str())
+        .filter(|path| !path.is_empty())
+        .ok_or_else(|| McpToolOutput::error("missing required argument: path"))?;
+    let path = mcp_resolve_path(raw, &cwd, home)
+        .ok_or_else(|| McpToolOutput::error(format!("cannot resolve path: {}", raw)))?;
+    Ok((path, cwd))
+}
+
+fn mcp_check_path(home: &str, policy: &SecurityPolicy, args: &serde_json::Value) -> McpToolOutput {
+    let (path, _) = match mcp_path_arg(args, home) {
+        Ok(resolved) => resolved,
+        Err(output) => return output,
+    };
+    let in_zone = policy.is_in_any_zone(&path, home);
+    let protected = policy.is_protected(&path, home) && !is_system_temp(&path, home);
+    let sensitive = policy.is_sensitive_path(&path);
+    let guidance = if protected {
+        "AI delete, move-out and truncate operations on this path are denied. Use quarantine_path to move it aside, or ask the user to run agentsmith-override for it."
+    } else if in_zone {
+        "Inside a protected zone, but a temporary override is active or the path is exempt."
+    } else {
+        "Not protected."
+    };
+    McpToolOutput::json(&serde_json::json!({
+        "path": path,
+        "in_protected_zone": in_zone,
+        "zone": if in_zone { Some(policy.matched_zone(&path, home)) } else { None },
+        "protected": protected,
+        "override_active": policy.is_override_active_for_path(&path, now_ts(), None),
+        "truncate_gated": policy.is_truncate_gated_path(&path, home),
+        "sensitive": sensitive,
+        "sensitive_zone": if sensitive { Some(policy.matched_sensitive_zone(&path)) } else { None },
+        "sensitive_export_allowed": policy.is_sensitive_export_allowed(&path),
+        "guidance": guidance,
+    }))
+}
+
+fn mcp_check_command(home: &str, policy: &SecurityPolicy, args: &serde_json::Value) -> McpToolOutput {
+    let Some(command) = args.get("command").and_then(|command| command.as_str()) else {
+        return McpToolOutput::error("missing required argument: command");
+    };
+    let cwd = mcp_cwd(args, home);
+    let findings = preflight_command(command, &cwd, policy, home);
+    let denied = !findings.is_empty() && !policy.audit_only_mode;
+    let report = PreflightReport {
+        decision: if denied { "deny" } else { "allow" },
+        audit_only: policy.audit_only_mode,
+        feedback: preflight_feedback(&findings, home),
+        cwd,
+        findings,
+    };
+    match serde_json::to_value(&report) {
+        Ok(value) => McpToolOutput::json(&value),
+        Err(err) => McpToolOutput::error(format!("cannot encode report: {}", err)),
+    }
+}
+
+/// Run one of the installed helper scripts and hand its output back to the agent.
+fn mcp_run_helper(program: &str, args: &[String], cwd: &str) -> McpToolOutput {
+    let output = match Command::new(program)
+        .args(args)
+        .current_dir(cwd)
+        .stdin(std::process::Stdio::null())
+        .output()
+    {
+        Ok(output) => output,
+        Err(err) => {
+            return McpToolOutput::error(format!(
+                "cannot run {}: {} (is it installed in PATH?)",
+                program, err
+            ))
+        },
+    };
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    McpToolOutput {
+        text: text.trim_end().to_string(),
+        is_error: !output.status.success(),
+    }
+}
+
+fn mcp_quarantine_path(home: &str, args: &serde_json::Value) -> McpToolOutput {
+    match mcp_path_arg(args, home) {
+        Ok((path, cwd)) => mcp_run_helper("agentsmith-quarantine", &[path], &cwd),
+        Err(output) => output,
+    }
+}
+
+fn mcp_request_override(home: &str, args: &serde_json::Value) -> McpToolOutput {
+    let (path, cwd) = match mcp_path_arg(args, home) {
+        Ok(resolved) => resolved,
+        Err(output) => return output,
+    };
+    let minutes = args
+        .get("minutes")
+        .and_then(|minutes| minutes.as_u64())
+        .unwrap_or(MCP_DEFAULT_OVERRIDE_MINUTES);
+    let sensitive_read = args
+        .get("sensitive_read")
+        .and_then(|flag| flag.as_bool())
+        .unwrap_or(false);
+    let mut helper_args = Vec::new();
+    if sensitive_read {
+        helper_args.push("--sensitive-read".to_string());
+    }
+    helper_args.extend(["--minutes".to_string(), minutes.to_string(), path.clone()]);
+
+    let mut output = mcp_run_helper("agentsmith-override", &helper_args, &cwd);
+    if output.is_error {
+        // The daemon rejects requests with an AI ancestor; only a human can approve.
+        output.text.push_str(&format!(
+            "\n\nOverrides are approved by a human. Stop and ask the user to run:\n  agentsmith-override {}\nthen retry the operation.",
+            helper_args.join(" ")
+        ));
+    }
+    output
+}
+
+/// `None` when `name` is not one of our tools.
+fn mcp_call_tool(home: &str, policy: &SecurityPolicy, name: &str, args: &serde_json::Value) -> Option<McpToolOutput> {
+    Some(match name {
+        "get_last_denial" => mcp_get_last_denial(home, args),
+        "list_protected_zones" => mcp_list_protected_zones(home, policy),
+        "check_path" => mcp_check_path(home, policy, args),
+        "check_command" => mcp_check_command(home, policy, args),
+        "quarantine_path" => mcp_quarantine_path(home, args),
+        "request_override" => mcp_request_override(home, args),
+        _ => return None,
+    })
+}
+
+/// Handle one JSON-RPC message; notifications (no `id`) get no response.
+fn handle_mcp_message(home: &str, message: &serde_json::Value) -> Option<serde_json::Value> {
+    let id = message.get("id")?.clone();
+    let params = message.get("params").cloned().unwrap_or_else(|| serde_json::json!({}));
+    let result = match message.get("method").and_then(|method| method.as_str()) {
+        Some("initialize") => {
+            let requested = params.get("protocolVersion").and_then(|version| version.as_str());
+            let version = MCP_PROTOCOL_VERSIONS
+                .iter()
+                .find(|version| Some(**version) == requested)
+                .unwrap_or(&MCP_PROTOCOL_VERSIONS[0]);
+            Ok(serde_json::json!({
+                "protocolVersion": version,
+                "capabilities": {"tools": {}},
+                "serverInfo": {"name": "agentsmith-rs", "version": env!("CARGO_PKG_VERSION")},
+                "instructions": "agentsmith-rs guards protected zones on this Mac. When an operation fails with EPERM, call get_last_denial before retrying, prefer quarantine_path over deletion, and never try to work around a denial.",
+            }))
+        },
+        Some("ping") => Ok(serde_json::json!({})),
+        Some("tools/list") => Ok(serde_json::json!({ "tools": mcp_tools() })),
+        Some("tools/call") => {
+            let name = params.get("name").and_then(|name| name.as_str()).unwrap_or_default();
+            let args = params
+                .get("arguments")
+                .cloned()
+                .unwrap_or_else(|| serde_json::json!({}));
+            // Reload per call so zone and override changes show up without restarting.
+            let policy = load_user_policy(home);
+            match mcp_call_tool(home, &policy, name, &args) {
+                Some(output) => Ok(serde_json::json!({
+                    "content": [{"type": "text", "text": output.text}],
+                    "isError": output.is_error,
+                })),
+                None => Err((-32602, format!("unknown tool: {}", name))),
+            }
+        },
+        Some(method) => Err((-32601, format!("method not found: {}", method))),
+        None => Err((-32600, "invalid request: missing method".to_string())),
+    };
+    Some(match result {
+        Ok(result) => serde_json::json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err((code, message)) => serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": {"code": code, "message": message},
+        }),
+    })
+}
+
+/// `agentsmith-rs mcp`: Model Context Protocol server over stdio, one JSON-RPC
+/// message per line. stdout carries only protocol traffic.
+pub fn run_mcp(args: &[String]) -> i32 {
+    if let Some(arg) = args.first() {
+        if matches!(arg.as_str(), "--help" | "-h") {
+            println!("Usage: agentsmith-rs mcp\nServe guard status, denials and override requests to agents over MCP (stdio).");
+            return 0;
+        }
+        eprintln!("[mcp] unknown argument: {}", arg);
+        return 1;
+    }
+
+    let home = std::env::var("HOME").unwrap_or_else(|_| "/root".into());
+    for line in io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                eprintln!("[mcp] cannot read stdin: {}", err);
+                return 1;
+            },
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<serde_json::Value>(&line) {
+            Ok(message) => handle_mcp_message(&home, &message),
+            Err(err) => Some(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": null,
+                "error": {"code": -32700, "message": format!("parse error: {}", err)},
+            })),
+        };
+        if let Some(response) = response {
+            let mut stdout = io::stdout().lock();
+            if writeln!(stdout, "{}", response).and_then(|_| stdout.flush()).is_err() {
+                return 1;
+            }
+        }
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_policy;
+    use crate::REASON_PROTECTED_ZONE_AI_DELETE;
+
+    #[test]
+    fn mcp_handles_initialize_list_and_unknown_methods() {
+        let init = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {"protocolVersion": "2025-03-26", "capabilities": {}}
+        });
+        let response = handle_mcp_message("/Users/jqwang", &init).expect("initialize response");
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"]["protocolVersion"], "2025-03-26");
+        assert!(response["result"]["capabilities"]["tools"].is_object());
+
+        let initialized = serde_json::json!({"jsonrpc": "2.0", "method": "notifications/initialized"});
+        assert!(handle_mcp_message("/Users/jqwang", &initialized).is_none());
+
+        let list = serde_json::json!({"jsonrpc": "2.0", "id": "l", "method": "tools/list"});
+        let response = handle_mcp_message("/Users/jqwang", &list).expect("tools/list response");
+        let names: Vec<&str> = response["result"]["tools"]
+            .as_array()
+            .expect("tools array")
+            .iter()
+            .filter_map(|tool| tool["name"].as_str())
+            .collect();
+        for expected in [
+            "get_last_denial",
+            "list_protected_zones",
+            "check_path",
+            "quarantine_path",
+            "request_override",
+        ] {
+            assert!(names.contains(&expected), "missing tool {}", expected);
+        }
+
+        let unknown = serde_json::json!({"jsonrpc": "2.0", "id": 2, "method": "resources/list"});
+        let response = handle_mcp_message("/Users/jqwang", &unknown).expect("error response");
+        assert_eq!(response["error"]["code"], -32601);
+        let bad_tool = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "tools/call",
+            "params": {"name": "delete_everything", "arguments": {}}
+        });
+        let response = handle_mcp_message("/Users/jqwang", &bad_tool).expect("error response");
+        assert_eq!(response["error"]["code"], -32602);
+    }
+
+    #[test]
+    fn mcp_check_tools_use_policy_decisions() {
+        let policy = test_policy();
+        let home = "/Users/jqwang";
+
+        let args = serde_json::json!({"path": "build", "cwd": "/Users/jqwang/project"});
+        let output = mcp_call_tool(home, &policy, "check_path", &args).expect("known tool");
+        assert!(!output.is_error);
+        let result: serde_json::Value = serde_json::from_str(&output.text).expect("check_path json");
+        assert_eq!(result["path"], "/Users/jqwang/project/build");
+        assert_eq!(result["protected"], true);
+        assert_eq!(result["zone"], "/Users/jqwang/project");
+
+        let args = serde_json::json!({"path": "~/scratch"});
+        let output = mcp_call_tool(home, &policy, "check_path", &args).expect("known tool");
+        let result: serde_json::Value = serde_json::from_str(&output.text).expect("check_path json");
+        assert_eq!(result["path"], "/Users/jqwang/scratch");
+        assert_eq!(result["protected"], false);
+
+        let args = serde_json::json!({"command": "git clean -fdx", "cwd": "/Users/jqwang/project"});
+        let output = mcp_call_tool(home, &policy, "check_command", &args).expect("known tool");
+        let result: serde_json::Value = serde_json::from_str(&output.text).expect("check_command json");
+        assert_eq!(result["decision"], "deny");
+        assert_eq!(
+            result["findings"][0]["reason"],
+            REASON_PROTECTED_ZONE_AI_DELETE
+        );
+
+        let output = mcp_call_tool(home, &policy, "check_path", &serde_json::json!({})).expect("known tool");
+        assert!(output.is_error);
+    }
+
+    #[test]
+    fn caller_feedback_dir_prefers_session_then_ancestor_pid() {
+        let feedback_root = std::env::temp_dir().join(format!(
+            "agentsmith-mcp-feedback-{}-{}",
+            std::process::id(),
+            now_ts()
+        ));
+        let pid = std::process::id() as i32;
+        for key in ["claude-code-77-5".to_string(), format!("pid-{}", pid)] {
+            let dir = feedback_root.join(key);
+            fs::create_dir_all(&dir).expect("create feedback dir");
+            fs::write(dir.join("last_denial.json"), "{}\n").expect("write last denial");
+        }
+
+        assert_eq!(
+            caller_feedback_dir(&feedback_root, Some("claude-code-77-5"), pid),
+            Some(feedback_root.join("claude-code-77-5"))
+        );
+        assert_eq!(
+            caller_feedback_dir(&feedback_root, Some("missing-1-1"), pid),
+            None
+        );
+        assert_eq!(
+            newest_session_feedback_dir(&feedback_root, 77),
+            Some(feedback_root.join("claude-code-77-5"))
+        );
+        if std::env::var("AGENTSMITH_SESSION_ID").is_err() {
+            assert_eq!(
+                caller_feedback_dir(&feedback_root, None, pid),
+                Some(feedback_root.join(format!("pid-{}", pid)))
+            );
+        }
+
+        let _ = fs::remove_dir_all(feedback_root);
+    }
+}
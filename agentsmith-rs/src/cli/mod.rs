@@ -0,0 +1,45 @@
+//! `agentsmith-rs <subcommand>` entry points. These run as the calling user,
+//! not as the daemon, and read the policy the daemon enforces.
+
+mod agent_instructions;
+mod correlate;
+mod ctl;
+mod mcp;
+mod policy;
+mod preflight;
+mod verify_logs;
+
+use crate::{load_policy, load_runtime_overrides, runtime_override_db_path, SecurityPolicy};
+
+/// Policy as the daemon currently enforces it, for subcommands running as the user.
+fn load_user_policy(home: &str) -> SecurityPolicy {
+    let mut policy = load_policy(&format!("{}/.agentsmith-rs/policy.json", home)).unwrap_or_default();
+    // Same override source as the daemon, so an approved override is reflected here too.
+    if let Ok(overrides) = load_runtime_overrides(&runtime_override_db_path(home)) {
+        policy.temporary_overrides = overrides;
+    }
+    policy
+}
+
+/// `~/...` for paths under home, so generated files read the same on every account.
+fn display_home_path(path: &str, home: &str) -> String {
+    match path.strip_prefix(home) {
+        Some("") => "~".to_string(),
+        Some(rest) if rest.starts_with('/') => format!("~{}", rest),
+        _ => path.to_string(),
+    }
+}
+
+/// Subcommands run instead of the daemon when the first argument names one.
+pub fn run_subcommand(args: &[String]) -> Option<i32> {
+    match args.first().map(String::as_str)? {
+        "preflight" => Some(preflight::run_preflight(&args[1..])),
+        "mcp" => Some(mcp::run_mcp(&args[1..])),
+        "correlate" => Some(correlate::run_correlate(&args[1..])),
+        "agent-instructions" => Some(agent_instructions::run_agent_instructions(&args[1..])),
+        "policy" => Some(policy::run_policy_io(&args[1..])),
+        "ctl" => Some(ctl::run_ctl(&args[1..])),
+        "verify-logs" => Some(verify_logs::run_verify_logs(&args[1..])),
+        _ => None,
+    }
+}
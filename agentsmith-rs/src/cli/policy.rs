@@ -0,0 +1,660 @@
+//! `agentsmith-rs policy import|export`: translate between the guard policy and
+//! Claude Code / Codex permission settings.
+
+use super::display_home_path;
+use crate::protocol::normalize_absolute_path;
+use crate::{default_exec_exfil_tool_blocklist, exe_name, join_path_component, load_policy, SecurityPolicy};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+/// Agent-native permission formats `agentsmith-rs policy import/export` translates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PolicyInterchangeFormat {
+    /// Claude Code `settings.json` `permissions.deny` rules.
+    ClaudeSettings,
+    /// Codex `config.toml` sandbox settings.
+    Codex,
+}
+
+impl PolicyInterchangeFormat {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "claude-settings" | "claude" => Some(Self::ClaudeSettings),
+            "codex" | "codex-config" => Some(Self::Codex),
+            _ => None,
+        }
+    }
+}
+
+/// Rules that did not survive a translation, so the user can carry them over by hand.
+#[derive(Debug, Default)]
+struct PolicyTranslationReport {
+    translated: usize,
+    approximated: Vec<String>,
+    unsupported: Vec<String>,
+}
+
+impl PolicyTranslationReport {
+    fn approximate(&mut self, rule: impl std::fmt::Display, note: &str) {
+        self.translated += 1;
+        self.approximated.push(format!("{}: {}", rule, note));
+    }
+
+    fn unsupported(&mut self, rule: impl std::fmt::Display, note: &str) {
+        self.unsupported.push(format!("{}: {}", rule, note));
+    }
+
+    fn print(&self, target: &str) {
+        eprintln!(
+            "[policy] {} rule(s) translated to {}",
+            self.translated, target
+        );
+        for entry in &self.approximated {
+            eprintln!("[policy] approximated: {}", entry);
+        }
+        for entry in &self.unsupported {
+            eprintln!("[policy] not expressible in {}: {}", target, entry);
+        }
+    }
+}
+
+/// Split `Read(./secrets/**)` into (`Read`, `Some("./secrets/**")`).
+fn parse_claude_permission_rule(rule: &str) -> (&str, Option<&str>) {
+    match rule.split_once('(') {
+        Some((tool, rest)) if rest.ends_with(')') => (tool.trim(), Some(&rest[..rest.len() - 1])),
+        _ => (rule.trim(), None),
+    }
+}
+
+/// Absolute directory for a Claude Code path pattern. `//abs` is absolute,
+/// `~/` is home, `/x` is relative to the project that owns the settings file
+/// and anything else to the current directory. Only "this directory and
+/// everything below it" patterns have a zone equivalent.
+fn claude_permission_path(pattern: &str, home: &str, project_root: &str, cwd: &str) -> Option<String> {
+    let pattern = pattern.trim();
+    let absolute = if let Some(rest) = pattern.strip_prefix("//") {
+        format!("/{}", rest)
+    } else if pattern == "~" || pattern.starts_with("~/") {
+        format!("{}{}", home, &pattern[1..])
+    } else if let Some(rest) = pattern.strip_prefix('/') {
+        join_path_component(project_root, rest)
+    } else {
+        join_path_component(cwd, pattern.trim_start_matches("./"))
+    };
+    let trimmed = absolute
+        .trim_end_matches("/**")
+        .trim_end_matches("/*")
+        .trim_end_matches("**");
+    if trimmed.contains(['*', '?', '[', '{']) {
+        return None;
+    }
+    normalize_absolute_path(trimmed)
+}
+
+/// `Bash(curl:*)`, `Bash(curl *)` and `Bash(curl)` deny a whole program.
+fn claude_bash_rule_program(spec: &str) -> Option<&str> {
+    let program = spec.trim().trim_end_matches(":*").trim_end_matches(" *").trim();
+    (!program.is_empty() && !program.contains(char::is_whitespace) && !program.contains(['*', '?'])).then_some(program)
+}
+
+fn push_unique_json_string(policy: &mut serde_json::Value, key: &str, default: Vec<String>, value: String) -> bool {
+    let Some(object) = policy.as_object_mut() else {
+        return false;
+    };
+    let entry = object
+        .entry(key.to_string())
+        .or_insert_with(|| serde_json::json!(default));
+    let Some(items) = entry.as_array_mut() else {
+        return false;
+    };
+    if items.iter().any(|item| item.as_str() == Some(value.as_str())) {
+        return false;
+    }
+    items.push(serde_json::Value::String(value));
+    true
+}
+
+/// Merge Claude Code `permissions.deny` rules into a policy JSON object.
+fn import_claude_settings(
+    settings: &serde_json::Value,
+    policy: &mut serde_json::Value,
+    home: &str,
+    project_root: &str,
+    cwd: &str,
+) -> PolicyTranslationReport {
+    let mut report = PolicyTranslationReport::default();
+    let permissions = settings.get("permissions");
+    for kind in ["allow", "ask"] {
+        let count = permissions
+            .and_then(|permissions| permissions.get(kind))
+            .and_then(|rules| rules.as_array())
+            .map_or(0, Vec::len);
+        if count > 0 {
+            report.unsupported(
+                format!("permissions.{} ({} rule(s))", kind, count),
+                "agentsmith has no standing allow or prompt rules; use agentsmith-override for one-off access",
+            );
+        }
+    }
+    let rules = permissions
+        .and_then(|permissions| permissions.get("deny"))
+        .and_then(|rules| rules.as_array())
+        .cloned()
+        .unwrap_or_default();
+    for rule in rules.iter().filter_map(|rule| rule.as_str()) {
+        let (tool, spec) = parse_claude_permission_rule(rule);
+        match (tool, spec) {
+            ("Read", Some(pattern)) => match claude_permission_path(pattern, home, project_root, cwd) {
+                Some(path) => {
+                    push_unique_json_string(policy, "sensitive_zones", Vec::new(), path);
+                    report.approximate(
+                        rule,
+                        "imported as a sensitive zone: agents may read it, but their writes out of the zone are denied afterwards",
+                    );
+                },
+                None => report.unsupported(rule, "zones are directory prefixes, not glob patterns"),
+            },
+            ("Edit" | "Write" | "MultiEdit" | "NotebookEdit", Some(pattern)) => {
+                match claude_permission_path(pattern, home, project_root, cwd) {
+                    Some(path) => {
+                        push_unique_json_string(policy, "protected_zones", Vec::new(), path);
+                        report.approximate(
+                            rule,
+                            "imported as a protected zone, which blocks deletes, moves out and truncation but not in-place edits",
+                        );
+                    },
+                    None => report.unsupported(rule, "zones are directory prefixes, not glob patterns"),
+                }
+            },
+            ("Bash", Some(spec)) => match claude_bash_rule_program(spec) {
+                Some(program) => {
+                    push_unique_json_string(
+                        policy,
+                        "exec_exfil_tool_blocklist",
+                        default_exec_exfil_tool_blocklist(),
+                        exe_name(program).to_string(),
+                    );
+                    report.translated += 1;
+                },
+                None => report.unsupported(
+                    rule,
+                    "only whole programs can be blocked, not argument patterns",
+                ),
+            },
+            _ => report.unsupported(rule, "no matching agentsmith gate"),
+        }
+    }
+    report
+}
+
+/// Claude Code `permissions.deny` rules equivalent to the policy.
+fn export_claude_settings(policy: &SecurityPolicy, home: &str) -> (serde_json::Value, PolicyTranslationReport) {
+    let mut report = PolicyTranslationReport::default();
+    let mut deny = Vec::new();
+    let claude_path = |path: &str| match display_home_path(path, home) {
+        display if display.starts_with('~') => format!("{}/**", display),
+        _ => format!("/{}/**", path.trim_end_matches('/')),
+    };
+
+    for zone in &policy.sensitive_zones {
+        let rule = format!("Read({})", claude_path(zone));
+        report.approximate(
+            &rule,
+            "Claude Code denies the read itself, while agentsmith lets agents read and blocks moving the data out",
+        );
+        deny.push(rule);
+    }
+    if policy.exec_gate_enabled {
+        for tool in &policy.exec_exfil_tool_blocklist {
+            deny.push(format!("Bash({}:*)", tool));
+            report.translated += 1;
+        }
+    }
+    for zone in &policy.protected_zones {
+        report.unsupported(
+            format!("protected zone {}", zone),
+            "Claude Code cannot deny only deletes and moves for a path; add the `agentsmith-rs preflight` PreToolUse hook",
+        );
+    }
+    if policy.auto_protect_home_digit_children {
+        report.unsupported(
+            "auto_protect_home_digit_children",
+            "Claude Code cannot match directories by name prefix",
+        );
+    }
+    if !policy.sensitive_export_allow_zones.is_empty() {
+        report.unsupported(
+            "sensitive_export_allow_zones",
+            "Claude Code has no data-flow rules",
+        );
+    }
+    if policy.truncate_gate_enabled {
+        report.unsupported(
+            "truncate_gate_enabled",
+            "Claude Code cannot deny only truncation",
+        );
+    }
+    if policy.metadata_gate_enabled {
+        report.unsupported(
+            "metadata_gate_enabled",
+            "Claude Code cannot deny only metadata changes",
+        );
+    }
+    (
+        serde_json::json!({ "permissions": { "deny": deny } }),
+        report,
+    )
+}
+
+/// Codex `config.toml` settings that overlap with the policy.
+#[derive(Debug, Default, Deserialize)]
+struct CodexSandboxConfig {
+    #[serde(default)]
+    sandbox_mode: Option<String>,
+    #[serde(default)]
+    approval_policy: Option<String>,
+    #[serde(default)]
+    sandbox_workspace_write: Option<CodexWorkspaceWrite>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct CodexWorkspaceWrite {
+    #[serde(default)]
+    writable_roots: Vec<String>,
+    #[serde(default)]
+    network_access: Option<bool>,
+}
+
+fn import_codex_config(config: &CodexSandboxConfig, policy: &mut serde_json::Value) -> PolicyTranslationReport {
+    let mut report = PolicyTranslationReport::default();
+    match config.sandbox_mode.as_deref() {
+        Some("read-only") => report.unsupported(
+            "sandbox_mode = \"read-only\"",
+            "agentsmith guards deletes, moves and reads in zones, not all writes",
+        ),
+        Some(mode) => report.unsupported(
+            format!("sandbox_mode = \"{}\"", mode),
+            "the Codex sandbox and agentsmith enforce independently; nothing to import",
+        ),
+        None => {},
+    }
+    if let Some(approval) = &config.approval_policy {
+        report.unsupported(
+            format!("approval_policy = \"{}\"", approval),
+            "agentsmith never prompts; denials are final until a human grants an override",
+        );
+    }
+    let Some(workspace) = &config.sandbox_workspace_write else {
+        return report;
+    };
+    if !workspace.writable_roots.is_empty() {
+        report.unsupported(
+            format!(
+                "sandbox_workspace_write.writable_roots ({} path(s))",
+                workspace.writable_roots.len()
+            ),
+            "agentsmith lists protected paths rather than writable ones",
+        );
+    }
+    if workspace.network_access == Some(false) {
+        let object = policy.as_object_mut();
+        if let Some(object) = object {
+            object.insert(
+                "exec_gate_enabled".to_string(),
+                serde_json::Value::Bool(true),
+            );
+            object
+                .entry("exec_exfil_tool_blocklist".to_string())
+                .or_insert_with(|| serde_json::json!(default_exec_exfil_tool_blocklist()));
+        }
+        report.approximate(
+            "sandbox_workspace_write.network_access = false",
+            "enabled the exec gate for network tools (exec_exfil_tool_blocklist); other network access is not blocked",
+        );
+    }
+    report
+}
+
+fn toml_string_array(items: &[String]) -> String {
+    let quoted: Vec<String> = items
+        .iter()
+        .map(|item| serde_json::Value::String(item.clone()).to_string())
+        .collect();
+    format!("[{}]", quoted.join(", "))
+}
+
+/// Codex sandbox settings closest to the policy, as a `config.toml` fragment.
+fn export_codex_config(policy: &SecurityPolicy) -> (String, PolicyTranslationReport) {
+    let mut report = PolicyTranslationReport::default();
+    let mut out =
+        String::from("# Generated by `agentsmith-rs policy export --to codex`.\nsandbox_mode = \"workspace-write\"\n");
+    if policy.exec_gate_enabled && !policy.exec_exfil_tool_blocklist.is_empty() {
+        out.push_str("\n[sandbox_workspace_write]\nnetwork_access = false\n");
+        report.approximate(
+            format!(
+                "exec_exfil_tool_blocklist {}",
+                toml_string_array(&policy.exec_exfil_tool_blocklist)
+            ),
+            "exported as network_access = false, which blocks all network access in the sandbox",
+        );
+    }
+    for zone in &policy.protected_zones {
+        report.unsupported(
+            format!("protected zone {}", zone),
+            "the Codex sandbox lists writable roots and cannot deny deletes under one",
+        );
+    }
+    if policy.auto_protect_home_digit_children {
+        report.unsupported(
+            "auto_protect_home_digit_children",
+            "Codex cannot match directories by name prefix",
+        );
+    }
+    for zone in &policy.sensitive_zones {
+        report.unsupported(
+            format!("sensitive zone {}", zone),
+            "the Codex sandbox cannot deny reads of specific paths",
+        );
+    }
+    (out, report)
+}
+
+fn policy_io_usage() -> &'static str {
+    "Usage:\n  agentsmith-rs policy import --from claude-settings|codex FILE [--policy FILE] [--output FILE]\n  agentsmith-rs policy export --to claude-settings|codex [--output FILE]\n\
+     import prints the policy with the translated rules merged in; export prints the agent config fragment.\n\
+     Rules that cannot be expressed on the other side are reported on stderr.\n"
+}
+
+fn write_policy_io_output(output: Option<&str>, content: &str) -> i32 {
+    match output {
+        Some(path) => match fs::write(path, content) {
+            Ok(()) => 0,
+            Err(err) => {
+                eprintln!("[policy] cannot write {}: {}", path, err);
+                1
+            },
+        },
+        None => {
+            print!("{}", content);
+            0
+        },
+    }
+}
+
+/// `agentsmith-rs policy import|export`.
+pub fn run_policy_io(args: &[String]) -> i32 {
+    let Some(action) = args.first().map(String::as_str) else {
+        eprint!("{}", policy_io_usage());
+        return 1;
+    };
+    if matches!(action, "--help" | "-h") {
+        print!("{}", policy_io_usage());
+        return 0;
+    }
+    let home = std::env::var("HOME").unwrap_or_else(|_| "/root".into());
+    let mut format = None;
+    let mut source = None;
+    let mut policy_path = format!("{}/.agentsmith-rs/policy.json", home);
+    let mut output = None;
+    let mut index = 1;
+    while let Some(arg) = args.get(index) {
+        let value = args.get(index + 1);
+        match (arg.as_str(), value) {
+            ("--from", Some(value)) if action == "import" => format = PolicyInterchangeFormat::parse(value),
+            ("--to", Some(value)) if action == "export" => format = PolicyInterchangeFormat::parse(value),
+            ("--policy", Some(value)) => policy_path = value.clone(),
+            ("--output" | "-o", Some(value)) => output = Some(value.clone()),
+            (file, _) if action == "import" && source.is_none() && !file.starts_with('-') => {
+                source = Some(file.to_string());
+                index += 1;
+                continue;
+            },
+            _ => {
+                eprintln!("[policy] invalid argument: {}\n{}", arg, policy_io_usage());
+                return 1;
+            },
+        }
+        index += 2;
+    }
+    let Some(format) = format else {
+        eprintln!("[policy] missing or unknown format\n{}", policy_io_usage());
+        return 1;
+    };
+
+    match action {
+        "import" => {
+            let Some(source) = source else {
+                eprintln!("[policy] missing FILE to import\n{}", policy_io_usage());
+                return 1;
+            };
+            let content = match fs::read_to_string(&source) {
+                Ok(content) => content,
+                Err(err) => {
+                    eprintln!("[policy] cannot read {}: {}", source, err);
+                    return 1;
+                },
+            };
+            // Merge into the raw JSON so fields the file leaves to defaults stay unset.
+            let mut policy = fs::read_to_string(&policy_path)
+                .ok()
+                .and_then(|text| serde_json::from_str::<serde_json::Value>(&text).ok())
+                .filter(serde_json::Value::is_object)
+                .unwrap_or_else(|| serde_json::json!({}));
+            let report = match format {
+                PolicyInterchangeFormat::ClaudeSettings => {
+                    let settings = match serde_json::from_str::<serde_json::Value>(&content) {
+                        Ok(settings) => settings,
+                        Err(err) => {
+                            eprintln!("[policy] invalid Claude Code settings {}: {}", source, err);
+                            return 1;
+                        },
+                    };
+                    let cwd = std::env::current_dir()
+                        .ok()
+                        .map(|dir| dir.to_string_lossy().into_owned())
+                        .unwrap_or_else(|| home.clone());
+                    let source_path = if source.starts_with('/') {
+                        source.clone()
+                    } else {
+                        join_path_component(&cwd, &source)
+                    };
+                    // `.claude/settings.json` belongs to the directory containing `.claude`.
+                    let settings_dir = Path::new(&source_path).parent().unwrap_or(Path::new("/"));
+                    let project_root = if settings_dir.file_name().is_some_and(|name| name == ".claude") {
+                        settings_dir.parent().unwrap_or(settings_dir)
+                    } else {
+                        settings_dir
+                    };
+                    import_claude_settings(
+                        &settings,
+                        &mut policy,
+                        &home,
+                        &project_root.to_string_lossy(),
+                        &cwd,
+                    )
+                },
+                PolicyInterchangeFormat::Codex => match toml::from_str::<CodexSandboxConfig>(&content) {
+                    Ok(config) => import_codex_config(&config, &mut policy),
+                    Err(err) => {
+                        eprintln!("[policy] invalid Codex config {}: {}", source, err);
+                        return 1;
+                    },
+                },
+            };
+            if let Err(err) = serde_json::from_value::<SecurityPolicy>(policy.clone()) {
+                eprintln!("[policy] merged policy does not decode: {}", err);
+                return 1;
+            }
+            report.print("agentsmith policy");
+            let rendered = serde_json::to_string_pretty(&policy).unwrap_or_default();
+            write_policy_io_output(output.as_deref(), &format!("{}\n", rendered))
+        },
+        "export" => {
+            let policy = load_policy(&policy_path).unwrap_or_default();
+            let (rendered, report, target) = match format {
+                PolicyInterchangeFormat::ClaudeSettings => {
+                    let (settings, report) = export_claude_settings(&policy, &home);
+                    let rendered = serde_json::to_string_pretty(&settings).unwrap_or_default();
+                    (format!("{}\n", rendered), report, "Claude Code settings")
+                },
+                PolicyInterchangeFormat::Codex => {
+                    let (config, report) = export_codex_config(&policy);
+                    (config, report, "Codex config")
+                },
+            };
+            report.print(target);
+            write_policy_io_output(output.as_deref(), &rendered)
+        },
+        other => {
+            eprintln!("[policy] unknown action: {}\n{}", other, policy_io_usage());
+            1
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_policy;
+
+    #[test]
+    fn claude_permission_paths_resolve_like_claude_code() {
+        let home = "/Users/jqwang";
+        let root = "/Users/jqwang/project";
+        let cwd = "/Users/jqwang/project/sub";
+        assert_eq!(
+            parse_claude_permission_rule("Read(./.env)"),
+            ("Read", Some("./.env"))
+        );
+        assert_eq!(parse_claude_permission_rule("WebFetch"), ("WebFetch", None));
+        assert_eq!(
+            claude_permission_path("//etc/secrets/**", home, root, cwd).as_deref(),
+            Some("/etc/secrets")
+        );
+        assert_eq!(
+            claude_permission_path("~/.ssh/**", home, root, cwd).as_deref(),
+            Some("/Users/jqwang/.ssh")
+        );
+        assert_eq!(
+            claude_permission_path("/build/**", home, root, cwd).as_deref(),
+            Some("/Users/jqwang/project/build")
+        );
+        assert_eq!(
+            claude_permission_path("./.env", home, root, cwd).as_deref(),
+            Some("/Users/jqwang/project/sub/.env")
+        );
+        assert_eq!(claude_permission_path("**/*.pem", home, root, cwd), None);
+        assert_eq!(claude_bash_rule_program("curl:*"), Some("curl"));
+        assert_eq!(claude_bash_rule_program("npm publish:*"), None);
+    }
+
+    #[test]
+    fn claude_settings_import_merges_denies_and_reports_the_rest() {
+        let settings = serde_json::json!({
+            "permissions": {
+                "allow": ["Bash(ls:*)"],
+                "deny": [
+                    "Read(~/.ssh/**)",
+                    "Read(**/*.pem)",
+                    "Edit(/src/**)",
+                    "Bash(curl:*)",
+                    "Bash(ssh:*)",
+                    "Bash(npm publish:*)",
+                    "WebFetch"
+                ]
+            }
+        });
+        let mut policy = serde_json::json!({"protected_zones": ["/Users/jqwang/0"]});
+        let report = import_claude_settings(
+            &settings,
+            &mut policy,
+            "/Users/jqwang",
+            "/Users/jqwang/project",
+            "/Users/jqwang/project",
+        );
+
+        assert_eq!(
+            policy["sensitive_zones"],
+            serde_json::json!(["/Users/jqwang/.ssh"])
+        );
+        assert_eq!(
+            policy["protected_zones"],
+            serde_json::json!(["/Users/jqwang/0", "/Users/jqwang/project/src"])
+        );
+        let blocklist: Vec<String> =
+            serde_json::from_value(policy["exec_exfil_tool_blocklist"].clone()).expect("blocklist");
+        assert!(blocklist.contains(&"scp".to_string()));
+        assert_eq!(blocklist.iter().filter(|tool| *tool == "curl").count(), 1);
+        assert!(blocklist.contains(&"ssh".to_string()));
+        assert_eq!(report.translated, 4);
+        assert_eq!(report.approximated.len(), 2);
+        assert_eq!(report.unsupported.len(), 4);
+        assert!(report
+            .unsupported
+            .iter()
+            .any(|entry| entry.starts_with("Bash(npm publish:*)")));
+        assert!(report
+            .unsupported
+            .iter()
+            .any(|entry| entry.starts_with("permissions.allow")));
+        serde_json::from_value::<SecurityPolicy>(policy).expect("merged policy decodes");
+    }
+
+    #[test]
+    fn claude_settings_export_covers_sensitive_zones_and_blocked_tools() {
+        let mut policy = test_policy();
+        policy.sensitive_zones = vec!["/Users/jqwang/.ssh".to_string(), "/etc/secrets".to_string()];
+        let (settings, report) = export_claude_settings(&policy, "/Users/jqwang");
+        let deny: Vec<String> = serde_json::from_value(settings["permissions"]["deny"].clone()).expect("deny rules");
+        assert!(deny.contains(&"Read(~/.ssh/**)".to_string()));
+        assert!(deny.contains(&"Read(//etc/secrets/**)".to_string()));
+        assert!(deny.contains(&"Bash(curl:*)".to_string()));
+        assert!(report
+            .unsupported
+            .iter()
+            .any(|entry| entry.starts_with("protected zone /Users/jqwang/project")));
+        assert!(report
+            .unsupported
+            .iter()
+            .any(|entry| entry.starts_with("auto_protect_home_digit_children")));
+    }
+
+    #[test]
+    fn codex_config_round_trips_network_access() {
+        let config: CodexSandboxConfig = toml::from_str(
+            r#"
+            sandbox_mode = "workspace-write"
+            approval_policy = "on-request"
+            model = "gpt-5"
+
+            [sandbox_workspace_write]
+            writable_roots = ["/Users/jqwang/cache"]
+            network_access = false
+            "#,
+        )
+        .expect("codex config parses");
+        let mut policy = serde_json::json!({"exec_gate_enabled": false});
+        let report = import_codex_config(&config, &mut policy);
+        assert_eq!(policy["exec_gate_enabled"], true);
+        assert!(policy["exec_exfil_tool_blocklist"]
+            .as_array()
+            .is_some_and(|tools| !tools.is_empty()));
+        assert_eq!(report.approximated.len(), 1);
+        assert_eq!(report.unsupported.len(), 3);
+
+        let (exported, report) = export_codex_config(&test_policy());
+        let parsed: CodexSandboxConfig = toml::from_str(&exported).expect("exported config parses");
+        assert_eq!(parsed.sandbox_mode.as_deref(), Some("workspace-write"));
+        assert_eq!(
+            parsed
+                .sandbox_workspace_write
+                .and_then(|workspace| workspace.network_access),
+            Some(false)
+        );
+        assert!(report
+            .unsupported
+            .iter()
+            .any(|entry| entry.starts_with("protected zone /Users/jqwang/project")));
+    }
+}
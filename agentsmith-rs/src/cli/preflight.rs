@@ -0,0 +1,252 @@
+//! `agentsmith-rs preflight`: agent hook front end for [`crate::preflight`].
+
+use super::load_user_policy;
+use crate::exe_name;
+use crate::preflight::{preflight_command, preflight_feedback, PreflightReport};
+use std::io::{self, Read};
+
+/// How `agentsmith-rs preflight` reports its verdict to the calling hook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PreflightFormat {
+    /// Claude Code PreToolUse hook: `hookSpecificOutput.permissionDecision`.
+    Claude,
+    /// Codex-style hook: `{"decision":"block"}` on stdout, feedback on stderr, exit 2.
+    Codex,
+    /// Full report for scripts and other agents.
+    Json,
+}
+
+impl PreflightFormat {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "claude" | "claude-code" => Some(Self::Claude),
+            "codex" => Some(Self::Codex),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Shell command and cwd from a hook payload: Claude Code sends
+/// `tool_input.command`, Codex sends `command` as a string or argv array.
+fn preflight_input_from_hook(payload: &serde_json::Value) -> Option<(String, Option<String>)> {
+    if let Some(tool) = payload.get("tool_name").and_then(|tool| tool.as_str()) {
+        if !matches!(tool, "Bash" | "shell" | "local_shell" | "exec_command") {
+            return None;
+        }
+    }
+    let input = payload.get("tool_input").unwrap_or(payload);
+    let command = match input.get("command")? {
+        serde_json::Value::String(command) => command.clone(),
+        serde_json::Value::Array(argv) => {
+            let argv: Vec<&str> = argv.iter().filter_map(|arg| arg.as_str()).collect();
+            match argv.as_slice() {
+                [shell, flag, script, ..]
+                    if matches!(exe_name(shell), "bash" | "sh" | "zsh")
+                        && flag.starts_with('-')
+                        && flag.contains('c') =>
+                {
+                    script.to_string()
+                },
+                _ => argv
+                    .iter()
+                    .map(|arg| format!("'{}'", arg.replace('\'', r"'\''")))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            }
+        },
+        _ => return None,
+    };
+    let cwd = ["cwd", "workdir"]
+        .iter()
+        .find_map(|key| input.get(*key).or_else(|| payload.get(*key)))
+        .and_then(|cwd| cwd.as_str())
+        .map(str::to_string);
+    Some((command, cwd))
+}
+
+fn preflight_usage() -> &'static str {
+    "Usage: agentsmith-rs preflight [--format claude|codex|json] [--cwd DIR] [--command CMD]\n\
+     Without --command, a hook payload is read from stdin (Claude Code PreToolUse or Codex).\n"
+}
+
+/// `agentsmith-rs preflight`: exit 0 when allowed; on deny the claude format
+/// still exits 0 (the decision is in the JSON), codex and json exit 2.
+pub fn run_preflight(args: &[String]) -> i32 {
+    let mut format = None;
+    let mut cwd = None;
+    let mut command = None;
+    let mut index = 0;
+    while let Some(arg) = args.get(index) {
+        if matches!(arg.as_str(), "--help" | "-h") {
+            print!("{}", preflight_usage());
+            return 0;
+        }
+        let Some(value) = args.get(index + 1).cloned() else {
+            eprintln!(
+                "[preflight] missing value for {}\n{}",
+                arg,
+                preflight_usage()
+            );
+            return 1;
+        };
+        match arg.as_str() {
+            "--format" => match PreflightFormat::parse(&value) {
+                Some(parsed) => format = Some(parsed),
+                None => {
+                    eprintln!(
+                        "[preflight] unknown format: {}\n{}",
+                        value,
+                        preflight_usage()
+                    );
+                    return 1;
+                },
+            },
+            "--cwd" => cwd = Some(value),
+            "--command" => command = Some(value),
+            other => {
+                eprintln!(
+                    "[preflight] unknown argument: {}\n{}",
+                    other,
+                    preflight_usage()
+                );
+                return 1;
+            },
+        }
+        index += 2;
+    }
+
+    let (command, cwd, format) = match command {
+        Some(command) => (command, cwd, format.unwrap_or(PreflightFormat::Json)),
+        None => {
+            let mut payload = String::new();
+            if let Err(err) = io::stdin().read_to_string(&mut payload) {
+                eprintln!("[preflight] cannot read hook payload: {}", err);
+                return 1;
+            }
+            let payload: serde_json::Value = match serde_json::from_str(&payload) {
+                Ok(payload) => payload,
+                Err(err) => {
+                    eprintln!("[preflight] invalid hook payload: {}", err);
+                    return 1;
+                },
+            };
+            let format = format.unwrap_or(if payload.get("hook_event_name").is_some() {
+                PreflightFormat::Claude
+            } else {
+                PreflightFormat::Json
+            });
+            match preflight_input_from_hook(&payload) {
+                Some((command, hook_cwd)) => (command, cwd.or(hook_cwd), format),
+                // Not a shell tool call: nothing to check.
+                None => return 0,
+            }
+        },
+    };
+
+    let home = std::env::var("HOME").unwrap_or_else(|_| "/root".into());
+    let policy = load_user_policy(&home);
+    let cwd = cwd
+        .or_else(|| {
+            std::env::current_dir()
+                .ok()
+                .map(|dir| dir.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| home.clone());
+    let findings = preflight_command(&command, &cwd, &policy, &home);
+    // In audit-only mode the daemon only records, so the hook must not block either.
+    let denied = !findings.is_empty() && !policy.audit_only_mode;
+    let feedback = preflight_feedback(&findings, &home);
+
+    match format {
+        PreflightFormat::Claude => {
+            if denied {
+                let output = serde_json::json!({
+                    "hookSpecificOutput": {
+                        "hookEventName": "PreToolUse",
+                        "permissionDecision": "deny",
+                        "permissionDecisionReason": feedback,
+                    }
+                });
+                println!("{}", output);
+            }
+            0
+        },
+        PreflightFormat::Codex => {
+            if !denied {
+                return 0;
+            }
+            let feedback = feedback.unwrap_or_default();
+            println!(
+                "{}",
+                serde_json::json!({"decision": "block", "reason": feedback})
+            );
+            eprint!("{}", feedback);
+            2
+        },
+        PreflightFormat::Json => {
+            let report = PreflightReport {
+                decision: if denied { "deny" } else { "allow" },
+                audit_only: policy.audit_only_mode,
+                cwd,
+                findings,
+                feedback,
+            };
+            match serde_json::to_string_pretty(&report) {
+                Ok(json) => println!("{}", json),
+                Err(err) => {
+                    eprintln!("[preflight] cannot encode report: {}", err);
+                    return 1;
+                },
+            }
+            if denied {
+                2
+            } else {
+                0
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_policy;
+
+    #[test]
+    fn preflight_reads_claude_and_codex_hook_payloads() {
+        let claude = serde_json::json!({
+            "hook_event_name": "PreToolUse",
+            "tool_name": "Bash",
+            "tool_input": {"command": "rm -rf build"},
+            "cwd": "/Users/jqwang/project"
+        });
+        assert_eq!(
+            preflight_input_from_hook(&claude),
+            Some((
+                "rm -rf build".to_string(),
+                Some("/Users/jqwang/project".to_string())
+            ))
+        );
+        let edit = serde_json::json!({"tool_name": "Edit", "tool_input": {"file_path": "/a"}});
+        assert_eq!(preflight_input_from_hook(&edit), None);
+
+        let codex = serde_json::json!({"command": ["bash", "-lc", "git clean -fdx"], "workdir": "/w"});
+        assert_eq!(
+            preflight_input_from_hook(&codex),
+            Some(("git clean -fdx".to_string(), Some("/w".to_string())))
+        );
+
+        let policy = test_policy();
+        let findings = preflight_command(
+            "rm -rf build",
+            "/Users/jqwang/project",
+            &policy,
+            "/Users/jqwang",
+        );
+        let feedback = preflight_feedback(&findings, "/Users/jqwang").expect("feedback for finding");
+        assert!(feedback.contains("/Users/jqwang/project/build"));
+        assert!(feedback.contains("Command: rm -rf build"));
+        assert_eq!(preflight_feedback(&[], "/Users/jqwang"), None);
+    }
+}
@@ -0,0 +1,419 @@
+//! `agentsmith-rs verify-logs`: check guard log hash chains against the signed
+//! checkpoints.
+
+use crate::{log_line_hash, read_guard_log, LogCheckpoint, LogCheckpointStore, CHAINED_LOG_NAMES};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+
+/// Result of checking one guard log against its hash chain and checkpoints.
+#[derive(Debug, Serialize, Default)]
+struct LogChainReport {
+    log: String,
+    records: usize,
+    first_seq: Option<u64>,
+    last_seq: Option<u64>,
+    unchained: usize,
+    checkpoints: usize,
+    issues: Vec<String>,
+}
+
+/// Walk `content` link by link. `checkpoints` must already be authenticated;
+/// they vouch for records that were later truncated or deleted from the tail.
+fn verify_log_chain(log: &str, content: &str, checkpoints: &[LogCheckpoint]) -> LogChainReport {
+    let checkpoints: Vec<&LogCheckpoint> = checkpoints.iter().filter(|checkpoint| checkpoint.log == log).collect();
+    let mut report = LogChainReport {
+        log: log.to_string(),
+        checkpoints: checkpoints.len(),
+        ..Default::default()
+    };
+    let mut prev: Option<(u64, String)> = None;
+    let mut seen: HashMap<u64, String> = HashMap::new();
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Ok(value) = serde_json::from_str::<serde_json::Value>(line) else {
+            report.issues.push(format!("line {} is not valid JSON", index + 1));
+            continue;
+        };
+        let hash = log_line_hash(line);
+        let (Some(seq), Some(link)) = (value["seq"].as_u64(), value["prev"].as_str()) else {
+            if prev.is_none() {
+                report.unchained += 1;
+            } else {
+                report.issues.push(format!("line {} has no chain fields", index + 1));
+            }
+            continue;
+        };
+        report.records += 1;
+        match &prev {
+            None => {
+                let rotated = checkpoints.iter().any(|checkpoint| {
+                    checkpoint.is_segment_boundary() && checkpoint.seq + 1 == seq && checkpoint.hash == link
+                });
+                if seq > 1 && !rotated {
+                    report.issues.push(format!("records before seq {} are missing", seq));
+                }
+                report.first_seq = Some(seq);
+            },
+            Some((prev_seq, _)) if seq != prev_seq + 1 => {
+                report.issues.push(format!(
+                    "records seq {}..{} are missing",
+                    prev_seq + 1,
+                    seq.saturating_sub(1)
+                ));
+            },
+            Some((prev_seq, prev_hash)) if link != prev_hash => {
+                report.issues.push(format!("record seq {} was modified", prev_seq));
+            },
+            Some(_) => {},
+        }
+        seen.insert(seq, hash.clone());
+        prev = Some((seq, hash));
+    }
+    report.last_seq = prev.as_ref().map(|(seq, _)| *seq);
+
+    for checkpoint in &checkpoints {
+        if let Some(hash) = seen.get(&checkpoint.seq) {
+            if *hash != checkpoint.hash {
+                report.issues.push(format!(
+                    "record seq {} does not match its checkpoint",
+                    checkpoint.seq
+                ));
+            }
+        }
+    }
+    let latest = checkpoints.iter().map(|checkpoint| checkpoint.seq).max();
+    if let Some(latest) = latest.filter(|latest| *latest > report.last_seq.unwrap_or(0)) {
+        report.issues.push(format!(
+            "records after seq {} are missing (checkpoint vouches for seq {})",
+            report.last_seq.unwrap_or(0),
+            latest
+        ));
+    }
+    report
+}
+
+/// Split the stored checkpoints into authentic ones and `(log, issue)` pairs
+/// for those whose HMAC does not verify; forged checkpoints vouch for nothing.
+fn authenticate_checkpoints(store: &LogCheckpointStore) -> (Vec<LogCheckpoint>, Vec<(String, String)>) {
+    let (authentic, forged): (Vec<_>, Vec<_>) = store
+        .load()
+        .into_iter()
+        .partition(|checkpoint| store.is_authentic(checkpoint));
+    let issues = forged
+        .into_iter()
+        .map(|checkpoint| {
+            (
+                checkpoint.log,
+                format!(
+                    "checkpoint for seq {} has an invalid signature",
+                    checkpoint.seq
+                ),
+            )
+        })
+        .collect();
+    (authentic, issues)
+}
+
+fn verify_logs_usage() -> &'static str {
+    "Usage: agentsmith-rs verify-logs [--home DIR] [--json]\n\
+     Check the hash chains of denials.jsonl, override-audit.jsonl and taint-marks.jsonl\n\
+     (including rotated segments) against the daemon's signed checkpoints.\n\
+     Run as root to read the checkpoint key.\n\
+     --home  user home whose guard logs to check (default $HOME)\n\
+     --json  print one report per log as JSON lines\n\
+     Exit status: 0 intact, 2 deletions/edits/gaps found, 1 usage error.\n"
+}
+
+/// `agentsmith-rs verify-logs`: detect edited, deleted or truncated guard log records.
+pub fn run_verify_logs(args: &[String]) -> i32 {
+    let mut home = std::env::var("HOME").unwrap_or_else(|_| "/root".into());
+    let mut json = false;
+    let mut index = 0;
+    while let Some(arg) = args.get(index) {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--help" | "-h" => {
+                print!("{}", verify_logs_usage());
+                return 0;
+            },
+            "--home" => {
+                index += 1;
+                let Some(value) = args.get(index) else {
+                    eprintln!(
+                        "[verify-logs] invalid value for --home\n{}",
+                        verify_logs_usage()
+                    );
+                    return 1;
+                };
+                home = value.clone();
+            },
+            other => {
+                eprintln!(
+                    "[verify-logs] unknown argument: {}\n{}",
+                    other,
+                    verify_logs_usage()
+                );
+                return 1;
+            },
+        }
+        index += 1;
+    }
+
+    let (checkpoints, checkpoint_issues) = match LogCheckpointStore::for_home(&home) {
+        Some(store) => authenticate_checkpoints(&store),
+        None => {
+            eprintln!(
+                "[verify-logs] checkpoint key unreadable; checking hash chains only (run as root for checkpoints)"
+            );
+            (Vec::new(), Vec::new())
+        },
+    };
+
+    let guard_dir = PathBuf::from(&home).join(".agentsmith-rs").join("guard");
+    let mut clean = true;
+    for log in CHAINED_LOG_NAMES {
+        let path = guard_dir.join(log);
+        let content = match read_guard_log(&path, 0) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => {
+                eprintln!("[verify-logs] cannot read {}: {}", path.display(), err);
+                return 1;
+            },
+        };
+        let mut report = verify_log_chain(log, &content, &checkpoints);
+        report.issues.extend(
+            checkpoint_issues
+                .iter()
+                .filter(|(name, _)| name == log)
+                .map(|(_, issue)| issue.clone()),
+        );
+        clean &= report.issues.is_empty();
+        if json {
+            println!("{}", serde_json::to_string(&report).unwrap_or_default());
+            continue;
+        }
+        let range = match (report.first_seq, report.last_seq) {
+            (Some(first), Some(last)) => format!("seq {}..{}", first, last),
+            _ => "no chained records".to_string(),
+        };
+        println!(
+            "{}: {} record(s), {}, {} checkpoint(s){}",
+            log,
+            report.records,
+            range,
+            report.checkpoints,
+            if report.issues.is_empty() { " — ok" } else { "" }
+        );
+        if report.unchained > 0 {
+            println!(
+                "  note: {} legacy record(s) before the chain started",
+                report.unchained
+            );
+        }
+        for issue in &report.issues {
+            println!("  - {}", issue);
+        }
+    }
+    if clean {
+        0
+    } else {
+        2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{append_chained_log_line, LOG_CHECKPOINT_INTERVAL_SECS};
+    use std::fs;
+    use std::io::Write;
+    use std::path::Path;
+
+    /// Records 1..=3 in a rotated segment and 4..=6 in the live log.
+    fn chained_log(tag: &str) -> (PathBuf, PathBuf, LogCheckpointStore) {
+        let dir = std::env::temp_dir().join(format!("as-log-chain-{}-{}", tag, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("temp dir");
+        let log_path = dir.join("denials.jsonl");
+        let store = LogCheckpointStore {
+            path: dir.join("checkpoints.jsonl"),
+            key: [7; 32],
+        };
+        for n in 1..=6 {
+            append_record(
+                &log_path,
+                &store,
+                n,
+                if n == 4 { 1 } else { 1_000_000 },
+                1000,
+            );
+        }
+        (dir, log_path, store)
+    }
+
+    fn append_record(log_path: &Path, store: &LogCheckpointStore, n: u64, max_bytes: u64, now: u64) {
+        let json = format!("{{\"path\":\"/repo/f{}\"}}", n);
+        append_chained_log_line(log_path, &json, max_bytes, Some(store), now).expect("append");
+    }
+
+    fn verify(log_path: &Path, checkpoints: &[LogCheckpoint]) -> LogChainReport {
+        let content = fs::read_to_string(log_path).expect("log");
+        verify_log_chain("denials.jsonl", &content, checkpoints)
+    }
+
+    #[test]
+    fn chained_guard_log_follows_rotation_vouched_by_a_checkpoint() {
+        let (dir, log_path, store) = chained_log("rotate");
+        let report = verify(&log_path, &store.load());
+        assert!(report.issues.is_empty(), "{:?}", report.issues);
+        assert_eq!((report.first_seq, report.last_seq), (Some(4), Some(6)));
+        let history = read_guard_log(&log_path, 0).expect("history");
+        let report = verify_log_chain("denials.jsonl", &history, &store.load());
+        assert!(report.issues.is_empty(), "{:?}", report.issues);
+        assert_eq!((report.first_seq, report.last_seq), (Some(1), Some(6)));
+        // Without the rotate checkpoint the live segment starts mid-chain.
+        assert_eq!(
+            verify(&log_path, &[]).issues,
+            vec!["records before seq 4 are missing".to_string()]
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn chained_guard_log_reports_an_edited_record() {
+        let (dir, log_path, store) = chained_log("edit");
+        append_record(
+            &log_path,
+            &store,
+            7,
+            1_000_000,
+            1000 + LOG_CHECKPOINT_INTERVAL_SECS,
+        );
+        let intact = fs::read_to_string(&log_path).expect("log");
+
+        // An edit inside the chain breaks the next record's link.
+        fs::write(&log_path, intact.replace("/repo/f5", "/repo/xx")).expect("edit");
+        assert_eq!(
+            verify(&log_path, &store.load()).issues,
+            vec!["record seq 5 was modified".to_string()]
+        );
+
+        // The last record has no successor; its checkpoint catches the edit.
+        fs::write(&log_path, intact.replace("/repo/f7", "/repo/xx")).expect("edit tail");
+        assert_eq!(
+            verify(&log_path, &store.load()).issues,
+            vec!["record seq 7 does not match its checkpoint".to_string()]
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn chained_guard_log_reports_removed_records() {
+        let (dir, log_path, store) = chained_log("remove");
+        let intact = fs::read_to_string(&log_path).expect("log");
+        let keep = |skip: &dyn Fn(&str) -> bool| -> String {
+            intact
+                .lines()
+                .filter(|line| !skip(line))
+                .map(|line| format!("{}\n", line))
+                .collect()
+        };
+
+        fs::write(&log_path, keep(&|line| line.contains("/repo/f5"))).expect("delete");
+        assert_eq!(
+            verify(&log_path, &store.load()).issues,
+            vec!["records seq 5..5 are missing".to_string()]
+        );
+
+        fs::write(&log_path, keep(&|line| line.contains("/repo/f4"))).expect("delete head");
+        assert_eq!(
+            verify(&log_path, &store.load()).issues,
+            vec!["records before seq 5 are missing".to_string()]
+        );
+
+        fs::write(&log_path, &intact).expect("restore");
+        append_record(
+            &log_path,
+            &store,
+            7,
+            1_000_000,
+            1000 + LOG_CHECKPOINT_INTERVAL_SECS,
+        );
+        fs::write(&log_path, &intact).expect("delete tail");
+        let issues = verify(&log_path, &store.load()).issues;
+        assert_eq!(issues.len(), 1);
+        assert!(
+            issues[0].starts_with("records after seq 6 are missing"),
+            "{:?}",
+            issues
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn forged_checkpoint_is_reported_and_vouches_for_nothing() {
+        let (dir, log_path, store) = chained_log("forged");
+        let intact = fs::read_to_string(&log_path).expect("log");
+        let head = intact.lines().next().expect("first record").to_string();
+        fs::write(
+            &log_path,
+            intact
+                .lines()
+                .skip(1)
+                .map(|line| format!("{}\n", line))
+                .collect::<String>(),
+        )
+        .expect("delete head");
+
+        // A rotate checkpoint that would excuse the deleted head, signed with
+        // the wrong key.
+        let mut forged = LogCheckpoint {
+            ts: 1000,
+            log: "denials.jsonl".to_string(),
+            seq: 4,
+            hash: log_line_hash(&head),
+            reason: "rotate".to_string(),
+            sig: String::new(),
+        };
+        forged.sig = LogCheckpointStore {
+            path: store.path.clone(),
+            key: [8; 32],
+        }
+        .sign(&forged);
+        let mut file = fs::OpenOptions::new()
+            .append(true)
+            .open(&store.path)
+            .expect("checkpoint file");
+        writeln!(
+            file,
+            "{}",
+            serde_json::to_string(&forged).expect("serialize")
+        )
+        .expect("append forged");
+        assert!(
+            verify(&log_path, &store.load()).issues.is_empty(),
+            "unauthenticated, the forgery hides the gap"
+        );
+
+        let (authentic, issues) = authenticate_checkpoints(&store);
+        assert!(!authentic.contains(&forged));
+        assert_eq!(authentic.len(), store.load().len() - 1);
+        assert_eq!(
+            issues,
+            vec![(
+                "denials.jsonl".to_string(),
+                "checkpoint for seq 4 has an invalid signature".to_string()
+            )]
+        );
+        assert_eq!(
+            verify(&log_path, &authentic).issues,
+            vec!["records before seq 5 are missing".to_string()]
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
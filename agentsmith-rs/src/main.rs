@@ -1,44 +1,108 @@
+mod cli;
+mod preflight;
+mod protocol;
+mod session_protocol;
+
 use agentsmith_rs_core::sys::{es_auth_result_t, es_event_type_t};
-use agentsmith_rs_core::{Client, Event, EventCreateDestinationFile, EventRenameDestinationFile, Message};
+use agentsmith_rs_core::{Client, Event, EventCreateDestinationFile, EventRenameDestinationFile, Message, Process};
+use protocol::{
+    control_socket_path, normalize_absolute_path, override_request_dir, ControlRequest, ControlResponse,
+    OverrideRequest, OverrideResponse, CONTROL_IO_TIMEOUT_SECS, CONTROL_PROTOCOL_VERSION, MAX_OVERRIDE_BATCH_PATHS,
+    MAX_OVERRIDE_PATH_LEN, MAX_OVERRIDE_USES, OVERRIDE_DEFAULT_MINUTES, OVERRIDE_MAX_MINUTES, OVERRIDE_OPS,
+};
 use serde::{Deserialize, Serialize};
+use session_protocol::{session_request_dir, SessionRequest, SessionResponse};
+use sha2::{Digest, Sha256};
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs;
 use std::fs::{File, OpenOptions};
-use std::io::{self, Read, Write};
+use std::io::{self, BufRead, Read, Write};
 use std::os::fd::AsRawFd;
-use std::os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::os::unix::fs::{FileTypeExt, MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
 use std::panic::AssertUnwindSafe;
-use std::path::{Component, Path, PathBuf};
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::{mpsc, Arc, Mutex, OnceLock};
 use std::thread;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 const CACHE_TTL_SECS: u64 = 5;
+const MAX_ANCESTRY_DEPTH: usize = 30;
+const AGENT_PROFILE_CATALOG_VERSION: u32 = 2;
+const LAUNCHER_HANDOFF_TTL_SECS: u64 = 120;
+const MAX_LAUNCHER_HANDOFFS: usize = 64;
 const TRUST_CACHE_TTL_SECS: u64 = 300;
 const CODESIGN_BIN: &str = "/usr/bin/codesign";
+const MENUBAR_PROCESS_NAME: &str = "AgentSmithMenuBar";
+const GZIP_BIN: &str = "/usr/bin/gzip";
 const MAXCOMLEN: usize = 16;
 const DEFAULT_FILE_MODE: u32 = 0o644;
 const DEFAULT_DIR_MODE: u32 = 0o700;
 const RUNTIME_OVERRIDE_FILE_MODE: u32 = 0o600;
-const OVERRIDE_DEFAULT_MINUTES: u64 = 3;
-const OVERRIDE_MAX_MINUTES: u64 = 30;
 const OVERRIDE_STORE_DIR: &str = "/var/db/agentsmith-rs";
-const MAX_OVERRIDE_PATH_LEN: usize = 4096;
 const MAX_RUNTIME_OVERRIDES: usize = 512;
+/// Pinned overrides hash the target when granted and again on each matching
+/// AUTH event, so they stay small.
+const MAX_PINNED_OVERRIDE_BYTES: u64 = 4 * 1024 * 1024;
 const MAX_OVERRIDE_REQUEST_SIZE_BYTES: u64 = 8192;
 const MAX_OVERRIDE_REQUESTS_PER_MINUTE: usize = 120;
 const MAX_OVERRIDE_REQUEST_FILES_PER_CYCLE: usize = 256;
+const MAX_PENDING_OVERRIDES: usize = 32;
+const DEFAULT_OVERRIDE_APPROVAL_TIMEOUT_SECS: u64 = 300;
+const MAX_OVERRIDE_APPROVAL_TIMEOUT_SECS: u64 = 1800;
+const PENDING_OVERRIDE_DENIAL_LOOKBACK_SECS: u64 = 3600;
+const CONTROL_SOCKET_DIR_MODE: u32 = 0o755;
+const CONTROL_SOCKET_MODE: u32 = 0o666;
+const CONTROL_QUEUE_BOUND: usize = 32;
+const MAX_CONTROL_CONNECTIONS: usize = 16;
+const MAX_SESSION_REQUEST_FILES_PER_CYCLE: usize = 64;
+const MAX_SESSION_AGENT_NAME_LEN: usize = 64;
+const MAX_PENDING_SESSION_EVENTS: usize = 256;
+const MAX_TRACKED_SESSIONS: usize = 1024;
+const SESSION_LOG_MAX_BYTES: u64 = 1_000_000;
+const FEEDBACK_DENIALS_MAX_BYTES: u64 = 256 * 1024;
+const FEEDBACK_RETENTION_SECS: u64 = 24 * 3600;
+const FEEDBACK_FILE_NAMES: [&str; 3] = ["last_denial.txt", "last_denial.json", "denials.jsonl"];
+const SESSION_SOURCE_LAUNCHER: &str = "agentsmith-run";
+const SESSION_SOURCE_DETECTOR: &str = "detector";
 const STALE_RESPONSE_RETENTION_SECS: u64 = 300;
+const DENIALS_LOG_MAX_BYTES: u64 = 1_000_000;
 const OVERRIDE_AUDIT_MAX_BYTES: u64 = 1_000_000;
 const TAINT_AUDIT_MAX_BYTES: u64 = 1_000_000;
+/// Guard logs written as hash chains and checked by `verify-logs`.
+const CHAINED_LOG_NAMES: [&str; 3] = ["denials.jsonl", "override-audit.jsonl", "taint-marks.jsonl"];
+const LOG_CHAIN_GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+const LOG_CHAIN_KEY_FILE: &str = "log-chain.key";
+const LOG_CHECKPOINT_EVERY_RECORDS: u64 = 64;
+const LOG_CHECKPOINT_INTERVAL_SECS: u64 = 300;
+const LOG_CHECKPOINT_MAX_BYTES: u64 = 1_000_000;
+const LOG_CHECKPOINT_KEEP: usize = 1024;
+const LOG_ROTATION_DEFAULT_KEEP_SEGMENTS: usize = 10;
+const LOG_ROTATION_MAX_KEEP_SEGMENTS: usize = 1000;
+const LOG_ROTATION_MIN_SEGMENT_BYTES: u64 = 64 * 1024;
+const LOG_ROTATION_MAX_SEGMENT_BYTES: u64 = 256 * 1024 * 1024;
 const OVERRIDE_CREATED_BY_HELPER: &str = "agentsmith-helper";
 const OVERRIDE_CREATED_BY_SENSITIVE_READ_HELPER: &str = "agentsmith-helper-sensitive-read";
 const FFLAG_READ: i32 = 0x0000_0001;
 const FFLAG_WRITE: i32 = 0x0000_0002;
+const FFLAG_TRUNC: i32 = 0x0000_0400;
 const DEFAULT_TAINT_TTL_SECS: u64 = 600;
+const METADATA_GATE_FRESH_FILE_SECS: i64 = 60;
+const METADATA_GATE_PROTECTED_FILE_FLAGS: u32 =
+    libc::UF_IMMUTABLE | libc::UF_APPEND | libc::UF_HIDDEN | libc::SF_IMMUTABLE | libc::SF_APPEND;
+const METADATA_GATE_OPS: [&str; 7] = [
+    "setmode",
+    "setflags",
+    "setacl",
+    "setextattr",
+    "deleteextattr",
+    "setowner",
+    "utimes",
+];
 const CACHE_WATERMARK_LOG_INTERVAL_SECS: u64 = 10;
+const PROCESS_TABLE_PRUNE_INTERVAL_SECS: u64 = 600;
 const TRUST_CACHE_PRUNE_INTERVAL_SECS: u64 = 10;
 const SIGNATURE_CACHE_TTL_SECS: u64 = 3600;
 const SIGNATURE_ERROR_CACHE_TTL_SECS: u64 = 30;
@@ -59,7 +123,10 @@ const REASON_SENSITIVE_TRANSFER_OUT: &str = "SENSITIVE_TRANSFER_OUT";
 const REASON_TAINT_WRITE_OUT: &str = "TAINT_WRITE_OUT";
 const REASON_EXEC_EXFIL_TOOL: &str = "EXEC_EXFIL_TOOL";
 const REASON_PROTECTED_ZONE_AI_DELETE: &str = "PROTECTED_ZONE_AI_DELETE";
+const REASON_PROTECTED_ZONE_AI_TRUNCATE: &str = "PROTECTED_ZONE_AI_TRUNCATE";
+const REASON_PROTECTED_ZONE_AI_METADATA: &str = "PROTECTED_ZONE_AI_METADATA";
 const REASON_TRUST_IDENTITY_MISMATCH: &str = "TRUST_IDENTITY_MISMATCH";
+const REASON_OVERRIDE_PIN_MISMATCH: &str = "OVERRIDE_PIN_MISMATCH";
 const TRUST_SIGNATURE_PENDING_PREFIX: &str = "signature verification pending";
 
 static LOG_EVENT_TX: OnceLock<mpsc::SyncSender<GuardLogMessage>> = OnceLock::new();
@@ -71,27 +138,14 @@ static LOG_QUEUE_PENDING: AtomicU64 = AtomicU64::new(0);
 static LOG_QUEUE_DROPPED_FULL: AtomicU64 = AtomicU64::new(0);
 static LOG_QUEUE_DROPPED_DISCONNECTED: AtomicU64 = AtomicU64::new(0);
 static AUDIT_ONLY_COALESCER: OnceLock<Mutex<AuditOnlyCoalescer>> = OnceLock::new();
-
-#[derive(Debug, Deserialize)]
-struct OverrideRequest {
-    id: String,
-    action: String,
-    #[serde(default)]
-    path: Option<String>,
-    #[serde(default)]
-    minutes: Option<u64>,
-    #[serde(default)]
-    requester_pid: Option<i32>,
-}
-
-#[derive(Debug, Serialize)]
-struct OverrideResponse {
-    id: String,
-    status: String,
-    message: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    expires_at: Option<u64>,
-}
+static SESSION_STATS: OnceLock<Mutex<SessionStats>> = OnceLock::new();
+static OVERRIDE_USE_LEDGER: OnceLock<Mutex<OverrideUseLedger>> = OnceLock::new();
+static LOG_CHAINS: OnceLock<Mutex<HashMap<PathBuf, LogChainState>>> = OnceLock::new();
+static LOG_CHAIN_KEY: OnceLock<Option<[u8; 32]>> = OnceLock::new();
+static LOG_ROTATION: OnceLock<Mutex<LogRotationPolicy>> = OnceLock::new();
+/// Held by the background segment maintenance so two rotations never compress
+/// or prune the same segments at once.
+static LOG_MAINTENANCE: Mutex<()> = Mutex::new(());
 
 #[derive(Debug, Deserialize, Serialize, Default, Clone)]
 struct SecurityPolicy {
@@ -122,6 +176,15 @@ struct SecurityPolicy {
     #[serde(default = "default_ai_agent_patterns")]
     ai_agent_patterns: Vec<String>,
 
+    #[serde(default)]
+    ai_agent_detectors: Vec<AgentDetectorRule>,
+
+    #[serde(default)]
+    ai_agent_profiles: Vec<String>,
+
+    #[serde(default)]
+    ai_agent_min_confidence: DetectorConfidence,
+
     #[serde(default)]
     allow_trusted_tools_in_ai_context: bool,
 
@@ -137,6 +200,24 @@ struct SecurityPolicy {
     #[serde(default = "default_true")]
     exec_gate_enabled: bool,
 
+    #[serde(default)]
+    truncate_gate_enabled: bool,
+
+    #[serde(default)]
+    truncate_gate_zones: Vec<String>,
+
+    #[serde(default)]
+    truncate_gate_min_bytes: u64,
+
+    #[serde(default = "default_true")]
+    metadata_gate_enabled: bool,
+
+    #[serde(default = "default_metadata_gate_ops")]
+    metadata_gate_ops: Vec<String>,
+
+    #[serde(default = "default_metadata_gate_xattr_allowlist")]
+    metadata_gate_xattr_allowlist: Vec<String>,
+
     #[serde(default)]
     audit_only_mode: bool,
 
@@ -145,6 +226,18 @@ struct SecurityPolicy {
 
     #[serde(default)]
     trusted_identity_require_cdhash: bool,
+
+    #[serde(default = "default_override_approval_required")]
+    override_approval_required: bool,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    override_approval_timeout_seconds: Option<u64>,
+
+    #[serde(default)]
+    override_policy: OverridePolicy,
+
+    #[serde(default)]
+    log_rotation: LogRotationPolicy,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
@@ -157,6 +250,356 @@ struct TrustedToolIdentity {
     cdhash: Option<String>,
 }
 
+/// How full guard logs are rotated. Segments are renamed to
+/// `<log>.<UTC timestamp>` and optionally gzipped; the oldest are removed past
+/// `keep_segments` or `keep_days`.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
+struct LogRotationPolicy {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_segment_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    keep_segments: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    keep_days: Option<u64>,
+    #[serde(default)]
+    compress: bool,
+}
+
+/// Operator rules for override requests. Limits can only tighten the built-in
+/// caps; `never_override` and `auto_approve` paths are globs (`**` crosses
+/// directories, `*` and `?` stay within one component).
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+struct OverridePolicy {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_minutes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_active: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    requests_per_minute: Option<usize>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    never_override: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    zones: Vec<OverrideZoneRule>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    auto_approve: Vec<OverrideAutoApproveRule>,
+    #[serde(skip)]
+    never_override_matchers: OnceLock<Result<Vec<regex_lite::Regex>, String>>,
+}
+
+/// Limits for overrides below `zone`; the longest matching zone applies.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+struct OverrideZoneRule {
+    zone: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_minutes: Option<u64>,
+    /// Overrides must target a path at least this many components below `zone`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    min_depth: Option<usize>,
+}
+
+/// Grants matching a rule skip human approval.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+struct OverrideAutoApproveRule {
+    name: String,
+    paths: Vec<String>,
+    /// Also require a `.git` / `.jj` directory at or above the path, inside its zone.
+    #[serde(default)]
+    inside_repo: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_minutes: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+enum DetectorConfidence {
+    #[default]
+    Low,
+    Medium,
+    High,
+}
+
+impl DetectorConfidence {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+        }
+    }
+}
+
+/// Structured AI-agent detector. Every criterion that is set must match, and a
+/// rule without criteria never matches. `env` entries are `NAME` (present) or
+/// `NAME=value` (exact); `argv_marker` matches any argument after argv[0] by
+/// value or basename (script hosted by node/python).
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+struct AgentDetectorRule {
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    #[serde(default = "default_detector_rule_confidence")]
+    confidence: DetectorConfidence,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    exe_exact: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    exe_glob: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    exe_regex: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    argv0: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    argv_marker: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    env: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signing_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    team_id: Option<String>,
+    #[serde(skip)]
+    exe_matchers: OnceLock<Result<Vec<regex_lite::Regex>, String>>,
+}
+
+/// Exec-time facts used by detector rules beyond the executable and argv[0].
+/// `env` only holds variables some detector rule refers to.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct ExecEvidence {
+    env: Vec<String>,
+    signing_id: String,
+    team_id: String,
+}
+
+/// Process signature of a built-in agent profile; expands into an
+/// `AgentDetectorRule` named `<profile>:<rule>`.
+struct AgentProfileSignature {
+    rule: &'static str,
+    confidence: DetectorConfidence,
+    exe_glob: Option<&'static str>,
+    exe_regex: Option<&'static str>,
+    argv0: Option<&'static str>,
+    argv_marker: Option<&'static str>,
+    env: &'static [&'static str],
+}
+
+const EMPTY_PROFILE_SIGNATURE: AgentProfileSignature = AgentProfileSignature {
+    rule: "",
+    confidence: DetectorConfidence::Medium,
+    exe_glob: None,
+    exe_regex: None,
+    argv0: None,
+    argv_marker: None,
+    env: &[],
+};
+
+struct AgentProfile {
+    name: &'static str,
+    label: &'static str,
+    signatures: &'static [AgentProfileSignature],
+}
+
+const NODE_HOST_REGEX: &str = "/(node|bun|deno)$";
+const PYTHON_HOST_REGEX: &str = "/python[0-9.]*$";
+
+/// Built-in agent profiles, enabled by name through `ai_agent_profiles`. Bump
+/// AGENT_PROFILE_CATALOG_VERSION whenever a signature changes.
+static AGENT_PROFILE_CATALOG: &[AgentProfile] = &[
+    AgentProfile {
+        name: "claude-code",
+        label: "claude",
+        signatures: &[
+            AgentProfileSignature {
+                rule: "env",
+                confidence: DetectorConfidence::High,
+                env: &["CLAUDECODE=1"],
+                ..EMPTY_PROFILE_SIGNATURE
+            },
+            AgentProfileSignature {
+                rule: "process-title",
+                argv0: Some("claude"),
+                ..EMPTY_PROFILE_SIGNATURE
+            },
+            AgentProfileSignature {
+                rule: "native-binary",
+                exe_glob: Some("**/claude"),
+                ..EMPTY_PROFILE_SIGNATURE
+            },
+        ],
+    },
+    AgentProfile {
+        name: "codex",
+        label: "codex",
+        signatures: &[
+            AgentProfileSignature {
+                rule: "sandbox-env",
+                confidence: DetectorConfidence::High,
+                env: &["CODEX_SANDBOX"],
+                ..EMPTY_PROFILE_SIGNATURE
+            },
+            AgentProfileSignature {
+                rule: "binary",
+                exe_regex: Some("/codex(-(aarch64|x86_64)-apple-darwin)?$"),
+                ..EMPTY_PROFILE_SIGNATURE
+            },
+            AgentProfileSignature {
+                rule: "node-launcher",
+                exe_regex: Some(NODE_HOST_REGEX),
+                argv_marker: Some("codex"),
+                ..EMPTY_PROFILE_SIGNATURE
+            },
+        ],
+    },
+    AgentProfile {
+        name: "cursor",
+        label: "cursor",
+        signatures: &[
+            AgentProfileSignature {
+                rule: "agent-cli",
+                exe_glob: Some("**/cursor-agent"),
+                ..EMPTY_PROFILE_SIGNATURE
+            },
+            // Also the parent of shells in the integrated terminal, so only a hint.
+            AgentProfileSignature {
+                rule: "electron-helper",
+                confidence: DetectorConfidence::Low,
+                exe_glob: Some(
+                    "/Applications/Cursor.app/Contents/Frameworks/Cursor Helper*/Contents/MacOS/Cursor Helper*",
+                ),
+                ..EMPTY_PROFILE_SIGNATURE
+            },
+        ],
+    },
+    AgentProfile {
+        name: "aider",
+        label: "aider",
+        signatures: &[
+            AgentProfileSignature {
+                rule: "binary",
+                exe_glob: Some("**/aider"),
+                ..EMPTY_PROFILE_SIGNATURE
+            },
+            AgentProfileSignature {
+                rule: "python-host",
+                exe_regex: Some(PYTHON_HOST_REGEX),
+                argv_marker: Some("aider"),
+                ..EMPTY_PROFILE_SIGNATURE
+            },
+        ],
+    },
+    AgentProfile {
+        name: "gemini-cli",
+        label: "gemini",
+        signatures: &[
+            AgentProfileSignature {
+                rule: "env",
+                confidence: DetectorConfidence::High,
+                env: &["GEMINI_CLI=1"],
+                ..EMPTY_PROFILE_SIGNATURE
+            },
+            AgentProfileSignature {
+                rule: "node-host",
+                exe_regex: Some(NODE_HOST_REGEX),
+                argv_marker: Some("gemini"),
+                ..EMPTY_PROFILE_SIGNATURE
+            },
+        ],
+    },
+    AgentProfile {
+        name: "opencode",
+        label: "opencode",
+        signatures: &[
+            AgentProfileSignature {
+                rule: "env",
+                confidence: DetectorConfidence::High,
+                env: &["OPENCODE=1"],
+                ..EMPTY_PROFILE_SIGNATURE
+            },
+            AgentProfileSignature {
+                rule: "binary",
+                exe_glob: Some("**/opencode"),
+                ..EMPTY_PROFILE_SIGNATURE
+            },
+        ],
+    },
+    AgentProfile {
+        name: "goose",
+        label: "goose",
+        signatures: &[
+            AgentProfileSignature {
+                rule: "binary",
+                exe_glob: Some("**/goose"),
+                ..EMPTY_PROFILE_SIGNATURE
+            },
+            AgentProfileSignature {
+                rule: "desktop-app",
+                exe_glob: Some("/Applications/Goose.app/**"),
+                ..EMPTY_PROFILE_SIGNATURE
+            },
+        ],
+    },
+    AgentProfile {
+        name: "copilot",
+        label: "copilot",
+        signatures: &[
+            AgentProfileSignature {
+                rule: "cli-binary",
+                exe_glob: Some("**/copilot"),
+                ..EMPTY_PROFILE_SIGNATURE
+            },
+            AgentProfileSignature {
+                rule: "node-host",
+                exe_regex: Some(NODE_HOST_REGEX),
+                argv_marker: Some("copilot"),
+                ..EMPTY_PROFILE_SIGNATURE
+            },
+        ],
+    },
+];
+
+fn agent_profile(name: &str) -> Option<&'static AgentProfile> {
+    AGENT_PROFILE_CATALOG.iter().find(|profile| profile.name == name)
+}
+
+impl AgentProfile {
+    fn detector_rules(&self) -> Vec<AgentDetectorRule> {
+        self.signatures
+            .iter()
+            .map(|signature| AgentDetectorRule {
+                name: format!("{}:{}", self.name, signature.rule),
+                label: Some(self.label.to_string()),
+                confidence: signature.confidence,
+                exe_glob: signature.exe_glob.map(str::to_string),
+                exe_regex: signature.exe_regex.map(str::to_string),
+                argv0: signature.argv0.map(str::to_string),
+                argv_marker: signature.argv_marker.map(str::to_string),
+                env: signature.env.iter().map(|marker| marker.to_string()).collect(),
+                ..AgentDetectorRule::default()
+            })
+            .collect()
+    }
+}
+
+/// Detector rules for every catalog profile, built once so compiled patterns
+/// are shared across policy clones.
+fn agent_profile_rules(name: &str) -> &'static [AgentDetectorRule] {
+    static RULES: OnceLock<HashMap<&'static str, Vec<AgentDetectorRule>>> = OnceLock::new();
+    RULES
+        .get_or_init(|| {
+            AGENT_PROFILE_CATALOG
+                .iter()
+                .map(|profile| (profile.name, profile.detector_rules()))
+                .collect()
+        })
+        .get(name)
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AgentDetection {
+    label: String,
+    rule: String,
+    confidence: DetectorConfidence,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 struct TemporaryOverrideRule {
     path: String,
@@ -166,8 +609,178 @@ struct TemporaryOverrideRule {
     created_at: Option<u64>,
     #[serde(default)]
     created_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scope: Option<OverrideScope>,
+    /// Operations this rule allows; empty allows every gated operation.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    ops: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rename_to: Option<String>,
+    /// Counted rules are removed once `uses` reaches `max_uses`; `id` keys
+    /// their consumption in the [`OverrideUseLedger`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_uses: Option<u32>,
+    #[serde(default, skip_serializing_if = "is_zero_u32")]
+    uses: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    /// Set for pinned rules, which only match their exact path while the file
+    /// still has this identity.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pin: Option<OverridePin>,
+}
+
+/// File identity and content hash captured when a pinned override is granted.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+struct OverridePin {
+    #[serde(flatten)]
+    file: FileIdentity,
+    sha256: String,
+}
+
+impl OverridePin {
+    /// Hash the file at `path` through a no-follow descriptor, so the identity
+    /// and the content come from the same inode.
+    fn capture(path: &str) -> Result<Self, String> {
+        let path = Path::new(path);
+        let mut file = open_read_no_follow(path).map_err(|err| format!("cannot open file to pin: {}", err))?;
+        let meta = file
+            .metadata()
+            .map_err(|err| format!("cannot stat file to pin: {}", err))?;
+        if !meta.is_file() {
+            return Err("only regular files can be pinned".to_string());
+        }
+        if meta.len() > MAX_PINNED_OVERRIDE_BYTES {
+            return Err(format!(
+                "file too large to pin (max {} bytes)",
+                MAX_PINNED_OVERRIDE_BYTES
+            ));
+        }
+        let mut hasher = Sha256::new();
+        let mut buffer = [0u8; 64 * 1024];
+        loop {
+            let read = file
+                .read(&mut buffer)
+                .map_err(|err| format!("cannot read file to pin: {}", err))?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+        // Re-stat after hashing so a write that raced the read changes the
+        // pinned mtime/ctime instead of slipping under the digest.
+        let after = file
+            .metadata()
+            .map_err(|err| format!("cannot stat file to pin: {}", err))?;
+        let file = FileIdentity::from_metadata(&after);
+        if file != FileIdentity::from_metadata(&meta) {
+            return Err("file changed while it was being pinned".to_string());
+        }
+        Ok(Self {
+            file,
+            sha256: hex_digest(&hasher.finalize()),
+        })
+    }
+
+    /// The event's stat is compared first, so a swapped or rewritten file is
+    /// refused without reading it. Timestamps can be put back, so a file that
+    /// still looks unchanged is re-hashed before the pin is honoured.
+    fn matches(&self, path: &str, file: Option<FileIdentity>) -> bool {
+        file == Some(self.file) && Self::capture(path).is_ok_and(|current| current == *self)
+    }
+}
+
+fn hex_digest(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn is_zero_u32(value: &u32) -> bool {
+    *value == 0
+}
+
+impl TemporaryOverrideRule {
+    /// Pinned rules cover the file itself, not whatever is created below it later.
+    fn covers_path(&self, target_path: &str) -> bool {
+        match self.pin {
+            Some(_) => trim_trailing_slashes(target_path) == self.path,
+            None => path_prefix_match(target_path, &self.path),
+        }
+    }
+
+    fn allows_op(&self, op: &str, dest: Option<&str>) -> bool {
+        let op_allowed = self.ops.is_empty() || self.ops.iter().any(|allowed| allowed == op);
+        let dest_allowed = match (&self.rename_to, op) {
+            (Some(rename_to), "rename") => dest.is_some_and(|dest| trim_trailing_slashes(dest) == rename_to),
+            _ => true,
+        };
+        op_allowed && dest_allowed
+    }
+
+    fn describe_limits(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        if !self.ops.is_empty() {
+            parts.push(self.ops.join("/"));
+        }
+        if let Some(rename_to) = &self.rename_to {
+            parts.push(format!("rename to {}", rename_to));
+        }
+        if let Some(max_uses) = self.max_uses {
+            parts.push(format!("{} use(s)", max_uses));
+        }
+        if self.pin.is_some() {
+            parts.push("pinned to current content".to_string());
+        }
+        (!parts.is_empty()).then(|| parts.join(", "))
+    }
+}
+
+/// Process subtree a scoped override is bound to, identified by pid plus start
+/// time so a recycled pid does not inherit it. Session scopes are rooted at the
+/// session's root process and also match the session's hand-off children.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Hash)]
+struct OverrideScope {
+    pid: i32,
+    start_tvsec: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    session_id: Option<String>,
+}
+
+impl OverrideScope {
+    fn is_alive(&self) -> bool {
+        get_process_bsd_info(self.pid).is_some_and(|info| info.pbi_start_tvsec == self.start_tvsec)
+    }
+
+    fn describe(&self) -> String {
+        match &self.session_id {
+            Some(session) => format!("session {}", session),
+            None => format!("pid {} and its descendants", self.pid),
+        }
+    }
+}
+
+/// The process an AUTH event is attributed to, as seen by scoped overrides:
+/// its ancestry (pid, start time) up to launchd, plus its AI session.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct OverrideActor {
+    ancestry: Vec<(i32, u64)>,
+    session_id: Option<String>,
+}
+
+impl OverrideActor {
+    fn is_within(&self, scope: &OverrideScope) -> bool {
+        scope
+            .session_id
+            .as_ref()
+            .is_some_and(|session| self.session_id.as_ref() == Some(session))
+            || self
+                .ancestry
+                .iter()
+                .any(|(pid, start)| *pid == scope.pid && *start == scope.start_tvsec)
+    }
 }
 
+// At most MAX_RUNTIME_OVERRIDES entries; not worth boxing the rule.
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(untagged)]
 enum TemporaryOverrideEntry {
@@ -223,6 +836,10 @@ fn default_allow_vcs_metadata_in_ai_context() -> bool {
     true
 }
 
+fn default_override_approval_required() -> bool {
+    true
+}
+
 fn default_allow_git_merge_pull_in_ai_context() -> bool {
     true
 }
@@ -234,6 +851,10 @@ fn default_ai_agent_patterns() -> Vec<String> {
         .collect()
 }
 
+fn default_detector_rule_confidence() -> DetectorConfidence {
+    DetectorConfidence::Medium
+}
+
 fn default_exec_exfil_tool_blocklist() -> Vec<String> {
     ["curl", "wget", "scp", "sftp", "rsync", "nc", "ncat", "netcat"]
         .iter()
@@ -241,10 +862,134 @@ fn default_exec_exfil_tool_blocklist() -> Vec<String> {
         .collect()
 }
 
+fn default_metadata_gate_ops() -> Vec<String> {
+    METADATA_GATE_OPS.iter().map(|s| s.to_string()).collect()
+}
+
+fn default_metadata_gate_xattr_allowlist() -> Vec<String> {
+    [
+        "com.apple.provenance",
+        "com.apple.lastuseddate#PS",
+        "com.apple.TextEncoding",
+        "com.apple.quarantine",
+        "com.apple.cs.*",
+        "com.apple.metadata:*",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()
+}
+
 #[derive(Debug, Clone)]
-struct CachedAncestor {
-    ai_ancestor: Option<String>,
-    updated_at: u64,
+struct ProcessTableEntry {
+    start: Option<ProcessStartTimeKey>,
+    links: ProcessLinks,
+    executable_path: String,
+    argv: Vec<String>,
+    evidence: ExecEvidence,
+    ai_label: Option<String>,
+    ai_lineage: Option<AiLineage>,
+    label_checked_at: u64,
+}
+
+/// AI agent a process descends from, plus the session of the tree's AI root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AiLineage {
+    label: String,
+    session: Option<Arc<AgentSession>>,
+}
+
+impl AiLineage {
+    fn from_label(label: String) -> Self {
+        Self { label, session: None }
+    }
+
+    /// Launcher sessions are authoritative: the tree stays AI regardless of
+    /// `ai_agent_patterns` and detector changes.
+    fn is_registered(&self) -> bool {
+        self.session
+            .as_ref()
+            .is_some_and(|session| session.source == SESSION_SOURCE_LAUNCHER)
+    }
+
+    fn is_rooted_at(&self, pid: i32) -> bool {
+        self.session.as_ref().is_some_and(|session| session.pid == pid)
+    }
+}
+
+/// One run of an AI agent, rooted at the process that was detected as an agent
+/// or registered by `agentsmith-run` before it execs the agent command.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct AgentSession {
+    id: String,
+    agent: String,
+    pid: i32,
+    started_at: u64,
+    cwd: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tty: Option<String>,
+    source: &'static str,
+}
+
+/// Session lifecycle change queued by the process table and written to
+/// `sessions.jsonl` by the log worker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SessionEvent {
+    Start(Arc<AgentSession>),
+    End(Arc<AgentSession>, u64),
+}
+
+/// What a session did, accumulated by the log worker until the session ends.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+struct SessionCounters {
+    denials: u64,
+    audit_only: u64,
+    taint_marks: u64,
+    override_uses: u64,
+}
+
+#[derive(Debug, Default)]
+struct SessionStats {
+    counters: HashMap<String, SessionCounters>,
+}
+
+#[derive(Debug, Serialize)]
+struct SessionLogLine<'a> {
+    ts: u64,
+    event: &'static str,
+    session: &'a AgentSession,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    counters: Option<SessionCounters>,
+}
+
+/// Process table keyed by pid and validated by start time, maintained from
+/// fork/exec/exit notifications and seeded from a process snapshot at startup.
+#[derive(Debug, Default)]
+struct ProcessTable {
+    entries: HashMap<i32, ProcessTableEntry>,
+    handoffs: Vec<LauncherHandoff>,
+    session_events: Vec<SessionEvent>,
+}
+
+/// Parent links captured when a process is recorded. `original_ppid` is the
+/// parent at fork time and survives reparenting to launchd; `responsible_pid`
+/// is the process launchd/XPC attributes the spawn to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct ProcessLinks {
+    ppid: i32,
+    original_ppid: i32,
+    responsible_pid: i32,
+}
+
+/// Pending hand-off from an AI process to a helper service (launchd, atrun,
+/// tmux server) that will spawn the real work outside the AI process tree.
+/// Each hand-off is consumed by the first matching exec.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LauncherHandoff {
+    lineage: AiLineage,
+    spawner: &'static str,
+    program: Option<String>,
+    expires_at: Option<u64>,
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
@@ -255,7 +1000,7 @@ struct ProcessIdentityKey {
     executable_path: String,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 struct ProcessStartTimeKey {
     start_tvsec: u64,
     start_tvusec: u64,
@@ -503,32 +1248,644 @@ impl TaintState {
     }
 }
 
-#[derive(Debug, Default, Clone, Copy)]
-struct CacheWatermarkHighs {
-    ancestor: usize,
-    trusted: usize,
-    taint: usize,
-}
+impl ProcessLinks {
+    fn from_ppid(ppid: i32) -> Self {
+        Self {
+            ppid,
+            original_ppid: ppid,
+            responsible_pid: 0,
+        }
+    }
 
-impl CacheWatermarkHighs {
-    fn update(&mut self, ancestor: usize, trusted: usize, taint: usize) -> (usize, usize, usize) {
-        self.ancestor = self.ancestor.max(ancestor);
-        self.trusted = self.trusted.max(trusted);
-        self.taint = self.taint.max(taint);
-        (self.ancestor, self.trusted, self.taint)
+    fn from_process(process: &Process<'_>) -> Self {
+        Self {
+            ppid: process.ppid(),
+            original_ppid: process.original_ppid(),
+            responsible_pid: process.responsible_audit_token().map(|token| token.pid()).unwrap_or(0),
+        }
     }
 }
 
-#[derive(Debug)]
-struct CallbackLatencyMetrics {
-    counts: [AtomicU64; CALLBACK_LATENCY_BUCKETS_US.len() + 1],
-    total: AtomicU64,
-    max_us: AtomicU64,
-}
+impl ProcessTable {
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
 
-#[derive(Debug, Clone)]
-struct CallbackLatencySnapshot {
-    counts: [u64; CALLBACK_LATENCY_BUCKETS_US.len() + 1],
+    fn is_current(&self, pid: i32, start: Option<ProcessStartTimeKey>) -> bool {
+        match (self.entries.get(&pid), start) {
+            (Some(entry), Some(start)) => entry.start.is_none_or(|known| known == start),
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+
+    fn lineage_from_parent(&self, ppid: i32, child_start: Option<ProcessStartTimeKey>) -> Option<AiLineage> {
+        let parent = self.entries.get(&ppid)?;
+        // A "parent" that started after the child is a reused pid, not the real parent.
+        if let (Some(parent_start), Some(child_start)) = (parent.start, child_start) {
+            if parent_start > child_start {
+                return None;
+            }
+        }
+        parent.ai_lineage.clone()
+    }
+
+    /// Live parent first, then the fork-time parent and the responsible process,
+    /// so a child reparented to launchd keeps the lineage it was spawned with.
+    fn lineage_from_links(
+        &self,
+        pid: i32,
+        links: ProcessLinks,
+        start: Option<ProcessStartTimeKey>,
+    ) -> Option<AiLineage> {
+        [links.ppid, links.original_ppid, links.responsible_pid]
+            .into_iter()
+            .filter(|candidate| *candidate > 1 && *candidate != pid)
+            .find_map(|candidate| self.lineage_from_parent(candidate, start))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn record_exec(
+        &mut self,
+        pid: i32,
+        start: Option<ProcessStartTimeKey>,
+        links: ProcessLinks,
+        executable_path: String,
+        argv: Vec<String>,
+        policy: &SecurityPolicy,
+        now: u64,
+    ) {
+        self.record_exec_with_evidence(
+            pid,
+            start,
+            links,
+            executable_path,
+            argv,
+            ExecEvidence::default(),
+            policy,
+            now,
+        );
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn record_exec_with_evidence(
+        &mut self,
+        pid: i32,
+        start: Option<ProcessStartTimeKey>,
+        links: ProcessLinks,
+        executable_path: String,
+        argv: Vec<String>,
+        evidence: ExecEvidence,
+        policy: &SecurityPolicy,
+        now: u64,
+    ) {
+        if pid <= 0 {
+            return;
+        }
+        // exec keeps the pid and start time; lineage recorded at fork survives it.
+        let previous = self
+            .entries
+            .get(&pid)
+            .filter(|entry| entry.start.is_none() || start.is_none() || entry.start == start)
+            .cloned();
+        let links = match previous.as_ref() {
+            Some(previous) => ProcessLinks {
+                ppid: links.ppid,
+                original_ppid: if previous.links.original_ppid > 1 {
+                    previous.links.original_ppid
+                } else {
+                    links.original_ppid
+                },
+                responsible_pid: if links.responsible_pid > 0 {
+                    links.responsible_pid
+                } else {
+                    previous.links.responsible_pid
+                },
+            },
+            None => links,
+        };
+        let detection = detect_ai_agent(
+            policy,
+            &executable_path,
+            argv.first().map(String::as_str),
+            &argv,
+            &evidence,
+        );
+        let previous_lineage = previous.and_then(|entry| entry.ai_lineage);
+        // Env rules are inherited by every child, so only a detection that
+        // starts a session or changes the inherited label is worth a log line.
+        if let Some(detection) = detection.as_ref() {
+            let inherited = previous_lineage
+                .clone()
+                .or_else(|| self.lineage_from_links(pid, links, start));
+            if inherited.is_none_or(|lineage| lineage.session.is_none() || lineage.label != detection.label) {
+                println!(
+                    "[detector] pid {} ({}) detected as {} by rule {} (confidence {})",
+                    pid,
+                    exe_name(&executable_path),
+                    detection.label,
+                    detection.rule,
+                    detection.confidence.as_str()
+                );
+            }
+        }
+        let ai_label = detection.map(|detection| detection.label);
+        // A registered session is authoritative for the whole tree, even over a
+        // different pattern-matched agent running inside it.
+        let ai_lineage = match (previous_lineage, ai_label.clone()) {
+            (Some(lineage), _) if lineage.is_registered() => Some(lineage),
+            (previous_lineage, Some(label)) => {
+                Some(self.lineage_for_detected(pid, start, links, previous_lineage, label, now))
+            },
+            (previous_lineage, None) => previous_lineage
+                .or_else(|| self.lineage_from_links(pid, links, start))
+                .or_else(|| self.claim_launcher_handoff(pid, links, &executable_path, &argv, now)),
+        };
+        if let Some(lineage) = ai_lineage.as_ref() {
+            self.register_launcher_handoff(lineage, &executable_path, &argv, now);
+        }
+        self.entries.insert(
+            pid,
+            ProcessTableEntry {
+                start,
+                links,
+                executable_path,
+                argv,
+                evidence,
+                ai_label,
+                ai_lineage,
+                label_checked_at: now,
+            },
+        );
+    }
+
+    fn record_fork(
+        &mut self,
+        parent_pid: i32,
+        child_pid: i32,
+        child_start: Option<ProcessStartTimeKey>,
+        policy: &SecurityPolicy,
+        now: u64,
+    ) {
+        if child_pid <= 0 {
+            return;
+        }
+        self.ensure_process(parent_pid, policy, now);
+        self.refresh_title_label(parent_pid, policy, now);
+        let Some(parent) = self.entries.get(&parent_pid).cloned() else {
+            self.load_from_proc(child_pid, policy, now, 0);
+            return;
+        };
+        self.entries.insert(
+            child_pid,
+            ProcessTableEntry {
+                start: child_start,
+                links: ProcessLinks {
+                    ppid: parent_pid,
+                    original_ppid: parent_pid,
+                    responsible_pid: parent.links.responsible_pid,
+                },
+                label_checked_at: now,
+                ..parent
+            },
+        );
+    }
+
+    /// Lineage for a process detected as an agent. An agent started inside another
+    /// agent's tree is attributed to the outer session; a detected root keeps its
+    /// session across exec and otherwise starts a new one.
+    fn lineage_for_detected(
+        &mut self,
+        pid: i32,
+        start: Option<ProcessStartTimeKey>,
+        links: ProcessLinks,
+        current: Option<AiLineage>,
+        label: String,
+        now: u64,
+    ) -> AiLineage {
+        let (own, inherited) = match current {
+            Some(lineage) if lineage.is_rooted_at(pid) => (Some(lineage), None),
+            current => (None, current.filter(|lineage| lineage.session.is_some())),
+        };
+        if let Some(session) = inherited
+            .or_else(|| self.lineage_from_links(pid, links, start))
+            .and_then(|lineage| lineage.session)
+        {
+            return AiLineage {
+                label,
+                session: Some(session),
+            };
+        }
+        if let Some(session) = own.and_then(|lineage| lineage.session) {
+            return AiLineage {
+                label,
+                session: Some(session),
+            };
+        }
+        let started_at = start.map(|start| start.start_tvsec).unwrap_or(now);
+        let session = Arc::new(AgentSession {
+            id: format!("{}-{}-{}", sanitize_component(&label), pid, started_at),
+            agent: label.clone(),
+            pid,
+            started_at,
+            cwd: process_cwd_for_pid(pid).unwrap_or_default(),
+            tty: process_tty_for_pid(pid),
+            source: SESSION_SOURCE_DETECTOR,
+        });
+        self.push_session_event(SessionEvent::Start(session.clone()));
+        AiLineage {
+            label,
+            session: Some(session),
+        }
+    }
+
+    fn push_session_event(&mut self, event: SessionEvent) {
+        if self.session_events.len() >= MAX_PENDING_SESSION_EVENTS {
+            self.session_events.remove(0);
+        }
+        self.session_events.push(event);
+    }
+
+    fn take_session_events(&mut self) -> Vec<SessionEvent> {
+        std::mem::take(&mut self.session_events)
+    }
+
+    /// Queue the end of the session rooted at an exited process.
+    fn end_session_rooted_at(&mut self, pid: i32, entry: &ProcessTableEntry, now: u64) {
+        if let Some(session) = entry
+            .ai_lineage
+            .as_ref()
+            .filter(|lineage| lineage.is_rooted_at(pid))
+            .and_then(|lineage| lineage.session.clone())
+        {
+            self.push_session_event(SessionEvent::End(session, now));
+        }
+    }
+
+    /// Register the process behind an ES message if the table has no entry for
+    /// this pid/start time yet (daemon restart, dropped notification, pid reuse).
+    fn observe_process(
+        &mut self,
+        pid: i32,
+        start: Option<ProcessStartTimeKey>,
+        links: ProcessLinks,
+        executable_path: &str,
+        policy: &SecurityPolicy,
+        now: u64,
+    ) {
+        if pid <= 0 || self.is_current(pid, start) {
+            return;
+        }
+        for linked_pid in [links.ppid, links.original_ppid, links.responsible_pid] {
+            if linked_pid > 1 && linked_pid != pid {
+                self.ensure_process(linked_pid, policy, now);
+            }
+        }
+        let argv = get_process_argv(pid).unwrap_or_default();
+        self.record_exec(
+            pid,
+            start,
+            links,
+            executable_path.to_string(),
+            argv,
+            policy,
+            now,
+        );
+    }
+
+    /// Remember that an AI process asked a helper service to start work on its
+    /// behalf (`launchctl submit`, `open -a`, `at`, `tmux new -d`), so the
+    /// process that service spawns can be attributed back to the agent.
+    fn register_launcher_handoff(&mut self, lineage: &AiLineage, executable_path: &str, argv: &[String], now: u64) {
+        let Some((spawner, program)) = launcher_handoff_target(executable_path, argv) else {
+            return;
+        };
+        // at jobs may run long after submission, so they wait until consumed.
+        let expires_at = (spawner != "atrun").then(|| now.saturating_add(LAUNCHER_HANDOFF_TTL_SECS));
+        self.prune_launcher_handoffs(now);
+        if self.handoffs.len() >= MAX_LAUNCHER_HANDOFFS {
+            self.handoffs.remove(0);
+        }
+        self.handoffs.push(LauncherHandoff {
+            lineage: lineage.clone(),
+            spawner,
+            program,
+            expires_at,
+        });
+    }
+
+    fn claim_launcher_handoff(
+        &mut self,
+        pid: i32,
+        links: ProcessLinks,
+        executable_path: &str,
+        argv: &[String],
+        now: u64,
+    ) -> Option<AiLineage> {
+        if self.handoffs.is_empty() {
+            return None;
+        }
+        self.prune_launcher_handoffs(now);
+        let spawner = if links.ppid <= 1 {
+            "launchd".to_string()
+        } else {
+            self.entries
+                .get(&links.ppid)
+                .map(|parent| exe_name(&parent.executable_path).to_string())?
+        };
+        let process_name = exe_name(executable_path);
+        let argv0 = argv.first().map(|value| value.rsplit('/').next().unwrap_or(value));
+        let index = self.handoffs.iter().position(|handoff| {
+            handoff.spawner == spawner
+                && handoff.program.as_deref().is_none_or(|program| {
+                    program.eq_ignore_ascii_case(process_name)
+                        || argv0.is_some_and(|argv0| program.eq_ignore_ascii_case(argv0))
+                })
+        })?;
+        let handoff = self.handoffs.remove(index);
+        println!(
+            "[process] pid {} ({}) inherits AI lineage {} via {} hand-off",
+            pid, process_name, handoff.lineage.label, handoff.spawner
+        );
+        Some(handoff.lineage)
+    }
+
+    fn prune_launcher_handoffs(&mut self, now: u64) {
+        self.handoffs
+            .retain(|handoff| handoff.expires_at.is_none_or(|expires_at| now <= expires_at));
+    }
+
+    fn remove(&mut self, pid: i32) {
+        if let Some(entry) = self.entries.remove(&pid) {
+            self.end_session_rooted_at(pid, &entry, now_ts());
+        }
+    }
+
+    fn ensure_process(&mut self, pid: i32, policy: &SecurityPolicy, now: u64) -> bool {
+        self.entries.contains_key(&pid) || self.load_from_proc(pid, policy, now, 0)
+    }
+
+    fn load_from_proc(&mut self, pid: i32, policy: &SecurityPolicy, now: u64, depth: usize) -> bool {
+        if pid <= 0 || depth >= MAX_ANCESTRY_DEPTH {
+            return false;
+        }
+        let info = match get_process_bsd_info(pid) {
+            Some(info) => info,
+            None => return false,
+        };
+        let ppid_raw = info.pbi_ppid as i32;
+        let ppid = if ppid_raw > 0 && ppid_raw != pid { ppid_raw } else { 0 };
+        if ppid > 1 && !self.entries.contains_key(&ppid) {
+            self.load_from_proc(ppid, policy, now, depth + 1);
+        }
+        let start = ProcessStartTimeKey {
+            start_tvsec: info.pbi_start_tvsec,
+            start_tvusec: info.pbi_start_tvusec,
+        };
+        let executable_path = get_process_path(pid).unwrap_or_default();
+        let argv = get_process_argv(pid).unwrap_or_default();
+        self.record_exec(
+            pid,
+            Some(start),
+            ProcessLinks::from_ppid(ppid),
+            executable_path,
+            argv,
+            policy,
+            now,
+        );
+        true
+    }
+
+    fn seed_from_snapshot(&mut self, policy: &SecurityPolicy, now: u64) {
+        for pid in list_all_pids() {
+            if !self.entries.contains_key(&pid) {
+                self.load_from_proc(pid, policy, now, 0);
+            }
+        }
+    }
+
+    /// Node-based agents rename themselves via `process.title` after exec, so a
+    /// non-AI entry re-reads argv[0] at most once per CACHE_TTL_SECS.
+    fn refresh_title_label(&mut self, pid: i32, policy: &SecurityPolicy, now: u64) {
+        let needs_refresh = self.entries.get(&pid).is_some_and(|entry| {
+            entry.ai_lineage.is_none() && now.saturating_sub(entry.label_checked_at) > CACHE_TTL_SECS
+        });
+        if !needs_refresh {
+            return;
+        }
+        let argv0 = get_process_argv0(pid);
+        if let Some(entry) = self.entries.get_mut(&pid) {
+            entry.label_checked_at = now;
+            if let Some(detection) = detect_ai_agent(
+                policy,
+                &entry.executable_path,
+                argv0.as_deref(),
+                &entry.argv,
+                &entry.evidence,
+            ) {
+                entry.ai_label = Some(detection.label.clone());
+                entry.ai_lineage = Some(AiLineage::from_label(detection.label));
+            }
+        }
+    }
+
+    fn ai_lineage_for_pid(&mut self, pid: i32, policy: &SecurityPolicy, now: u64) -> Option<String> {
+        if !self.ensure_process(pid, policy, now) {
+            return None;
+        }
+        self.refresh_title_label(pid, policy, now);
+        self.entries
+            .get(&pid)
+            .and_then(|entry| entry.ai_lineage.as_ref())
+            .map(|lineage| lineage.label.clone())
+    }
+
+    fn session_for_pid(&self, pid: i32) -> Option<Arc<AgentSession>> {
+        self.entries
+            .get(&pid)
+            .and_then(|entry| entry.ai_lineage.as_ref())
+            .and_then(|lineage| lineage.session.clone())
+    }
+
+    /// Ancestry of `pid` with start times, for matching scoped overrides.
+    /// Reparented processes follow their fork-time parent.
+    fn override_actor(&mut self, pid: i32, policy: &SecurityPolicy, now: u64) -> OverrideActor {
+        let _ = self.ensure_process(pid, policy, now);
+        let mut actor = OverrideActor {
+            ancestry: Vec::new(),
+            session_id: self.session_for_pid(pid).map(|session| session.id.clone()),
+        };
+        let mut current = pid;
+        while actor.ancestry.len() < MAX_ANCESTRY_DEPTH {
+            let Some(entry) = self.entries.get(&current) else {
+                break;
+            };
+            if let Some(start) = entry.start {
+                actor.ancestry.push((current, start.start_tvsec));
+            }
+            let parent = if entry.links.ppid > 1 {
+                entry.links.ppid
+            } else {
+                entry.links.original_ppid
+            };
+            if parent <= 1 || parent == current {
+                break;
+            }
+            current = parent;
+        }
+        actor
+    }
+
+    /// Mark `pid` as the root of a launcher-registered session. Existing
+    /// descendants are relabeled so work started before registration is covered.
+    fn register_session(&mut self, session: AgentSession, policy: &SecurityPolicy, now: u64) -> bool {
+        let pid = session.pid;
+        if !self.ensure_process(pid, policy, now) {
+            return false;
+        }
+        let session = Arc::new(session);
+        self.push_session_event(SessionEvent::Start(session.clone()));
+        let lineage = AiLineage {
+            label: session.agent.clone(),
+            session: Some(session),
+        };
+        let mut pending = vec![pid];
+        while let Some(current) = pending.pop() {
+            if let Some(entry) = self.entries.get_mut(&current) {
+                entry.ai_lineage = Some(lineage.clone());
+            }
+            pending.extend(
+                self.entries
+                    .iter()
+                    .filter(|(child, entry)| entry.links.original_ppid == current && **child != current)
+                    .filter(|(_, entry)| {
+                        entry
+                            .ai_lineage
+                            .as_ref()
+                            .is_none_or(|existing| !existing.is_registered())
+                    })
+                    .map(|(child, _)| *child),
+            );
+        }
+        true
+    }
+
+    fn argv_for_pid(&self, pid: i32) -> Option<Vec<String>> {
+        match self.entries.get(&pid) {
+            Some(entry) if !entry.argv.is_empty() => Some(entry.argv.clone()),
+            _ => get_process_argv(pid),
+        }
+    }
+
+    /// Recompute labels and lineage after `ai_agent_patterns` changes, parents first.
+    /// Lineage a process inherited (fork, exec, hand-off) stays sticky even when its
+    /// parents are gone; only lineage derived from its own label can be cleared.
+    fn refresh_ai_labels(&mut self, policy: &SecurityPolicy, now: u64) {
+        let mut order: Vec<(Option<ProcessStartTimeKey>, i32)> =
+            self.entries.iter().map(|(pid, entry)| (entry.start, *pid)).collect();
+        order.sort();
+        for (_, pid) in order {
+            let Some(entry) = self.entries.get(&pid) else {
+                continue;
+            };
+            let ai_label = detect_ai_agent(
+                policy,
+                &entry.executable_path,
+                entry.argv.first().map(String::as_str),
+                &entry.argv,
+                &entry.evidence,
+            )
+            .map(|detection| detection.label);
+            if entry.ai_lineage.as_ref().is_some_and(AiLineage::is_registered) {
+                if let Some(entry) = self.entries.get_mut(&pid) {
+                    entry.ai_label = ai_label;
+                }
+                continue;
+            }
+            let (links, start) = (entry.links, entry.start);
+            let own = entry.ai_lineage.clone().filter(|lineage| lineage.is_rooted_at(pid));
+            let inherited = entry
+                .ai_lineage
+                .clone()
+                .filter(|lineage| !lineage.is_rooted_at(pid) && entry.ai_label.as_ref() != Some(&lineage.label));
+            let ai_lineage = match ai_label.clone() {
+                Some(label) => Some(self.lineage_for_detected(pid, start, links, own.or(inherited), label, now)),
+                None => {
+                    if let Some(session) = own.and_then(|lineage| lineage.session) {
+                        self.push_session_event(SessionEvent::End(session, now));
+                    }
+                    self.lineage_from_links(pid, links, start).or(inherited)
+                },
+            };
+            if let Some(entry) = self.entries.get_mut(&pid) {
+                entry.ai_label = ai_label;
+                entry.ai_lineage = ai_lineage;
+            }
+        }
+    }
+
+    fn start_snapshot(&self) -> Vec<(i32, Option<ProcessStartTimeKey>)> {
+        self.entries.iter().map(|(pid, entry)| (*pid, entry.start)).collect()
+    }
+
+    /// Remove `pid` unless it was re-recorded since `start` was snapshotted.
+    fn remove_if_unchanged(&mut self, pid: i32, start: Option<ProcessStartTimeKey>) {
+        if self.entries.get(&pid).is_some_and(|entry| entry.start == start) {
+            self.remove(pid);
+        }
+    }
+}
+
+/// Fallback for exits NOTIFY_EXIT never delivered (dropped events, processes
+/// that predate the client). The per-pid probes run without the table lock so
+/// AUTH handlers and fork/exec bookkeeping are not held up.
+fn prune_exited_processes(process_table: &Mutex<ProcessTable>) {
+    let known = process_table
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .start_snapshot();
+    let exited: Vec<(i32, Option<ProcessStartTimeKey>)> = known
+        .into_iter()
+        .filter(|(pid, known)| match process_start_time_for_pid(*pid) {
+            Some(start) => known.is_some_and(|known| known != start),
+            None => true,
+        })
+        .collect();
+    if exited.is_empty() {
+        return;
+    }
+    let mut table = process_table.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    for (pid, start) in exited {
+        table.remove_if_unchanged(pid, start);
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct CacheWatermarkHighs {
+    ancestor: usize,
+    trusted: usize,
+    taint: usize,
+}
+
+impl CacheWatermarkHighs {
+    fn update(&mut self, ancestor: usize, trusted: usize, taint: usize) -> (usize, usize, usize) {
+        self.ancestor = self.ancestor.max(ancestor);
+        self.trusted = self.trusted.max(trusted);
+        self.taint = self.taint.max(taint);
+        (self.ancestor, self.trusted, self.taint)
+    }
+}
+
+#[derive(Debug)]
+struct CallbackLatencyMetrics {
+    counts: [AtomicU64; CALLBACK_LATENCY_BUCKETS_US.len() + 1],
+    total: AtomicU64,
+    max_us: AtomicU64,
+}
+
+#[derive(Debug, Clone)]
+struct CallbackLatencySnapshot {
+    counts: [u64; CALLBACK_LATENCY_BUCKETS_US.len() + 1],
     total: u64,
     max_us: u64,
 }
@@ -709,6 +2066,20 @@ fn path_prefix_match(path: &str, prefix: &str) -> bool {
     false
 }
 
+/// Home-digit children that are protected automatically (`~/00-notes`, `~/1-work`, ...).
+fn auto_home_digit_zones(home: &str) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(home) else {
+        return Vec::new();
+    };
+    let mut zones: Vec<String> = entries
+        .filter_map(Result::ok)
+        .map(|entry| join_path_component(home, &entry.file_name().to_string_lossy()))
+        .filter(|path| home_digit_root(path, home).is_some())
+        .collect();
+    zones.sort();
+    zones
+}
+
 fn home_digit_root(path: &str, home: &str) -> Option<String> {
     let normalized_path = trim_trailing_slashes(path);
     let normalized_home = trim_trailing_slashes(home);
@@ -734,33 +2105,6 @@ fn home_digit_root(path: &str, home: &str) -> Option<String> {
     }
 }
 
-fn normalize_absolute_path(path: &str) -> Option<String> {
-    if !path.starts_with('/') {
-        return None;
-    }
-
-    let mut parts: Vec<String> = Vec::new();
-    for component in Path::new(path).components() {
-        match component {
-            Component::RootDir => {},
-            Component::CurDir => {},
-            Component::ParentDir => {
-                let _ = parts.pop();
-            },
-            Component::Normal(segment) => {
-                parts.push(segment.to_string_lossy().to_string());
-            },
-            _ => return None,
-        }
-    }
-
-    if parts.is_empty() {
-        Some("/".to_string())
-    } else {
-        Some(format!("/{}", parts.join("/")))
-    }
-}
-
 fn sanitize_component(value: &str) -> String {
     let sanitized: String = value
         .chars()
@@ -788,16 +2132,14 @@ fn policy_user_name(home: &str) -> String {
     sanitize_component(base)
 }
 
-fn runtime_override_db_path(home: &str) -> PathBuf {
+fn log_checkpoint_path(home: &str) -> PathBuf {
     let user = policy_user_name(home);
-    PathBuf::from(OVERRIDE_STORE_DIR).join(format!("{}.json", user))
+    PathBuf::from(OVERRIDE_STORE_DIR).join(format!("{}.log-checkpoints.jsonl", user))
 }
 
-fn override_request_dir(home: &str) -> PathBuf {
-    PathBuf::from(home)
-        .join(".agentsmith-rs")
-        .join("guard")
-        .join("override-requests")
+fn runtime_override_db_path(home: &str) -> PathBuf {
+    let user = policy_user_name(home);
+    PathBuf::from(OVERRIDE_STORE_DIR).join(format!("{}.json", user))
 }
 
 fn request_response_path(request_dir: &Path, id: &str) -> PathBuf {
@@ -901,6 +2243,8 @@ struct DenialRecord {
     pid: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     ppid: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session: Option<AgentSession>,
 }
 
 impl DenialRecord {
@@ -917,6 +2261,7 @@ impl DenialRecord {
             reason: reason.to_string(),
             pid: None,
             ppid: None,
+            session: None,
         }
     }
 }
@@ -1045,6 +2390,11 @@ struct OverrideAuditRecord {
     message: String,
     requester_pid: Option<i32>,
     requester_process: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    approval_id: Option<String>,
+    /// Every target of a multi-path or glob request.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    paths: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -1056,6 +2406,8 @@ struct TaintMarkRecord {
     pid: i32,
     #[serde(skip_serializing_if = "Option::is_none")]
     ppid: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session_id: Option<String>,
 }
 
 #[derive(Debug)]
@@ -1063,6 +2415,7 @@ enum GuardLogMessage {
     Denial(DenialRecord),
     AuditOnly(DenialRecord),
     TaintMark(TaintMarkRecord),
+    Session(SessionEvent),
 }
 
 impl SecurityPolicy {
@@ -1092,16 +2445,71 @@ impl SecurityPolicy {
             .unwrap_or(DEFAULT_TAINT_TTL_SECS)
     }
 
-    fn is_override_active_for_path(&self, target_path: &str, now: u64) -> bool {
+    fn override_approval_timeout_or_default(&self) -> u64 {
+        self.override_approval_timeout_seconds
+            .filter(|timeout| *timeout > 0)
+            .unwrap_or(DEFAULT_OVERRIDE_APPROVAL_TIMEOUT_SECS)
+            .min(MAX_OVERRIDE_APPROVAL_TIMEOUT_SECS)
+    }
+
+    /// Scoped overrides only apply when `actor` is inside their scope; callers
+    /// without a process (`None`) see unscoped overrides only. Operation-limited
+    /// counted and pinned rules are never matched here, see [`Self::overrides_for_op`].
+    fn is_override_active_for_path(&self, target_path: &str, now: u64, actor: Option<&OverrideActor>) -> bool {
+        self.temporary_overrides.iter().any(|entry| {
+            !entry.is_expired(now)
+                && !entry.is_sensitive_read_only()
+                && !entry.is_event_bound()
+                && path_prefix_match(target_path, entry.path())
+                && entry
+                    .scope()
+                    .is_none_or(|scope| actor.is_some_and(|actor| actor.is_within(scope)))
+        }) && !self.override_policy.blocks_override(target_path)
+    }
+
+    fn has_restricted_override_for_path(&self, target_path: &str, now: u64) -> bool {
         self.temporary_overrides.iter().any(|entry| {
-            !entry.is_expired(now) && !entry.is_sensitive_read_only() && path_prefix_match(target_path, entry.path())
+            entry.is_restricted() && !entry.is_expired(now) && path_prefix_match(target_path, entry.path())
         })
     }
 
+    /// Restricted rules that would let `actor` perform `op` on `target_path`
+    /// (renames also match `dest`), uncounted rules first so a broader grant
+    /// is used before a single-use one is spent.
+    fn overrides_for_op(
+        &self,
+        target_path: &str,
+        now: u64,
+        actor: &OverrideActor,
+        op: &str,
+        dest: Option<&str>,
+    ) -> Vec<&TemporaryOverrideRule> {
+        let mut rules: Vec<&TemporaryOverrideRule> = self
+            .temporary_overrides
+            .iter()
+            .filter_map(|entry| match entry {
+                TemporaryOverrideEntry::Rule(rule) if entry.is_restricted() => Some(rule),
+                _ => None,
+            })
+            .filter(|rule| {
+                !matches!(rule.expires_at, Some(exp) if exp <= now)
+                    && rule.created_by.as_deref() != Some(OVERRIDE_CREATED_BY_SENSITIVE_READ_HELPER)
+                    && rule.covers_path(target_path)
+                    && rule.scope.as_ref().is_none_or(|scope| actor.is_within(scope))
+                    && rule.allows_op(op, dest)
+            })
+            .collect();
+        if !rules.is_empty() && self.override_policy.blocks_override(target_path) {
+            return Vec::new();
+        }
+        rules.sort_by_key(|rule| rule.max_uses.is_some());
+        rules
+    }
+
     fn is_sensitive_read_override_active_for_path(&self, target_path: &str, now: u64) -> bool {
         self.temporary_overrides.iter().any(|entry| {
             !entry.is_expired(now) && entry.is_sensitive_read_only() && path_prefix_match(target_path, entry.path())
-        })
+        }) && !self.override_policy.blocks_override(target_path)
     }
 
     fn is_in_configured_zone(&self, target_path: &str) -> bool {
@@ -1124,7 +2532,7 @@ impl SecurityPolicy {
             return false;
         }
 
-        let is_overridden = self.is_override_active_for_path(target_path, now_ts());
+        let is_overridden = self.is_override_active_for_path(target_path, now_ts(), None);
         if is_overridden {
             return false;
         }
@@ -1132,14 +2540,38 @@ impl SecurityPolicy {
         true
     }
 
-    fn matched_zone(&self, target_path: &str, home: &str) -> String {
-        if let Some(zone) = self
-            .protected_zones
-            .iter()
-            .find(|zone| path_prefix_match(target_path, zone.as_str()))
-            .cloned()
-        {
-            return zone;
+    fn is_truncate_gated_path(&self, target_path: &str, home: &str) -> bool {
+        if !self.truncate_gate_enabled || !self.is_protected(target_path, home) {
+            return false;
+        }
+        self.truncate_gate_zones.is_empty()
+            || self
+                .truncate_gate_zones
+                .iter()
+                .any(|zone| path_prefix_match(target_path, zone.as_str()))
+    }
+
+    fn is_metadata_gate_op_enabled(&self, op: &str) -> bool {
+        self.metadata_gate_enabled && self.metadata_gate_ops.iter().any(|entry| entry == op)
+    }
+
+    fn is_metadata_xattr_allowed(&self, name: &str) -> bool {
+        self.metadata_gate_xattr_allowlist
+            .iter()
+            .any(|entry| match entry.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => name == entry,
+            })
+    }
+
+    fn matched_zone(&self, target_path: &str, home: &str) -> String {
+        if let Some(zone) = self
+            .protected_zones
+            .iter()
+            .find(|zone| path_prefix_match(target_path, zone.as_str()))
+            .cloned()
+        {
+            return zone;
         }
 
         if self.auto_protect_home_digit_children {
@@ -1167,6 +2599,24 @@ impl SecurityPolicy {
             .collect()
     }
 
+    /// Policy detector rules followed by the rules of enabled built-in profiles.
+    fn effective_detector_rules(&self) -> impl Iterator<Item = &AgentDetectorRule> {
+        self.ai_agent_detectors.iter().chain(
+            self.ai_agent_profiles
+                .iter()
+                .flat_map(|name| agent_profile_rules(name.as_str()).iter()),
+        )
+    }
+
+    /// Environment variable names referenced by detector rules; only these are
+    /// copied out of an exec event.
+    fn detector_env_names(&self) -> HashSet<&str> {
+        self.effective_detector_rules()
+            .flat_map(|rule| rule.env.iter())
+            .map(|marker| marker.split('=').next().unwrap_or(marker))
+            .collect()
+    }
+
     fn matches_ai_agent(&self, exe_path: &str) -> bool {
         let exe_name = exe_path.rsplit('/').next().unwrap_or(exe_path);
         self.ai_agent_patterns
@@ -1189,17 +2639,19 @@ impl SecurityPolicy {
                 .any(|zone| path_prefix_match(path, zone.as_str()));
             let in_auto_zone = auto_home_digit && home_digit_root(path, home).is_some();
             !entry.is_expired(now)
+                && entry.scope().is_none_or(OverrideScope::is_alive)
+                && !entry.is_exhausted()
                 && !path.is_empty()
                 && path.starts_with('/')
                 && path != "/"
                 && (in_configured_zone || in_auto_zone || in_sensitive_zone)
         });
 
-        let mut seen_paths: HashSet<String> = HashSet::new();
+        let mut seen_paths: HashSet<(String, Option<OverrideScope>)> = HashSet::new();
         let mut deduped: Vec<TemporaryOverrideEntry> = Vec::with_capacity(self.temporary_overrides.len());
         for entry in self.temporary_overrides.iter().rev() {
             let path = trim_trailing_slashes(entry.path()).to_string();
-            if seen_paths.insert(path) {
+            if seen_paths.insert((path, entry.scope().cloned())) {
                 deduped.push(entry.clone());
             }
         }
@@ -1233,10 +2685,38 @@ impl TemporaryOverrideEntry {
         }
     }
 
+    fn scope(&self) -> Option<&OverrideScope> {
+        match self {
+            TemporaryOverrideEntry::Path(_) => None,
+            TemporaryOverrideEntry::Rule(rule) => rule.scope.as_ref(),
+        }
+    }
+
     fn is_sensitive_read_only(&self) -> bool {
         self.created_by() == Some(OVERRIDE_CREATED_BY_SENSITIVE_READ_HELPER)
     }
 
+    /// Operation-limited, counted and pinned rules can only be checked
+    /// against a concrete AUTH event.
+    fn is_event_bound(&self) -> bool {
+        match self {
+            TemporaryOverrideEntry::Path(_) => false,
+            TemporaryOverrideEntry::Rule(rule) => !rule.ops.is_empty() || rule.max_uses.is_some() || rule.pin.is_some(),
+        }
+    }
+
+    /// Restricted entries only apply to a known process and operation.
+    fn is_restricted(&self) -> bool {
+        self.scope().is_some() || self.is_event_bound()
+    }
+
+    fn is_exhausted(&self) -> bool {
+        match self {
+            TemporaryOverrideEntry::Path(_) => false,
+            TemporaryOverrideEntry::Rule(rule) => rule.max_uses.is_some_and(|max| rule.uses >= max),
+        }
+    }
+
     fn is_expired(&self, now: u64) -> bool {
         matches!(self.expires_at(), Some(exp) if exp <= now)
     }
@@ -1507,6 +2987,300 @@ fn is_dangerous_override_path(path: &str, policy: &SecurityPolicy, home: &str) -
     false
 }
 
+fn compile_path_globs(globs: &[String]) -> Result<Vec<regex_lite::Regex>, String> {
+    globs
+        .iter()
+        .map(|glob| {
+            regex_lite::Regex::new(&glob_to_regex(glob)).map_err(|err| format!("invalid glob {:?}: {}", glob, err))
+        })
+        .collect()
+}
+
+/// Directory part of a glob before its first wildcard.
+fn glob_literal_dir(glob: &str) -> &str {
+    match glob.find(['*', '?']) {
+        Some(index) => trim_trailing_slashes(&glob[..glob[..index].rfind('/').map_or(0, |slash| slash.max(1))]),
+        None => trim_trailing_slashes(glob),
+    }
+}
+
+fn path_depth_below(path: &str, root: &str) -> usize {
+    let root = trim_trailing_slashes(root);
+    trim_trailing_slashes(path)
+        .strip_prefix(root)
+        .unwrap_or_default()
+        .split('/')
+        .filter(|component| !component.is_empty())
+        .count()
+}
+
+impl OverridePolicy {
+    fn max_minutes_or_default(&self) -> u64 {
+        self.max_minutes
+            .filter(|max| *max > 0)
+            .unwrap_or(OVERRIDE_MAX_MINUTES)
+            .min(OVERRIDE_MAX_MINUTES)
+    }
+
+    fn max_active_or_default(&self) -> usize {
+        self.max_active
+            .filter(|max| *max > 0)
+            .unwrap_or(MAX_RUNTIME_OVERRIDES)
+            .min(MAX_RUNTIME_OVERRIDES)
+    }
+
+    fn requests_per_minute_or_default(&self) -> usize {
+        self.requests_per_minute
+            .filter(|max| *max > 0)
+            .unwrap_or(MAX_OVERRIDE_REQUESTS_PER_MINUTE)
+            .min(MAX_OVERRIDE_REQUESTS_PER_MINUTE)
+    }
+
+    fn never_override_matchers(&self) -> &Result<Vec<regex_lite::Regex>, String> {
+        self.never_override_matchers
+            .get_or_init(|| compile_path_globs(&self.never_override))
+    }
+
+    /// Event-time check: overrides never apply to a matching path, and an
+    /// invalid pattern disables every override.
+    fn blocks_override(&self, target_path: &str) -> bool {
+        match self.never_override_matchers() {
+            Ok(matchers) => matchers.iter().any(|regex| regex.is_match(target_path)),
+            Err(_) => true,
+        }
+    }
+
+    /// Grant-time check: the `never_override` entry a grant on `path` would
+    /// match or cover.
+    fn never_override_conflict(&self, path: &str) -> Option<String> {
+        let matchers = match self.never_override_matchers() {
+            Ok(matchers) => matchers,
+            Err(err) => return Some(err.clone()),
+        };
+        self.never_override
+            .iter()
+            .zip(matchers)
+            .find(|(glob, regex)| regex.is_match(path) || path_prefix_match(glob_literal_dir(glob), path))
+            .map(|(glob, _)| format!("never_override {}", glob))
+    }
+
+    fn zone_rule(&self, path: &str) -> Option<&OverrideZoneRule> {
+        self.zones
+            .iter()
+            .filter(|rule| path_prefix_match(path, &rule.zone))
+            .max_by_key(|rule| trim_trailing_slashes(&rule.zone).len())
+    }
+}
+
+impl OverrideAutoApproveRule {
+    /// Whether a grant of `minutes` on `path` qualifies; repositories are only
+    /// looked for up to `zone_root`.
+    fn matches(&self, path: &str, minutes: u64, zone_root: &str) -> bool {
+        self.max_minutes.is_none_or(|max| minutes <= max)
+            && compile_path_globs(&self.paths).is_ok_and(|regexes| regexes.iter().any(|regex| regex.is_match(path)))
+            && (!self.inside_repo || is_inside_repo(path, zone_root))
+    }
+}
+
+fn is_inside_repo(path: &str, zone_root: &str) -> bool {
+    Path::new(path)
+        .ancestors()
+        .take_while(|dir| path_prefix_match(&dir.to_string_lossy(), zone_root))
+        .any(|dir| {
+            [".git", ".jj"]
+                .iter()
+                .any(|marker| fs::symlink_metadata(dir.join(marker)).is_ok_and(|meta| meta.is_dir()))
+        })
+}
+
+/// Check a grant of `minutes` on `path` against `override_policy`. The
+/// explanation lists the rules that applied; an error also carries a message.
+fn evaluate_override_policy(
+    path: &str,
+    minutes: u64,
+    policy: &SecurityPolicy,
+) -> Result<Vec<String>, (String, Vec<String>)> {
+    let rules = &policy.override_policy;
+    let mut explanation = Vec::new();
+    if let Some(conflict) = rules.never_override_conflict(path) {
+        explanation.push(format!("{} forbids overrides here", conflict));
+        return Err(("path can never be overridden".to_string(), explanation));
+    }
+
+    let mut max_minutes = rules.max_minutes_or_default();
+    if max_minutes < OVERRIDE_MAX_MINUTES {
+        explanation.push(format!(
+            "override_policy caps grants at {} minute(s)",
+            max_minutes
+        ));
+    }
+    if let Some(zone) = rules.zone_rule(path) {
+        if let Some(limit) = zone.max_minutes.filter(|limit| *limit > 0 && *limit < max_minutes) {
+            max_minutes = limit;
+            explanation.push(format!(
+                "zone {} caps grants at {} minute(s)",
+                zone.zone, limit
+            ));
+        }
+        if let Some(min_depth) = zone.min_depth {
+            let depth = path_depth_below(path, &zone.zone);
+            if depth < min_depth {
+                explanation.push(format!(
+                    "zone {} requires overrides at least {} level(s) below its root",
+                    zone.zone, min_depth
+                ));
+                return Err((
+                    format!(
+                        "path is {} level(s) below {} (min {})",
+                        depth, zone.zone, min_depth
+                    ),
+                    explanation,
+                ));
+            }
+        }
+    }
+    if minutes > max_minutes {
+        return Err((
+            format!("minutes exceeds max allowed ({})", max_minutes),
+            explanation,
+        ));
+    }
+    Ok(explanation)
+}
+
+fn is_batch_override_request(req: &OverrideRequest) -> bool {
+    !req.paths.is_empty() || req.glob.is_some()
+}
+
+/// Concrete targets of a multi-path or glob request: `path`, then `paths`,
+/// then the entries `glob` matches, normalized and deduplicated.
+fn expand_override_targets(req: &OverrideRequest) -> Result<Vec<String>, String> {
+    let mut targets: Vec<String> = Vec::new();
+    for raw in req.path.iter().chain(&req.paths) {
+        let path = normalize_absolute_path(raw.trim()).ok_or_else(|| format!("invalid absolute path: {}", raw))?;
+        if !targets.contains(&path) {
+            targets.push(path);
+        }
+    }
+    if let Some(glob) = req.glob.as_deref() {
+        for path in expand_override_glob(glob.trim())? {
+            if !targets.contains(&path) {
+                targets.push(path);
+            }
+        }
+    }
+    if targets.is_empty() {
+        return Err("missing path".to_string());
+    }
+    if targets.len() > MAX_OVERRIDE_BATCH_PATHS {
+        return Err(format!("too many paths (max {})", MAX_OVERRIDE_BATCH_PATHS));
+    }
+    Ok(targets)
+}
+
+/// Entries of the glob's directory whose names match its last component;
+/// hidden entries only match a pattern starting with `.`.
+fn expand_override_glob(glob: &str) -> Result<Vec<String>, String> {
+    let normalized = normalize_absolute_path(glob).ok_or_else(|| format!("invalid glob: {}", glob))?;
+    let (dir, pattern) = normalized.rsplit_once('/').unwrap_or(("", normalized.as_str()));
+    let dir = if dir.is_empty() { "/" } else { dir };
+    if dir.contains(['*', '?']) || pattern.contains("**") {
+        return Err("glob wildcards are only allowed in the last path component".to_string());
+    }
+    if !pattern.contains(['*', '?']) {
+        return Err(format!("glob {} has no wildcard", normalized));
+    }
+    let matcher = regex_lite::Regex::new(&glob_to_regex(pattern))
+        .map_err(|err| format!("invalid glob {:?}: {}", normalized, err))?;
+    let entries = fs::read_dir(dir).map_err(|err| format!("cannot read {}: {}", dir, err))?;
+    let mut matched: Vec<String> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+        .filter(|name| (pattern.starts_with('.') || !name.starts_with('.')) && matcher.is_match(name))
+        .map(|name| join_path_component(dir, &name))
+        .collect();
+    if matched.is_empty() {
+        return Err(format!("glob {} matched nothing", normalized));
+    }
+    matched.sort();
+    Ok(matched)
+}
+
+/// Grant every target of a multi-path or glob request as its own rule, or
+/// none of them if any target is refused.
+fn apply_override_batch(
+    req: &OverrideRequest,
+    policy: &SecurityPolicy,
+    home: &str,
+    overrides: &mut Vec<TemporaryOverrideEntry>,
+) -> (bool, OverrideResponse) {
+    let refuse = |status: &str, message: String, explanation: Vec<String>| OverrideResponse {
+        id: req.id.clone(),
+        status: status.to_string(),
+        message,
+        expires_at: None,
+        explanation,
+        paths: Vec::new(),
+    };
+    let targets = match expand_override_targets(req) {
+        Ok(targets) => targets,
+        Err(message) => return (false, refuse("invalid", message, Vec::new())),
+    };
+    if req.rename_to.is_some() && targets.len() > 1 {
+        return (
+            false,
+            refuse(
+                "invalid",
+                "rename_to needs a single path".to_string(),
+                Vec::new(),
+            ),
+        );
+    }
+
+    let mut staged = overrides.clone();
+    let mut explanation: Vec<String> = Vec::new();
+    let mut granted = None;
+    for (index, path) in targets.iter().enumerate() {
+        let single = OverrideRequest {
+            id: format!("{}-{}", req.id, index + 1),
+            path: Some(path.clone()),
+            paths: Vec::new(),
+            glob: None,
+            ..req.clone()
+        };
+        let (_, response) = apply_override_request(&single, policy, home, &mut staged);
+        if response.status != "ok" {
+            return (
+                false,
+                refuse(
+                    &response.status,
+                    format!("{}: {}", path, response.message),
+                    response.explanation,
+                ),
+            );
+        }
+        for line in response.explanation {
+            if !explanation.contains(&line) {
+                explanation.push(line);
+            }
+        }
+        granted = Some((response.message, response.expires_at));
+    }
+    *overrides = staged;
+    let (message, expires_at) = granted.unwrap_or_default();
+    (
+        true,
+        OverrideResponse {
+            id: req.id.clone(),
+            status: "ok".to_string(),
+            message: format!("{} path(s): {}", targets.len(), message),
+            expires_at,
+            explanation,
+            paths: targets,
+        },
+    )
+}
+
 fn apply_override_request(
     req: &OverrideRequest,
     policy: &SecurityPolicy,
@@ -1514,6 +3288,22 @@ fn apply_override_request(
     overrides: &mut Vec<TemporaryOverrideEntry>,
 ) -> (bool, OverrideResponse) {
     let action = req.action.trim().to_lowercase();
+    if is_batch_override_request(req) {
+        if is_override_grant_action(&action) {
+            return apply_override_batch(req, policy, home, overrides);
+        }
+        return (
+            false,
+            OverrideResponse {
+                id: req.id.clone(),
+                status: "invalid".to_string(),
+                message: "paths and glob are only valid for grants".to_string(),
+                expires_at: None,
+                explanation: Vec::new(),
+                paths: Vec::new(),
+            },
+        );
+    }
     match action.as_str() {
         "grant" | "grant-sensitive-read" => {
             let is_sensitive_read_grant = action == "grant-sensitive-read";
@@ -1526,9 +3316,11 @@ fn apply_override_request(
                     false,
                     OverrideResponse {
                         id: req.id.clone(),
-                        status: "error".to_string(),
+                        status: "invalid".to_string(),
                         message: "missing path".to_string(),
                         expires_at: None,
+                        explanation: Vec::new(),
+                        paths: Vec::new(),
                     },
                 );
             }
@@ -1539,9 +3331,11 @@ fn apply_override_request(
                         false,
                         OverrideResponse {
                             id: req.id.clone(),
-                            status: "error".to_string(),
+                            status: "invalid".to_string(),
                             message: "invalid absolute path".to_string(),
                             expires_at: None,
+                            explanation: Vec::new(),
+                            paths: Vec::new(),
                         },
                     );
                 },
@@ -1551,9 +3345,11 @@ fn apply_override_request(
                     false,
                     OverrideResponse {
                         id: req.id.clone(),
-                        status: "error".to_string(),
+                        status: "invalid".to_string(),
                         message: "invalid absolute path".to_string(),
                         expires_at: None,
+                        explanation: Vec::new(),
+                        paths: Vec::new(),
                     },
                 );
             }
@@ -1562,9 +3358,11 @@ fn apply_override_request(
                     false,
                     OverrideResponse {
                         id: req.id.clone(),
-                        status: "error".to_string(),
+                        status: "invalid".to_string(),
                         message: format!("path too long (max {})", MAX_OVERRIDE_PATH_LEN),
                         expires_at: None,
+                        explanation: Vec::new(),
+                        paths: Vec::new(),
                     },
                 );
             }
@@ -1586,6 +3384,8 @@ fn apply_override_request(
                         status: "error".to_string(),
                         message,
                         expires_at: None,
+                        explanation: Vec::new(),
+                        paths: Vec::new(),
                     },
                 );
             }
@@ -1597,6 +3397,8 @@ fn apply_override_request(
                         status: "error".to_string(),
                         message: "broad path override is not allowed".to_string(),
                         expires_at: None,
+                        explanation: Vec::new(),
+                        paths: Vec::new(),
                     },
                 );
             }
@@ -1607,40 +3409,145 @@ fn apply_override_request(
                     false,
                     OverrideResponse {
                         id: req.id.clone(),
-                        status: "error".to_string(),
+                        status: "invalid".to_string(),
                         message: "no-expire override is disabled for automated requests".to_string(),
                         expires_at: None,
+                        explanation: Vec::new(),
+                        paths: Vec::new(),
                     },
                 );
             }
-            if minutes > OVERRIDE_MAX_MINUTES {
-                return (
-                    false,
-                    OverrideResponse {
-                        id: req.id.clone(),
-                        status: "error".to_string(),
-                        message: format!("minutes exceeds max allowed ({})", OVERRIDE_MAX_MINUTES),
-                        expires_at: None,
+            let explanation = match evaluate_override_policy(&path, minutes, policy) {
+                Ok(explanation) => explanation,
+                Err((message, explanation)) => {
+                    return (
+                        false,
+                        OverrideResponse {
+                            id: req.id.clone(),
+                            status: "error".to_string(),
+                            message,
+                            expires_at: None,
+                            explanation,
+                            paths: Vec::new(),
+                        },
+                    );
+                },
+            };
+
+            let scope = match resolve_override_scope(req) {
+                Ok(scope) if scope.is_some() && is_sensitive_read_grant => {
+                    return (
+                        false,
+                        OverrideResponse {
+                            id: req.id.clone(),
+                            status: "invalid".to_string(),
+                            message: "sensitive read overrides cannot be scoped".to_string(),
+                            expires_at: None,
+                            explanation: Vec::new(),
+                            paths: Vec::new(),
+                        },
+                    );
+                },
+                Ok(scope) => scope,
+                Err(message) => {
+                    return (
+                        false,
+                        OverrideResponse {
+                            id: req.id.clone(),
+                            status: "invalid".to_string(),
+                            message,
+                            expires_at: None,
+                            explanation: Vec::new(),
+                            paths: Vec::new(),
+                        },
+                    );
+                },
+            };
+
+            let limits = match resolve_override_limits(req) {
+                Ok(limits) if limits.is_limited() && is_sensitive_read_grant => {
+                    return (
+                        false,
+                        OverrideResponse {
+                            id: req.id.clone(),
+                            status: "invalid".to_string(),
+                            message: "sensitive read overrides cannot be limited to operations".to_string(),
+                            expires_at: None,
+                            explanation: Vec::new(),
+                            paths: Vec::new(),
+                        },
+                    );
+                },
+                Ok(limits) => limits,
+                Err(message) => {
+                    return (
+                        false,
+                        OverrideResponse {
+                            id: req.id.clone(),
+                            status: "invalid".to_string(),
+                            message,
+                            expires_at: None,
+                            explanation: Vec::new(),
+                            paths: Vec::new(),
+                        },
+                    );
+                },
+            };
+
+            let pin = match (req.pin, is_sensitive_read_grant) {
+                (false, _) => None,
+                (true, true) => {
+                    return (
+                        false,
+                        OverrideResponse {
+                            id: req.id.clone(),
+                            status: "invalid".to_string(),
+                            message: "sensitive read overrides cannot be pinned".to_string(),
+                            expires_at: None,
+                            explanation: Vec::new(),
+                            paths: Vec::new(),
+                        },
+                    );
+                },
+                (true, false) => match OverridePin::capture(&path) {
+                    Ok(pin) => Some(pin),
+                    Err(message) => {
+                        return (
+                            false,
+                            OverrideResponse {
+                                id: req.id.clone(),
+                                status: "error".to_string(),
+                                message,
+                                expires_at: None,
+                                explanation: Vec::new(),
+                                paths: Vec::new(),
+                            },
+                        );
                     },
-                );
-            }
+                },
+            };
 
             let now = now_ts();
             let expires_at = now.saturating_add(minutes.saturating_mul(60));
             let normalized = path.clone();
-            overrides.retain(|entry| trim_trailing_slashes(entry.path()) != normalized.as_str());
-            if overrides.len() >= MAX_RUNTIME_OVERRIDES {
+            overrides.retain(|entry| {
+                trim_trailing_slashes(entry.path()) != normalized.as_str() || entry.scope() != scope.as_ref()
+            });
+            let max_active = policy.override_policy.max_active_or_default();
+            if overrides.len() >= max_active {
                 return (
                     false,
                     OverrideResponse {
                         id: req.id.clone(),
                         status: "error".to_string(),
-                        message: format!("too many active overrides (max {})", MAX_RUNTIME_OVERRIDES),
+                        message: format!("too many active overrides (max {})", max_active),
                         expires_at: None,
+                        explanation: Vec::new(),
+                        paths: Vec::new(),
                     },
                 );
             }
-            overrides.push(TemporaryOverrideEntry::Rule(TemporaryOverrideRule {
+            let rule = TemporaryOverrideRule {
                 path,
                 expires_at: Some(expires_at),
                 created_at: Some(now),
@@ -1649,13 +3556,30 @@ fn apply_override_request(
                 } else {
                     OVERRIDE_CREATED_BY_HELPER.to_string()
                 }),
-            }));
+                scope: scope.clone(),
+                id: limits
+                    .max_uses
+                    .map(|_| format!("{}-{}", sanitize_component(&req.id), now)),
+                ops: limits.ops,
+                rename_to: limits.rename_to,
+                max_uses: limits.max_uses,
+                uses: 0,
+                pin,
+            };
+            let limit_description = rule.describe_limits();
+            overrides.push(TemporaryOverrideEntry::Rule(rule));
 
-            let message = if is_sensitive_read_grant {
+            let mut message = if is_sensitive_read_grant {
                 format!("sensitive read override granted for {} minute(s)", minutes)
             } else {
                 format!("override granted for {} minute(s)", minutes)
             };
+            if let Some(scope) = &scope {
+                message.push_str(&format!(", limited to {}", scope.describe()));
+            }
+            if let Some(limits) = limit_description {
+                message.push_str(&format!(" ({})", limits));
+            }
 
             (
                 true,
@@ -1664,6 +3588,8 @@ fn apply_override_request(
                     status: "ok".to_string(),
                     message,
                     expires_at: Some(expires_at),
+                    explanation,
+                    paths: Vec::new(),
                 },
             )
         },
@@ -1677,9 +3603,11 @@ fn apply_override_request(
                     false,
                     OverrideResponse {
                         id: req.id.clone(),
-                        status: "error".to_string(),
+                        status: "invalid".to_string(),
                         message: "missing path".to_string(),
                         expires_at: None,
+                        explanation: Vec::new(),
+                        paths: Vec::new(),
                     },
                 );
             }
@@ -1690,9 +3618,11 @@ fn apply_override_request(
                         false,
                         OverrideResponse {
                             id: req.id.clone(),
-                            status: "error".to_string(),
+                            status: "invalid".to_string(),
                             message: "invalid absolute path".to_string(),
                             expires_at: None,
+                            explanation: Vec::new(),
+                            paths: Vec::new(),
                         },
                     );
                 },
@@ -1702,9 +3632,11 @@ fn apply_override_request(
                     false,
                     OverrideResponse {
                         id: req.id.clone(),
-                        status: "error".to_string(),
+                        status: "invalid".to_string(),
                         message: format!("path too long (max {})", MAX_OVERRIDE_PATH_LEN),
                         expires_at: None,
+                        explanation: Vec::new(),
+                        paths: Vec::new(),
                     },
                 );
             }
@@ -1718,6 +3650,8 @@ fn apply_override_request(
                     status: "ok".to_string(),
                     message: format!("removed {} override entries", removed),
                     expires_at: None,
+                    explanation: Vec::new(),
+                    paths: Vec::new(),
                 },
             )
         },
@@ -1731,6 +3665,8 @@ fn apply_override_request(
                     status: "ok".to_string(),
                     message: "all overrides cleared".to_string(),
                     expires_at: None,
+                    explanation: Vec::new(),
+                    paths: Vec::new(),
                 },
             )
         },
@@ -1738,15 +3674,123 @@ fn apply_override_request(
             false,
             OverrideResponse {
                 id: req.id.clone(),
-                status: "error".to_string(),
+                status: "invalid".to_string(),
                 message: format!("unknown action: {}", req.action),
                 expires_at: None,
+                explanation: Vec::new(),
+                paths: Vec::new(),
             },
         ),
     }
 }
 
-fn consume_override_request_budget(window: &mut VecDeque<u64>, now: u64) -> bool {
+/// Resolve a grant's requested scope to the live process it binds to. Session
+/// ids are `<agent>-<root pid>-<root start time>`, so a session scope is the
+/// subtree of the session's root process.
+fn resolve_override_scope(req: &OverrideRequest) -> Result<Option<OverrideScope>, String> {
+    let session = req
+        .scope_session
+        .as_deref()
+        .map(str::trim)
+        .filter(|session| !session.is_empty());
+    match (req.scope_pid, session) {
+        (None, None) => Ok(None),
+        (Some(_), Some(_)) => Err("scope_pid and scope_session are mutually exclusive".to_string()),
+        (Some(pid), None) => {
+            if pid <= 1 {
+                return Err("invalid scope_pid".to_string());
+            }
+            let info = get_process_bsd_info(pid).ok_or_else(|| format!("scope pid {} is not alive", pid))?;
+            Ok(Some(OverrideScope {
+                pid,
+                start_tvsec: info.pbi_start_tvsec,
+                session_id: None,
+            }))
+        },
+        (None, Some(session)) => {
+            let mut parts = session.rsplitn(3, '-');
+            let started_at = parts.next().and_then(|value| value.parse::<u64>().ok());
+            let pid = parts.next().and_then(|value| value.parse::<i32>().ok());
+            let (Some(started_at), Some(pid), Some(_agent)) = (started_at, pid, parts.next()) else {
+                return Err(format!("malformed session id: {}", session));
+            };
+            let scope = OverrideScope {
+                pid,
+                start_tvsec: started_at,
+                session_id: Some(session.to_string()),
+            };
+            if pid <= 1 || !scope.is_alive() {
+                return Err(format!("session {} has ended", session));
+            }
+            Ok(Some(scope))
+        },
+    }
+}
+
+/// Operation and use-count limits requested for a grant.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct OverrideLimits {
+    ops: Vec<String>,
+    rename_to: Option<String>,
+    max_uses: Option<u32>,
+}
+
+impl OverrideLimits {
+    fn is_limited(&self) -> bool {
+        !self.ops.is_empty() || self.max_uses.is_some()
+    }
+}
+
+fn resolve_override_limits(req: &OverrideRequest) -> Result<OverrideLimits, String> {
+    let mut ops: Vec<String> = Vec::new();
+    for op in &req.ops {
+        let op = op.trim().to_lowercase();
+        if !OVERRIDE_OPS.contains(&op.as_str()) {
+            return Err(format!(
+                "unknown operation: {} (expected {})",
+                op,
+                OVERRIDE_OPS.join(", ")
+            ));
+        }
+        if !ops.contains(&op) {
+            ops.push(op);
+        }
+    }
+
+    let rename_to = match req.rename_to.as_deref().map(str::trim).filter(|dest| !dest.is_empty()) {
+        Some(dest) => {
+            let dest = normalize_absolute_path(dest)
+                .filter(|dest| dest != "/" && dest.len() <= MAX_OVERRIDE_PATH_LEN)
+                .ok_or_else(|| "invalid rename_to path".to_string())?;
+            if ops.is_empty() {
+                ops.push("rename".to_string());
+            } else if !ops.iter().any(|op| op == "rename") {
+                return Err("rename_to requires the rename operation".to_string());
+            }
+            Some(dest)
+        },
+        None => None,
+    };
+
+    let max_uses = match req.max_uses {
+        Some(0) => return Err("max_uses must be at least 1".to_string()),
+        Some(uses) if uses > MAX_OVERRIDE_USES => {
+            return Err(format!(
+                "max_uses exceeds max allowed ({})",
+                MAX_OVERRIDE_USES
+            ));
+        },
+        uses => uses,
+    };
+
+    Ok(OverrideLimits {
+        ops,
+        rename_to,
+        max_uses,
+    })
+}
+
+fn consume_override_request_budget(window: &mut VecDeque<u64>, now: u64, limit: usize) -> bool {
     while let Some(oldest) = window.front().copied() {
         if now.saturating_sub(oldest) < 60 {
             break;
@@ -1754,7 +3798,7 @@ fn consume_override_request_budget(window: &mut VecDeque<u64>, now: u64) -> bool
         let _ = window.pop_front();
     }
 
-    if window.len() >= MAX_OVERRIDE_REQUESTS_PER_MINUTE {
+    if window.len() >= limit {
         return false;
     }
 
@@ -1762,7 +3806,11 @@ fn consume_override_request_budget(window: &mut VecDeque<u64>, now: u64) -> bool
     true
 }
 
-fn validate_override_request_origin(req: &OverrideRequest, policy: &SecurityPolicy) -> Result<(i32, String), String> {
+fn validate_override_request_origin(
+    req: &OverrideRequest,
+    policy: &SecurityPolicy,
+    process_table: &Mutex<ProcessTable>,
+) -> Result<(i32, String), String> {
     let pid = req.requester_pid.ok_or_else(|| "missing requester_pid".to_string())?;
     if pid <= 1 {
         return Err("invalid requester_pid".to_string());
@@ -1782,15 +3830,31 @@ fn validate_override_request_origin(req: &OverrideRequest, policy: &SecurityPoli
         return Err("requester process is not agentsmith-override helper".to_string());
     }
 
-    let mut cache = HashMap::new();
-    if let Some(ai_ancestor) = find_ai_ancestor(pid, policy, &mut cache) {
-        return Err(format!(
+    reject_ai_originated_request(pid, policy, process_table)?;
+    Ok((pid, process))
+}
+
+/// Refuse a request from a process with AI lineage. Lineage comes from the
+/// daemon's event-driven process table, which keeps fork-time and responsible
+/// links and launcher hand-offs, so a helper detached with `nohup setsid` or
+/// `launchctl submit` is still attributed to its agent.
+fn reject_ai_originated_request(
+    pid: i32,
+    policy: &SecurityPolicy,
+    process_table: &Mutex<ProcessTable>,
+) -> Result<(), String> {
+    let mut table = process_table.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    // An entry left behind by an earlier process with the same pid says nothing.
+    if !table.is_current(pid, process_start_time_for_pid(pid)) {
+        table.remove(pid);
+    }
+    match find_ai_ancestor(pid, policy, &mut table) {
+        Some(ai_ancestor) => Err(format!(
             "AI-originated override request is blocked (ancestor: {})",
             ai_ancestor
-        ));
+        )),
+        None => Ok(()),
     }
-
-    Ok((pid, process))
 }
 
 fn write_override_response(response_path: &Path, response: &OverrideResponse) -> io::Result<()> {
@@ -1850,25 +3914,498 @@ fn log_override_audit(home: &str, record: &OverrideAuditRecord) {
 
     let log_path = guard_dir.join("override-audit.jsonl");
     if let Ok(json) = serde_json::to_string(record) {
-        if let Ok(mut file) = open_append_no_follow(&log_path, DEFAULT_FILE_MODE) {
-            if verify_regular_file(&file, &log_path).is_ok() {
-                if let Ok(meta) = file.metadata() {
-                    if meta.len() > OVERRIDE_AUDIT_MAX_BYTES {
-                        let _ = file.set_len(0);
-                    }
-                }
-                let _ = writeln!(file, "{}", json);
+        append_guard_log(home, &log_path, &json, OVERRIDE_AUDIT_MAX_BYTES);
+    }
+}
+
+/// Where the final answer for a pending override goes once a human decides.
+#[derive(Debug)]
+enum PendingReply {
+    File(PathBuf),
+    Socket(mpsc::Sender<ControlResponse>),
+}
+
+/// Grant request parked until a human approves or rejects it.
+#[derive(Debug)]
+struct PendingOverride {
+    request: OverrideRequest,
+    requester: OverrideParty,
+    created_at: u64,
+    deadline: u64,
+    related_denial: Option<serde_json::Value>,
+    reply: PendingReply,
+}
+
+/// Grants awaiting a decision, owned by the policy thread. Answers to parked
+/// requesters go through `outbox` so they are sent after the change is live.
+#[derive(Debug, Default)]
+struct PendingOverrideQueue {
+    entries: Vec<PendingOverride>,
+    outbox: Vec<(PendingReply, OverrideResponse)>,
+    dirty: bool,
+}
+
+impl PendingOverrideQueue {
+    fn flush_replies(&mut self) {
+        for (reply, response) in self.outbox.drain(..) {
+            send_pending_reply(reply, response);
+        }
+    }
+}
+
+/// Outcome of routing a validated override request.
+#[derive(Debug)]
+enum OverrideRouting {
+    Done(bool, OverrideResponse),
+    Pending(OverrideResponse),
+}
+
+fn is_override_grant_action(action: &str) -> bool {
+    matches!(action, "grant" | "grant-sensitive-read")
+}
+
+fn pending_overrides_path(home: &str) -> PathBuf {
+    PathBuf::from(home)
+        .join(".agentsmith-rs")
+        .join("guard")
+        .join("override-pending.json")
+}
+
+/// Most recent denial (within an hour) on the requested path or below it, so
+/// the approver sees what the agent actually tried.
+fn related_denial_for_path(home: &str, path: &str, now: u64) -> Option<serde_json::Value> {
+    let log_path = PathBuf::from(home)
+        .join(".agentsmith-rs")
+        .join("guard")
+        .join("denials.jsonl");
+    let content = read_guard_log(
+        &log_path,
+        now.saturating_sub(PENDING_OVERRIDE_DENIAL_LOOKBACK_SECS),
+    )
+    .ok()?;
+    content
+        .lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .take_while(|record| {
+            record["ts"]
+                .as_u64()
+                .is_some_and(|ts| now.saturating_sub(ts) <= PENDING_OVERRIDE_DENIAL_LOOKBACK_SECS)
+        })
+        .find(|record| {
+            record["path"]
+                .as_str()
+                .is_some_and(|denied| path_prefix_match(denied, path))
+        })
+}
+
+fn pending_override_snapshot(queue: &PendingOverrideQueue) -> serde_json::Value {
+    serde_json::Value::Array(
+        queue
+            .entries
+            .iter()
+            .map(|entry| {
+                serde_json::json!({
+                    "id": entry.request.id,
+                    "action": entry.request.action,
+                    "path": entry.request.path,
+                    "minutes": entry.request.minutes,
+                    "scope_pid": entry.request.scope_pid,
+                    "scope_session": entry.request.scope_session,
+                    "ops": entry.request.ops,
+                    "rename_to": entry.request.rename_to,
+                    "max_uses": entry.request.max_uses,
+                    "pin": entry.request.pin,
+                    "paths": entry.request.paths,
+                    "requester_pid": entry.requester.pid,
+                    "requester_process": entry.requester.process,
+                    "created_at": entry.created_at,
+                    "deadline": entry.deadline,
+                    "related_denial": entry.related_denial,
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Mirror the queue to `guard/override-pending.json` for the CLI and menubar.
+/// Informational only: decisions are checked against the in-memory queue.
+fn write_pending_override_snapshot(home: &str, queue: &mut PendingOverrideQueue) {
+    if !queue.dirty {
+        return;
+    }
+    queue.dirty = false;
+    let path = pending_overrides_path(home);
+    let result = serde_json::to_vec_pretty(&pending_override_snapshot(queue))
+        .map_err(|err| io::Error::other(err.to_string()))
+        .and_then(|content| {
+            let mut file = open_truncate_no_follow(&path, DEFAULT_FILE_MODE)?;
+            verify_regular_file(&file, &path)?;
+            file.write_all(&content)
+        });
+    if let Err(err) = result {
+        eprintln!("[override] failed to write pending snapshot: {}", err);
+    }
+}
+
+/// All targets of a parked multi-path request, empty for a single path.
+fn parked_override_targets(req: &OverrideRequest) -> Vec<String> {
+    if req.paths.is_empty() {
+        return Vec::new();
+    }
+    req.path.iter().chain(&req.paths).cloned().collect()
+}
+
+fn send_pending_reply(reply: PendingReply, response: OverrideResponse) {
+    match reply {
+        PendingReply::File(path) => {
+            if let Err(err) = write_override_response(&path, &response) {
+                eprintln!("[override] failed to write response: {}", err);
             }
+        },
+        PendingReply::Socket(sender) => {
+            let _ = sender.send(ControlResponse::from_override(response));
+        },
+    }
+}
+
+fn log_pending_override_step(home: &str, entry: &PendingOverride, status: &str, message: &str) {
+    log_override_audit(
+        home,
+        &OverrideAuditRecord {
+            ts: now_ts(),
+            request_id: entry.request.id.clone(),
+            action: entry.request.action.clone(),
+            path: entry.request.path.clone(),
+            minutes: entry.request.minutes,
+            status: status.to_string(),
+            message: message.to_string(),
+            requester_pid: Some(entry.requester.pid),
+            requester_process: Some(entry.requester.process.clone()),
+            approval_id: Some(entry.request.id.clone()),
+            paths: parked_override_targets(&entry.request),
+        },
+    );
+}
+
+/// A process that asked for or decided a parked override, captured when it
+/// reached the daemon.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct OverrideParty {
+    pid: i32,
+    process: String,
+    actor: OverrideActor,
+    tty: Option<String>,
+    uid: Option<u32>,
+    via_menubar: bool,
+}
+
+impl OverrideParty {
+    fn resolve(pid: i32, process: String, policy: &SecurityPolicy, process_table: &Mutex<ProcessTable>) -> Self {
+        let actor = process_table
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .override_actor(pid, policy, now_ts());
+        let info = get_process_bsd_info(pid);
+        let via_menubar = actor
+            .ancestry
+            .get(1)
+            .and_then(|(parent, _)| process_name_for_pid(*parent))
+            .is_some_and(|name| name == MENUBAR_PROCESS_NAME);
+        Self {
+            pid,
+            process,
+            actor,
+            tty: info.as_ref().and_then(tty_name_from_bsd_info),
+            uid: info.map(|info| info.pbi_uid),
+            via_menubar,
         }
     }
 }
 
+/// An approval only means something if it comes from someone else: not the
+/// requester's session, not its process or the shell that launched it, and
+/// from root, the menubar app or a terminal the requester is not attached to.
+fn check_override_approver(requester: &OverrideParty, approver: &OverrideParty) -> Result<(), String> {
+    if approver.pid == requester.pid {
+        return Err("a request cannot be decided by its own requester".to_string());
+    }
+    if requester.actor.session_id.is_some() && requester.actor.session_id == approver.actor.session_id {
+        return Err("the approver belongs to the requester's agent session".to_string());
+    }
+    // Every menubar click runs under the same app, so it does not tie requests together.
+    let requester_tree = if requester.via_menubar { 1 } else { 2 };
+    let shares_tree = requester
+        .actor
+        .ancestry
+        .iter()
+        .take(requester_tree)
+        .any(|link| approver.actor.ancestry.contains(link))
+        || approver
+            .actor
+            .ancestry
+            .first()
+            .is_some_and(|own| requester.actor.ancestry.contains(own));
+    if shares_tree {
+        return Err("the approver shares the requester's process tree".to_string());
+    }
+    let separate_terminal = approver
+        .tty
+        .as_ref()
+        .is_some_and(|tty| requester.tty.as_ref() != Some(tty));
+    if approver.uid != Some(0) && !approver.via_menubar && !separate_terminal {
+        return Err(
+            "approval must come from root, the menubar app or a terminal the requester is not attached to".to_string(),
+        );
+    }
+    Ok(())
+}
+
+/// Route a request whose origin has been validated. With
+/// `override_approval_required`, grants that would succeed are parked for a
+/// human; `approve` / `reject` settle a parked grant and answer its requester.
+fn route_override_request(
+    req: &OverrideRequest,
+    requester: &OverrideParty,
+    reply: PendingReply,
+    policy: &SecurityPolicy,
+    home: &str,
+    overrides: &mut Vec<TemporaryOverrideEntry>,
+    pending: &mut PendingOverrideQueue,
+) -> OverrideRouting {
+    let action = req.action.trim().to_lowercase();
+    if action == "approve" || action == "reject" {
+        let (changed, response) = decide_pending_override(
+            req,
+            action == "approve",
+            requester,
+            policy,
+            home,
+            overrides,
+            pending,
+        );
+        return OverrideRouting::Done(changed, response);
+    }
+    if !policy.override_approval_required || !is_override_grant_action(&action) {
+        let (changed, response) = apply_override_request(req, policy, home, overrides);
+        return OverrideRouting::Done(changed, response);
+    }
+
+    // Validate now so a request that would be refused never reaches a human.
+    let mut probe = overrides.clone();
+    let (_, mut probe_response) = apply_override_request(req, policy, home, &mut probe);
+    if probe_response.status != "ok" {
+        return OverrideRouting::Done(false, probe_response);
+    }
+    let targets: Vec<String> = if probe_response.paths.is_empty() {
+        req.path
+            .as_deref()
+            .map(str::trim)
+            .and_then(normalize_absolute_path)
+            .into_iter()
+            .collect()
+    } else {
+        probe_response.paths.clone()
+    };
+    if let Some(rule) = auto_approve_rule_for_request(req, &action, &targets, policy, home) {
+        probe_response
+            .message
+            .push_str(&format!(" (auto-approved by rule {})", rule.name));
+        probe_response.explanation.push(format!(
+            "auto_approve rule {} skips human approval",
+            rule.name
+        ));
+        *overrides = probe;
+        return OverrideRouting::Done(true, probe_response);
+    }
+    if pending.entries.len() >= MAX_PENDING_OVERRIDES {
+        return OverrideRouting::Done(
+            false,
+            OverrideResponse {
+                id: req.id.clone(),
+                status: "error".to_string(),
+                message: format!(
+                    "too many pending override requests (max {})",
+                    MAX_PENDING_OVERRIDES
+                ),
+                expires_at: None,
+                explanation: Vec::new(),
+                paths: Vec::new(),
+            },
+        );
+    }
+
+    let now = now_ts();
+    let deadline = now.saturating_add(policy.override_approval_timeout_or_default());
+    // Park the expanded paths so the human approves exactly what was listed.
+    let mut request = req.clone();
+    request.path = targets.first().cloned();
+    request.paths = targets.iter().skip(1).cloned().collect();
+    request.glob = None;
+    let related_denial = request
+        .path
+        .as_deref()
+        .and_then(|path| related_denial_for_path(home, path, now));
+    pending.entries.push(PendingOverride {
+        request,
+        requester: requester.clone(),
+        created_at: now,
+        deadline,
+        related_denial,
+        reply,
+    });
+    pending.dirty = true;
+    OverrideRouting::Pending(OverrideResponse {
+        id: req.id.clone(),
+        status: "pending".to_string(),
+        message: format!(
+            "awaiting human approval: agentsmith-override --approve {} (or --reject)",
+            req.id
+        ),
+        expires_at: Some(deadline),
+        explanation: Vec::new(),
+        paths: probe_response.paths,
+    })
+}
+
+/// Auto-approve rule covering every target of a plain grant that already
+/// passed validation.
+fn auto_approve_rule_for_request<'a>(
+    req: &OverrideRequest,
+    action: &str,
+    targets: &[String],
+    policy: &'a SecurityPolicy,
+    home: &str,
+) -> Option<&'a OverrideAutoApproveRule> {
+    if action != "grant" || targets.is_empty() {
+        return None;
+    }
+    let minutes = req.minutes.unwrap_or(OVERRIDE_DEFAULT_MINUTES);
+    policy.override_policy.auto_approve.iter().find(|rule| {
+        targets
+            .iter()
+            .all(|path| rule.matches(path, minutes, &policy.matched_zone(path, home)))
+    })
+}
+
+fn decide_pending_override(
+    req: &OverrideRequest,
+    approve: bool,
+    approver: &OverrideParty,
+    policy: &SecurityPolicy,
+    home: &str,
+    overrides: &mut Vec<TemporaryOverrideEntry>,
+    pending: &mut PendingOverrideQueue,
+) -> (bool, OverrideResponse) {
+    let error = |message: String| OverrideResponse {
+        id: req.id.clone(),
+        status: "error".to_string(),
+        message,
+        expires_at: None,
+        explanation: Vec::new(),
+        paths: Vec::new(),
+    };
+    let Some(target) = req.approval_id.as_deref().map(sanitize_component) else {
+        return (
+            false,
+            OverrideResponse {
+                status: "invalid".to_string(),
+                ..error("missing approval_id".to_string())
+            },
+        );
+    };
+    let Some(index) = pending.entries.iter().position(|entry| entry.request.id == target) else {
+        return (
+            false,
+            error(format!("no pending override request {}", target)),
+        );
+    };
+    if let Err(err) = check_override_approver(&pending.entries[index].requester, approver) {
+        return (false, error(err));
+    }
+
+    let entry = pending.entries.remove(index);
+    pending.dirty = true;
+    let decided_by = format!("pid {} ({})", approver.pid, approver.process);
+    if !approve {
+        let message = format!("override request rejected by {}", decided_by);
+        log_pending_override_step(home, &entry, "rejected", &message);
+        pending.outbox.push((
+            entry.reply,
+            OverrideResponse {
+                id: entry.request.id.clone(),
+                status: "error".to_string(),
+                message: message.clone(),
+                expires_at: None,
+                explanation: Vec::new(),
+                paths: Vec::new(),
+            },
+        ));
+        return (
+            false,
+            OverrideResponse {
+                id: req.id.clone(),
+                status: "ok".to_string(),
+                message: format!("rejected {}", target),
+                expires_at: None,
+                explanation: Vec::new(),
+                paths: Vec::new(),
+            },
+        );
+    }
+
+    // Re-validate against the current policy and overrides.
+    let (changed, mut outcome) = apply_override_request(&entry.request, policy, home, overrides);
+    outcome.message = format!("{} (approved by {})", outcome.message, decided_by);
+    log_pending_override_step(home, &entry, &outcome.status, &outcome.message);
+    let approver_response = OverrideResponse {
+        id: req.id.clone(),
+        status: outcome.status.clone(),
+        message: format!("approved {}: {}", target, outcome.message),
+        expires_at: outcome.expires_at,
+        explanation: Vec::new(),
+        paths: Vec::new(),
+    };
+    pending.outbox.push((entry.reply, outcome));
+    (changed, approver_response)
+}
+
+/// Fail pending requests whose deadline passed or whose requester exited.
+fn expire_pending_overrides(home: &str, pending: &mut PendingOverrideQueue, now: u64) {
+    let (expired, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut pending.entries)
+        .into_iter()
+        .partition(|entry| entry.deadline <= now || get_process_info(entry.requester.pid).is_none());
+    pending.entries = kept;
+    for entry in expired {
+        pending.dirty = true;
+        let message = if entry.deadline <= now {
+            "override request expired without approval"
+        } else {
+            "override request abandoned: requester exited"
+        };
+        log_pending_override_step(home, &entry, "expired", message);
+        pending.outbox.push((
+            entry.reply,
+            OverrideResponse {
+                id: entry.request.id.clone(),
+                status: "error".to_string(),
+                message: message.to_string(),
+                expires_at: None,
+                explanation: Vec::new(),
+                paths: Vec::new(),
+            },
+        ));
+    }
+}
+
 fn process_override_requests(
     request_dir: &Path,
     policy: &SecurityPolicy,
     home: &str,
     overrides: &mut Vec<TemporaryOverrideEntry>,
     request_window: &mut VecDeque<u64>,
+    pending: &mut PendingOverrideQueue,
+    process_table: &Mutex<ProcessTable>,
 ) -> bool {
     let guard_dir = match ensure_guard_dirs(home) {
         Ok(dir) => dir,
@@ -1925,15 +4462,18 @@ fn process_override_requests(
         let request_minutes: Option<u64> = None;
         let request_pid: Option<i32> = None;
 
-        if !consume_override_request_budget(request_window, now_ts()) {
+        let request_limit = policy.override_policy.requests_per_minute_or_default();
+        if !consume_override_request_budget(request_window, now_ts(), request_limit) {
             let response = OverrideResponse {
                 id: request_id.clone(),
                 status: "error".to_string(),
                 message: format!(
                     "override request rate limit exceeded (max {} per minute)",
-                    MAX_OVERRIDE_REQUESTS_PER_MINUTE
+                    request_limit
                 ),
                 expires_at: None,
+                explanation: Vec::new(),
+                paths: Vec::new(),
             };
             let _ = write_override_response(&response_path, &response);
             log_override_audit(
@@ -1948,6 +4488,8 @@ fn process_override_requests(
                     message: response.message.clone(),
                     requester_pid: request_pid,
                     requester_process: None,
+                    approval_id: None,
+                    paths: Vec::new(),
                 },
             );
             let _ = fs::remove_file(&request_path);
@@ -1979,6 +4521,8 @@ fn process_override_requests(
                     status: "error".to_string(),
                     message: format!("invalid request: {}", err),
                     expires_at: None,
+                    explanation: Vec::new(),
+                    paths: Vec::new(),
                 };
                 let _ = write_override_response(&response_path, &response);
                 log_override_audit(
@@ -1993,6 +4537,8 @@ fn process_override_requests(
                         message: response.message.clone(),
                         requester_pid: request_pid,
                         requester_process: None,
+                        approval_id: None,
+                        paths: Vec::new(),
                     },
                 );
                 let _ = fs::remove_file(&request_path);
@@ -2007,14 +4553,17 @@ fn process_override_requests(
         let request_minutes = request.minutes;
         let request_pid = request.requester_pid;
 
-        let (requester_pid, requester_process) = match validate_override_request_origin(&request, policy) {
-            Ok((pid, process)) => (Some(pid), Some(process)),
+        let (requester_pid, requester_process) = match validate_override_request_origin(&request, policy, process_table)
+        {
+            Ok((pid, process)) => (pid, process),
             Err(err) => {
                 let response = OverrideResponse {
                     id: request_id.clone(),
                     status: "error".to_string(),
                     message: err,
                     expires_at: None,
+                    explanation: Vec::new(),
+                    paths: Vec::new(),
                 };
                 let _ = write_override_response(&response_path, &response);
                 log_override_audit(
@@ -2029,6 +4578,8 @@ fn process_override_requests(
                         message: response.message.clone(),
                         requester_pid: request_pid,
                         requester_process: None,
+                        approval_id: None,
+                        paths: Vec::new(),
                     },
                 );
                 if let Err(remove_err) = fs::remove_file(&request_path) {
@@ -2039,10 +4590,27 @@ fn process_override_requests(
         };
 
         let response_path = request_response_path(active_request_dir, request.id.as_str());
-        let (request_changed, response) = apply_override_request(&request, policy, home, overrides);
-        if request_changed {
-            changed = true;
-        }
+        let requester = OverrideParty::resolve(
+            requester_pid,
+            requester_process.clone(),
+            policy,
+            process_table,
+        );
+        let (response, approval_id) = match route_override_request(
+            &request,
+            &requester,
+            PendingReply::File(response_path.clone()),
+            policy,
+            home,
+            overrides,
+            pending,
+        ) {
+            OverrideRouting::Done(request_changed, response) => {
+                changed |= request_changed;
+                (response, request.approval_id.clone())
+            },
+            OverrideRouting::Pending(response) => (response, Some(request.id.clone())),
+        };
         log_override_audit(
             home,
             &OverrideAuditRecord {
@@ -2053,8 +4621,10 @@ fn process_override_requests(
                 minutes: request_minutes,
                 status: response.status.clone(),
                 message: response.message.clone(),
-                requester_pid,
-                requester_process,
+                requester_pid: Some(requester_pid),
+                requester_process: Some(requester_process),
+                approval_id,
+                paths: response.paths.clone(),
             },
         );
         if let Err(err) = write_override_response(&response_path, &response) {
@@ -2068,2214 +4638,7127 @@ fn process_override_requests(
     changed
 }
 
-fn is_system_temp(path: &str, home: &str) -> bool {
-    path_prefix_match(path, &format!("{}/.Trash", home))
-        || path_prefix_match(path, "/private/tmp")
-        || path_prefix_match(path, "/private/var/folders")
-        || path.ends_with(".DS_Store")
-}
-
-fn warning_relevant_trusted_tools(policy: &SecurityPolicy) -> Vec<&str> {
-    if policy.trusted_tools.is_empty() {
-        return vec![];
+impl ControlResponse {
+    fn ok(id: &str, message: impl Into<String>, data: Option<serde_json::Value>) -> Self {
+        Self {
+            version: CONTROL_PROTOCOL_VERSION,
+            id: id.to_string(),
+            status: "ok".to_string(),
+            message: message.into(),
+            expires_at: None,
+            data,
+            explanation: Vec::new(),
+        }
     }
 
-    let trusted_set = policy
-        .trusted_tools
-        .iter()
-        .map(|tool| tool.as_str())
-        .collect::<HashSet<_>>();
-    let mut relevant = HashSet::new();
+    fn error(id: &str, message: impl Into<String>) -> Self {
+        Self {
+            version: CONTROL_PROTOCOL_VERSION,
+            id: id.to_string(),
+            status: "error".to_string(),
+            message: message.into(),
+            expires_at: None,
+            data: None,
+            explanation: Vec::new(),
+        }
+    }
 
-    if policy.allow_trusted_tools_in_ai_context {
-        relevant.extend(trusted_set.iter().copied());
+    /// The request itself is malformed, as opposed to refused by policy.
+    fn invalid(id: &str, message: impl Into<String>) -> Self {
+        Self {
+            status: "invalid".to_string(),
+            ..Self::error(id, message)
+        }
     }
 
-    if policy.allow_vcs_metadata_in_ai_context {
-        for tool in ["git", "jj"] {
-            if trusted_set.contains(tool) {
-                relevant.insert(tool);
-            }
+    fn from_override(response: OverrideResponse) -> Self {
+        Self {
+            version: CONTROL_PROTOCOL_VERSION,
+            id: response.id,
+            status: response.status,
+            message: response.message,
+            expires_at: response.expires_at,
+            data: (!response.paths.is_empty()).then(|| serde_json::json!({ "paths": response.paths })),
+            explanation: response.explanation,
         }
     }
+}
 
-    let mut tools = relevant.into_iter().collect::<Vec<_>>();
-    tools.sort_unstable();
-    tools
+/// Connecting process as reported by the kernel (`LOCAL_PEERCRED` /
+/// `LOCAL_PEERPID`), not as claimed in the request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ControlPeer {
+    uid: u32,
+    pid: i32,
 }
 
-fn trusted_identity_configuration_warning(policy: &SecurityPolicy) -> Option<String> {
-    let relevant_tools = warning_relevant_trusted_tools(policy);
-    if relevant_tools.is_empty() {
-        return None;
+/// Request handed from the socket thread to the policy thread, which owns the
+/// runtime overrides and replies once the change is live.
+struct ControlCommand {
+    request: ControlRequest,
+    peer: ControlPeer,
+    reply: mpsc::Sender<ControlResponse>,
+}
+
+fn control_request_id(request: &ControlRequest, peer: &ControlPeer) -> String {
+    request
+        .id
+        .as_deref()
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(sanitize_component)
+        .unwrap_or_else(|| format!("ctl-{}-{}", peer.pid, now_ts()))
+}
+
+/// Override mutations carry the same human-origin requirement as the file
+/// queue; read-only commands and reload are open to the home owner.
+fn control_command_mutates_overrides(command: &str) -> bool {
+    matches!(
+        command,
+        "override.grant"
+            | "override.grant-sensitive-read"
+            | "override.remove"
+            | "override.clear"
+            | "override.approve"
+            | "override.reject"
+    )
+}
+
+fn control_peer_credentials(stream: &UnixStream) -> io::Result<ControlPeer> {
+    let fd = stream.as_raw_fd();
+    let mut uid: libc::uid_t = 0;
+    let mut gid: libc::gid_t = 0;
+    // getpeereid is LOCAL_PEERCRED underneath on macOS.
+    if unsafe { libc::getpeereid(fd, &mut uid, &mut gid) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut pid: libc::pid_t = 0;
+    let mut len = std::mem::size_of::<libc::pid_t>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_LOCAL,
+            libc::LOCAL_PEERPID,
+            &mut pid as *mut libc::pid_t as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
     }
+    Ok(ControlPeer { uid, pid })
+}
 
-    if policy.trusted_tool_identities.is_empty() {
-        return Some(format!(
-            "[WARN] trusted checks are active for [{}], but trusted_tool_identities is empty; these tools will fail-closed with TRUST_IDENTITY_MISMATCH. Run nix switch/make to refresh policy defaults or set trusted_tool_identities in ~/.agentsmith-rs/policy.json.",
-            relevant_tools.join(", ")
+/// Check the envelope before anything reaches the policy thread: only root and
+/// the protected user may talk to the daemon, and only in a known version.
+fn validate_control_envelope(request: &ControlRequest, peer: &ControlPeer, owner_uid: u32) -> Result<(), String> {
+    if peer.uid != 0 && peer.uid != owner_uid {
+        return Err(format!(
+            "uid {} is not allowed to use the control socket",
+            peer.uid
         ));
     }
-
-    let missing_tools = relevant_tools
-        .iter()
-        .filter(|tool| policy.trusted_identity_candidates_for_process(tool).is_empty())
-        .map(|tool| (*tool).to_string())
-        .collect::<Vec<_>>();
-    if missing_tools.is_empty() {
-        return None;
+    if request.version != CONTROL_PROTOCOL_VERSION {
+        return Err(format!(
+            "unsupported protocol version {} (daemon speaks {})",
+            request.version, CONTROL_PROTOCOL_VERSION
+        ));
     }
+    Ok(())
+}
 
-    Some(format!(
-        "[WARN] trusted_tool_identities is missing entries for trusted_tools [{}]; these tools will fail-closed with TRUST_IDENTITY_MISMATCH. Add matching identities or remove them from trusted_tools.",
-        missing_tools.join(", ")
-    ))
+fn validate_control_peer_origin(
+    peer: &ControlPeer,
+    policy: &SecurityPolicy,
+    process_table: &Mutex<ProcessTable>,
+) -> Result<String, String> {
+    if peer.pid <= 1 {
+        return Err("invalid peer pid".to_string());
+    }
+    let process = process_name_for_pid(peer.pid).unwrap_or_else(|| format!("pid:{}", peer.pid));
+    reject_ai_originated_request(peer.pid, policy, process_table)?;
+    Ok(process)
 }
 
-fn log_policy_self_checks(policy: &SecurityPolicy, warning_state: &mut Option<(String, u64)>, now: u64) {
-    if let Some(warning) = trusted_identity_configuration_warning(policy) {
-        let should_emit = match warning_state {
-            Some((previous, ts))
-                if previous == &warning && now.saturating_sub(*ts) < POLICY_SELF_CHECK_WARNING_INTERVAL_SECS =>
-            {
-                false
-            },
-            _ => true,
-        };
-        if should_emit {
-            eprintln!("{}", warning);
-            *warning_state = Some((warning, now));
-        }
-    } else {
-        *warning_state = None;
-    }
+fn control_override_list(overrides: &[TemporaryOverrideEntry], now: u64) -> serde_json::Value {
+    serde_json::Value::Array(
+        overrides
+            .iter()
+            .filter(|entry| !entry.is_expired(now))
+            .map(|entry| {
+                serde_json::json!({
+                    "path": entry.path(),
+                    "expires_at": entry.expires_at(),
+                    "remaining_secs": entry.expires_at().map(|exp| exp.saturating_sub(now)),
+                    "created_by": entry.created_by(),
+                })
+            })
+            .collect(),
+    )
 }
 
-fn is_safe_taint_device_path(path: &str) -> bool {
-    matches!(path, "/dev/null" | "/dev/tty" | "/dev/dtracehelper")
-}
+/// Run an `override.*` command against the runtime overrides. Mutations go
+/// through the same validation, rate limit and audit log as the file queue; a
+/// grant parked for approval keeps `reply` to send the final answer later.
+/// Returns whether the overrides changed.
+#[allow(clippy::too_many_arguments)]
+fn handle_control_override_command(
+    request: &ControlRequest,
+    peer: &ControlPeer,
+    reply: &mpsc::Sender<ControlResponse>,
+    policy: &SecurityPolicy,
+    home: &str,
+    overrides: &mut Vec<TemporaryOverrideEntry>,
+    request_window: &mut VecDeque<u64>,
+    pending: &mut PendingOverrideQueue,
+    process_table: &Mutex<ProcessTable>,
+) -> (bool, ControlResponse) {
+    let id = control_request_id(request, peer);
+    if request.command == "override.list" {
+        return (
+            false,
+            ControlResponse::ok(
+                &id,
+                "active overrides",
+                Some(control_override_list(overrides, now_ts())),
+            ),
+        );
+    }
+    if request.command == "override.pending" {
+        return (
+            false,
+            ControlResponse::ok(
+                &id,
+                "pending override requests",
+                Some(pending_override_snapshot(pending)),
+            ),
+        );
+    }
+    let Some(action) = request
+        .command
+        .strip_prefix("override.")
+        .filter(|_| control_command_mutates_overrides(&request.command))
+    else {
+        return (
+            false,
+            ControlResponse::invalid(&id, format!("unknown command: {}", request.command)),
+        );
+    };
 
-fn format_cache_watermark_log(
-    ts: u64,
-    ancestor_current: usize,
-    ancestor_high: usize,
-    trusted_current: usize,
-    trusted_high: usize,
-    taint_current: usize,
-    taint_high: usize,
-) -> String {
-    format!(
-        "[METRIC] cache-watermark ts={} ancestor={}/{} trusted={}/{} taint={}/{}",
-        ts, ancestor_current, ancestor_high, trusted_current, trusted_high, taint_current, taint_high
-    )
+    let audit = |status: &str,
+                 message: &str,
+                 requester_process: Option<String>,
+                 approval_id: Option<String>,
+                 paths: &[String]| {
+        log_override_audit(
+            home,
+            &OverrideAuditRecord {
+                ts: now_ts(),
+                request_id: id.clone(),
+                action: action.to_string(),
+                path: request.path.clone(),
+                minutes: request.minutes,
+                status: status.to_string(),
+                message: message.to_string(),
+                requester_pid: Some(peer.pid),
+                requester_process,
+                approval_id,
+                paths: paths.to_vec(),
+            },
+        );
+    };
+
+    let request_limit = policy.override_policy.requests_per_minute_or_default();
+    if !consume_override_request_budget(request_window, now_ts(), request_limit) {
+        let response = ControlResponse::error(
+            &id,
+            format!(
+                "override request rate limit exceeded (max {} per minute)",
+                request_limit
+            ),
+        );
+        audit(
+            &response.status,
+            &response.message,
+            None,
+            request.approval_id.clone(),
+            &[],
+        );
+        return (false, response);
+    }
+    let process = match validate_control_peer_origin(peer, policy, process_table) {
+        Ok(process) => process,
+        Err(err) => {
+            audit("error", &err, None, request.approval_id.clone(), &[]);
+            return (false, ControlResponse::error(&id, err));
+        },
+    };
+
+    let override_request = OverrideRequest {
+        id: id.clone(),
+        action: action.to_string(),
+        path: request.path.clone(),
+        minutes: request.minutes,
+        requester_pid: Some(peer.pid),
+        approval_id: request.approval_id.clone(),
+        scope_pid: request.scope_pid,
+        scope_session: request.scope_session.clone(),
+        ops: request.ops.clone(),
+        rename_to: request.rename_to.clone(),
+        max_uses: request.max_uses,
+        pin: request.pin,
+        paths: request.paths.clone(),
+        glob: request.glob.clone(),
+    };
+    let requester = OverrideParty::resolve(peer.pid, process.clone(), policy, process_table);
+    let (changed, response, approval_id) = match route_override_request(
+        &override_request,
+        &requester,
+        PendingReply::Socket(reply.clone()),
+        policy,
+        home,
+        overrides,
+        pending,
+    ) {
+        OverrideRouting::Done(changed, response) => (changed, response, request.approval_id.clone()),
+        OverrideRouting::Pending(response) => (false, response, Some(id.clone())),
+    };
+    audit(
+        &response.status,
+        &response.message,
+        Some(process),
+        approval_id,
+        &response.paths,
+    );
+    (changed, ControlResponse::from_override(response))
 }
 
-fn format_runtime_health_log(
-    ts: u64,
-    signature_queue_pending: u64,
-    signature_queue_dropped_full: u64,
-    signature_queue_dropped_disconnected: u64,
-    log_queue_pending: u64,
-    log_queue_dropped_full: u64,
-    log_queue_dropped_disconnected: u64,
-    callback_latency: &CallbackLatencySnapshot,
-) -> String {
-    format!(
-        "[METRIC] runtime-health ts={} sigq_pending={} sigq_drop_full={} sigq_drop_disconnected={} logq_pending={} logq_drop_full={} logq_drop_disconnected={} cb_count={} cb_p50_us={} cb_p95_us={} cb_p99_us={} cb_max_us={}",
-        ts,
-        signature_queue_pending,
-        signature_queue_dropped_full,
-        signature_queue_dropped_disconnected,
-        log_queue_pending,
-        log_queue_dropped_full,
-        log_queue_dropped_disconnected,
-        callback_latency.total,
-        callback_latency.percentile_upper_bound_us(50),
-        callback_latency.percentile_upper_bound_us(95),
-        callback_latency.percentile_upper_bound_us(99),
-        callback_latency.max_us
-    )
+fn control_status(
+    policy: &SecurityPolicy,
+    overrides: &[TemporaryOverrideEntry],
+    started_at: u64,
+    process_table: &Arc<Mutex<ProcessTable>>,
+    trust_cache: &Arc<Mutex<TrustedProcessCache>>,
+    taint_state: &Arc<Mutex<TaintState>>,
+) -> serde_json::Value {
+    let now = now_ts();
+    let process_entries = process_table.lock().map(|table| table.len()).unwrap_or_default();
+    let trusted_entries = trust_cache.lock().map(|cache| cache.len()).unwrap_or_default();
+    let taint_entries = taint_state.lock().map(|taint| taint.len()).unwrap_or_default();
+    serde_json::json!({
+        "daemon_version": env!("CARGO_PKG_VERSION"),
+        "protocol_version": CONTROL_PROTOCOL_VERSION,
+        "pid": std::process::id(),
+        "started_at": started_at,
+        "uptime_secs": now.saturating_sub(started_at),
+        "audit_only_mode": policy.audit_only_mode,
+        "protected_zones": policy.protected_zones.len(),
+        "sensitive_zones": policy.sensitive_zones.len(),
+        "active_overrides": overrides.iter().filter(|entry| !entry.is_expired(now)).count(),
+        "process_table_entries": process_entries,
+        "trusted_process_cache_entries": trusted_entries,
+        "taint_entries": taint_entries,
+        "signature_queue_pending": SIGNATURE_QUEUE_PENDING.load(Ordering::Relaxed),
+        "log_queue_pending": LOG_QUEUE_PENDING.load(Ordering::Relaxed),
+        "log_queue_dropped": LOG_QUEUE_DROPPED_FULL.load(Ordering::Relaxed)
+            + LOG_QUEUE_DROPPED_DISCONNECTED.load(Ordering::Relaxed),
+    })
 }
 
-fn emit_cache_watermark_log(
-    ancestor_cache: &Arc<Mutex<HashMap<i32, CachedAncestor>>>,
+/// AI-labelled processes, taint marks and trust decisions, for debugging a
+/// surprising allow or deny without attaching a debugger to the daemon.
+fn control_cache_dump(
+    process_table: &Arc<Mutex<ProcessTable>>,
     trust_cache: &Arc<Mutex<TrustedProcessCache>>,
+    signature_cache: &Arc<Mutex<BinarySignatureCache>>,
     taint_state: &Arc<Mutex<TaintState>>,
-    highs: &mut CacheWatermarkHighs,
-) {
-    let ancestor_current = {
-        let cache = ancestor_cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
-        cache.len()
+) -> serde_json::Value {
+    let now = now_ts();
+    let (process_total, ai_processes) = {
+        let table = process_table.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut ai_processes: Vec<serde_json::Value> = table
+            .entries
+            .iter()
+            .filter_map(|(pid, entry)| {
+                let lineage = entry.ai_lineage.as_ref()?;
+                Some(serde_json::json!({
+                    "pid": pid,
+                    "ppid": entry.links.ppid,
+                    "executable_path": entry.executable_path,
+                    "ai_label": entry.ai_label,
+                    "lineage": lineage.label,
+                    "session": lineage.session.as_ref().map(|session| session.id.clone()),
+                }))
+            })
+            .collect();
+        ai_processes.sort_by_key(|entry| entry["pid"].as_i64());
+        (table.len(), ai_processes)
     };
-    let trusted_current = {
+    let taint = {
+        let taint = taint_state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut entries: Vec<serde_json::Value> = taint
+            .touched
+            .iter()
+            .filter(|(_, entry)| now.saturating_sub(entry.touched_at) <= taint.ttl_secs)
+            .map(|(pid, entry)| {
+                serde_json::json!({
+                    "pid": pid,
+                    "touched_at": entry.touched_at,
+                    "expires_in_secs": (entry.touched_at + taint.ttl_secs).saturating_sub(now),
+                })
+            })
+            .collect();
+        entries.sort_by_key(|entry| entry["pid"].as_i64());
+        entries
+    };
+    let trusted = {
         let cache = trust_cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
-        cache.len()
+        let mut entries: Vec<serde_json::Value> = cache
+            .entries
+            .iter()
+            .map(|(key, entry)| {
+                serde_json::json!({
+                    "pid": key.pid,
+                    "executable_path": key.executable_path,
+                    "decision": format!("{:?}", entry.decision),
+                    "updated_at": entry.updated_at,
+                })
+            })
+            .collect();
+        entries.sort_by_key(|entry| entry["pid"].as_i64());
+        entries
     };
-    let taint_current = {
-        let taint = taint_state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
-        taint.len()
+    let signatures = {
+        let cache = signature_cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut entries: Vec<serde_json::Value> = cache
+            .entries
+            .iter()
+            .map(|(path, entry)| match &entry.signature {
+                Ok(signature) => serde_json::json!({
+                    "path": path,
+                    "signing_identifier": signature.signing_identifier,
+                    "team_identifier": signature.team_identifier,
+                    "cdhash": signature.cdhash,
+                    "updated_at": entry.updated_at,
+                }),
+                Err(err) => serde_json::json!({
+                    "path": path,
+                    "error": err,
+                    "updated_at": entry.updated_at,
+                }),
+            })
+            .collect();
+        entries.sort_by(|a, b| a["path"].as_str().cmp(&b["path"].as_str()));
+        entries
     };
-    let (ancestor_high, trusted_high, taint_high) = highs.update(ancestor_current, trusted_current, taint_current);
-    println!(
-        "{}",
-        format_cache_watermark_log(
-            now_ts(),
-            ancestor_current,
-            ancestor_high,
-            trusted_current,
-            trusted_high,
-            taint_current,
-            taint_high
-        )
-    );
+    serde_json::json!({
+        "process_table_entries": process_total,
+        "ai_processes": ai_processes,
+        "taint": taint,
+        "trusted_process_cache": trusted,
+        "signature_cache": signatures,
+    })
 }
 
-fn emit_runtime_health_log(callback_latency_metrics: &CallbackLatencyMetrics) {
-    let callback_latency = callback_latency_metrics.snapshot_and_reset();
-    println!(
-        "{}",
-        format_runtime_health_log(
-            now_ts(),
-            SIGNATURE_QUEUE_PENDING.load(Ordering::Relaxed),
-            SIGNATURE_QUEUE_DROPPED_FULL.load(Ordering::Relaxed),
-            SIGNATURE_QUEUE_DROPPED_DISCONNECTED.load(Ordering::Relaxed),
-            LOG_QUEUE_PENDING.load(Ordering::Relaxed),
-            LOG_QUEUE_DROPPED_FULL.load(Ordering::Relaxed),
-            LOG_QUEUE_DROPPED_DISCONNECTED.load(Ordering::Relaxed),
-            &callback_latency,
+fn prepare_control_socket(path: &Path) -> io::Result<UnixListener> {
+    let dir = path.parent().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "control socket path has no parent",
         )
-    );
-}
-
-fn maybe_warn_log_queue_full(kind: &str) {
-    let now = now_ts();
-    let last_warn = LOG_QUEUE_FULL_LAST_WARN_TS.load(Ordering::Relaxed);
-    if now.saturating_sub(last_warn) < LOG_QUEUE_FULL_WARN_INTERVAL_SECS {
-        return;
-    }
-    if LOG_QUEUE_FULL_LAST_WARN_TS
-        .compare_exchange(last_warn, now, Ordering::Relaxed, Ordering::Relaxed)
-        .is_ok()
-    {
-        eprintln!("[log] async queue full; dropping {} event", kind);
+    })?;
+    ensure_dir_not_symlink(dir, CONTROL_SOCKET_DIR_MODE)?;
+    fs::set_permissions(dir, fs::Permissions::from_mode(CONTROL_SOCKET_DIR_MODE))?;
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => fs::remove_file(path)?,
+        Ok(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("refuse to replace non-socket: {}", path.display()),
+            ))
+        },
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {},
+        Err(err) => return Err(err),
     }
+    let listener = UnixListener::bind(path)?;
+    // Anyone may connect; peer credentials decide who is served.
+    fs::set_permissions(path, fs::Permissions::from_mode(CONTROL_SOCKET_MODE))?;
+    Ok(listener)
 }
 
-fn process_log_message_sync(home: &str, message: GuardLogMessage) {
-    match message {
-        GuardLogMessage::Denial(record) => log_denial_sync(home, &record),
-        GuardLogMessage::AuditOnly(record) => log_audit_only_sync(home, &record),
-        GuardLogMessage::TaintMark(record) => log_taint_mark_sync(home, &record),
-    }
+fn write_control_response(stream: &mut UnixStream, response: &ControlResponse) -> io::Result<()> {
+    let mut line = serde_json::to_vec(response).map_err(|err| io::Error::other(err.to_string()))?;
+    line.push(b'\n');
+    stream.write_all(&line)
 }
 
-fn decrement_queue_pending(counter: &AtomicU64) {
-    let _ = counter.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |value| {
-        value.checked_sub(1)
-    });
-}
+fn serve_control_connection(mut stream: UnixStream, owner_uid: u32, commands: &mpsc::SyncSender<ControlCommand>) {
+    let timeout = Some(Duration::from_secs(CONTROL_IO_TIMEOUT_SECS));
+    let _ = stream.set_read_timeout(timeout);
+    let _ = stream.set_write_timeout(timeout);
+    let peer = match control_peer_credentials(&stream) {
+        Ok(peer) => peer,
+        Err(err) => {
+            eprintln!("[control] cannot read peer credentials: {}", err);
+            return;
+        },
+    };
 
-fn enqueue_log_message_or_fallback(home: &str, message: GuardLogMessage) {
-    if let Some(tx) = LOG_EVENT_TX.get() {
-        let kind = match &message {
-            GuardLogMessage::Denial(_) => "denial",
-            GuardLogMessage::AuditOnly(_) => "audit-only",
-            GuardLogMessage::TaintMark(_) => "taint",
-        };
-        LOG_QUEUE_PENDING.fetch_add(1, Ordering::Relaxed);
-        match tx.try_send(message) {
-            Ok(()) => {},
-            Err(mpsc::TrySendError::Full(_)) => {
-                decrement_queue_pending(&LOG_QUEUE_PENDING);
-                LOG_QUEUE_DROPPED_FULL.fetch_add(1, Ordering::Relaxed);
-                maybe_warn_log_queue_full(kind);
-            },
-            Err(mpsc::TrySendError::Disconnected(message)) => {
-                decrement_queue_pending(&LOG_QUEUE_PENDING);
-                LOG_QUEUE_DROPPED_DISCONNECTED.fetch_add(1, Ordering::Relaxed);
-                process_log_message_sync(home, message);
-            },
-        }
+    let mut line = String::new();
+    let read = match stream.try_clone() {
+        Ok(reader) => io::BufReader::new(reader)
+            .take(MAX_OVERRIDE_REQUEST_SIZE_BYTES)
+            .read_line(&mut line),
+        Err(err) => Err(err),
+    };
+    let request = match read
+        .map_err(|err| err.to_string())
+        .and_then(|_| serde_json::from_str::<ControlRequest>(&line).map_err(|err| err.to_string()))
+    {
+        Ok(request) => request,
+        Err(err) => {
+            let _ = write_control_response(
+                &mut stream,
+                &ControlResponse::invalid("", format!("invalid request: {}", err)),
+            );
+            return;
+        },
+    };
+    let id = control_request_id(&request, &peer);
+    if let Err(err) = validate_control_envelope(&request, &peer, owner_uid) {
+        let _ = write_control_response(&mut stream, &ControlResponse::error(&id, err));
         return;
     }
 
-    process_log_message_sync(home, message);
-}
-
-fn init_async_log_worker(home: &str) {
-    let (tx, rx) = mpsc::sync_channel::<GuardLogMessage>(LOG_EVENT_QUEUE_BOUND);
-    if LOG_EVENT_TX.set(tx).is_err() {
+    let (reply_tx, reply_rx) = mpsc::channel();
+    let command = ControlCommand {
+        request,
+        peer,
+        reply: reply_tx,
+    };
+    if let Err(err) = commands.try_send(command) {
+        let message = match err {
+            mpsc::TrySendError::Full(_) => "control queue is full; retry",
+            mpsc::TrySendError::Disconnected(_) => "policy thread is not running",
+        };
+        let _ = write_control_response(&mut stream, &ControlResponse::error(&id, message));
         return;
     }
 
-    let worker_home = home.to_string();
-    thread::spawn(move || {
-        while let Ok(message) = rx.recv() {
-            process_log_message_sync(worker_home.as_str(), message);
-            decrement_queue_pending(&LOG_QUEUE_PENDING);
+    // A grant parked for approval is answered twice: `pending` now, the
+    // decision once a human makes it or the deadline passes.
+    let mut wait = Duration::from_secs(CONTROL_IO_TIMEOUT_SECS);
+    loop {
+        let response = reply_rx
+            .recv_timeout(wait)
+            .unwrap_or_else(|_| ControlResponse::error(&id, "daemon did not answer in time"));
+        if let Err(err) = write_control_response(&mut stream, &response) {
+            eprintln!("[control] failed to write response: {}", err);
+            return;
         }
-    });
-}
-
-fn log_denial(home: &str, record: &DenialRecord) {
-    enqueue_log_message_or_fallback(home, GuardLogMessage::Denial(record.clone()));
-}
-
-fn audit_only_coalescer() -> &'static Mutex<AuditOnlyCoalescer> {
-    AUDIT_ONLY_COALESCER.get_or_init(|| Mutex::new(AuditOnlyCoalescer::default()))
+        if response.status != "pending" {
+            return;
+        }
+        let deadline = response.expires_at.unwrap_or_default();
+        wait = Duration::from_secs(deadline.saturating_sub(now_ts()) + CONTROL_IO_TIMEOUT_SECS);
+    }
 }
 
-fn serialize_audit_only_entry(entry: &AuditOnlyAggregateEntry) -> Option<String> {
-    let is_aggregated = entry.count > 1;
-    let line = AuditOnlyLogLine {
-        record: &entry.sample,
-        count: is_aggregated.then_some(entry.count),
-        first_ts: is_aggregated.then_some(entry.first_ts),
-        last_ts: is_aggregated.then_some(entry.last_ts),
-    };
-    serde_json::to_string(&line).ok()
-}
-
-fn write_audit_only_entries(home: &str, entries: Vec<AuditOnlyAggregateEntry>) {
-    if entries.is_empty() {
-        return;
-    }
-
-    let guard_dir = match ensure_guard_dirs(home) {
-        Ok(dir) => dir,
+/// Serve each control connection on its own thread, up to
+/// `MAX_CONTROL_CONNECTIONS`; most are short, but one waiting on an override
+/// approval stays open until the decision.
+fn spawn_control_socket_server(home: &str, commands: mpsc::SyncSender<ControlCommand>) {
+    let owner_uid = match fs::metadata(home) {
+        Ok(meta) => meta.uid(),
         Err(err) => {
-            eprintln!("[log] cannot use guard dir: {}", err);
+            eprintln!(
+                "[control] cannot stat home {}: {}; control socket disabled",
+                home, err
+            );
             return;
         },
     };
-
-    let log_path = guard_dir.join("audit-only.jsonl");
-    let lines: Vec<String> = entries.iter().filter_map(serialize_audit_only_entry).collect();
-    if lines.is_empty() {
-        return;
-    }
-
-    if let Ok(mut file) = open_append_no_follow(&log_path, DEFAULT_FILE_MODE) {
-        if verify_regular_file(&file, &log_path).is_ok() {
-            if let Ok(meta) = file.metadata() {
-                if meta.len() > AUDIT_ONLY_LOG_MAX_BYTES {
-                    let _ = file.set_len(0);
-                }
-            }
-            for line in lines {
-                let _ = writeln!(file, "{}", line);
+    let path = control_socket_path();
+    let listener = match prepare_control_socket(&path) {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!(
+                "[control] cannot listen on {}: {}; file queue only",
+                path.display(),
+                err
+            );
+            return;
+        },
+    };
+    println!("[control] listening on {}", path.display());
+    let active = Arc::new(AtomicUsize::new(0));
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    eprintln!("[control] accept failed: {}", err);
+                    continue;
+                },
+            };
+            if active.fetch_add(1, Ordering::SeqCst) >= MAX_CONTROL_CONNECTIONS {
+                active.fetch_sub(1, Ordering::SeqCst);
+                let _ = write_control_response(
+                    &mut stream,
+                    &ControlResponse::error("", "too many control connections; retry"),
+                );
+                continue;
             }
+            let active = active.clone();
+            let commands = commands.clone();
+            thread::spawn(move || {
+                serve_control_connection(stream, owner_uid, &commands);
+                active.fetch_sub(1, Ordering::SeqCst);
+            });
         }
-    }
+    });
 }
 
-fn flush_audit_only_coalescer(home: &str, now: u64, force_all: bool) {
-    let ready = {
-        let mut coalescer = audit_only_coalescer()
-            .lock()
-            .unwrap_or_else(|poisoned| poisoned.into_inner());
-        coalescer.take_ready(now, force_all)
-    };
-    write_audit_only_entries(home, ready);
+fn session_agent_name(raw: Option<&str>) -> Result<String, String> {
+    let raw = raw.map(str::trim).unwrap_or_default();
+    if raw.is_empty() {
+        return Err("missing agent name".to_string());
+    }
+    if raw.len() > MAX_SESSION_AGENT_NAME_LEN {
+        return Err(format!(
+            "agent name too long (max {} chars)",
+            MAX_SESSION_AGENT_NAME_LEN
+        ));
+    }
+    Ok(sanitize_component(raw))
 }
 
-fn log_audit_only(home: &str, record: &DenialRecord) {
-    enqueue_log_message_or_fallback(home, GuardLogMessage::AuditOnly(record.clone()));
+/// Build the session for a launcher request. The requester must be a live
+/// `agentsmith-run` process owned by the user that wrote the request file, so
+/// a session can only be claimed for the launcher's own pid.
+fn validate_session_request(req: &SessionRequest, request_owner_uid: u32) -> Result<AgentSession, String> {
+    let pid = req.pid.ok_or_else(|| "missing pid".to_string())?;
+    if pid <= 1 {
+        return Err("invalid pid".to_string());
+    }
+    let agent = session_agent_name(req.agent.as_deref())?;
+    let info = get_process_bsd_info(pid).ok_or_else(|| format!("launcher pid {} is not alive", pid))?;
+    if info.pbi_uid != request_owner_uid {
+        return Err("launcher pid is owned by a different user".to_string());
+    }
+    let args = get_process_argv(pid).unwrap_or_default();
+    if !argv_runs_helper(&args, "agentsmith-run") {
+        return Err("requester process is not agentsmith-run".to_string());
+    }
+    let cwd = req.cwd.as_deref().and_then(normalize_absolute_path).unwrap_or_default();
+    Ok(AgentSession {
+        id: format!("{}-{}-{}", agent, pid, info.pbi_start_tvsec),
+        agent,
+        pid,
+        started_at: info.pbi_start_tvsec,
+        cwd,
+        tty: tty_name_from_bsd_info(&info),
+        source: SESSION_SOURCE_LAUNCHER,
+    })
 }
 
-fn log_taint_mark(home: &str, record: &TaintMarkRecord) {
-    enqueue_log_message_or_fallback(home, GuardLogMessage::TaintMark(record.clone()));
+fn write_session_response(response_path: &Path, response: &SessionResponse) -> io::Result<()> {
+    let mut file = open_truncate_no_follow(response_path, DEFAULT_FILE_MODE)?;
+    verify_regular_file(&file, response_path)?;
+    let content = serde_json::to_vec(response).map_err(|err| io::Error::other(err.to_string()))?;
+    file.write_all(&content)?;
+    file.sync_data()?;
+    Ok(())
 }
 
-fn record_denial_or_audit_only(home: &str, policy: &SecurityPolicy, record: DenialRecord) -> bool {
-    if policy.audit_only_mode {
-        log_audit_only(home, &record);
-        return false;
+fn read_session_request(request_path: &Path) -> io::Result<(SessionRequest, u32)> {
+    let mut file = open_read_no_follow(request_path)?;
+    verify_regular_file(&file, request_path)?;
+    let meta = file.metadata()?;
+    if meta.len() > MAX_OVERRIDE_REQUEST_SIZE_BYTES {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "request too large ({} bytes > {})",
+                meta.len(),
+                MAX_OVERRIDE_REQUEST_SIZE_BYTES
+            ),
+        ));
     }
-
-    log_denial(home, &record);
-    true
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+    let request = serde_json::from_str::<SessionRequest>(&content)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+    Ok((request, meta.uid()))
 }
 
-fn log_denial_sync(home: &str, record: &DenialRecord) {
-    let guard_dir = match ensure_guard_dirs(home) {
-        Ok(dir) => dir,
+/// Register sessions requested by `agentsmith-run`. The launcher blocks on the
+/// response, so the session is in the process table before the agent execs.
+fn process_session_requests(home: &str, policy: &SecurityPolicy, process_table: &Arc<Mutex<ProcessTable>>) {
+    if let Err(err) = ensure_guard_dirs(home) {
+        eprintln!("[session] cannot use guard dir: {}", err);
+        return;
+    }
+    let request_dir = session_request_dir(home);
+    if let Err(err) = ensure_dir_not_symlink(&request_dir, DEFAULT_DIR_MODE) {
+        eprintln!("[session] cannot use request dir: {}", err);
+        return;
+    }
+    prune_stale_response_files(&request_dir, now_ts());
+
+    let mut request_files: Vec<PathBuf> = match fs::read_dir(&request_dir) {
+        Ok(iter) => iter
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.ends_with(".request.json"))
+            })
+            .collect(),
         Err(err) => {
-            eprintln!("[log] cannot use guard dir: {}", err);
+            eprintln!("[session] failed to scan request dir: {}", err);
             return;
         },
     };
+    request_files.sort();
+    request_files.truncate(MAX_SESSION_REQUEST_FILES_PER_CYCLE);
 
-    let log_path = guard_dir.join("denials.jsonl");
-    let feedback_path = guard_dir.join("last_denial.txt");
-
-    if let Ok(json) = serde_json::to_string(record) {
-        if let Ok(mut file) = open_append_no_follow(&log_path, DEFAULT_FILE_MODE) {
-            if verify_regular_file(&file, &log_path).is_ok() {
-                if let Ok(meta) = file.metadata() {
-                    if meta.len() > 1_000_000 {
-                        let _ = file.set_len(0);
-                    }
+    for request_path in request_files {
+        let request_id = request_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(".request.json"))
+            .map(sanitize_component)
+            .unwrap_or_else(|| format!("unknown-{}", now_ts()));
+        let response_path = request_response_path(&request_dir, &request_id);
+
+        let registered = read_session_request(&request_path)
+            .map_err(|err| format!("invalid request: {}", err))
+            .and_then(|(request, owner_uid)| validate_session_request(&request, owner_uid))
+            .and_then(|session| {
+                let mut table = process_table.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                if table.register_session(session.clone(), policy, now_ts()) {
+                    Ok(session)
+                } else {
+                    Err(format!(
+                        "launcher pid {} exited before registration",
+                        session.pid
+                    ))
                 }
-                let _ = writeln!(file, "{}", json);
-            }
+            });
+        let response = match registered {
+            Ok(session) => {
+                println!(
+                    "[session] registered {} (agent={}, pid={}, cwd={})",
+                    session.id, session.agent, session.pid, session.cwd
+                );
+                SessionResponse {
+                    id: request_id.clone(),
+                    status: "ok".to_string(),
+                    message: format!("session {} registered", session.id),
+                    session_id: Some(session.id),
+                }
+            },
+            Err(err) => {
+                eprintln!("[session] rejected request {}: {}", request_id, err);
+                SessionResponse {
+                    id: request_id.clone(),
+                    status: "error".to_string(),
+                    message: err,
+                    session_id: None,
+                }
+            },
+        };
+        if let Err(err) = write_session_response(&response_path, &response) {
+            eprintln!("[session] failed to write response: {}", err);
         }
-    }
-
-    let feedback = build_denial_feedback(home, record);
-    if let Ok(mut file) = open_truncate_no_follow(&feedback_path, DEFAULT_FILE_MODE) {
-        if verify_regular_file(&file, &feedback_path).is_ok() {
-            let _ = file.write_all(feedback.as_bytes());
+        if let Err(err) = fs::remove_file(&request_path) {
+            eprintln!("[session] failed to remove request file: {}", err);
         }
     }
 }
 
-fn log_audit_only_sync(home: &str, record: &DenialRecord) {
-    let ready = {
-        let mut coalescer = audit_only_coalescer()
-            .lock()
-            .unwrap_or_else(|poisoned| poisoned.into_inner());
-        coalescer.ingest(record.clone())
-    };
-    write_audit_only_entries(home, ready);
+fn is_system_temp(path: &str, home: &str) -> bool {
+    path_prefix_match(path, &format!("{}/.Trash", home))
+        || path_prefix_match(path, "/private/tmp")
+        || path_prefix_match(path, "/private/var/folders")
+        || path.ends_with(".DS_Store")
 }
 
-fn log_taint_mark_sync(home: &str, record: &TaintMarkRecord) {
-    let guard_dir = match ensure_guard_dirs(home) {
-        Ok(dir) => dir,
-        Err(err) => {
-            eprintln!("[taint] cannot use guard dir: {}", err);
-            return;
-        },
-    };
-
-    let log_path = guard_dir.join("taint-marks.jsonl");
-    if let Ok(json) = serde_json::to_string(record) {
-        if let Ok(mut file) = open_append_no_follow(&log_path, DEFAULT_FILE_MODE) {
-            if verify_regular_file(&file, &log_path).is_ok() {
-                if let Ok(meta) = file.metadata() {
-                    if meta.len() > TAINT_AUDIT_MAX_BYTES {
-                        let _ = file.set_len(0);
-                    }
-                }
-                let _ = writeln!(file, "{}", json);
-            }
-        }
+fn warning_relevant_trusted_tools(policy: &SecurityPolicy) -> Vec<&str> {
+    if policy.trusted_tools.is_empty() {
+        return vec![];
     }
-}
 
-fn build_denial_feedback(home: &str, record: &DenialRecord) -> String {
-    let dest_info = record
-        .dest
-        .as_deref()
-        .map(|d| format!("\nDest: {}", d))
-        .unwrap_or_default();
-    let header = format!(
-        "[AGENTSMITH DENIED]\n\
-         Operation: {}\n\
-         Reason: {}\n\
-         Path: {}{}\n\
-         Zone: {}\n\
-         Process: {} (via {})\n{}{}\
-         \n",
-        record.op,
-        record.reason,
-        record.path,
-        dest_info,
-        record.zone,
-        record.process,
-        record.ancestor,
-        record.pid.map(|pid| format!("Pid: {}\n", pid)).unwrap_or_default(),
-        record.ppid.map(|ppid| format!("PPid: {}\n", ppid)).unwrap_or_default(),
-    );
+    let trusted_set = policy
+        .trusted_tools
+        .iter()
+        .map(|tool| tool.as_str())
+        .collect::<HashSet<_>>();
+    let mut relevant = HashSet::new();
 
-    let quarantine_dir = format!("{}/.agentsmith-rs/guard/quarantine", home);
-    let guidance = match record.reason.as_str() {
-        REASON_SENSITIVE_READ_NON_AI => format!(
-            "Recommended next step:\n\
-             - Sensitive read gate: non-AI reads are denied.\n\
-             - Retry from AI context (Codex/Claude ancestor).\n\
-             - If human review is required, export reviewed data only into: {}\n",
-            quarantine_dir
-        ),
-        REASON_SENSITIVE_TRANSFER_OUT => format!(
-            "Recommended next step:\n\
-             - Sensitive export is blocked outside allow-zones.\n\
-             - Move/copy only into quarantine allow-zone: {}\n\
-             - Example: mv {} {}/\n",
-            quarantine_dir, record.path, quarantine_dir
-        ),
-        REASON_TAINT_WRITE_OUT => format!(
-            "Recommended next step:\n\
-             - This process is tainted by a prior sensitive read.\n\
-             - Write outputs only into: {}\n\
-             - Or retry after taint TTL expires.\n",
-            quarantine_dir
-        ),
-        REASON_EXEC_EXFIL_TOOL => "Recommended next step:\n\
-             - Exfil tooling is blocked in AI context.\n\
-             - Keep processing inside allowed local zones or approved channels.\n"
-            .to_string(),
-        REASON_TRUST_IDENTITY_MISMATCH => format!(
-            "Recommended next step:\n\
-             - Trusted process identity verification failed.\n\
-             - Check `trusted_tool_identities` for exact path + signing identity.\n\
-             - If high-security mode is on, ensure cdhash is pinned correctly.\n\
-             - Current process: {}\n",
-            record.process
-        ),
-        _ => format!(
-            "Recommended (safer first step): agentsmith-quarantine {}\n\
-             This moves the target into ./temp under your CURRENT working directory.\n\
-             \n\
-             If you must permanently delete via AI, request one-time override with TTL:\n\
-             agentsmith-override --minutes 3 {}\n\
-             (Short TTL only; no-expire is rejected by helper)\n\
-             Then retry the operation.\n",
-            record.path, record.path
-        ),
-    };
+    if policy.allow_trusted_tools_in_ai_context {
+        relevant.extend(trusted_set.iter().copied());
+    }
 
-    format!("{}{}", header, guidance)
-}
+    if policy.allow_vcs_metadata_in_ai_context {
+        for tool in ["git", "jj"] {
+            if trusted_set.contains(tool) {
+                relevant.insert(tool);
+            }
+        }
+    }
 
-fn now_ts() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs()
+    let mut tools = relevant.into_iter().collect::<Vec<_>>();
+    tools.sort_unstable();
+    tools
 }
 
-// --- Process tree walking (macOS) ---
-
-fn get_process_path(pid: i32) -> Option<String> {
-    let mut buf = vec![0u8; 4096];
-    let ret = unsafe { libc::proc_pidpath(pid, buf.as_mut_ptr() as *mut libc::c_void, buf.len() as u32) };
-    if ret > 0 {
-        buf.truncate(ret as usize);
-        String::from_utf8(buf).ok()
-    } else {
-        None
+fn trusted_identity_configuration_warning(policy: &SecurityPolicy) -> Option<String> {
+    let relevant_tools = warning_relevant_trusted_tools(policy);
+    if relevant_tools.is_empty() {
+        return None;
     }
-}
 
-/// Get the process argv[0] via sysctl KERN_PROCARGS2.
-/// This reflects process.title changes (e.g., Node.js setting title to "claude").
-fn parse_procargs_argv(buf: &[u8]) -> Vec<String> {
-    if buf.len() < 4 {
-        return Vec::new();
+    if policy.trusted_tool_identities.is_empty() {
+        return Some(format!(
+            "[WARN] trusted checks are active for [{}], but trusted_tool_identities is empty; these tools will fail-closed with TRUST_IDENTITY_MISMATCH. Run nix switch/make to refresh policy defaults or set trusted_tool_identities in ~/.agentsmith-rs/policy.json.",
+            relevant_tools.join(", ")
+        ));
     }
-    let argc_raw = i32::from_ne_bytes([buf[0], buf[1], buf[2], buf[3]]);
-    if argc_raw <= 0 {
-        return Vec::new();
+
+    let missing_tools = relevant_tools
+        .iter()
+        .filter(|tool| policy.trusted_identity_candidates_for_process(tool).is_empty())
+        .map(|tool| (*tool).to_string())
+        .collect::<Vec<_>>();
+    if missing_tools.is_empty() {
+        return None;
     }
-    let argc = argc_raw as usize;
 
-    // Layout: argc(i32) + exec_path + \0 + padding(\0s) + argv[0] + \0 + argv[1] + ...
-    let mut pos = 4;
-    while pos < buf.len() && buf[pos] != 0 {
-        pos += 1;
-    }
-    while pos < buf.len() && buf[pos] == 0 {
-        pos += 1;
-    }
+    Some(format!(
+        "[WARN] trusted_tool_identities is missing entries for trusted_tools [{}]; these tools will fail-closed with TRUST_IDENTITY_MISMATCH. Add matching identities or remove them from trusted_tools.",
+        missing_tools.join(", ")
+    ))
+}
 
-    let mut args: Vec<String> = Vec::with_capacity(argc);
-    while pos < buf.len() && args.len() < argc {
-        while pos < buf.len() && buf[pos] == 0 {
-            pos += 1;
+fn log_policy_self_checks(policy: &SecurityPolicy, warning_state: &mut Option<(String, u64)>, now: u64) {
+    for name in &policy.ai_agent_profiles {
+        if agent_profile(name).is_none() {
+            let known: Vec<&str> = AGENT_PROFILE_CATALOG.iter().map(|profile| profile.name).collect();
+            eprintln!(
+                "[WARN] ai_agent_profiles entry {:?} is not in agent profile catalog v{} (known: {})",
+                name,
+                AGENT_PROFILE_CATALOG_VERSION,
+                known.join(", ")
+            );
         }
-        if pos >= buf.len() {
-            break;
+    }
+    if let Err(err) = policy.override_policy.never_override_matchers() {
+        eprintln!(
+            "[WARN] override_policy.never_override is invalid, no override applies: {}",
+            err
+        );
+    }
+    for rule in &policy.override_policy.auto_approve {
+        if let Err(err) = compile_path_globs(&rule.paths) {
+            eprintln!(
+                "[WARN] override_policy auto_approve rule {:?} is ignored: {}",
+                rule.name, err
+            );
         }
-
-        let start = pos;
-        while pos < buf.len() && buf[pos] != 0 {
-            pos += 1;
+    }
+    for rule in &policy.ai_agent_detectors {
+        if let Some(err) = rule.validation_error() {
+            eprintln!(
+                "[WARN] ai_agent_detectors rule {:?} is ignored: {}",
+                rule.name, err
+            );
         }
-        if start < pos {
-            args.push(String::from_utf8_lossy(&buf[start..pos]).to_string());
+    }
+    if let Some(warning) = trusted_identity_configuration_warning(policy) {
+        let should_emit = match warning_state {
+            Some((previous, ts))
+                if previous == &warning && now.saturating_sub(*ts) < POLICY_SELF_CHECK_WARNING_INTERVAL_SECS =>
+            {
+                false
+            },
+            _ => true,
+        };
+        if should_emit {
+            eprintln!("{}", warning);
+            *warning_state = Some((warning, now));
         }
+    } else {
+        *warning_state = None;
     }
+}
 
-    args
+fn is_safe_taint_device_path(path: &str) -> bool {
+    matches!(path, "/dev/null" | "/dev/tty" | "/dev/dtracehelper")
 }
 
-fn get_process_argv(pid: i32) -> Option<Vec<String>> {
-    let mut mib = [libc::CTL_KERN, libc::KERN_PROCARGS2, pid];
-    let mut size: libc::size_t = 0;
+fn format_cache_watermark_log(
+    ts: u64,
+    ancestor_current: usize,
+    ancestor_high: usize,
+    trusted_current: usize,
+    trusted_high: usize,
+    taint_current: usize,
+    taint_high: usize,
+) -> String {
+    format!(
+        "[METRIC] cache-watermark ts={} ancestor={}/{} trusted={}/{} taint={}/{}",
+        ts, ancestor_current, ancestor_high, trusted_current, trusted_high, taint_current, taint_high
+    )
+}
 
-    // First call: get buffer size
-    let ret = unsafe {
-        libc::sysctl(
-            mib.as_mut_ptr(),
-            3,
-            std::ptr::null_mut(),
-            &mut size,
-            std::ptr::null_mut(),
-            0,
-        )
+fn format_runtime_health_log(
+    ts: u64,
+    signature_queue_pending: u64,
+    signature_queue_dropped_full: u64,
+    signature_queue_dropped_disconnected: u64,
+    log_queue_pending: u64,
+    log_queue_dropped_full: u64,
+    log_queue_dropped_disconnected: u64,
+    callback_latency: &CallbackLatencySnapshot,
+) -> String {
+    format!(
+        "[METRIC] runtime-health ts={} sigq_pending={} sigq_drop_full={} sigq_drop_disconnected={} logq_pending={} logq_drop_full={} logq_drop_disconnected={} cb_count={} cb_p50_us={} cb_p95_us={} cb_p99_us={} cb_max_us={}",
+        ts,
+        signature_queue_pending,
+        signature_queue_dropped_full,
+        signature_queue_dropped_disconnected,
+        log_queue_pending,
+        log_queue_dropped_full,
+        log_queue_dropped_disconnected,
+        callback_latency.total,
+        callback_latency.percentile_upper_bound_us(50),
+        callback_latency.percentile_upper_bound_us(95),
+        callback_latency.percentile_upper_bound_us(99),
+        callback_latency.max_us
+    )
+}
+
+fn emit_cache_watermark_log(
+    process_table: &Arc<Mutex<ProcessTable>>,
+    trust_cache: &Arc<Mutex<TrustedProcessCache>>,
+    taint_state: &Arc<Mutex<TaintState>>,
+    highs: &mut CacheWatermarkHighs,
+) {
+    let ancestor_current = {
+        let table = process_table.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        table.len()
     };
-    if ret != 0 || size == 0 {
-        return None;
-    }
+    let trusted_current = {
+        let cache = trust_cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        cache.len()
+    };
+    let taint_current = {
+        let taint = taint_state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        taint.len()
+    };
+    let (ancestor_high, trusted_high, taint_high) = highs.update(ancestor_current, trusted_current, taint_current);
+    println!(
+        "{}",
+        format_cache_watermark_log(
+            now_ts(),
+            ancestor_current,
+            ancestor_high,
+            trusted_current,
+            trusted_high,
+            taint_current,
+            taint_high
+        )
+    );
+}
 
-    let mut buf = vec![0u8; size];
-    let ret = unsafe {
-        libc::sysctl(
-            mib.as_mut_ptr(),
-            3,
-            buf.as_mut_ptr() as *mut libc::c_void,
-            &mut size,
-            std::ptr::null_mut(),
-            0,
+fn emit_runtime_health_log(callback_latency_metrics: &CallbackLatencyMetrics) {
+    let callback_latency = callback_latency_metrics.snapshot_and_reset();
+    println!(
+        "{}",
+        format_runtime_health_log(
+            now_ts(),
+            SIGNATURE_QUEUE_PENDING.load(Ordering::Relaxed),
+            SIGNATURE_QUEUE_DROPPED_FULL.load(Ordering::Relaxed),
+            SIGNATURE_QUEUE_DROPPED_DISCONNECTED.load(Ordering::Relaxed),
+            LOG_QUEUE_PENDING.load(Ordering::Relaxed),
+            LOG_QUEUE_DROPPED_FULL.load(Ordering::Relaxed),
+            LOG_QUEUE_DROPPED_DISCONNECTED.load(Ordering::Relaxed),
+            &callback_latency,
         )
-    };
-    if ret != 0 || size < 8 {
-        return None;
+    );
+}
+
+fn maybe_warn_log_queue_full(kind: &str) {
+    let now = now_ts();
+    let last_warn = LOG_QUEUE_FULL_LAST_WARN_TS.load(Ordering::Relaxed);
+    if now.saturating_sub(last_warn) < LOG_QUEUE_FULL_WARN_INTERVAL_SECS {
+        return;
+    }
+    if LOG_QUEUE_FULL_LAST_WARN_TS
+        .compare_exchange(last_warn, now, Ordering::Relaxed, Ordering::Relaxed)
+        .is_ok()
+    {
+        eprintln!("[log] async queue full; dropping {} event", kind);
     }
+}
 
-    buf.truncate(size);
-    let args = parse_procargs_argv(&buf);
-    if args.is_empty() {
-        None
-    } else {
-        Some(args)
+fn process_log_message_sync(home: &str, message: GuardLogMessage) {
+    match message {
+        GuardLogMessage::Denial(record) => {
+            count_session_activity(
+                record.session.as_ref().map(|session| session.id.as_str()),
+                |counters| counters.denials += 1,
+            );
+            log_denial_sync(home, &record)
+        },
+        GuardLogMessage::AuditOnly(record) => {
+            count_session_activity(
+                record.session.as_ref().map(|session| session.id.as_str()),
+                |counters| counters.audit_only += 1,
+            );
+            log_audit_only_sync(home, &record)
+        },
+        GuardLogMessage::TaintMark(record) => {
+            count_session_activity(record.session_id.as_deref(), |counters| {
+                counters.taint_marks += 1
+            });
+            log_taint_mark_sync(home, &record)
+        },
+        GuardLogMessage::Session(event) => log_session_event_sync(home, &event),
     }
 }
 
-fn get_process_argv0(pid: i32) -> Option<String> {
-    get_process_argv(pid).and_then(|args| {
-        args.first()
-            .map(|argv0| argv0.rsplit('/').next().unwrap_or(argv0.as_str()).to_string())
-    })
+fn decrement_queue_pending(counter: &AtomicU64) {
+    let _ = counter.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |value| {
+        value.checked_sub(1)
+    });
 }
 
-fn is_override_helper_argv(args: &[String]) -> bool {
-    args.iter().any(|arg| {
-        Path::new(arg)
-            .file_name()
-            .and_then(|name| name.to_str())
-            .is_some_and(|name| name == "agentsmith-override")
-    })
+fn enqueue_log_message_or_fallback(home: &str, message: GuardLogMessage) {
+    if let Some(tx) = LOG_EVENT_TX.get() {
+        let kind = match &message {
+            GuardLogMessage::Denial(_) => "denial",
+            GuardLogMessage::AuditOnly(_) => "audit-only",
+            GuardLogMessage::TaintMark(_) => "taint",
+            GuardLogMessage::Session(_) => "session",
+        };
+        LOG_QUEUE_PENDING.fetch_add(1, Ordering::Relaxed);
+        match tx.try_send(message) {
+            Ok(()) => {},
+            Err(mpsc::TrySendError::Full(_)) => {
+                decrement_queue_pending(&LOG_QUEUE_PENDING);
+                LOG_QUEUE_DROPPED_FULL.fetch_add(1, Ordering::Relaxed);
+                maybe_warn_log_queue_full(kind);
+            },
+            Err(mpsc::TrySendError::Disconnected(message)) => {
+                decrement_queue_pending(&LOG_QUEUE_PENDING);
+                LOG_QUEUE_DROPPED_DISCONNECTED.fetch_add(1, Ordering::Relaxed);
+                process_log_message_sync(home, message);
+            },
+        }
+        return;
+    }
+
+    process_log_message_sync(home, message);
 }
 
-fn is_override_helper_process(pid: i32) -> bool {
-    let args = match get_process_argv(pid) {
-        Some(args) => args,
-        None => return false,
-    };
-    is_override_helper_argv(&args)
+fn init_async_log_worker(home: &str) {
+    let (tx, rx) = mpsc::sync_channel::<GuardLogMessage>(LOG_EVENT_QUEUE_BOUND);
+    if LOG_EVENT_TX.set(tx).is_err() {
+        return;
+    }
+
+    let worker_home = home.to_string();
+    thread::spawn(move || {
+        while let Ok(message) = rx.recv() {
+            process_log_message_sync(worker_home.as_str(), message);
+            decrement_queue_pending(&LOG_QUEUE_PENDING);
+        }
+    });
 }
 
-/// Query proc_bsdinfo for a process. Returns (ppid, comm_name).
-#[repr(C)]
-struct ProcBsdInfo {
-    pbi_flags: u32,
-    pbi_status: u32,
-    pbi_xstatus: u32,
-    pbi_pid: u32,
-    pbi_ppid: u32,
-    pbi_uid: libc::uid_t,
-    pbi_gid: libc::gid_t,
-    pbi_ruid: libc::uid_t,
-    pbi_rgid: libc::gid_t,
-    pbi_svuid: libc::uid_t,
-    pbi_svgid: libc::gid_t,
-    rfu_1: u32,
-    pbi_comm: [libc::c_char; MAXCOMLEN],
-    pbi_name: [libc::c_char; MAXCOMLEN * 2],
-    pbi_nfiles: u32,
-    pbi_pgid: u32,
-    pbi_pjobc: u32,
-    e_tdev: u32,
-    e_tpgid: u32,
-    pbi_nice: i32,
-    pbi_start_tvsec: u64,
-    pbi_start_tvusec: u64,
+fn log_denial(home: &str, record: &DenialRecord) {
+    enqueue_log_message_or_fallback(home, GuardLogMessage::Denial(record.clone()));
 }
 
-fn c_char_buf_to_string(buf: &[libc::c_char]) -> String {
-    let bytes: Vec<u8> = buf
-        .iter()
-        .copied()
-        .take_while(|value| *value != 0)
-        .map(|value| value as u8)
-        .collect();
-    String::from_utf8_lossy(&bytes).to_string()
+fn audit_only_coalescer() -> &'static Mutex<AuditOnlyCoalescer> {
+    AUDIT_ONLY_COALESCER.get_or_init(|| Mutex::new(AuditOnlyCoalescer::default()))
 }
 
-fn get_process_bsd_info(pid: i32) -> Option<ProcBsdInfo> {
-    const PROC_PIDTBSDINFO: i32 = 3;
-    let mut info: ProcBsdInfo = unsafe { std::mem::zeroed() };
-    let info_size = std::mem::size_of::<ProcBsdInfo>() as i32;
-    let ret = unsafe {
-        libc::proc_pidinfo(
-            pid,
-            PROC_PIDTBSDINFO,
-            0,
-            &mut info as *mut ProcBsdInfo as *mut libc::c_void,
-            info_size,
-        )
+fn serialize_audit_only_entry(entry: &AuditOnlyAggregateEntry) -> Option<String> {
+    let is_aggregated = entry.count > 1;
+    let line = AuditOnlyLogLine {
+        record: &entry.sample,
+        count: is_aggregated.then_some(entry.count),
+        first_ts: is_aggregated.then_some(entry.first_ts),
+        last_ts: is_aggregated.then_some(entry.last_ts),
     };
-    if ret == info_size {
-        Some(info)
-    } else {
-        None
-    }
+    serde_json::to_string(&line).ok()
 }
 
-fn get_process_info(pid: i32) -> Option<(i32, String)> {
-    let info = get_process_bsd_info(pid)?;
-    let ppid_raw = info.pbi_ppid as i32;
-    let ppid = if ppid_raw > 0 && ppid_raw != pid { ppid_raw } else { 0 };
-    let comm = c_char_buf_to_string(&info.pbi_comm);
-    Some((ppid, comm))
-}
+fn write_audit_only_entries(home: &str, entries: Vec<AuditOnlyAggregateEntry>) {
+    if entries.is_empty() {
+        return;
+    }
 
-fn parent_pid_for_pid(pid: i32) -> Option<i32> {
-    get_process_info(pid).map(|(ppid, _)| ppid).filter(|ppid| *ppid > 0)
+    let guard_dir = match ensure_guard_dirs(home) {
+        Ok(dir) => dir,
+        Err(err) => {
+            eprintln!("[log] cannot use guard dir: {}", err);
+            return;
+        },
+    };
+
+    let log_path = guard_dir.join("audit-only.jsonl");
+    let lines: Vec<String> = entries.iter().filter_map(serialize_audit_only_entry).collect();
+    if lines.is_empty() {
+        return;
+    }
+
+    match open_rotating_log(
+        &log_path,
+        AUDIT_ONLY_LOG_MAX_BYTES,
+        &log_rotation_policy(),
+        now_ts(),
+        || {},
+    ) {
+        Ok(mut file) => {
+            for line in lines {
+                let _ = writeln!(file, "{}", line);
+            }
+        },
+        Err(err) => eprintln!("[log] cannot append to {}: {}", log_path.display(), err),
+    }
 }
 
-fn pid_for_record(pid: i32) -> Option<i32> {
-    (pid > 0).then_some(pid)
+fn flush_audit_only_coalescer(home: &str, now: u64, force_all: bool) {
+    let ready = {
+        let mut coalescer = audit_only_coalescer()
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        coalescer.take_ready(now, force_all)
+    };
+    write_audit_only_entries(home, ready);
 }
 
-fn process_start_time_for_pid(pid: i32) -> Option<ProcessStartTimeKey> {
-    let info = get_process_bsd_info(pid)?;
-    Some(ProcessStartTimeKey {
-        start_tvsec: info.pbi_start_tvsec,
-        start_tvusec: info.pbi_start_tvusec,
-    })
+fn log_audit_only(home: &str, record: &DenialRecord) {
+    enqueue_log_message_or_fallback(home, GuardLogMessage::AuditOnly(record.clone()));
 }
 
-fn process_identity_key(pid: i32) -> Option<ProcessIdentityKey> {
-    let info = get_process_bsd_info(pid)?;
-    let executable_path = get_process_path(pid)?;
-    Some(ProcessIdentityKey {
-        pid,
-        start_tvsec: info.pbi_start_tvsec,
-        start_tvusec: info.pbi_start_tvusec,
-        executable_path,
-    })
+fn log_taint_mark(home: &str, record: &TaintMarkRecord) {
+    enqueue_log_message_or_fallback(home, GuardLogMessage::TaintMark(record.clone()));
 }
 
-fn exe_name(path: &str) -> &str {
-    path.rsplit('/').next().unwrap_or(path)
+fn log_session_events(home: &str, events: Vec<SessionEvent>) {
+    for event in events {
+        enqueue_log_message_or_fallback(home, GuardLogMessage::Session(event));
+    }
 }
 
-/// Walk the process tree to find if any ancestor is an AI agent.
-/// Returns (is_ai_context, ai_ancestor_name).
-fn find_ai_ancestor(pid: i32, policy: &SecurityPolicy, cache: &mut HashMap<i32, CachedAncestor>) -> Option<String> {
-    let ts = now_ts();
-    cache.retain(|_, cached| ts.saturating_sub(cached.updated_at) <= CACHE_TTL_SECS);
-    let mut current = pid;
-    let mut depth = 0;
+fn record_denial_or_audit_only(home: &str, policy: &SecurityPolicy, record: DenialRecord) -> bool {
+    if policy.audit_only_mode {
+        log_audit_only(home, &record);
+        return false;
+    }
 
-    while current > 1 && depth < 30 {
-        if let Some(cached) = cache.get(&current) {
-            if ts.saturating_sub(cached.updated_at) <= CACHE_TTL_SECS {
-                return cached.ai_ancestor.clone();
-            }
-        }
+    log_denial(home, &record);
+    true
+}
 
-        // Check executable path (Mach-O binary name)
-        let exe_path = get_process_path(current);
-        let exe_match = exe_path.as_ref().map(|p| policy.matches_ai_agent(p)).unwrap_or(false);
+fn log_denial_sync(home: &str, record: &DenialRecord) {
+    let guard_dir = match ensure_guard_dirs(home) {
+        Ok(dir) => dir,
+        Err(err) => {
+            eprintln!("[log] cannot use guard dir: {}", err);
+            return;
+        },
+    };
 
-        // Check argv[0] (reflects process.title, e.g. Node.js "claude")
-        let argv0 = get_process_argv0(current);
-        let argv0_match = argv0
-            .as_ref()
-            .map(|a| policy.ai_agent_patterns.iter().any(|pat| a.contains(pat.as_str())))
-            .unwrap_or(false);
+    let log_path = guard_dir.join("denials.jsonl");
+    let feedback_path = guard_dir.join("last_denial.txt");
 
-        if exe_match || argv0_match {
-            let label = if argv0_match {
-                argv0.unwrap_or_else(|| exe_path.as_ref().map(|p| exe_name(p).to_string()).unwrap_or_default())
-            } else {
-                exe_path.as_ref().map(|p| exe_name(p).to_string()).unwrap_or_default()
-            };
-            cache.insert(
-                current,
-                CachedAncestor {
-                    ai_ancestor: Some(label.clone()),
-                    updated_at: ts,
-                },
-            );
-            return Some(label);
-        }
+    if let Ok(json) = serde_json::to_string(record) {
+        append_guard_log(home, &log_path, &json, DENIALS_LOG_MAX_BYTES);
+    }
 
-        // Walk up to parent via proc_bsdinfo
-        match get_process_info(current).map(|(pp, _)| pp).filter(|&pp| pp > 0) {
-            Some(pp) => current = pp,
-            None => break,
+    let feedback = build_denial_feedback(home, record);
+    if let Ok(mut file) = open_truncate_no_follow(&feedback_path, DEFAULT_FILE_MODE) {
+        if verify_regular_file(&file, &feedback_path).is_ok() {
+            let _ = file.write_all(feedback.as_bytes());
         }
-        depth += 1;
     }
 
-    cache.insert(
-        pid,
-        CachedAncestor {
-            ai_ancestor: None,
-            updated_at: ts,
-        },
-    );
-    None
-}
-
-fn process_name_for_pid(pid: i32) -> Option<String> {
-    get_process_path(pid).map(|path| exe_name(&path).to_string())
-}
-
-fn canonicalize_executable_path_strict(executable_path: &str) -> Result<String, String> {
-    let normalized = normalize_absolute_path(executable_path)
-        .ok_or_else(|| format!("executable path is not absolute: {}", executable_path))?;
-    let metadata =
-        fs::symlink_metadata(&normalized).map_err(|err| format!("cannot stat executable {}: {}", normalized, err))?;
-    if metadata.file_type().is_symlink() {
-        return Err(format!(
-            "symbolic-link executable path is rejected: {}",
-            normalized
-        ));
-    }
-    let canonical = fs::canonicalize(&normalized)
-        .map_err(|err| format!("cannot canonicalize executable {}: {}", normalized, err))?;
-    let canonical_text = canonical.to_string_lossy().to_string();
-    if canonical_text != normalized {
-        return Err(format!(
-            "executable path must be canonical (got {}, canonical {})",
-            normalized, canonical_text
-        ));
+    if let Err(err) = write_keyed_denial_feedback(&guard_dir, record, &feedback, now_ts()) {
+        eprintln!("[log] cannot write per-session feedback: {}", err);
     }
-    Ok(canonical_text)
 }
 
-fn codesign_field(report: &str, key: &str) -> Option<String> {
-    report
-        .lines()
-        .find_map(|line| line.strip_prefix(key).map(|value| value.trim().to_string()))
+/// Feedback directory name for a denial: the AI session when known, otherwise
+/// the denied process's parent (usually the agent or its shell).
+fn denial_feedback_key(record: &DenialRecord) -> String {
+    match (&record.session, record.ppid.or(record.pid)) {
+        (Some(session), _) => sanitize_component(&session.id),
+        (None, Some(pid)) => format!("pid-{}", pid),
+        (None, None) => "unknown".to_string(),
+    }
 }
 
-fn read_binary_signature(executable_path: &str) -> Result<BinaryCodeSignature, String> {
-    let output = Command::new(CODESIGN_BIN)
-        .args(["-dv", "--verbose=4", executable_path])
-        .output()
-        .map_err(|err| format!("failed to run codesign: {}", err))?;
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let stderr = String::from_utf8_lossy(&output.stderr);
-    let report = format!("{}\n{}", stdout, stderr);
-    if !output.status.success() {
-        return Err(format!(
-            "codesign verification failed for {}: {}",
-            executable_path,
-            report.trim()
-        ));
+/// `last_denial.txt` is shared by every agent on the machine; this keeps a copy
+/// per session under `guard/feedback/<key>/` so concurrent agents each read their own.
+fn write_keyed_denial_feedback(guard_dir: &Path, record: &DenialRecord, feedback: &str, now: u64) -> io::Result<()> {
+    let feedback_root = guard_dir.join("feedback");
+    ensure_dir_not_symlink(&feedback_root, DEFAULT_DIR_MODE)?;
+    let key_dir = feedback_root.join(denial_feedback_key(record));
+    if fs::symlink_metadata(&key_dir).is_err() {
+        prune_stale_feedback_dirs(&feedback_root, now);
     }
+    ensure_dir_not_symlink(&key_dir, DEFAULT_DIR_MODE)?;
 
-    let signing_identifier = codesign_field(&report, "Identifier=")
-        .ok_or_else(|| format!("missing signing identifier for {}", executable_path))?;
-    let team_identifier = match codesign_field(&report, "TeamIdentifier=") {
-        Some(value) if value == "not set" || value.is_empty() => None,
-        Some(value) => Some(value),
-        None => None,
-    };
-    let cdhash = codesign_field(&report, "CDHash=").map(|value| value.to_lowercase());
+    let text_path = key_dir.join("last_denial.txt");
+    let mut file = open_truncate_no_follow(&text_path, DEFAULT_FILE_MODE)?;
+    verify_regular_file(&file, &text_path)?;
+    file.write_all(feedback.as_bytes())?;
 
-    Ok(BinaryCodeSignature {
-        signing_identifier,
-        team_identifier,
-        cdhash,
-    })
-}
+    let json = serde_json::to_string(record).map_err(|err| io::Error::other(err.to_string()))?;
+    let json_path = key_dir.join("last_denial.json");
+    let mut file = open_truncate_no_follow(&json_path, DEFAULT_FILE_MODE)?;
+    verify_regular_file(&file, &json_path)?;
+    writeln!(file, "{}", json)?;
 
-fn normalized_expected_trusted_identity_path(entry: &TrustedToolIdentity) -> Option<String> {
-    normalize_absolute_path(entry.path.as_str()).map(|path| trim_trailing_slashes(&path).to_string())
+    let log_path = key_dir.join("denials.jsonl");
+    let mut file = open_rotating_log(
+        &log_path,
+        FEEDBACK_DENIALS_MAX_BYTES,
+        &log_rotation_policy(),
+        now,
+        || {},
+    )?;
+    writeln!(file, "{}", json)?;
+    Ok(())
 }
 
-fn trusted_identity_decision_from_signature(
-    process_name: &str,
-    canonical_path: &str,
-    actual_signature: &BinaryCodeSignature,
-    expected_identities: &[&TrustedToolIdentity],
-    require_cdhash: bool,
-) -> TrustedProcessDecision {
-    let matched = expected_identities.iter().any(|entry| {
-        let expected_path = match normalized_expected_trusted_identity_path(entry) {
-            Some(path) => path,
-            None => return false,
+/// Remove feedback directories nobody has been denied into for a day. Only the
+/// files this daemon writes (including rotated history segments) are removed,
+/// so anything else keeps its directory.
+fn prune_stale_feedback_dirs(feedback_root: &Path, now: u64) {
+    let Ok(iter) = fs::read_dir(feedback_root) else {
+        return;
+    };
+    for entry in iter.filter_map(Result::ok) {
+        let path = entry.path();
+        let Ok(meta) = fs::symlink_metadata(&path) else {
+            continue;
         };
-        if expected_path != canonical_path {
-            return false;
-        }
-        if entry.signing_identifier != actual_signature.signing_identifier {
-            return false;
+        if !meta.is_dir() {
+            continue;
         }
-        if entry.team_identifier != actual_signature.team_identifier {
-            return false;
+        // Files are rewritten in place, so the directory mtime only tracks creation.
+        let modified_secs = fs::symlink_metadata(path.join("last_denial.json"))
+            .unwrap_or(meta)
+            .modified()
+            .ok()
+            .and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok())
+            .map(|delta| delta.as_secs())
+            .unwrap_or(now);
+        if now.saturating_sub(modified_secs) <= FEEDBACK_RETENTION_SECS {
+            continue;
         }
-
-        let expected_cdhash = entry.cdhash.as_ref().map(|value| value.to_lowercase());
-        if require_cdhash && expected_cdhash.is_none() {
-            return false;
+        for segment in log_segment_paths(&path.join("denials.jsonl")) {
+            let _ = fs::remove_file(segment);
         }
-        if let Some(expected) = expected_cdhash {
-            if actual_signature.cdhash.as_deref() != Some(expected.as_str()) {
-                return false;
-            }
+        for name in FEEDBACK_FILE_NAMES {
+            let _ = fs::remove_file(path.join(name));
         }
+        let _ = fs::remove_dir(&path);
+    }
+}
 
-        true
-    });
-    if matched {
-        return TrustedProcessDecision::Trusted;
+fn log_audit_only_sync(home: &str, record: &DenialRecord) {
+    let ready = {
+        let mut coalescer = audit_only_coalescer()
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        coalescer.ingest(record.clone())
+    };
+    write_audit_only_entries(home, ready);
+}
+
+fn log_taint_mark_sync(home: &str, record: &TaintMarkRecord) {
+    let guard_dir = match ensure_guard_dirs(home) {
+        Ok(dir) => dir,
+        Err(err) => {
+            eprintln!("[taint] cannot use guard dir: {}", err);
+            return;
+        },
+    };
+
+    let log_path = guard_dir.join("taint-marks.jsonl");
+    if let Ok(json) = serde_json::to_string(record) {
+        append_guard_log(home, &log_path, &json, TAINT_AUDIT_MAX_BYTES);
     }
+}
 
-    let expected_paths = expected_identities
-        .iter()
-        .filter_map(|entry| normalized_expected_trusted_identity_path(entry))
-        .collect::<Vec<_>>()
-        .join(", ");
-    let expected_signing_ids = expected_identities
-        .iter()
-        .map(|entry| entry.signing_identifier.clone())
-        .collect::<Vec<_>>()
-        .join(", ");
-    let expected_team_ids = expected_identities
-        .iter()
-        .map(|entry| entry.team_identifier.clone().unwrap_or_else(|| "not set".to_string()))
-        .collect::<Vec<_>>()
-        .join(", ");
+/// Chain position of one guard log: the last record's `seq` and line hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LogChainState {
+    seq: u64,
+    hash: String,
+    since_checkpoint: u64,
+    checkpoint_at: u64,
+}
 
-    TrustedProcessDecision::IdentityMismatch(format!(
-        "identity mismatch for {}: path={} signing_id={} team_id={} (expected paths=[{}], signing_ids=[{}], team_ids=[{}])",
-        process_name,
-        canonical_path,
-        actual_signature.signing_identifier,
-        actual_signature
-            .team_identifier
-            .clone()
-            .unwrap_or_else(|| "not set".to_string()),
-        expected_paths,
-        expected_signing_ids,
-        expected_team_ids
-    ))
+/// Signed statement that record `seq` of `log` hashed to `hash`. Written every
+/// few records and right before a log is rotated (`reason: "rotate"`; logs
+/// emptied by older daemons carry `"truncate"`).
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+struct LogCheckpoint {
+    ts: u64,
+    log: String,
+    seq: u64,
+    hash: String,
+    reason: String,
+    #[serde(default)]
+    sig: String,
 }
 
-#[cfg(test)]
-fn evaluate_trusted_process_from_path(
-    process_name: &str,
-    executable_path: &str,
-    policy: &SecurityPolicy,
-) -> TrustedProcessDecision {
-    if !policy.is_trusted_tool(process_name) {
-        return TrustedProcessDecision::NotTrusted;
+impl LogCheckpoint {
+    fn signing_input(&self) -> String {
+        format!(
+            "{}|{}|{}|{}|{}",
+            self.log, self.seq, self.hash, self.ts, self.reason
+        )
     }
 
-    let expected_identities = policy.trusted_identity_candidates_for_process(process_name);
-    if expected_identities.is_empty() {
-        return TrustedProcessDecision::IdentityMismatch(format!(
-            "trusted tool {} has no trusted_tool_identities entry",
-            process_name
-        ));
+    /// Written as the log was moved aside; the next record links to `hash`.
+    fn is_segment_boundary(&self) -> bool {
+        matches!(self.reason.as_str(), "rotate" | "truncate")
     }
+}
 
-    let canonical_path = match canonicalize_executable_path_strict(executable_path) {
-        Ok(path) => path,
-        Err(err) => return TrustedProcessDecision::IdentityMismatch(err),
-    };
-    let actual_signature = match read_binary_signature(&canonical_path) {
-        Ok(signature) => signature,
-        Err(err) => return TrustedProcessDecision::IdentityMismatch(err),
-    };
-    trusted_identity_decision_from_signature(
-        process_name,
-        canonical_path.as_str(),
-        &actual_signature,
-        expected_identities.as_slice(),
-        policy.trusted_identity_require_cdhash,
-    )
+/// Root-held HMAC key and the user's checkpoint file under `OVERRIDE_STORE_DIR`,
+/// out of reach of a process with user rights.
+struct LogCheckpointStore {
+    path: PathBuf,
+    key: [u8; 32],
 }
 
-fn request_signature_refresh_if_needed(
-    canonical_path: &str,
-    signature_cache: &Arc<Mutex<BinarySignatureCache>>,
-    signature_refresh_tx: &mpsc::SyncSender<String>,
-) {
-    let should_enqueue = {
-        let mut cache = signature_cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
-        cache.should_enqueue_refresh(canonical_path)
-    };
-    if !should_enqueue {
-        return;
+impl LogCheckpointStore {
+    fn for_home(home: &str) -> Option<Self> {
+        let key = (*LOG_CHAIN_KEY.get_or_init(load_or_create_log_chain_key))?;
+        Some(Self {
+            path: log_checkpoint_path(home),
+            key,
+        })
     }
 
-    SIGNATURE_QUEUE_PENDING.fetch_add(1, Ordering::Relaxed);
-    if let Err(err) = signature_refresh_tx.try_send(canonical_path.to_string()) {
-        decrement_queue_pending(&SIGNATURE_QUEUE_PENDING);
-        let mut cache = signature_cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
-        cache.cancel_in_flight_refresh(canonical_path);
-        match err {
-            mpsc::TrySendError::Full(_) => {
-                SIGNATURE_QUEUE_DROPPED_FULL.fetch_add(1, Ordering::Relaxed);
-                eprintln!(
-                    "[trust] signature refresh queue is full; skipping enqueue for {}",
-                    canonical_path
-                );
-            },
-            mpsc::TrySendError::Disconnected(_) => {
-                SIGNATURE_QUEUE_DROPPED_DISCONNECTED.fetch_add(1, Ordering::Relaxed);
-                eprintln!(
-                    "[trust] signature refresh worker disconnected; unable to verify {}",
-                    canonical_path
-                );
-            },
-        }
+    fn sign(&self, checkpoint: &LogCheckpoint) -> String {
+        hmac_sha256(&self.key, checkpoint.signing_input().as_bytes())
     }
-}
 
-fn warm_signature_cache_for_policy(
-    policy: &SecurityPolicy,
-    signature_cache: &Arc<Mutex<BinarySignatureCache>>,
-    signature_refresh_tx: &mpsc::SyncSender<String>,
-) {
-    let mut prewarm_paths = HashSet::new();
-    for entry in policy.trusted_tool_identities.iter() {
-        if let Some(path) = normalized_expected_trusted_identity_path(entry) {
-            prewarm_paths.insert(path);
-        }
+    fn is_authentic(&self, checkpoint: &LogCheckpoint) -> bool {
+        self.sign(checkpoint) == checkpoint.sig
     }
 
-    for path in prewarm_paths.into_iter() {
-        request_signature_refresh_if_needed(path.as_str(), signature_cache, signature_refresh_tx);
+    fn load(&self) -> Vec<LogCheckpoint> {
+        fs::read_to_string(&self.path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect()
     }
-}
 
-fn spawn_signature_refresh_worker(
-    signature_refresh_rx: mpsc::Receiver<String>,
-    signature_cache: Arc<Mutex<BinarySignatureCache>>,
-) {
-    thread::spawn(move || {
-        while let Ok(canonical_path) = signature_refresh_rx.recv() {
-            let signature = read_binary_signature(canonical_path.as_str());
-            let now = now_ts();
-            let mut cache = signature_cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
-            cache.insert(canonical_path, signature, now);
-            decrement_queue_pending(&SIGNATURE_QUEUE_PENDING);
+    fn append(&self, mut checkpoint: LogCheckpoint) -> io::Result<()> {
+        checkpoint.sig = self.sign(&checkpoint);
+        if let Some(parent) = self.path.parent() {
+            ensure_dir_not_symlink(parent, DEFAULT_DIR_MODE)?;
         }
-    });
+        if fs::symlink_metadata(&self.path).is_ok_and(|meta| meta.len() > LOG_CHECKPOINT_MAX_BYTES) {
+            self.compact()?;
+        }
+        let json = serde_json::to_string(&checkpoint).map_err(|err| io::Error::other(err.to_string()))?;
+        let mut file = open_append_no_follow(&self.path, RUNTIME_OVERRIDE_FILE_MODE)?;
+        verify_regular_file(&file, &self.path)?;
+        writeln!(file, "{}", json)
+    }
+
+    /// Keep the newest checkpoints plus the segment boundaries `verify-logs`
+    /// needs to accept where the oldest retained segment starts.
+    fn compact(&self) -> io::Result<()> {
+        let checkpoints = self.load();
+        let keep_from = checkpoints.len().saturating_sub(LOG_CHECKPOINT_KEEP);
+        let mut content = String::new();
+        for (index, checkpoint) in checkpoints.iter().enumerate() {
+            let retained_boundary = checkpoint.is_segment_boundary()
+                && checkpoints[index + 1..]
+                    .iter()
+                    .filter(|later| later.log == checkpoint.log && later.is_segment_boundary())
+                    .count()
+                    <= LOG_ROTATION_MAX_KEEP_SEGMENTS;
+            if index >= keep_from || retained_boundary {
+                let json = serde_json::to_string(checkpoint).map_err(|err| io::Error::other(err.to_string()))?;
+                content.push_str(&json);
+                content.push('\n');
+            }
+        }
+        let tmp_path = self.path.with_extension(format!("tmp.{}", std::process::id()));
+        let mut file = open_truncate_no_follow(&tmp_path, RUNTIME_OVERRIDE_FILE_MODE)?;
+        verify_regular_file(&file, &tmp_path)?;
+        file.write_all(content.as_bytes())?;
+        fs::rename(&tmp_path, &self.path)
+    }
 }
 
-fn evaluate_trusted_process_from_path_nonblocking(
-    process_name: &str,
-    executable_path: &str,
-    policy: &SecurityPolicy,
-    signature_cache: &Arc<Mutex<BinarySignatureCache>>,
-    signature_refresh_tx: &mpsc::SyncSender<String>,
-) -> TrustedProcessDecision {
-    if !policy.is_trusted_tool(process_name) {
-        return TrustedProcessDecision::NotTrusted;
+fn load_or_create_log_chain_key() -> Option<[u8; 32]> {
+    let path = Path::new(OVERRIDE_STORE_DIR).join(LOG_CHAIN_KEY_FILE);
+    let mut key = [0u8; 32];
+    if let Ok(mut file) = open_read_no_follow(&path) {
+        return (verify_regular_file(&file, &path).is_ok() && file.read_exact(&mut key).is_ok()).then_some(key);
+    }
+    if unsafe { libc::geteuid() } != 0 {
+        return None;
     }
+    File::open("/dev/urandom")
+        .and_then(|mut random| random.read_exact(&mut key))
+        .ok()?;
+    ensure_dir_not_symlink(Path::new(OVERRIDE_STORE_DIR), DEFAULT_DIR_MODE).ok()?;
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(RUNTIME_OVERRIDE_FILE_MODE)
+        .custom_flags(libc::O_NOFOLLOW | libc::O_CLOEXEC)
+        .open(&path)
+        .ok()?;
+    file.write_all(&key).and_then(|_| file.sync_all()).ok()?;
+    Some(key)
+}
 
-    let expected_identities = policy.trusted_identity_candidates_for_process(process_name);
-    if expected_identities.is_empty() {
-        return TrustedProcessDecision::IdentityMismatch(format!(
-            "trusted tool {} has no trusted_tool_identities entry",
-            process_name
-        ));
+fn hmac_sha256(key: &[u8; 32], message: &[u8]) -> String {
+    let mut inner_pad = [0x36u8; 64];
+    let mut outer_pad = [0x5cu8; 64];
+    for (index, byte) in key.iter().enumerate() {
+        inner_pad[index] ^= byte;
+        outer_pad[index] ^= byte;
     }
+    let inner = Sha256::new().chain_update(inner_pad).chain_update(message).finalize();
+    hex_digest(&Sha256::new().chain_update(outer_pad).chain_update(inner).finalize())
+}
 
-    let canonical_path = match canonicalize_executable_path_strict(executable_path) {
-        Ok(path) => path,
-        Err(err) => return TrustedProcessDecision::IdentityMismatch(err),
-    };
+fn log_line_hash(line: &str) -> String {
+    hex_digest(&Sha256::digest(line.as_bytes()))
+}
 
-    let now = now_ts();
-    let cached_signature = {
-        let mut cache = signature_cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
-        cache.get(canonical_path.as_str(), now)
-    };
+/// `json` (an object) with the record's `seq` and the previous line's hash
+/// appended, keeping the serialized field order.
+fn chain_log_line(json: &str, seq: u64, prev: &str) -> String {
+    let body = json.strip_suffix('}').unwrap_or(json);
+    format!("{},\"seq\":{},\"prev\":\"{}\"}}", body, seq, prev)
+}
 
-    let actual_signature = match cached_signature {
-        Some(Ok(signature)) => signature,
-        Some(Err(err)) => return TrustedProcessDecision::IdentityMismatch(err),
-        None => {
-            request_signature_refresh_if_needed(
-                canonical_path.as_str(),
-                signature_cache,
-                signature_refresh_tx,
-            );
-            return TrustedProcessDecision::IdentityMismatch(format!(
-                "{}: {}",
-                TRUST_SIGNATURE_PENDING_PREFIX, canonical_path
-            ));
-        },
+/// Chain position after the last record of `log_path`, never behind the
+/// latest checkpoint, so records removed while the daemon was down stay a gap.
+fn recover_log_chain(log_path: &Path, checkpoints: &[LogCheckpoint], now: u64) -> LogChainState {
+    let mut content = fs::read_to_string(log_path).unwrap_or_default();
+    if content.trim().is_empty() {
+        if let Some(segment) = log_segment_paths(log_path).last() {
+            content = read_log_segment(segment).unwrap_or_default();
+        }
+    }
+    let mut lines = content.lines().filter(|line| !line.trim().is_empty());
+    let mut state = LogChainState {
+        seq: 0,
+        hash: LOG_CHAIN_GENESIS.to_string(),
+        since_checkpoint: 0,
+        checkpoint_at: now,
     };
-
-    trusted_identity_decision_from_signature(
-        process_name,
-        canonical_path.as_str(),
-        &actual_signature,
-        expected_identities.as_slice(),
-        policy.trusted_identity_require_cdhash,
-    )
+    if let Some(last) = lines.next_back() {
+        state.hash = log_line_hash(last);
+        state.seq = std::iter::once(last)
+            .chain(lines.rev())
+            .find_map(|line| serde_json::from_str::<serde_json::Value>(line).ok()?["seq"].as_u64())
+            .unwrap_or(0);
+    }
+    let log_name = log_path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+    if let Some(checkpoint) = checkpoints
+        .iter()
+        .filter(|checkpoint| checkpoint.log == log_name && checkpoint.seq > state.seq)
+        .max_by_key(|checkpoint| checkpoint.seq)
+    {
+        state.seq = checkpoint.seq;
+        state.hash = checkpoint.hash.clone();
+    }
+    state
 }
 
-fn trust_decision_is_signature_pending(decision: &TrustedProcessDecision) -> bool {
-    matches!(
-        decision,
-        TrustedProcessDecision::IdentityMismatch(reason) if reason.starts_with(TRUST_SIGNATURE_PENDING_PREFIX)
-    )
+fn append_guard_log(home: &str, log_path: &Path, json: &str, max_bytes: u64) {
+    let store = LogCheckpointStore::for_home(home);
+    if let Err(err) = append_chained_log_line(log_path, json, max_bytes, store.as_ref(), now_ts()) {
+        eprintln!("[log] cannot append to {}: {}", log_path.display(), err);
+    }
 }
 
-fn prune_trust_cache(cache: &mut HashMap<ProcessIdentityKey, CachedTrustedProcess>, now: u64) {
-    cache.retain(|_, entry| now.saturating_sub(entry.updated_at) <= TRUST_CACHE_TTL_SECS);
-}
+/// Append `json` to `log_path` as the next link of its hash chain. A log over
+/// `max_bytes` is rotated first, after a `rotate` checkpoint that lets
+/// `verify-logs` tell pruned segments from deleted records.
+fn append_chained_log_line(
+    log_path: &Path,
+    json: &str,
+    max_bytes: u64,
+    store: Option<&LogCheckpointStore>,
+    now: u64,
+) -> io::Result<()> {
+    let log_name = log_path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+    let mut chains = LOG_CHAINS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let state = chains.entry(log_path.to_path_buf()).or_insert_with(|| {
+        let checkpoints = store.map(LogCheckpointStore::load).unwrap_or_default();
+        recover_log_chain(log_path, &checkpoints, now)
+    });
 
-fn invalidate_trust_cache_for_pid(cache: &mut HashMap<ProcessIdentityKey, CachedTrustedProcess>, pid: i32) {
-    cache.retain(|key, _| key.pid != pid);
+    let mut file = open_rotating_log(log_path, max_bytes, &log_rotation_policy(), now, || {
+        write_log_checkpoint(store, log_name, state, "rotate", now)
+    })?;
+    let line = chain_log_line(json, state.seq + 1, &state.hash);
+    writeln!(file, "{}", line)?;
+    state.seq += 1;
+    state.hash = log_line_hash(&line);
+    state.since_checkpoint += 1;
+    if state.since_checkpoint >= LOG_CHECKPOINT_EVERY_RECORDS
+        || now.saturating_sub(state.checkpoint_at) >= LOG_CHECKPOINT_INTERVAL_SECS
+    {
+        write_log_checkpoint(store, log_name, state, "interval", now);
+    }
+    Ok(())
 }
 
-fn clear_process_state_for_pid(
-    pid: i32,
-    ancestor_cache: &mut HashMap<i32, CachedAncestor>,
-    trust_cache: &mut TrustedProcessCache,
-    taint_state: &mut TaintState,
+fn write_log_checkpoint(
+    store: Option<&LogCheckpointStore>,
+    log: &str,
+    state: &mut LogChainState,
+    reason: &str,
+    now: u64,
 ) {
-    if pid <= 0 {
+    state.since_checkpoint = 0;
+    state.checkpoint_at = now;
+    let Some(store) = store.filter(|_| state.seq > 0) else {
         return;
+    };
+    let checkpoint = LogCheckpoint {
+        ts: now,
+        log: log.to_string(),
+        seq: state.seq,
+        hash: state.hash.clone(),
+        reason: reason.to_string(),
+        sig: String::new(),
+    };
+    if let Err(err) = store.append(checkpoint) {
+        eprintln!("[log] cannot write checkpoint for {}: {}", log, err);
     }
-    ancestor_cache.remove(&pid);
-    trust_cache.clear_pid(pid);
-    taint_state.clear_pid(pid);
 }
 
-fn clear_trust_cache_for_exec(trust_cache: &Arc<Mutex<TrustedProcessCache>>, pid: i32) {
-    let mut cache = trust_cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
-    cache.clear_pid(pid);
+impl LogRotationPolicy {
+    fn max_segment_bytes_or(&self, default: u64) -> u64 {
+        self.max_segment_bytes
+            .map(|max| {
+                max.clamp(
+                    LOG_ROTATION_MIN_SEGMENT_BYTES,
+                    LOG_ROTATION_MAX_SEGMENT_BYTES,
+                )
+            })
+            .unwrap_or(default)
+    }
+
+    fn keep_segments_or_default(&self) -> usize {
+        self.keep_segments
+            .unwrap_or(LOG_ROTATION_DEFAULT_KEEP_SEGMENTS)
+            .min(LOG_ROTATION_MAX_KEEP_SEGMENTS)
+    }
 }
 
-fn evaluate_trusted_process(
-    pid: i32,
-    process_name: &str,
-    policy: &SecurityPolicy,
-    trust_cache: &Arc<Mutex<TrustedProcessCache>>,
-    signature_cache: &Arc<Mutex<BinarySignatureCache>>,
-    signature_refresh_tx: &mpsc::SyncSender<String>,
-) -> TrustedProcessDecision {
-    let now = now_ts();
+fn log_rotation_policy() -> LogRotationPolicy {
+    LOG_ROTATION
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+}
 
-    let key = process_identity_key(pid);
-    if let Some(key) = key.as_ref() {
-        let cached = {
-            let mut cache = trust_cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
-            cache.get(key, now)
+fn set_log_rotation_policy(rotation: &LogRotationPolicy) {
+    *LOG_ROTATION
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = rotation.clone();
+}
+
+/// Open `log_path` for appending. A file over its size cap is synced and
+/// renamed to a dated segment first (`before_rotate` runs while it is still in
+/// place); older segments are then compressed and pruned per `rotation` on a
+/// background thread. Each step is a rename or an unlink, so a crash never
+/// loses records.
+fn open_rotating_log(
+    log_path: &Path,
+    max_bytes: u64,
+    rotation: &LogRotationPolicy,
+    now: u64,
+    before_rotate: impl FnOnce(),
+) -> io::Result<File> {
+    let file = open_append_no_follow(log_path, DEFAULT_FILE_MODE)?;
+    verify_regular_file(&file, log_path)?;
+    if file.metadata()?.len() <= rotation.max_segment_bytes_or(max_bytes) {
+        return Ok(file);
+    }
+    before_rotate();
+    file.sync_all()?;
+    drop(file);
+    fs::rename(log_path, next_log_segment_path(log_path, now))?;
+    let (segment_log, rotation) = (log_path.to_path_buf(), rotation.clone());
+    thread::spawn(move || {
+        let _maintenance = LOG_MAINTENANCE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        maintain_log_segments(&segment_log, &rotation, now);
+    });
+
+    let file = open_append_no_follow(log_path, DEFAULT_FILE_MODE)?;
+    verify_regular_file(&file, log_path)?;
+    Ok(file)
+}
+
+fn format_utc_ts(ts: u64) -> String {
+    let days = (ts / 86_400) as i64;
+    let secs = ts % 86_400;
+    // Inverse of days_from_civil.
+    let shifted = days + 719_468;
+    let era = shifted.div_euclid(146_097);
+    let day_of_era = shifted - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+/// `denials.jsonl.20261019T120000Z`, with `-N` appended if that name (or its
+/// `.gz`) is taken.
+fn next_log_segment_path(log_path: &Path, now: u64) -> PathBuf {
+    let stamp = format_utc_ts(now).replace(['-', ':'], "");
+    let base = format!("{}.{}", log_path.display(), stamp);
+    let taken = |candidate: &str| {
+        fs::symlink_metadata(candidate).is_ok() || fs::symlink_metadata(format!("{}.gz", candidate)).is_ok()
+    };
+    let mut candidate = base.clone();
+    let mut suffix = 1;
+    while taken(&candidate) {
+        candidate = format!("{}-{}", base, suffix);
+        suffix += 1;
+    }
+    PathBuf::from(candidate)
+}
+
+/// Rotated segments of `log_path`, oldest first. A segment whose `.gz` is
+/// already complete is listed once, as the `.gz`.
+fn log_segment_paths(log_path: &Path) -> Vec<PathBuf> {
+    let (Some(dir), Some(name)) = (
+        log_path.parent(),
+        log_path.file_name().and_then(|name| name.to_str()),
+    ) else {
+        return Vec::new();
+    };
+    let prefix = format!("{}.", name);
+    let Ok(iter) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut stems: Vec<(String, bool)> = Vec::new();
+    for entry in iter.filter_map(Result::ok) {
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        let Some(stamp) = file_name.strip_prefix(&prefix) else {
+            continue;
         };
-        if let Some(entry) = cached {
-            return entry.decision.clone();
+        if stamp.is_empty() || stamp.ends_with(".tmp") || !entry.file_type().is_ok_and(|kind| kind.is_file()) {
+            continue;
+        }
+        match stamp.strip_suffix(".gz") {
+            Some(stem) => stems.push((stem.to_string(), true)),
+            None => stems.push((stamp.to_string(), false)),
         }
     }
+    // Gzipped first so a leftover plain copy of the same segment is skipped.
+    stems.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+    stems.dedup_by(|later, earlier| later.0 == earlier.0);
+    stems
+        .into_iter()
+        .map(|(stem, gzipped)| {
+            let suffix = if gzipped { ".gz" } else { "" };
+            dir.join(format!("{}{}{}", prefix, stem, suffix))
+        })
+        .collect()
+}
 
-    let executable_path = match get_process_path(pid) {
-        Some(path) => path,
-        None => {
-            return TrustedProcessDecision::IdentityMismatch(format!(
-                "unable to resolve executable path for pid {}",
-                pid
-            ))
-        },
+/// Finish interrupted compressions, compress plain segments when enabled and
+/// drop segments past the retention limits.
+fn maintain_log_segments(log_path: &Path, rotation: &LogRotationPolicy, now: u64) {
+    let Some(name) = log_path.file_name().and_then(|name| name.to_str()) else {
+        return;
     };
-    let decision = evaluate_trusted_process_from_path_nonblocking(
-        process_name,
-        &executable_path,
-        policy,
-        signature_cache,
-        signature_refresh_tx,
-    );
-    if let Some(key) = key {
-        if trust_decision_is_signature_pending(&decision) {
-            return decision;
+    let prefix = format!("{}.", name);
+    if let Some(Ok(iter)) = log_path.parent().map(fs::read_dir) {
+        for path in iter.filter_map(Result::ok).map(|entry| entry.path()) {
+            let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let Some(stamp) = file_name.strip_prefix(&prefix) else {
+                continue;
+            };
+            let superseded = !stamp.ends_with(".gz")
+                && fs::symlink_metadata(format!("{}.gz", path.display())).is_ok_and(|meta| meta.is_file());
+            if stamp.ends_with(".gz.tmp") || superseded {
+                let _ = fs::remove_file(&path);
+            }
+        }
+    }
+
+    let mut segments = log_segment_paths(log_path);
+    if rotation.compress {
+        for segment in segments.iter_mut() {
+            if segment.extension().is_some_and(|ext| ext == "gz") {
+                continue;
+            }
+            match compress_log_segment(segment) {
+                Ok(gzipped) => *segment = gzipped,
+                Err(err) => eprintln!("[log] cannot compress {}: {}", segment.display(), err),
+            }
+        }
+    }
+
+    let excess = segments.len().saturating_sub(rotation.keep_segments_or_default());
+    let cutoff = rotation
+        .keep_days
+        .map(|days| now.saturating_sub(days.saturating_mul(86_400)));
+    for (index, segment) in segments.iter().enumerate() {
+        let expired = cutoff.is_some_and(|cutoff| {
+            fs::symlink_metadata(segment)
+                .ok()
+                .and_then(|meta| meta.modified().ok())
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .is_some_and(|age| age.as_secs() < cutoff)
+        });
+        if index < excess || expired {
+            let _ = fs::remove_file(segment);
         }
-        let mut cache = trust_cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
-        cache.insert(key, decision.clone(), now);
     }
-    decision
 }
 
-fn is_vcs_tool(process_name: &str) -> bool {
-    matches!(process_name, "git" | "jj")
+/// Gzip `segment` into `<segment>.gz` via a synced temp file, then remove the
+/// original. An interrupted run leaves either the original or both copies.
+/// The guard dir is user-writable, so gzip reads a descriptor vetted here
+/// rather than a name that could be swapped for a hard link to another file.
+fn compress_log_segment(segment: &Path) -> io::Result<PathBuf> {
+    let input = open_read_no_follow(segment)?;
+    let meta = input.metadata()?;
+    if !meta.is_file() || meta.nlink() != 1 || meta.uid() != unsafe { libc::geteuid() } {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "not a single-link regular file owned by the daemon: {}",
+                segment.display()
+            ),
+        ));
+    }
+    let gzipped = PathBuf::from(format!("{}.gz", segment.display()));
+    let tmp_path = PathBuf::from(format!("{}.gz.tmp", segment.display()));
+    let _ = fs::remove_file(&tmp_path);
+    let output = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .custom_flags(libc::O_NOFOLLOW | libc::O_CLOEXEC)
+        .mode(DEFAULT_FILE_MODE)
+        .open(&tmp_path)?;
+    let status = Command::new(GZIP_BIN)
+        .arg("-c")
+        .stdin(input)
+        .stdout(output.try_clone()?)
+        .status();
+    if !status.as_ref().is_ok_and(|status| status.success()) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(io::Error::other(format!(
+            "{} exited with {:?}",
+            GZIP_BIN, status
+        )));
+    }
+    output.sync_all()?;
+    fs::rename(&tmp_path, &gzipped)?;
+    fs::remove_file(segment)?;
+    Ok(gzipped)
 }
 
-fn git_global_option_takes_value(option: &str) -> bool {
-    matches!(
-        option,
-        "-C" | "-c" | "--exec-path" | "--git-dir" | "--work-tree" | "--namespace" | "--super-prefix" | "--config-env"
-    )
+fn read_log_segment(segment: &Path) -> io::Result<String> {
+    if segment.extension().is_none_or(|ext| ext != "gz") {
+        return fs::read_to_string(segment);
+    }
+    let output = Command::new(GZIP_BIN).arg("-dc").arg(segment).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "{} -dc {} exited with {}",
+            GZIP_BIN,
+            segment.display(),
+            output.status
+        )));
+    }
+    String::from_utf8(output.stdout).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
 }
 
-fn git_subcommand_from_args(args: &[String]) -> Option<&str> {
-    if args.len() < 2 {
-        return None;
+/// A guard log with its rotated segments, oldest record first. Segments last
+/// written before `since` are skipped; pass 0 for the full history.
+fn read_guard_log(log_path: &Path, since: u64) -> io::Result<String> {
+    let mut content = String::new();
+    let mut found = false;
+    for segment in log_segment_paths(log_path) {
+        let modified = fs::symlink_metadata(&segment)
+            .and_then(|meta| meta.modified())
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map_or(u64::MAX, |age| age.as_secs());
+        if modified < since {
+            continue;
+        }
+        content.push_str(&read_log_segment(&segment)?);
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        found = true;
+    }
+    match fs::read_to_string(log_path) {
+        Ok(current) => content.push_str(&current),
+        Err(err) if err.kind() == io::ErrorKind::NotFound && found => {},
+        Err(err) => return Err(err),
     }
+    Ok(content)
+}
 
-    let mut idx = 1;
-    while idx < args.len() {
-        let arg = args[idx].as_str();
-        if arg == "--" {
-            idx += 1;
-            break;
+impl SessionStats {
+    fn start(&mut self, session_id: &str) {
+        if self.counters.len() < MAX_TRACKED_SESSIONS {
+            self.counters.entry(session_id.to_string()).or_default();
         }
+    }
 
-        if arg.starts_with("--") {
-            if let Some((opt, _)) = arg.split_once('=') {
-                if git_global_option_takes_value(opt) {
-                    idx += 1;
-                    continue;
+    fn count(&mut self, session_id: &str, bump: impl FnOnce(&mut SessionCounters)) {
+        if let Some(counters) = self.counters.get_mut(session_id) {
+            bump(counters);
+        } else if self.counters.len() < MAX_TRACKED_SESSIONS {
+            bump(self.counters.entry(session_id.to_string()).or_default());
+        }
+    }
+
+    fn finish(&mut self, session_id: &str) -> SessionCounters {
+        self.counters.remove(session_id).unwrap_or_default()
+    }
+}
+
+fn session_stats() -> &'static Mutex<SessionStats> {
+    SESSION_STATS.get_or_init(|| Mutex::new(SessionStats::default()))
+}
+
+fn count_session_activity(session_id: Option<&str>, bump: impl FnOnce(&mut SessionCounters)) {
+    let Some(session_id) = session_id else {
+        return;
+    };
+    let mut stats = session_stats().lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    stats.count(session_id, bump);
+}
+
+/// An override let `pid` through a protected-zone gate: charge the session it
+/// belongs to. Counted in memory; the session's end record carries the total.
+fn count_session_override_use(process_table: &ProcessTable, pid: i32) {
+    if let Some(session) = process_table.session_for_pid(pid) {
+        count_session_activity(Some(session.id.as_str()), |counters| {
+            counters.override_uses += 1
+        });
+    }
+}
+
+fn log_session_event_sync(home: &str, event: &SessionEvent) {
+    let line = {
+        let mut stats = session_stats().lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        match event {
+            SessionEvent::Start(session) => {
+                stats.start(&session.id);
+                println!(
+                    "[session] started {} (agent={}, pid={}, source={})",
+                    session.id, session.agent, session.pid, session.source
+                );
+                SessionLogLine {
+                    ts: now_ts(),
+                    event: "start",
+                    session,
+                    counters: None,
                 }
-            } else if git_global_option_takes_value(arg) {
-                idx = idx.saturating_add(2);
-                continue;
-            }
-            idx += 1;
-            continue;
+            },
+            SessionEvent::End(session, ts) => {
+                let counters = stats.finish(&session.id);
+                println!(
+                    "[session] ended {} (denials={}, audit_only={}, taint_marks={}, override_uses={})",
+                    session.id, counters.denials, counters.audit_only, counters.taint_marks, counters.override_uses
+                );
+                SessionLogLine {
+                    ts: *ts,
+                    event: "end",
+                    session,
+                    counters: Some(counters),
+                }
+            },
         }
+    };
 
-        if arg.starts_with('-') {
-            if git_global_option_takes_value(arg) {
-                idx = idx.saturating_add(2);
-            } else {
-                idx += 1;
-            }
-            continue;
+    let guard_dir = match ensure_guard_dirs(home) {
+        Ok(dir) => dir,
+        Err(err) => {
+            eprintln!("[session] cannot use guard dir: {}", err);
+            return;
+        },
+    };
+    let log_path = guard_dir.join("sessions.jsonl");
+    let Ok(json) = serde_json::to_string(&line) else {
+        return;
+    };
+    match open_rotating_log(
+        &log_path,
+        SESSION_LOG_MAX_BYTES,
+        &log_rotation_policy(),
+        now_ts(),
+        || {},
+    ) {
+        Ok(mut file) => {
+            let _ = writeln!(file, "{}", json);
+        },
+        Err(err) => eprintln!("[session] cannot append to {}: {}", log_path.display(), err),
+    }
+}
+
+/// Guidance appended to denial feedback, keyed by reason (`*` for any other
+/// reason) and locale. Overridable per reason, agent and locale through
+/// `guard/feedback-templates.json`.
+const BUILTIN_FEEDBACK_TEMPLATES: &[(&str, &str, &str)] = &[
+    (
+        REASON_SENSITIVE_READ_NON_AI,
+        "en",
+        "Recommended next step:\n\
+         - Sensitive read gate: non-AI reads are denied.\n\
+         - Retry from AI context (Codex/Claude ancestor).\n\
+         - If human review is required, export reviewed data only into: {quarantine_dir}\n",
+    ),
+    (
+        REASON_SENSITIVE_TRANSFER_OUT,
+        "en",
+        "Recommended next step:\n\
+         - Sensitive export is blocked outside allow-zones.\n\
+         - Move/copy only into quarantine allow-zone: {quarantine_dir}\n\
+         - Example: mv {path} {quarantine_dir}/\n",
+    ),
+    (
+        REASON_TAINT_WRITE_OUT,
+        "en",
+        "Recommended next step:\n\
+         - This process is tainted by a prior sensitive read.\n\
+         - Write outputs only into: {quarantine_dir}\n\
+         - Or retry after taint TTL expires.\n",
+    ),
+    (
+        REASON_EXEC_EXFIL_TOOL,
+        "en",
+        "Recommended next step:\n\
+         - Exfil tooling is blocked in AI context.\n\
+         - Keep processing inside allowed local zones or approved channels.\n",
+    ),
+    (
+        REASON_PROTECTED_ZONE_AI_TRUNCATE,
+        "en",
+        "Recommended next step:\n\
+         - Truncating/overwriting existing files in this protected zone is blocked in AI context.\n\
+         - Ask the user to perform the overwrite, or edit the file in place without truncating it.\n\
+         - If the overwrite is intended, request a short override:\n\
+         {override_command}\n",
+    ),
+    (
+        REASON_PROTECTED_ZONE_AI_METADATA,
+        "en",
+        "Recommended next step:\n\
+         - Changing permissions/flags/ACL/xattrs/owner/timestamps in a protected zone is blocked in AI context.\n\
+         - Ask the user to apply the metadata change ({op}) manually.\n\
+         - If it is required for the task, request a short override:\n\
+         {override_command}\n",
+    ),
+    (
+        REASON_TRUST_IDENTITY_MISMATCH,
+        "en",
+        "Recommended next step:\n\
+         - Trusted process identity verification failed.\n\
+         - Check `trusted_tool_identities` for exact path + signing identity.\n\
+         - If high-security mode is on, ensure cdhash is pinned correctly.\n\
+         - Current process: {process}\n",
+    ),
+    (
+        REASON_OVERRIDE_PIN_MISMATCH,
+        "en",
+        "Recommended next step:\n\
+         - The override for this file was pinned to its content at approval time, and the file has changed since.\n\
+         - Do not retry: ask the user to review the current file and approve a new pinned override:\n\
+         {override_command} --pin\n",
+    ),
+    (
+        "*",
+        "en",
+        "Recommended (safer first step): agentsmith-quarantine {path}\n\
+         This moves the target into ./temp under your CURRENT working directory.\n\
+         \n\
+         If you must permanently delete via AI, request one-time override with TTL:\n\
+         {override_command}\n\
+         (Short TTL only; no-expire is rejected by helper)\n\
+         Then retry the operation.\n",
+    ),
+    (
+        REASON_SENSITIVE_READ_NON_AI,
+        "zh",
+        "建议下一步：\n\
+         - 敏感读取闸门：非 AI 进程的读取会被拒绝。\n\
+         - 请在 AI 上下文中重试（祖先进程为 Codex/Claude）。\n\
+         - 如需人工审阅，只把审阅后的数据导出到：{quarantine_dir}\n",
+    ),
+    (
+        REASON_SENSITIVE_TRANSFER_OUT,
+        "zh",
+        "建议下一步：\n\
+         - 敏感数据不能导出到允许区之外。\n\
+         - 只能移动/复制到隔离允许区：{quarantine_dir}\n\
+         - 示例：mv {path} {quarantine_dir}/\n",
+    ),
+    (
+        REASON_TAINT_WRITE_OUT,
+        "zh",
+        "建议下一步：\n\
+         - 该进程此前读取过敏感文件，已被标记。\n\
+         - 输出只能写入：{quarantine_dir}\n\
+         - 或等标记过期后再重试。\n",
+    ),
+    (
+        REASON_EXEC_EXFIL_TOOL,
+        "zh",
+        "建议下一步：\n\
+         - AI 上下文中禁止使用外传工具。\n\
+         - 请在允许的本地区域或经批准的渠道内处理数据。\n",
+    ),
+    (
+        REASON_PROTECTED_ZONE_AI_TRUNCATE,
+        "zh",
+        "建议下一步：\n\
+         - AI 上下文中禁止截断/覆盖该保护区内的已有文件。\n\
+         - 请用户执行覆盖，或在不截断的前提下原地编辑文件。\n\
+         - 若确实需要覆盖，请用户申请短时放行：\n\
+         {override_command}\n",
+    ),
+    (
+        REASON_PROTECTED_ZONE_AI_METADATA,
+        "zh",
+        "建议下一步：\n\
+         - AI 上下文中禁止修改保护区内文件的权限/标志/ACL/扩展属性/属主/时间戳。\n\
+         - 请用户手动执行该元数据修改（{op}）。\n\
+         - 若任务确实需要，请用户申请短时放行：\n\
+         {override_command}\n",
+    ),
+    (
+        REASON_TRUST_IDENTITY_MISMATCH,
+        "zh",
+        "建议下一步：\n\
+         - 受信进程身份校验失败。\n\
+         - 检查 `trusted_tool_identities` 中的路径与签名身份是否完全一致。\n\
+         - 若开启了高安全模式，确认 cdhash 固定值正确。\n\
+         - 当前进程：{process}\n",
+    ),
+    (
+        REASON_OVERRIDE_PIN_MISMATCH,
+        "zh",
+        "建议下一步：\n\
+         - 该文件的放行在批准时已绑定文件内容，此后文件已被替换或修改。\n\
+         - 不要直接重试：请用户检查当前文件后重新批准一个绑定内容的放行：\n\
+         {override_command} --pin\n",
+    ),
+    (
+        "*",
+        "zh",
+        "建议（更安全的第一步）：agentsmith-quarantine {path}\n\
+         该命令把目标移到当前工作目录下的 ./temp。\n\
+         \n\
+         如果确实需要由 AI 永久删除，请申请带 TTL 的一次性放行：\n\
+         {override_command}\n\
+         （只允许短 TTL；helper 拒绝永不过期的放行）\n\
+         然后重试该操作。\n",
+    ),
+];
+
+const FEEDBACK_TEMPLATE_DEFAULT_LOCALE: &str = "en";
+const FEEDBACK_TEMPLATE_MAX_BYTES: u64 = 256 * 1024;
+
+/// One entry of `feedback-templates.json`. Omitted `agent` / `locale` match any.
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+struct FeedbackTemplate {
+    reason: String,
+    #[serde(default)]
+    agent: Option<String>,
+    #[serde(default)]
+    locale: Option<String>,
+    text: String,
+}
+
+/// `~/.agentsmith-rs/guard/feedback-templates.json`: lets denial guidance be
+/// tuned without rebuilding. Missing reasons fall back to the built-in text.
+#[derive(Debug, Deserialize, Clone, Default, PartialEq, Eq)]
+struct FeedbackTemplateConfig {
+    #[serde(default)]
+    locale: Option<String>,
+    #[serde(default)]
+    agent_locales: HashMap<String, String>,
+    #[serde(default)]
+    templates: Vec<FeedbackTemplate>,
+}
+
+type FeedbackTemplateCache = Option<(SystemTime, Arc<FeedbackTemplateConfig>)>;
+
+fn feedback_templates_path(home: &str) -> PathBuf {
+    PathBuf::from(home)
+        .join(".agentsmith-rs")
+        .join("guard")
+        .join("feedback-templates.json")
+}
+
+fn load_feedback_template_config(path: &Path) -> io::Result<FeedbackTemplateConfig> {
+    let mut file = open_read_no_follow(path)?;
+    verify_regular_file(&file, path)?;
+    if file.metadata()?.len() > FEEDBACK_TEMPLATE_MAX_BYTES {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "feedback templates file too large",
+        ));
+    }
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+    serde_json::from_str(&content).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))
+}
+
+/// Template config for `home`, re-read only when the file's mtime changes.
+/// A broken file keeps the built-in text rather than failing the denial log.
+fn feedback_template_config(home: &str) -> Arc<FeedbackTemplateConfig> {
+    static CACHE: OnceLock<Mutex<FeedbackTemplateCache>> = OnceLock::new();
+    let path = feedback_templates_path(home);
+    let Ok(mtime) = fs::metadata(&path).and_then(|meta| meta.modified()) else {
+        return Arc::default();
+    };
+    let mut cache = CACHE
+        .get_or_init(|| Mutex::new(None))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if let Some((cached_mtime, config)) = cache.as_ref() {
+        if *cached_mtime == mtime {
+            return config.clone();
+        }
+    }
+    let config = match load_feedback_template_config(&path) {
+        Ok(config) => Arc::new(config),
+        Err(err) => {
+            eprintln!("[feedback] ignoring {}: {}", path.display(), err);
+            Arc::default()
+        },
+    };
+    *cache = Some((mtime, config.clone()));
+    config
+}
+
+/// Agent the feedback is addressed to: the session's agent, else the AI ancestor.
+fn denial_feedback_agent(record: &DenialRecord) -> String {
+    record
+        .session
+        .as_ref()
+        .map(|session| session.agent.clone())
+        .unwrap_or_else(|| record.ancestor.to_ascii_lowercase())
+}
+
+impl FeedbackTemplateConfig {
+    fn locale_for(&self, agent: &str) -> &str {
+        self.agent_locales
+            .get(agent)
+            .or(self.locale.as_ref())
+            .map(String::as_str)
+            .unwrap_or(FEEDBACK_TEMPLATE_DEFAULT_LOCALE)
+    }
+
+    /// Most specific configured template: exact reason beats `*`, then a
+    /// matching agent, then a matching locale.
+    fn select(&self, reason: &str, agent: &str, locale: &str) -> Option<&str> {
+        self.templates
+            .iter()
+            .filter(|template| template.reason == reason || template.reason == "*")
+            .filter(|template| template.agent.as_deref().is_none_or(|value| value == agent))
+            .filter(|template| template.locale.as_deref().is_none_or(|value| value == locale))
+            .max_by_key(|template| {
+                (
+                    template.reason == reason,
+                    template.agent.is_some(),
+                    template.locale.is_some(),
+                )
+            })
+            .map(|template| template.text.as_str())
+    }
+}
+
+fn builtin_feedback_template(reason: &str, locale: &str) -> &'static str {
+    let lookup = |reason: &str, locale: &str| {
+        BUILTIN_FEEDBACK_TEMPLATES
+            .iter()
+            .find(|(r, l, _)| *r == reason && *l == locale)
+            .map(|(_, _, text)| *text)
+    };
+    lookup(reason, locale)
+        .or_else(|| lookup("*", locale))
+        .or_else(|| lookup(reason, FEEDBACK_TEMPLATE_DEFAULT_LOCALE))
+        .or_else(|| lookup("*", FEEDBACK_TEMPLATE_DEFAULT_LOCALE))
+        .unwrap_or_default()
+}
+
+/// Substitute `{name}` placeholders; unknown names are left untouched so a
+/// typo in a template shows up in the output instead of vanishing.
+fn render_feedback_template(template: &str, vars: &[(&str, String)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('}').and_then(|end| {
+            let name = &after[..end];
+            vars.iter().find(|(key, _)| *key == name).map(|(_, value)| (end, value))
+        }) {
+            Some((end, value)) => {
+                out.push_str(value);
+                rest = &after[end + 1..];
+            },
+            None => {
+                out.push('{');
+                rest = after;
+            },
         }
+    }
+    out.push_str(rest);
+    out
+}
 
-        return Some(arg);
+fn build_denial_feedback(home: &str, record: &DenialRecord) -> String {
+    build_denial_feedback_with(home, record, &feedback_template_config(home))
+}
+
+fn build_denial_feedback_with(home: &str, record: &DenialRecord, config: &FeedbackTemplateConfig) -> String {
+    let dest_info = record
+        .dest
+        .as_deref()
+        .map(|d| format!("\nDest: {}", d))
+        .unwrap_or_default();
+    let header = format!(
+        "[AGENTSMITH DENIED]\n\
+         Operation: {}\n\
+         Reason: {}\n\
+         Path: {}{}\n\
+         Zone: {}\n\
+         Process: {} (via {})\n{}{}{}\
+         \n",
+        record.op,
+        record.reason,
+        record.path,
+        dest_info,
+        record.zone,
+        record.process,
+        record.ancestor,
+        record.pid.map(|pid| format!("Pid: {}\n", pid)).unwrap_or_default(),
+        record.ppid.map(|ppid| format!("PPid: {}\n", ppid)).unwrap_or_default(),
+        record
+            .session
+            .as_ref()
+            .map(|session| format!(
+                "Session: {} (agent {}, cwd {})\n",
+                session.id, session.agent, session.cwd
+            ))
+            .unwrap_or_default(),
+    );
+
+    let agent = denial_feedback_agent(record);
+    let locale = config.locale_for(&agent);
+    let template = config
+        .select(&record.reason, &agent, locale)
+        .unwrap_or_else(|| builtin_feedback_template(&record.reason, locale));
+    let vars = [
+        ("path", record.path.clone()),
+        ("dest", record.dest.clone().unwrap_or_default()),
+        ("zone", record.zone.clone()),
+        ("op", record.op.clone()),
+        ("reason", record.reason.clone()),
+        ("process", record.process.clone()),
+        ("ancestor", record.ancestor.clone()),
+        ("agent", agent.clone()),
+        (
+            "session",
+            record
+                .session
+                .as_ref()
+                .map(|session| session.id.clone())
+                .unwrap_or_default(),
+        ),
+        (
+            "cwd",
+            record
+                .session
+                .as_ref()
+                .map(|session| session.cwd.clone())
+                .unwrap_or_default(),
+        ),
+        (
+            "quarantine_dir",
+            format!("{}/.agentsmith-rs/guard/quarantine", home),
+        ),
+        ("override_minutes", OVERRIDE_DEFAULT_MINUTES.to_string()),
+        (
+            "override_command",
+            format!(
+                "agentsmith-override --minutes {} {}",
+                OVERRIDE_DEFAULT_MINUTES, record.path
+            ),
+        ),
+    ];
+
+    format!("{}{}", header, render_feedback_template(template, &vars))
+}
+
+fn now_ts() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+// --- Process tree walking (macOS) ---
+
+fn get_process_path(pid: i32) -> Option<String> {
+    let mut buf = vec![0u8; 4096];
+    let ret = unsafe { libc::proc_pidpath(pid, buf.as_mut_ptr() as *mut libc::c_void, buf.len() as u32) };
+    if ret > 0 {
+        buf.truncate(ret as usize);
+        String::from_utf8(buf).ok()
+    } else {
+        None
+    }
+}
+
+/// Get the process argv[0] via sysctl KERN_PROCARGS2.
+/// This reflects process.title changes (e.g., Node.js setting title to "claude").
+fn parse_procargs_argv(buf: &[u8]) -> Vec<String> {
+    if buf.len() < 4 {
+        return Vec::new();
+    }
+    let argc_raw = i32::from_ne_bytes([buf[0], buf[1], buf[2], buf[3]]);
+    if argc_raw <= 0 {
+        return Vec::new();
+    }
+    let argc = argc_raw as usize;
+
+    // Layout: argc(i32) + exec_path + \0 + padding(\0s) + argv[0] + \0 + argv[1] + ...
+    let mut pos = 4;
+    while pos < buf.len() && buf[pos] != 0 {
+        pos += 1;
+    }
+    while pos < buf.len() && buf[pos] == 0 {
+        pos += 1;
+    }
+
+    let mut args: Vec<String> = Vec::with_capacity(argc);
+    while pos < buf.len() && args.len() < argc {
+        while pos < buf.len() && buf[pos] == 0 {
+            pos += 1;
+        }
+        if pos >= buf.len() {
+            break;
+        }
+
+        let start = pos;
+        while pos < buf.len() && buf[pos] != 0 {
+            pos += 1;
+        }
+        if start < pos {
+            args.push(String::from_utf8_lossy(&buf[start..pos]).to_string());
+        }
+    }
+
+    args
+}
+
+fn get_process_argv(pid: i32) -> Option<Vec<String>> {
+    let mut mib = [libc::CTL_KERN, libc::KERN_PROCARGS2, pid];
+    let mut size: libc::size_t = 0;
+
+    // First call: get buffer size
+    let ret = unsafe {
+        libc::sysctl(
+            mib.as_mut_ptr(),
+            3,
+            std::ptr::null_mut(),
+            &mut size,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if ret != 0 || size == 0 {
+        return None;
+    }
+
+    let mut buf = vec![0u8; size];
+    let ret = unsafe {
+        libc::sysctl(
+            mib.as_mut_ptr(),
+            3,
+            buf.as_mut_ptr() as *mut libc::c_void,
+            &mut size,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if ret != 0 || size < 8 {
+        return None;
+    }
+
+    buf.truncate(size);
+    let args = parse_procargs_argv(&buf);
+    if args.is_empty() {
+        None
+    } else {
+        Some(args)
+    }
+}
+
+fn get_process_argv0(pid: i32) -> Option<String> {
+    get_process_argv(pid).and_then(|args| {
+        args.first()
+            .map(|argv0| argv0.rsplit('/').next().unwrap_or(argv0.as_str()).to_string())
+    })
+}
+
+fn argv_runs_helper(args: &[String], helper: &str) -> bool {
+    args.iter().any(|arg| {
+        Path::new(arg)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name == helper)
+    })
+}
+
+fn is_override_helper_argv(args: &[String]) -> bool {
+    argv_runs_helper(args, "agentsmith-override")
+}
+
+fn is_override_helper_process(pid: i32) -> bool {
+    let args = match get_process_argv(pid) {
+        Some(args) => args,
+        None => return false,
+    };
+    is_override_helper_argv(&args)
+}
+
+/// Query proc_bsdinfo for a process. Returns (ppid, comm_name).
+#[repr(C)]
+struct ProcBsdInfo {
+    pbi_flags: u32,
+    pbi_status: u32,
+    pbi_xstatus: u32,
+    pbi_pid: u32,
+    pbi_ppid: u32,
+    pbi_uid: libc::uid_t,
+    pbi_gid: libc::gid_t,
+    pbi_ruid: libc::uid_t,
+    pbi_rgid: libc::gid_t,
+    pbi_svuid: libc::uid_t,
+    pbi_svgid: libc::gid_t,
+    rfu_1: u32,
+    pbi_comm: [libc::c_char; MAXCOMLEN],
+    pbi_name: [libc::c_char; MAXCOMLEN * 2],
+    pbi_nfiles: u32,
+    pbi_pgid: u32,
+    pbi_pjobc: u32,
+    e_tdev: u32,
+    e_tpgid: u32,
+    pbi_nice: i32,
+    pbi_start_tvsec: u64,
+    pbi_start_tvusec: u64,
+}
+
+fn c_char_buf_to_string(buf: &[libc::c_char]) -> String {
+    let bytes: Vec<u8> = buf
+        .iter()
+        .copied()
+        .take_while(|value| *value != 0)
+        .map(|value| value as u8)
+        .collect();
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+fn get_process_bsd_info(pid: i32) -> Option<ProcBsdInfo> {
+    const PROC_PIDTBSDINFO: i32 = 3;
+    let mut info: ProcBsdInfo = unsafe { std::mem::zeroed() };
+    let info_size = std::mem::size_of::<ProcBsdInfo>() as i32;
+    let ret = unsafe {
+        libc::proc_pidinfo(
+            pid,
+            PROC_PIDTBSDINFO,
+            0,
+            &mut info as *mut ProcBsdInfo as *mut libc::c_void,
+            info_size,
+        )
+    };
+    if ret == info_size {
+        Some(info)
+    } else {
+        None
+    }
+}
+
+fn get_process_info(pid: i32) -> Option<(i32, String)> {
+    let info = get_process_bsd_info(pid)?;
+    let ppid_raw = info.pbi_ppid as i32;
+    let ppid = if ppid_raw > 0 && ppid_raw != pid { ppid_raw } else { 0 };
+    let comm = c_char_buf_to_string(&info.pbi_comm);
+    Some((ppid, comm))
+}
+
+fn process_cwd_for_pid(pid: i32) -> Option<String> {
+    let mut info: libc::proc_vnodepathinfo = unsafe { std::mem::zeroed() };
+    let info_size = std::mem::size_of::<libc::proc_vnodepathinfo>() as i32;
+    let ret = unsafe {
+        libc::proc_pidinfo(
+            pid,
+            libc::PROC_PIDVNODEPATHINFO,
+            0,
+            &mut info as *mut libc::proc_vnodepathinfo as *mut libc::c_void,
+            info_size,
+        )
+    };
+    if ret != info_size {
+        return None;
+    }
+    let cwd = c_char_buf_to_string(info.pvi_cdir.vip_path.as_flattened());
+    (!cwd.is_empty()).then_some(cwd)
+}
+
+fn tty_name_from_bsd_info(info: &ProcBsdInfo) -> Option<String> {
+    // NODEV: the process has no controlling terminal.
+    if info.e_tdev == u32::MAX {
+        return None;
+    }
+    let name = unsafe { libc::devname(info.e_tdev as libc::dev_t, libc::S_IFCHR) };
+    if name.is_null() {
+        return None;
+    }
+    let name = unsafe { std::ffi::CStr::from_ptr(name) }.to_string_lossy().into_owned();
+    (!name.is_empty() && name != "??").then(|| format!("/dev/{}", name))
+}
+
+fn process_tty_for_pid(pid: i32) -> Option<String> {
+    get_process_bsd_info(pid).as_ref().and_then(tty_name_from_bsd_info)
+}
+
+fn parent_pid_for_pid(pid: i32) -> Option<i32> {
+    get_process_info(pid).map(|(ppid, _)| ppid).filter(|ppid| *ppid > 0)
+}
+
+fn pid_for_record(pid: i32) -> Option<i32> {
+    (pid > 0).then_some(pid)
+}
+
+fn process_start_time_for_pid(pid: i32) -> Option<ProcessStartTimeKey> {
+    let info = get_process_bsd_info(pid)?;
+    Some(ProcessStartTimeKey {
+        start_tvsec: info.pbi_start_tvsec,
+        start_tvusec: info.pbi_start_tvusec,
+    })
+}
+
+fn process_identity_key(pid: i32) -> Option<ProcessIdentityKey> {
+    let info = get_process_bsd_info(pid)?;
+    let executable_path = get_process_path(pid)?;
+    Some(ProcessIdentityKey {
+        pid,
+        start_tvsec: info.pbi_start_tvsec,
+        start_tvusec: info.pbi_start_tvusec,
+        executable_path,
+    })
+}
+
+fn exe_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+fn list_all_pids() -> Vec<i32> {
+    let count = unsafe { libc::proc_listallpids(std::ptr::null_mut(), 0) };
+    if count <= 0 {
+        return Vec::new();
+    }
+    // Leave headroom for processes spawned between the two calls.
+    let mut pids = vec![0i32; count as usize + 64];
+    let buffer_size = (pids.len() * std::mem::size_of::<i32>()) as i32;
+    let filled = unsafe { libc::proc_listallpids(pids.as_mut_ptr() as *mut libc::c_void, buffer_size) };
+    if filled <= 0 {
+        return Vec::new();
+    }
+    pids.truncate(filled as usize);
+    pids.retain(|pid| *pid > 0);
+    pids
+}
+
+fn process_start_key_from_system_time(start: SystemTime) -> Option<ProcessStartTimeKey> {
+    let elapsed = start.duration_since(UNIX_EPOCH).ok()?;
+    Some(ProcessStartTimeKey {
+        start_tvsec: elapsed.as_secs(),
+        start_tvusec: elapsed.subsec_micros() as u64,
+    })
+}
+
+/// Translate a path glob into an anchored regex: `**` crosses directories,
+/// `*` and `?` stay within one path component.
+fn glob_to_regex(glob: &str) -> String {
+    let mut pattern = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                pattern.push_str(".*");
+            },
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            other => pattern.push_str(&regex_lite::escape(other.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    pattern.push('$');
+    pattern
+}
+
+impl AgentDetectorRule {
+    fn has_criteria(&self) -> bool {
+        self.exe_exact.is_some()
+            || self.exe_glob.is_some()
+            || self.exe_regex.is_some()
+            || self.argv0.is_some()
+            || self.argv_marker.is_some()
+            || !self.env.is_empty()
+            || self.signing_id.is_some()
+            || self.team_id.is_some()
+    }
+
+    fn exe_matchers(&self) -> &Result<Vec<regex_lite::Regex>, String> {
+        self.exe_matchers.get_or_init(|| {
+            let mut matchers = Vec::new();
+            if let Some(glob) = self.exe_glob.as_deref() {
+                let regex = regex_lite::Regex::new(&glob_to_regex(glob))
+                    .map_err(|err| format!("invalid exe_glob {:?}: {}", glob, err))?;
+                matchers.push(regex);
+            }
+            if let Some(pattern) = self.exe_regex.as_deref() {
+                let regex = regex_lite::Regex::new(pattern)
+                    .map_err(|err| format!("invalid exe_regex {:?}: {}", pattern, err))?;
+                matchers.push(regex);
+            }
+            Ok(matchers)
+        })
+    }
+
+    fn validation_error(&self) -> Option<String> {
+        if !self.has_criteria() {
+            return Some("rule has no match criteria".to_string());
+        }
+        self.exe_matchers().as_ref().err().cloned()
+    }
+
+    fn matches(&self, executable_path: &str, argv0: Option<&str>, args: &[String], evidence: &ExecEvidence) -> bool {
+        if !self.has_criteria() {
+            return false;
+        }
+        if self.exe_exact.as_deref().is_some_and(|exact| exact != executable_path) {
+            return false;
+        }
+        match self.exe_matchers() {
+            Ok(matchers) if matchers.iter().all(|regex| regex.is_match(executable_path)) => {},
+            _ => return false,
+        }
+        if let Some(expected) = self.argv0.as_deref() {
+            let matched =
+                argv0.is_some_and(|value| value == expected || value.rsplit('/').next().unwrap_or(value) == expected);
+            if !matched {
+                return false;
+            }
+        }
+        if let Some(marker) = self.argv_marker.as_deref() {
+            let matched = args
+                .iter()
+                .skip(1)
+                .any(|arg| arg == marker || arg.rsplit('/').next() == Some(marker));
+            if !matched {
+                return false;
+            }
+        }
+        let env_matched = self.env.iter().all(|marker| match marker.split_once('=') {
+            Some(_) => evidence.env.iter().any(|entry| entry == marker),
+            None => evidence
+                .env
+                .iter()
+                .any(|entry| entry.split('=').next() == Some(marker.as_str())),
+        });
+        if !env_matched {
+            return false;
+        }
+        if self
+            .signing_id
+            .as_deref()
+            .is_some_and(|expected| expected != evidence.signing_id)
+        {
+            return false;
+        }
+        if self
+            .team_id
+            .as_deref()
+            .is_some_and(|expected| expected != evidence.team_id)
+        {
+            return false;
+        }
+        true
+    }
+
+    fn label_for(&self, executable_path: &str) -> String {
+        self.label.clone().filter(|label| !label.is_empty()).unwrap_or_else(|| {
+            if self.name.is_empty() {
+                exe_name(executable_path).to_string()
+            } else {
+                self.name.clone()
+            }
+        })
+    }
+}
+
+/// Detect an AI agent from detector rules (highest confidence wins, first rule
+/// on ties) and fall back to the low-confidence `ai_agent_patterns` substring
+/// match. Matches below `ai_agent_min_confidence` are ignored.
+fn detect_ai_agent(
+    policy: &SecurityPolicy,
+    executable_path: &str,
+    argv0: Option<&str>,
+    args: &[String],
+    evidence: &ExecEvidence,
+) -> Option<AgentDetection> {
+    let mut best: Option<&AgentDetectorRule> = None;
+    for rule in policy.effective_detector_rules() {
+        if rule.confidence < policy.ai_agent_min_confidence || !rule.matches(executable_path, argv0, args, evidence) {
+            continue;
+        }
+        if best.is_none_or(|current| rule.confidence > current.confidence) {
+            best = Some(rule);
+        }
+    }
+    if let Some(rule) = best {
+        return Some(AgentDetection {
+            label: rule.label_for(executable_path),
+            rule: if rule.name.is_empty() {
+                "unnamed".to_string()
+            } else {
+                rule.name.clone()
+            },
+            confidence: rule.confidence,
+        });
+    }
+    if policy.ai_agent_min_confidence > DetectorConfidence::Low {
+        return None;
+    }
+    ai_agent_label(policy, executable_path, argv0).map(|label| AgentDetection {
+        label,
+        rule: "ai_agent_patterns".to_string(),
+        confidence: DetectorConfidence::Low,
+    })
+}
+
+/// Label a process as an AI agent by executable name or argv[0] (which reflects
+/// `process.title`, e.g. Node.js "claude").
+fn ai_agent_label(policy: &SecurityPolicy, executable_path: &str, argv0: Option<&str>) -> Option<String> {
+    let argv0 = argv0.map(|value| value.rsplit('/').next().unwrap_or(value));
+    let argv0_match = argv0.is_some_and(|value| {
+        policy
+            .ai_agent_patterns
+            .iter()
+            .any(|pattern| value.contains(pattern.as_str()))
+    });
+    if argv0_match {
+        return argv0.map(str::to_string);
+    }
+    if !executable_path.is_empty() && policy.matches_ai_agent(executable_path) {
+        return Some(exe_name(executable_path).to_string());
+    }
+    None
+}
+
+/// Helper services that start work outside the caller's process tree. Returns
+/// the process expected to spawn the work and, when the command line names it,
+/// the program that will run. `nohup`/`setsid`/double-fork need no entry: the
+/// table already carries lineage across fork and exec.
+fn launcher_handoff_target(executable_path: &str, argv: &[String]) -> Option<(&'static str, Option<String>)> {
+    let args = argv.get(1..).unwrap_or_default();
+    match exe_name(executable_path) {
+        "launchctl" => {
+            if args.first().map(String::as_str) != Some("submit") {
+                return None;
+            }
+            let program = args.iter().skip_while(|arg| arg.as_str() != "--").nth(1)?;
+            Some(("launchd", Some(exe_name(program).to_string())))
+        },
+        "open" => {
+            let app = match args.iter().position(|arg| arg == "-a") {
+                Some(idx) => args.get(idx + 1)?,
+                None => args.iter().find(|arg| arg.trim_end_matches('/').ends_with(".app"))?,
+            };
+            let app = exe_name(app.trim_end_matches('/'));
+            Some(("launchd", Some(app.trim_end_matches(".app").to_string())))
+        },
+        "at" | "batch" => {
+            let query = args.iter().any(|arg| matches!(arg.as_str(), "-l" | "-r" | "-d" | "-c"));
+            (!query).then_some(("atrun", None))
+        },
+        "tmux" => {
+            const SPAWNING_COMMANDS: &[&str] = &[
+                "new-session",
+                "new",
+                "new-window",
+                "neww",
+                "split-window",
+                "splitw",
+                "respawn-pane",
+                "respawnp",
+                "respawn-window",
+                "respawnw",
+                "run-shell",
+                "run",
+            ];
+            args.iter()
+                .any(|arg| SPAWNING_COMMANDS.contains(&arg.as_str()))
+                .then_some(("tmux", None))
+        },
+        _ => None,
+    }
+}
+
+/// Look up the AI agent lineage of a process in the process table.
+/// Returns the matching AI ancestor label, or None for user processes.
+fn find_ai_ancestor(pid: i32, policy: &SecurityPolicy, process_table: &mut ProcessTable) -> Option<String> {
+    process_table.ai_lineage_for_pid(pid, policy, now_ts())
+}
+
+fn process_name_for_pid(pid: i32) -> Option<String> {
+    get_process_path(pid).map(|path| exe_name(&path).to_string())
+}
+
+fn canonicalize_executable_path_strict(executable_path: &str) -> Result<String, String> {
+    let normalized = normalize_absolute_path(executable_path)
+        .ok_or_else(|| format!("executable path is not absolute: {}", executable_path))?;
+    let metadata =
+        fs::symlink_metadata(&normalized).map_err(|err| format!("cannot stat executable {}: {}", normalized, err))?;
+    if metadata.file_type().is_symlink() {
+        return Err(format!(
+            "symbolic-link executable path is rejected: {}",
+            normalized
+        ));
+    }
+    let canonical = fs::canonicalize(&normalized)
+        .map_err(|err| format!("cannot canonicalize executable {}: {}", normalized, err))?;
+    let canonical_text = canonical.to_string_lossy().to_string();
+    if canonical_text != normalized {
+        return Err(format!(
+            "executable path must be canonical (got {}, canonical {})",
+            normalized, canonical_text
+        ));
+    }
+    Ok(canonical_text)
+}
+
+fn codesign_field(report: &str, key: &str) -> Option<String> {
+    report
+        .lines()
+        .find_map(|line| line.strip_prefix(key).map(|value| value.trim().to_string()))
+}
+
+fn read_binary_signature(executable_path: &str) -> Result<BinaryCodeSignature, String> {
+    let output = Command::new(CODESIGN_BIN)
+        .args(["-dv", "--verbose=4", executable_path])
+        .output()
+        .map_err(|err| format!("failed to run codesign: {}", err))?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let report = format!("{}\n{}", stdout, stderr);
+    if !output.status.success() {
+        return Err(format!(
+            "codesign verification failed for {}: {}",
+            executable_path,
+            report.trim()
+        ));
+    }
+
+    let signing_identifier = codesign_field(&report, "Identifier=")
+        .ok_or_else(|| format!("missing signing identifier for {}", executable_path))?;
+    let team_identifier = match codesign_field(&report, "TeamIdentifier=") {
+        Some(value) if value == "not set" || value.is_empty() => None,
+        Some(value) => Some(value),
+        None => None,
+    };
+    let cdhash = codesign_field(&report, "CDHash=").map(|value| value.to_lowercase());
+
+    Ok(BinaryCodeSignature {
+        signing_identifier,
+        team_identifier,
+        cdhash,
+    })
+}
+
+fn normalized_expected_trusted_identity_path(entry: &TrustedToolIdentity) -> Option<String> {
+    normalize_absolute_path(entry.path.as_str()).map(|path| trim_trailing_slashes(&path).to_string())
+}
+
+fn trusted_identity_decision_from_signature(
+    process_name: &str,
+    canonical_path: &str,
+    actual_signature: &BinaryCodeSignature,
+    expected_identities: &[&TrustedToolIdentity],
+    require_cdhash: bool,
+) -> TrustedProcessDecision {
+    let matched = expected_identities.iter().any(|entry| {
+        let expected_path = match normalized_expected_trusted_identity_path(entry) {
+            Some(path) => path,
+            None => return false,
+        };
+        if expected_path != canonical_path {
+            return false;
+        }
+        if entry.signing_identifier != actual_signature.signing_identifier {
+            return false;
+        }
+        if entry.team_identifier != actual_signature.team_identifier {
+            return false;
+        }
+
+        let expected_cdhash = entry.cdhash.as_ref().map(|value| value.to_lowercase());
+        if require_cdhash && expected_cdhash.is_none() {
+            return false;
+        }
+        if let Some(expected) = expected_cdhash {
+            if actual_signature.cdhash.as_deref() != Some(expected.as_str()) {
+                return false;
+            }
+        }
+
+        true
+    });
+    if matched {
+        return TrustedProcessDecision::Trusted;
+    }
+
+    let expected_paths = expected_identities
+        .iter()
+        .filter_map(|entry| normalized_expected_trusted_identity_path(entry))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let expected_signing_ids = expected_identities
+        .iter()
+        .map(|entry| entry.signing_identifier.clone())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let expected_team_ids = expected_identities
+        .iter()
+        .map(|entry| entry.team_identifier.clone().unwrap_or_else(|| "not set".to_string()))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    TrustedProcessDecision::IdentityMismatch(format!(
+        "identity mismatch for {}: path={} signing_id={} team_id={} (expected paths=[{}], signing_ids=[{}], team_ids=[{}])",
+        process_name,
+        canonical_path,
+        actual_signature.signing_identifier,
+        actual_signature
+            .team_identifier
+            .clone()
+            .unwrap_or_else(|| "not set".to_string()),
+        expected_paths,
+        expected_signing_ids,
+        expected_team_ids
+    ))
+}
+
+#[cfg(test)]
+fn evaluate_trusted_process_from_path(
+    process_name: &str,
+    executable_path: &str,
+    policy: &SecurityPolicy,
+) -> TrustedProcessDecision {
+    if !policy.is_trusted_tool(process_name) {
+        return TrustedProcessDecision::NotTrusted;
+    }
+
+    let expected_identities = policy.trusted_identity_candidates_for_process(process_name);
+    if expected_identities.is_empty() {
+        return TrustedProcessDecision::IdentityMismatch(format!(
+            "trusted tool {} has no trusted_tool_identities entry",
+            process_name
+        ));
+    }
+
+    let canonical_path = match canonicalize_executable_path_strict(executable_path) {
+        Ok(path) => path,
+        Err(err) => return TrustedProcessDecision::IdentityMismatch(err),
+    };
+    let actual_signature = match read_binary_signature(&canonical_path) {
+        Ok(signature) => signature,
+        Err(err) => return TrustedProcessDecision::IdentityMismatch(err),
+    };
+    trusted_identity_decision_from_signature(
+        process_name,
+        canonical_path.as_str(),
+        &actual_signature,
+        expected_identities.as_slice(),
+        policy.trusted_identity_require_cdhash,
+    )
+}
+
+fn request_signature_refresh_if_needed(
+    canonical_path: &str,
+    signature_cache: &Arc<Mutex<BinarySignatureCache>>,
+    signature_refresh_tx: &mpsc::SyncSender<String>,
+) {
+    let should_enqueue = {
+        let mut cache = signature_cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        cache.should_enqueue_refresh(canonical_path)
+    };
+    if !should_enqueue {
+        return;
+    }
+
+    SIGNATURE_QUEUE_PENDING.fetch_add(1, Ordering::Relaxed);
+    if let Err(err) = signature_refresh_tx.try_send(canonical_path.to_string()) {
+        decrement_queue_pending(&SIGNATURE_QUEUE_PENDING);
+        let mut cache = signature_cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        cache.cancel_in_flight_refresh(canonical_path);
+        match err {
+            mpsc::TrySendError::Full(_) => {
+                SIGNATURE_QUEUE_DROPPED_FULL.fetch_add(1, Ordering::Relaxed);
+                eprintln!(
+                    "[trust] signature refresh queue is full; skipping enqueue for {}",
+                    canonical_path
+                );
+            },
+            mpsc::TrySendError::Disconnected(_) => {
+                SIGNATURE_QUEUE_DROPPED_DISCONNECTED.fetch_add(1, Ordering::Relaxed);
+                eprintln!(
+                    "[trust] signature refresh worker disconnected; unable to verify {}",
+                    canonical_path
+                );
+            },
+        }
+    }
+}
+
+fn warm_signature_cache_for_policy(
+    policy: &SecurityPolicy,
+    signature_cache: &Arc<Mutex<BinarySignatureCache>>,
+    signature_refresh_tx: &mpsc::SyncSender<String>,
+) {
+    let mut prewarm_paths = HashSet::new();
+    for entry in policy.trusted_tool_identities.iter() {
+        if let Some(path) = normalized_expected_trusted_identity_path(entry) {
+            prewarm_paths.insert(path);
+        }
+    }
+
+    for path in prewarm_paths.into_iter() {
+        request_signature_refresh_if_needed(path.as_str(), signature_cache, signature_refresh_tx);
+    }
+}
+
+fn spawn_signature_refresh_worker(
+    signature_refresh_rx: mpsc::Receiver<String>,
+    signature_cache: Arc<Mutex<BinarySignatureCache>>,
+) {
+    thread::spawn(move || {
+        while let Ok(canonical_path) = signature_refresh_rx.recv() {
+            let signature = read_binary_signature(canonical_path.as_str());
+            let now = now_ts();
+            let mut cache = signature_cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            cache.insert(canonical_path, signature, now);
+            decrement_queue_pending(&SIGNATURE_QUEUE_PENDING);
+        }
+    });
+}
+
+fn evaluate_trusted_process_from_path_nonblocking(
+    process_name: &str,
+    executable_path: &str,
+    policy: &SecurityPolicy,
+    signature_cache: &Arc<Mutex<BinarySignatureCache>>,
+    signature_refresh_tx: &mpsc::SyncSender<String>,
+) -> TrustedProcessDecision {
+    if !policy.is_trusted_tool(process_name) {
+        return TrustedProcessDecision::NotTrusted;
+    }
+
+    let expected_identities = policy.trusted_identity_candidates_for_process(process_name);
+    if expected_identities.is_empty() {
+        return TrustedProcessDecision::IdentityMismatch(format!(
+            "trusted tool {} has no trusted_tool_identities entry",
+            process_name
+        ));
+    }
+
+    let canonical_path = match canonicalize_executable_path_strict(executable_path) {
+        Ok(path) => path,
+        Err(err) => return TrustedProcessDecision::IdentityMismatch(err),
+    };
+
+    let now = now_ts();
+    let cached_signature = {
+        let mut cache = signature_cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        cache.get(canonical_path.as_str(), now)
+    };
+
+    let actual_signature = match cached_signature {
+        Some(Ok(signature)) => signature,
+        Some(Err(err)) => return TrustedProcessDecision::IdentityMismatch(err),
+        None => {
+            request_signature_refresh_if_needed(
+                canonical_path.as_str(),
+                signature_cache,
+                signature_refresh_tx,
+            );
+            return TrustedProcessDecision::IdentityMismatch(format!(
+                "{}: {}",
+                TRUST_SIGNATURE_PENDING_PREFIX, canonical_path
+            ));
+        },
+    };
+
+    trusted_identity_decision_from_signature(
+        process_name,
+        canonical_path.as_str(),
+        &actual_signature,
+        expected_identities.as_slice(),
+        policy.trusted_identity_require_cdhash,
+    )
+}
+
+fn trust_decision_is_signature_pending(decision: &TrustedProcessDecision) -> bool {
+    matches!(
+        decision,
+        TrustedProcessDecision::IdentityMismatch(reason) if reason.starts_with(TRUST_SIGNATURE_PENDING_PREFIX)
+    )
+}
+
+fn prune_trust_cache(cache: &mut HashMap<ProcessIdentityKey, CachedTrustedProcess>, now: u64) {
+    cache.retain(|_, entry| now.saturating_sub(entry.updated_at) <= TRUST_CACHE_TTL_SECS);
+}
+
+fn invalidate_trust_cache_for_pid(cache: &mut HashMap<ProcessIdentityKey, CachedTrustedProcess>, pid: i32) {
+    cache.retain(|key, _| key.pid != pid);
+}
+
+fn clear_process_state_for_pid(
+    pid: i32,
+    process_table: &mut ProcessTable,
+    trust_cache: &mut TrustedProcessCache,
+    taint_state: &mut TaintState,
+) {
+    if pid <= 0 {
+        return;
+    }
+    process_table.remove(pid);
+    trust_cache.clear_pid(pid);
+    taint_state.clear_pid(pid);
+}
+
+fn clear_trust_cache_for_exec(trust_cache: &Arc<Mutex<TrustedProcessCache>>, pid: i32) {
+    let mut cache = trust_cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    cache.clear_pid(pid);
+}
+
+fn evaluate_trusted_process(
+    pid: i32,
+    process_name: &str,
+    policy: &SecurityPolicy,
+    trust_cache: &Arc<Mutex<TrustedProcessCache>>,
+    signature_cache: &Arc<Mutex<BinarySignatureCache>>,
+    signature_refresh_tx: &mpsc::SyncSender<String>,
+) -> TrustedProcessDecision {
+    let now = now_ts();
+
+    let key = process_identity_key(pid);
+    if let Some(key) = key.as_ref() {
+        let cached = {
+            let mut cache = trust_cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            cache.get(key, now)
+        };
+        if let Some(entry) = cached {
+            return entry.decision.clone();
+        }
+    }
+
+    let executable_path = match get_process_path(pid) {
+        Some(path) => path,
+        None => {
+            return TrustedProcessDecision::IdentityMismatch(format!(
+                "unable to resolve executable path for pid {}",
+                pid
+            ))
+        },
+    };
+    let decision = evaluate_trusted_process_from_path_nonblocking(
+        process_name,
+        &executable_path,
+        policy,
+        signature_cache,
+        signature_refresh_tx,
+    );
+    if let Some(key) = key {
+        if trust_decision_is_signature_pending(&decision) {
+            return decision;
+        }
+        let mut cache = trust_cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        cache.insert(key, decision.clone(), now);
+    }
+    decision
+}
+
+fn is_vcs_tool(process_name: &str) -> bool {
+    matches!(process_name, "git" | "jj")
+}
+
+fn git_global_option_takes_value(option: &str) -> bool {
+    matches!(
+        option,
+        "-C" | "-c" | "--exec-path" | "--git-dir" | "--work-tree" | "--namespace" | "--super-prefix" | "--config-env"
+    )
+}
+
+fn git_subcommand_from_args(args: &[String]) -> Option<&str> {
+    if args.len() < 2 {
+        return None;
+    }
+
+    let mut idx = 1;
+    while idx < args.len() {
+        let arg = args[idx].as_str();
+        if arg == "--" {
+            idx += 1;
+            break;
+        }
+
+        if arg.starts_with("--") {
+            if let Some((opt, _)) = arg.split_once('=') {
+                if git_global_option_takes_value(opt) {
+                    idx += 1;
+                    continue;
+                }
+            } else if git_global_option_takes_value(arg) {
+                idx = idx.saturating_add(2);
+                continue;
+            }
+            idx += 1;
+            continue;
+        }
+
+        if arg.starts_with('-') {
+            if git_global_option_takes_value(arg) {
+                idx = idx.saturating_add(2);
+            } else {
+                idx += 1;
+            }
+            continue;
+        }
+
+        return Some(arg);
+    }
+
+    if idx < args.len() {
+        Some(args[idx].as_str())
+    } else {
+        None
+    }
+}
+
+fn is_git_merge_or_pull_invocation(args: &[String]) -> bool {
+    matches!(git_subcommand_from_args(args), Some("merge" | "pull"))
+}
+
+fn should_allow_git_merge_pull_worktree_change_in_ai_context(
+    process_name: &str,
+    args: &[String],
+    trusted_process: &TrustedProcessDecision,
+    policy: &SecurityPolicy,
+) -> bool {
+    policy.allow_git_merge_pull_in_ai_context
+        && process_name == "git"
+        && trusted_process.is_trusted()
+        && is_git_merge_or_pull_invocation(args)
+}
+
+fn should_allow_git_merge_pull_for_process(
+    pid: i32,
+    process_name: &str,
+    trusted_process: &TrustedProcessDecision,
+    policy: &SecurityPolicy,
+    process_table: &ProcessTable,
+) -> bool {
+    if process_name != "git" {
+        return false;
+    }
+
+    let args = match process_table.argv_for_pid(pid) {
+        Some(args) => args,
+        None => return false,
+    };
+    should_allow_git_merge_pull_worktree_change_in_ai_context(process_name, &args, trusted_process, policy)
+}
+
+fn is_vcs_metadata_path(path: &str) -> bool {
+    trim_trailing_slashes(path)
+        .split('/')
+        .any(|component| component == ".git" || component == ".jj")
+}
+
+fn should_allow_vcs_metadata_unlink_in_ai_context(
+    path: &str,
+    process_name: Option<&str>,
+    trusted_process: &TrustedProcessDecision,
+    policy: &SecurityPolicy,
+) -> bool {
+    if !policy.allow_vcs_metadata_in_ai_context || !is_vcs_metadata_path(path) {
+        return false;
+    }
+    let process_name = match process_name {
+        Some(name) => name,
+        None => return false,
+    };
+
+    is_vcs_tool(process_name) && trusted_process.is_trusted()
+}
+
+fn should_allow_vcs_metadata_rename_in_ai_context(
+    source_path: &str,
+    dest_path: &str,
+    process_name: Option<&str>,
+    trusted_process: &TrustedProcessDecision,
+    policy: &SecurityPolicy,
+) -> bool {
+    if !policy.allow_vcs_metadata_in_ai_context
+        || !is_vcs_metadata_path(source_path)
+        || !is_vcs_metadata_path(dest_path)
+    {
+        return false;
+    }
+    let process_name = match process_name {
+        Some(name) => name,
+        None => return false,
+    };
+
+    is_vcs_tool(process_name) && trusted_process.is_trusted()
+}
+
+fn is_read_intent(fflag: i32) -> bool {
+    (fflag & FFLAG_READ) != 0 || !is_write_intent(fflag)
+}
+
+fn is_write_intent(fflag: i32) -> bool {
+    (fflag & FFLAG_WRITE) != 0
+}
+
+fn is_truncate_intent(fflag: i32) -> bool {
+    (fflag & FFLAG_TRUNC) != 0
+}
+
+/// ES does not report the requested length for truncate(2), so any truncation of
+/// existing content at or above `truncate_gate_min_bytes` is treated as destructive.
+fn should_gate_protected_truncate(path: &str, current_size: i64, policy: &SecurityPolicy, home: &str) -> bool {
+    current_size > 0
+        && current_size as u64 >= policy.truncate_gate_min_bytes
+        && policy.is_truncate_gated_path(path, home)
+}
+
+fn should_gate_protected_open_truncate(
+    path: &str,
+    fflag: i32,
+    current_size: i64,
+    policy: &SecurityPolicy,
+    home: &str,
+) -> bool {
+    is_write_intent(fflag)
+        && is_truncate_intent(fflag)
+        && should_gate_protected_truncate(path, current_size, policy, home)
+}
+
+#[derive(Debug)]
+struct MetadataChange {
+    op: &'static str,
+    path: String,
+    file: FileIdentity,
+    destructive: bool,
+}
+
+fn is_destructive_mode_change(current_mode: u32, new_mode: u32) -> bool {
+    let drops_owner_access = (current_mode & !new_mode & 0o600) != 0;
+    let adds_setid = (new_mode & !current_mode & 0o6000) != 0;
+    drops_owner_access || adds_setid
+}
+
+fn is_protected_flag_change(current_flags: u32, new_flags: u32) -> bool {
+    ((current_flags ^ new_flags) & METADATA_GATE_PROTECTED_FILE_FLAGS) != 0
+}
+
+fn is_owner_change(current_uid: u32, current_gid: u32, new_uid: u32, new_gid: u32) -> bool {
+    (new_uid != u32::MAX && new_uid != current_uid) || (new_gid != u32::MAX && new_gid != current_gid)
+}
+
+/// Moving mtime backwards hides edits; freshly created files are exempt so
+/// `tar -x` / `cp -p` into a protected zone keep working.
+fn is_backdated_utimes(current_mtime: i64, new_mtime: &libc::timespec, birthtime: i64, now: u64) -> bool {
+    if new_mtime.tv_nsec == libc::UTIME_OMIT || new_mtime.tv_nsec == libc::UTIME_NOW {
+        return false;
+    }
+    let file_age = (now as i64).saturating_sub(birthtime);
+    new_mtime.tv_sec < current_mtime && file_age > METADATA_GATE_FRESH_FILE_SECS
+}
+
+fn metadata_change_for_event(event: &Event<'_>, policy: &SecurityPolicy, now: u64) -> Option<MetadataChange> {
+    let (op, target, destructive) = match event {
+        Event::AuthSetMode(setmode) => {
+            let target = setmode.target();
+            let destructive = is_destructive_mode_change(target.stat().st_mode as u32, setmode.mode() as u32);
+            ("setmode", target, destructive)
+        },
+        Event::AuthSetFlags(setflags) => {
+            let target = setflags.target();
+            let destructive = is_protected_flag_change(target.stat().st_flags, setflags.flags());
+            ("setflags", target, destructive)
+        },
+        Event::AuthSetAcl(setacl) => ("setacl", setacl.target(), true),
+        Event::AuthSetExtAttr(setextattr) => {
+            let destructive = !policy.is_metadata_xattr_allowed(&setextattr.extattr().to_string_lossy());
+            ("setextattr", setextattr.target(), destructive)
+        },
+        Event::AuthDeleteExtAttr(deleteextattr) => {
+            let destructive = !policy.is_metadata_xattr_allowed(&deleteextattr.extattr().to_string_lossy());
+            ("deleteextattr", deleteextattr.target(), destructive)
+        },
+        Event::AuthSetOwner(setowner) => {
+            let target = setowner.target();
+            let stat = target.stat();
+            let destructive = is_owner_change(stat.st_uid, stat.st_gid, setowner.uid(), setowner.gid());
+            ("setowner", target, destructive)
+        },
+        Event::AuthUTimes(utimes) => {
+            let target = utimes.target();
+            let stat = target.stat();
+            let destructive = is_backdated_utimes(stat.st_mtime, &utimes.raw_mtime(), stat.st_birthtime, now);
+            ("utimes", target, destructive)
+        },
+        _ => return None,
+    };
+    Some(MetadataChange {
+        op,
+        path: target.path().to_string_lossy().into_owned(),
+        file: FileIdentity::from_stat(target.stat()),
+        destructive,
+    })
+}
+
+fn should_gate_protected_metadata_change(change: &MetadataChange, policy: &SecurityPolicy, home: &str) -> bool {
+    change.destructive && policy.is_metadata_gate_op_enabled(change.op) && policy.is_protected(&change.path, home)
+}
+
+fn should_fast_allow_open(path: &str, fflag: i32, policy: &SecurityPolicy) -> bool {
+    !is_write_intent(fflag) && !policy.is_sensitive_path(path)
+}
+
+fn should_deny_sensitive_open_for_process(
+    path: &str,
+    is_ai_context: bool,
+    fflag: i32,
+    policy: &SecurityPolicy,
+    is_guard_process: bool,
+) -> bool {
+    policy.read_gate_enabled
+        && policy.is_sensitive_path(path)
+        && is_read_intent(fflag)
+        && !is_ai_context
+        && !is_guard_process
+        && !policy.is_sensitive_read_override_active_for_path(path, now_ts())
+}
+
+fn should_deny_sensitive_open(path: &str, is_ai_context: bool, fflag: i32, policy: &SecurityPolicy) -> bool {
+    should_deny_sensitive_open_for_process(path, is_ai_context, fflag, policy, false)
+}
+
+fn is_sensitive_read_observer_process(process_name: &str) -> bool {
+    process_name == "AgentSmith"
+}
+
+fn is_sensitive_read_observer_path(path: &str, home: &str) -> bool {
+    let policy_path = format!("{}/.agentsmith-rs/policy.json", home);
+    let policy_lock_path = format!("{}.lock", policy_path);
+    let guard_dir = format!("{}/.agentsmith-rs/guard", home);
+    path == policy_path
+        || path == policy_lock_path
+        || path_prefix_match(path, &policy_lock_path)
+        || path_prefix_match(path, &guard_dir)
+}
+
+fn should_allow_sensitive_read_observer(path: &str, process_name: &str, home: &str) -> bool {
+    is_sensitive_read_observer_process(process_name) && is_sensitive_read_observer_path(path, home)
+}
+
+fn should_mark_taint_on_sensitive_read(path: &str, policy: &SecurityPolicy, home: &str) -> bool {
+    policy.is_sensitive_path(path) && !is_sensitive_read_observer_path(path, home)
+}
+
+fn should_deny_sensitive_transfer(source: &str, dest: &str, policy: &SecurityPolicy) -> bool {
+    policy.transfer_gate_enabled
+        && policy.is_sensitive_path(source)
+        && !policy.is_sensitive_export_allowed(dest)
+        && !policy.is_sensitive_path(dest)
+}
+
+fn should_allow_vcs_metadata_tainted_write(
+    target_path: &str,
+    process_name: Option<&str>,
+    trusted_process: &TrustedProcessDecision,
+    policy: &SecurityPolicy,
+) -> bool {
+    if !policy.allow_vcs_metadata_in_ai_context || !is_vcs_metadata_path(target_path) {
+        return false;
+    }
+    let process_name = match process_name {
+        Some(name) => name,
+        None => return false,
+    };
+
+    is_vcs_tool(process_name) && trusted_process.is_trusted()
+}
+
+fn should_deny_tainted_write(
+    pid: i32,
+    target: &str,
+    process_name: Option<&str>,
+    trusted_process: &TrustedProcessDecision,
+    now: u64,
+    taint: &TaintState,
+    policy: &SecurityPolicy,
+) -> bool {
+    taint.is_tainted(pid, now)
+        && !is_safe_taint_device_path(target)
+        && !policy.is_sensitive_export_allowed(target)
+        && !policy.is_sensitive_path(target)
+        && !should_allow_vcs_metadata_tainted_write(target, process_name, trusted_process, policy)
+}
+
+fn should_deny_tainted_open_write(
+    pid: i32,
+    path: &str,
+    fflag: i32,
+    process_name: Option<&str>,
+    trusted_process: &TrustedProcessDecision,
+    now: u64,
+    taint: &TaintState,
+    policy: &SecurityPolicy,
+) -> bool {
+    is_write_intent(fflag) && should_deny_tainted_write(pid, path, process_name, trusted_process, now, taint, policy)
+}
+
+fn should_deny_exec_in_ai_context(proc_name: &str, is_ai_context: bool, policy: &SecurityPolicy) -> bool {
+    policy.exec_gate_enabled && is_ai_context && policy.exec_exfil_tool_blocklist.iter().any(|tool| tool == proc_name)
+}
+
+fn join_path_component(dir: &str, name: &str) -> String {
+    let normalized_dir = trim_trailing_slashes(dir);
+    if normalized_dir == "/" {
+        format!("/{}", name)
+    } else {
+        format!("{}/{}", normalized_dir, name)
+    }
+}
+
+#[derive(Debug)]
+struct GateDenyDecision {
+    process: String,
+    ancestor: String,
+    reason: &'static str,
+}
+
+/// Core decision: should this operation be denied?
+/// Returns Some(decision) if denied, None if allowed.
+#[allow(clippy::too_many_arguments)]
+fn should_deny(
+    path: &str,
+    file: Option<FileIdentity>,
+    pid: i32,
+    home: &str,
+    policy: &SecurityPolicy,
+    process_table: &mut ProcessTable,
+    trust_cache: &Arc<Mutex<TrustedProcessCache>>,
+    signature_cache: &Arc<Mutex<BinarySignatureCache>>,
+    signature_refresh_tx: &mpsc::SyncSender<String>,
+) -> Option<GateDenyDecision> {
+    should_deny_protected_zone_op(
+        path,
+        pid,
+        home,
+        policy,
+        process_table,
+        trust_cache,
+        signature_cache,
+        signature_refresh_tx,
+        GateOp::new("unlink", file),
+        REASON_PROTECTED_ZONE_AI_DELETE,
+    )
+}
+
+/// One operation allowed by a counted override.
+#[derive(Debug, Clone)]
+struct OverrideConsumption {
+    ts: u64,
+    rule_id: String,
+    op: String,
+    path: String,
+    dest: Option<String>,
+    pid: i32,
+    process: String,
+    use_number: u32,
+    max_uses: u32,
+}
+
+/// Uses claimed from counted overrides, keyed by rule id. AUTH handlers claim
+/// here so concurrent events cannot overspend a rule whatever policy snapshot
+/// they hold; the policy thread folds the counts into the persisted rules.
+#[derive(Debug, Default)]
+struct OverrideUseLedger {
+    claimed: HashMap<String, (u32, Option<u64>)>,
+    consumed: Vec<OverrideConsumption>,
+}
+
+impl OverrideUseLedger {
+    fn claim(&mut self, rule: &TemporaryOverrideRule) -> Option<u32> {
+        let max_uses = rule.max_uses?;
+        let (claimed, _) = self.claimed.entry(rule.use_key()).or_insert((0, rule.expires_at));
+        let used = (*claimed).max(rule.uses);
+        if used >= max_uses {
+            return None;
+        }
+        *claimed = used + 1;
+        Some(*claimed)
+    }
+
+    /// Copy claimed counts into `overrides`; returns whether any rule changed.
+    /// Entries are kept until their rule expires so a stale snapshot that still
+    /// lists an exhausted rule cannot claim it again.
+    fn fold_into(&mut self, overrides: &mut [TemporaryOverrideEntry], now: u64) -> bool {
+        let mut changed = false;
+        for entry in overrides.iter_mut() {
+            if let TemporaryOverrideEntry::Rule(rule) = entry {
+                if let Some((claimed, _)) = self.claimed.get(&rule.use_key()) {
+                    if *claimed > rule.uses {
+                        rule.uses = *claimed;
+                        changed = true;
+                    }
+                }
+            }
+        }
+        self.claimed
+            .retain(|_, (_, expires_at)| expires_at.is_none_or(|exp| exp > now));
+        changed
+    }
+}
+
+impl TemporaryOverrideRule {
+    fn use_key(&self) -> String {
+        self.id
+            .clone()
+            .unwrap_or_else(|| format!("{}@{}", self.path, self.created_at.unwrap_or_default()))
+    }
+}
+
+/// Device, inode, size and change times of an AUTH event's target, from the
+/// event's stat.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+struct FileIdentity {
+    dev: u64,
+    ino: u64,
+    size: u64,
+    #[serde(default)]
+    mtime: i64,
+    #[serde(default)]
+    mtime_nsec: i64,
+    #[serde(default)]
+    ctime: i64,
+    #[serde(default)]
+    ctime_nsec: i64,
+}
+
+impl FileIdentity {
+    fn from_stat(stat: &libc::stat) -> Self {
+        Self {
+            dev: stat.st_dev as u64,
+            ino: stat.st_ino,
+            size: stat.st_size.max(0) as u64,
+            mtime: stat.st_mtime,
+            mtime_nsec: stat.st_mtime_nsec,
+            ctime: stat.st_ctime,
+            ctime_nsec: stat.st_ctime_nsec,
+        }
+    }
+
+    fn from_metadata(meta: &fs::Metadata) -> Self {
+        Self {
+            dev: meta.dev(),
+            ino: meta.ino(),
+            size: meta.len(),
+            mtime: meta.mtime(),
+            mtime_nsec: meta.mtime_nsec(),
+            ctime: meta.ctime(),
+            ctime_nsec: meta.ctime_nsec(),
+        }
+    }
+}
+
+/// The operation a protected-zone gate is deciding, as restricted overrides
+/// see it.
+#[derive(Debug, Clone, Copy)]
+struct GateOp<'a> {
+    name: &'a str,
+    dest: Option<&'a str>,
+    file: Option<FileIdentity>,
+}
+
+impl<'a> GateOp<'a> {
+    fn new(name: &'a str, file: Option<FileIdentity>) -> Self {
+        Self { name, dest: None, file }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OverrideClaim {
+    Allowed,
+    /// Only pinned rules matched, and the file no longer has their identity.
+    PinMismatch,
+    NotCovered,
+}
+
+/// Whether a restricted (scoped, operation-limited, counted or pinned) override
+/// lets `pid` perform `op` on `path`. Only walks the ancestry when such an
+/// override exists. Counted rules are charged here, so callers ask only once
+/// the operation would otherwise be denied.
+fn claim_restricted_override_for_pid(
+    path: &str,
+    op: GateOp<'_>,
+    pid: i32,
+    policy: &SecurityPolicy,
+    process_table: &mut ProcessTable,
+) -> OverrideClaim {
+    let now = now_ts();
+    if !policy.has_restricted_override_for_path(path, now) {
+        return OverrideClaim::NotCovered;
+    }
+    let actor = process_table.override_actor(pid, policy, now);
+    let mut pin_mismatch = false;
+    for rule in policy.overrides_for_op(path, now, &actor, op.name, op.dest) {
+        if rule.pin.as_ref().is_some_and(|pin| !pin.matches(path, op.file)) {
+            pin_mismatch = true;
+            continue;
+        }
+        let Some(max_uses) = rule.max_uses else {
+            return OverrideClaim::Allowed;
+        };
+        let mut ledger = OVERRIDE_USE_LEDGER
+            .get_or_init(|| Mutex::new(OverrideUseLedger::default()))
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(use_number) = ledger.claim(rule) {
+            ledger.consumed.push(OverrideConsumption {
+                ts: now,
+                rule_id: rule.use_key(),
+                op: op.name.to_string(),
+                path: path.to_string(),
+                dest: op.dest.map(str::to_string),
+                pid,
+                process: process_name_for_pid(pid).unwrap_or_else(|| format!("pid:{}", pid)),
+                use_number,
+                max_uses,
+            });
+            return OverrideClaim::Allowed;
+        }
+    }
+    if pin_mismatch {
+        OverrideClaim::PinMismatch
+    } else {
+        OverrideClaim::NotCovered
+    }
+}
+
+/// Policy-thread side of the ledger: fold claimed uses into the runtime
+/// overrides (exhausted rules are then dropped by `sanitize_overrides`) and
+/// audit each consumption. Returns whether the overrides changed.
+fn sync_override_uses(home: &str, overrides: &mut [TemporaryOverrideEntry], now: u64) -> bool {
+    let Some(ledger) = OVERRIDE_USE_LEDGER.get() else {
+        return false;
+    };
+    let (changed, consumed) = {
+        let mut ledger = ledger.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let changed = ledger.fold_into(overrides, now);
+        (changed, std::mem::take(&mut ledger.consumed))
+    };
+    for consumption in consumed {
+        let target = match &consumption.dest {
+            Some(dest) => format!("{} -> {}", consumption.path, dest),
+            None => consumption.path.clone(),
+        };
+        let message = format!(
+            "{} of {} allowed, use {} of {}",
+            consumption.op, target, consumption.use_number, consumption.max_uses
+        );
+        println!(
+            "[override] {} by {} (pid {}): {}",
+            consumption.rule_id, consumption.process, consumption.pid, message
+        );
+        log_override_audit(
+            home,
+            &OverrideAuditRecord {
+                ts: consumption.ts,
+                request_id: consumption.rule_id,
+                action: "consume".to_string(),
+                path: Some(consumption.path),
+                minutes: None,
+                status: "ok".to_string(),
+                message,
+                requester_pid: Some(consumption.pid),
+                requester_process: Some(consumption.process),
+                approval_id: None,
+                paths: Vec::new(),
+            },
+        );
+    }
+    changed
+}
+
+/// Shared protected-zone gate for destructive operations; `zone_reason` is reported
+/// when the process is denied for being in AI context (as opposed to a trust mismatch).
+#[allow(clippy::too_many_arguments)]
+fn should_deny_protected_zone_op(
+    path: &str,
+    pid: i32,
+    home: &str,
+    policy: &SecurityPolicy,
+    process_table: &mut ProcessTable,
+    trust_cache: &Arc<Mutex<TrustedProcessCache>>,
+    signature_cache: &Arc<Mutex<BinarySignatureCache>>,
+    signature_refresh_tx: &mpsc::SyncSender<String>,
+    op: GateOp<'_>,
+    zone_reason: &'static str,
+) -> Option<GateDenyDecision> {
+    // 1. Not in protected zone, or overridden → ALLOW
+    if !policy.is_protected(path, home) {
+        if policy.is_in_any_zone(path, home) {
+            count_session_override_use(process_table, pid);
+        }
+        return None;
+    }
+
+    // 2. System temp paths → ALLOW
+    if is_system_temp(path, home) {
+        return None;
+    }
+
+    // 3. Not in AI agent process tree → ALLOW (user operation)
+    let ai_ancestor = match find_ai_ancestor(pid, policy, process_table) {
+        Some(name) => name,
+        None => return None,
+    };
+
+    let process_name = process_name_for_pid(pid).unwrap_or_else(|| format!("pid:{}", pid));
+    let trusted_process = evaluate_trusted_process(
+        pid,
+        process_name.as_str(),
+        policy,
+        trust_cache,
+        signature_cache,
+        signature_refresh_tx,
+    );
+
+    // 4. Keep git/jj commit internals workable while still blocking `git rm` on working tree files.
+    if should_allow_vcs_metadata_unlink_in_ai_context(path, Some(process_name.as_str()), &trusted_process, policy) {
+        return None;
+    }
+    let vcs_metadata_mismatch = policy.allow_vcs_metadata_in_ai_context
+        && is_vcs_metadata_path(path)
+        && is_vcs_tool(process_name.as_str())
+        && trusted_process.is_identity_mismatch();
+
+    // 5. Keep `git merge` / `git pull` workflow writable inside protected zones (no rebase/rm bypass).
+    if should_allow_git_merge_pull_for_process(
+        pid,
+        process_name.as_str(),
+        &trusted_process,
+        policy,
+        process_table,
+    ) {
+        return None;
+    }
+    let merge_pull_mismatch = policy.allow_git_merge_pull_in_ai_context
+        && process_name == "git"
+        && trusted_process.is_identity_mismatch()
+        && process_table
+            .argv_for_pid(pid)
+            .is_some_and(|args| is_git_merge_or_pull_invocation(&args));
+
+    // 5. Optional compatibility mode for trusted tools in AI context.
+    if policy.allow_trusted_tools_in_ai_context && trusted_process.is_trusted() {
+        return None;
+    }
+    let trusted_tool_mismatch = policy.allow_trusted_tools_in_ai_context
+        && policy.is_trusted_tool(process_name.as_str())
+        && trusted_process.is_identity_mismatch();
+
+    // 6. Overridden for this process tree and operation (counted rules are charged) → ALLOW
+    let claim = claim_restricted_override_for_pid(path, op, pid, policy, process_table);
+    if claim == OverrideClaim::Allowed {
+        count_session_override_use(process_table, pid);
+        return None;
+    }
+
+    let reason = if claim == OverrideClaim::PinMismatch {
+        REASON_OVERRIDE_PIN_MISMATCH
+    } else if vcs_metadata_mismatch || merge_pull_mismatch || trusted_tool_mismatch {
+        REASON_TRUST_IDENTITY_MISMATCH
+    } else {
+        zone_reason
+    };
+
+    // 7. In AI agent context and protected path → DENY
+    Some(GateDenyDecision {
+        process: process_name,
+        ancestor: ai_ancestor,
+        reason,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::send_control_request;
+
+    #[test]
+    fn sensitive_policy_defaults_are_safe() {
+        let policy: SecurityPolicy =
+            serde_json::from_str(r#"{"protected_zones":[],"temporary_overrides":[]}"#).expect("default policy json");
+        assert!(policy.sensitive_zones.is_empty());
+        assert!(policy.read_gate_enabled);
+        assert!(policy.transfer_gate_enabled);
+        assert!(policy.exec_gate_enabled);
+        assert!(!policy.audit_only_mode);
+        assert!(policy.override_approval_required);
+        assert!(policy.trusted_tool_identities.is_empty());
+        assert!(!policy.trusted_identity_require_cdhash);
+        assert!(policy.metadata_gate_enabled);
+        assert_eq!(policy.metadata_gate_ops, default_metadata_gate_ops());
+        assert_eq!(
+            policy.taint_ttl_seconds_or_default(),
+            DEFAULT_TAINT_TTL_SECS
+        );
+    }
+
+    #[test]
+    fn policy_taint_ttl_seconds_can_be_overridden() {
+        let policy: SecurityPolicy = serde_json::from_str(
+            r#"{
+                "protected_zones":[],
+                "temporary_overrides":[],
+                "taint_ttl_seconds":42
+            }"#,
+        )
+        .expect("policy json");
+        assert_eq!(policy.taint_ttl_seconds_or_default(), 42);
+    }
+
+    #[test]
+    fn policy_audit_only_mode_defaults_to_false() {
+        let policy: SecurityPolicy =
+            serde_json::from_str(r#"{"protected_zones":[],"temporary_overrides":[]}"#).expect("policy json");
+        assert!(!policy.audit_only_mode);
+    }
+
+    #[test]
+    fn policy_audit_only_mode_can_be_enabled() {
+        let policy: SecurityPolicy = serde_json::from_str(
+            r#"{
+                "protected_zones":[],
+                "temporary_overrides":[],
+                "audit_only_mode":true
+            }"#,
+        )
+        .expect("policy json");
+        assert!(policy.audit_only_mode);
+    }
+
+    #[test]
+    fn save_policy_preserves_taint_ttl_seconds_field() {
+        let tmp_dir = std::env::temp_dir().join(format!(
+            "agentsmith-policy-roundtrip-{}-{}",
+            std::process::id(),
+            now_ts()
+        ));
+        fs::create_dir_all(&tmp_dir).expect("create temp dir");
+        let policy_path = tmp_dir.join("policy.json");
+        fs::write(
+            &policy_path,
+            r#"{
+              "protected_zones": [],
+              "temporary_overrides": [],
+              "taint_ttl_seconds": 42
+            }"#,
+        )
+        .expect("write policy json");
+
+        let policy = load_policy(policy_path.to_str().expect("utf8 path")).expect("load policy");
+        save_policy(policy_path.to_str().expect("utf8 path"), &policy).expect("save policy");
+
+        let persisted = fs::read_to_string(&policy_path).expect("read persisted policy");
+        let persisted_json: serde_json::Value = serde_json::from_str(&persisted).expect("decode persisted policy");
+        assert_eq!(
+            persisted_json.get("taint_ttl_seconds").and_then(|value| value.as_u64()),
+            Some(42)
+        );
+
+        let _ = fs::remove_dir_all(tmp_dir);
+    }
+
+    #[test]
+    fn sensitive_zone_boundary_match_is_correct() {
+        let mut policy = test_policy();
+        policy.sensitive_zones = vec!["/Users/jqwang/.codex".to_string()];
+        assert!(policy.is_sensitive_path("/Users/jqwang/.codex/sessions/a.json"));
+        assert!(!policy.is_sensitive_path("/Users/jqwang/.codexx/sessions/a.json"));
+    }
+
+    #[test]
+    fn sensitive_destination_allows_only_allowlist() {
+        let mut policy = test_policy();
+        policy.sensitive_export_allow_zones = vec!["/Users/jqwang/.agentsmith-rs/guard/quarantine".to_string()];
+        assert!(policy.is_sensitive_export_allowed("/Users/jqwang/.agentsmith-rs/guard/quarantine/a"));
+        assert!(!policy.is_sensitive_export_allowed("/Users/jqwang/Desktop/a"));
+    }
+
+    #[test]
+    fn open_read_on_sensitive_path_denies_non_ai_context() {
+        let policy = test_sensitive_policy();
+        let decision = decide_sensitive_open_for_test(
+            "/Users/jqwang/.codex/chat/history.jsonl",
+            false,
+            FFLAG_READ,
+            &policy,
+        );
+        assert!(decision.deny);
+    }
+
+    #[test]
+    fn open_read_on_sensitive_path_denies_non_ai_with_zero_flag() {
+        let policy = test_sensitive_policy();
+        let decision = decide_sensitive_open_for_test("/Users/jqwang/.codex/chat/history.jsonl", false, 0, &policy);
+        assert!(decision.deny);
+    }
+
+    #[test]
+    fn open_read_on_sensitive_path_allows_ai_context() {
+        let policy = test_sensitive_policy();
+        let decision = decide_sensitive_open_for_test(
+            "/Users/jqwang/.codex/chat/history.jsonl",
+            true,
+            FFLAG_READ,
+            &policy,
+        );
+        assert!(!decision.deny);
+    }
+
+    #[test]
+    fn open_read_on_sensitive_path_allows_temporary_override_for_human_read() {
+        let mut policy = test_sensitive_policy();
+        policy.temporary_overrides = vec![TemporaryOverrideEntry::Rule(TemporaryOverrideRule {
+            path: "/Users/jqwang/.codex/chat".to_string(),
+            expires_at: Some(now_ts().saturating_add(300)),
+            created_at: Some(now_ts()),
+            created_by: Some(OVERRIDE_CREATED_BY_SENSITIVE_READ_HELPER.to_string()),
+            scope: None,
+            ops: Vec::new(),
+            rename_to: None,
+            max_uses: None,
+            uses: 0,
+            pin: None,
+            id: None,
+        })];
+
+        let decision = decide_sensitive_open_for_test(
+            "/Users/jqwang/.codex/chat/history.jsonl",
+            false,
+            FFLAG_READ,
+            &policy,
+        );
+        assert!(!decision.deny);
+    }
+
+    #[test]
+    fn open_read_on_sensitive_path_allows_guard_process() {
+        let policy = test_sensitive_policy();
+        assert!(!should_deny_sensitive_open_for_process(
+            "/Users/jqwang/.agentsmith-rs/policy.json",
+            false,
+            FFLAG_READ,
+            &policy,
+            true,
+        ));
+    }
+
+    #[test]
+    fn sensitive_read_observer_allows_policy_and_guard_logs() {
+        let home = "/Users/jqwang";
+        assert!(should_allow_sensitive_read_observer(
+            "/Users/jqwang/.agentsmith-rs/policy.json",
+            "AgentSmith",
+            home,
+        ));
+        assert!(should_allow_sensitive_read_observer(
+            "/Users/jqwang/.agentsmith-rs/policy.json.lock",
+            "AgentSmith",
+            home,
+        ));
+        assert!(should_allow_sensitive_read_observer(
+            "/Users/jqwang/.agentsmith-rs/guard/denials.jsonl",
+            "AgentSmith",
+            home,
+        ));
+    }
+
+    #[test]
+    fn sensitive_read_observer_does_not_allow_chat_history_or_other_process() {
+        let home = "/Users/jqwang";
+        assert!(!should_allow_sensitive_read_observer(
+            "/Users/jqwang/.codex/chat/history.jsonl",
+            "AgentSmith",
+            home,
+        ));
+        assert!(!should_allow_sensitive_read_observer(
+            "/Users/jqwang/.agentsmith-rs/policy.json",
+            "cat",
+            home,
+        ));
+    }
+
+    #[test]
+    fn observer_metadata_reads_do_not_mark_taint() {
+        let policy = test_sensitive_policy();
+        let home = "/Users/jqwang";
+        assert!(!should_mark_taint_on_sensitive_read(
+            "/Users/jqwang/.agentsmith-rs/policy.json",
+            &policy,
+            home,
+        ));
+        assert!(!should_mark_taint_on_sensitive_read(
+            "/Users/jqwang/.agentsmith-rs/guard/denials.jsonl",
+            &policy,
+            home,
+        ));
+        assert!(should_mark_taint_on_sensitive_read(
+            "/Users/jqwang/.codex/chat/history.jsonl",
+            &policy,
+            home,
+        ));
+    }
+
+    #[test]
+    fn sensitive_source_to_desktop_is_denied() {
+        let policy = test_sensitive_policy();
+        assert!(should_deny_sensitive_transfer(
+            "/Users/jqwang/.codex/sessions/a.json",
+            "/Users/jqwang/Desktop/a.json",
+            &policy
+        ));
+    }
+
+    #[test]
+    fn sensitive_source_to_quarantine_is_allowed() {
+        let policy = test_sensitive_policy();
+        assert!(!should_deny_sensitive_transfer(
+            "/Users/jqwang/.codex/sessions/a.json",
+            "/Users/jqwang/.agentsmith-rs/guard/quarantine/a.json",
+            &policy
+        ));
+    }
+
+    #[test]
+    fn taint_expires_after_ttl() {
+        let mut taint = TaintState::new(60);
+        taint.mark(100, 1_000);
+        assert!(taint.is_tainted(100, 1_030));
+        assert!(!taint.is_tainted(100, 1_061));
+    }
+
+    #[test]
+    fn taint_state_accepts_matching_pid_start_time_identity() {
+        let mut taint = TaintState::new(60);
+        let pid = std::process::id() as i32;
+        let process_start = process_start_time_for_pid(pid).expect("current process start time must be available");
+        taint.mark_with_process_start(pid, 1_000, Some(process_start));
+        assert!(taint.is_tainted(pid, 1_030));
+    }
+
+    #[test]
+    fn taint_state_rejects_pid_start_time_mismatch() {
+        let mut taint = TaintState::new(60);
+        let pid = std::process::id() as i32;
+        taint.mark_with_process_start(
+            pid,
+            1_000,
+            Some(ProcessStartTimeKey {
+                start_tvsec: u64::MAX,
+                start_tvusec: u64::MAX,
+            }),
+        );
+        assert!(!taint.is_tainted(pid, 1_030));
+    }
+
+    #[test]
+    fn taint_state_prune_expired_entries_keeps_recent_entries() {
+        let mut taint = TaintState::new(60);
+        taint.mark(100, 1_000);
+        taint.mark(200, 1_150);
+        taint.prune_expired(1_200);
+        assert!(!taint.is_tainted(100, 1_200));
+        assert!(taint.is_tainted(200, 1_200));
+    }
+
+    #[test]
+    fn taint_state_inherits_to_child_pid_from_tainted_parent() {
+        let mut taint = TaintState::new(60);
+        taint.mark(100, 1_000);
+        assert!(taint.inherit_from_parent(100, 200, 1_020));
+        assert!(taint.is_tainted(200, 1_020));
+    }
+
+    #[test]
+    fn taint_state_does_not_inherit_from_clean_parent() {
+        let mut taint = TaintState::new(60);
+        assert!(!taint.inherit_from_parent(100, 200, 1_020));
+        assert!(!taint.is_tainted(200, 1_020));
+    }
+
+    #[test]
+    fn tainted_process_write_outside_allow_zone_is_denied() {
+        let policy = test_sensitive_policy();
+        let mut taint = TaintState::new(600);
+        taint.mark(100, 1_000);
+        assert!(should_deny_tainted_write(
+            100,
+            "/Users/jqwang/Desktop/out.txt",
+            Some("python3"),
+            &TrustedProcessDecision::NotTrusted,
+            1_100,
+            &taint,
+            &policy
+        ));
+    }
+
+    #[test]
+    fn tainted_git_metadata_write_is_allowed() {
+        let policy = test_sensitive_policy();
+        let mut taint = TaintState::new(600);
+        taint.mark(100, 1_000);
+        assert!(!should_deny_tainted_write(
+            100,
+            "/Users/jqwang/00-nixos-config/nixos-config/.git/index.lock",
+            Some("git"),
+            &TrustedProcessDecision::Trusted,
+            1_100,
+            &taint,
+            &policy
+        ));
+    }
+
+    #[test]
+    fn tainted_non_vcs_metadata_write_is_denied() {
+        let policy = test_sensitive_policy();
+        let mut taint = TaintState::new(600);
+        taint.mark(100, 1_000);
+        assert!(should_deny_tainted_write(
+            100,
+            "/Users/jqwang/00-nixos-config/nixos-config/.git/index.lock",
+            Some("python3"),
+            &TrustedProcessDecision::NotTrusted,
+            1_100,
+            &taint,
+            &policy
+        ));
+    }
+
+    #[test]
+    fn tainted_write_to_safe_device_paths_is_allowed() {
+        let policy = test_sensitive_policy();
+        let mut taint = TaintState::new(600);
+        taint.mark(100, 1_000);
+
+        for path in ["/dev/null", "/dev/tty", "/dev/dtracehelper"] {
+            assert!(!should_deny_tainted_write(
+                100,
+                path,
+                Some("bash"),
+                &TrustedProcessDecision::NotTrusted,
+                1_100,
+                &taint,
+                &policy
+            ));
+        }
+    }
+
+    #[test]
+    fn tainted_write_to_other_device_paths_is_denied() {
+        let policy = test_sensitive_policy();
+        let mut taint = TaintState::new(600);
+        taint.mark(100, 1_000);
+        assert!(should_deny_tainted_write(
+            100,
+            "/dev/random",
+            Some("bash"),
+            &TrustedProcessDecision::NotTrusted,
+            1_100,
+            &taint,
+            &policy
+        ));
+    }
+
+    #[test]
+    fn cache_watermark_highs_track_peaks() {
+        let mut highs = CacheWatermarkHighs::default();
+        assert_eq!(highs.update(2, 3, 1), (2, 3, 1));
+        assert_eq!(highs.update(1, 4, 0), (2, 4, 1));
+        assert_eq!(highs.update(8, 2, 9), (8, 4, 9));
+    }
+
+    #[test]
+    fn cache_watermark_log_format_is_stable() {
+        let line = format_cache_watermark_log(1_770_000_000, 2, 5, 3, 7, 1, 4);
+        assert_eq!(
+            line,
+            "[METRIC] cache-watermark ts=1770000000 ancestor=2/5 trusted=3/7 taint=1/4"
+        );
+    }
+
+    #[test]
+    fn callback_latency_metrics_snapshot_tracks_percentiles_and_resets() {
+        let metrics = CallbackLatencyMetrics::new();
+        metrics.observe(Duration::from_micros(80));
+        metrics.observe(Duration::from_micros(1_200));
+        metrics.observe(Duration::from_micros(700_000));
+
+        let snapshot = metrics.snapshot_and_reset();
+        assert_eq!(snapshot.total, 3);
+        assert_eq!(snapshot.max_us, 700_000);
+        assert_eq!(snapshot.percentile_upper_bound_us(50), 2_000);
+        assert_eq!(snapshot.percentile_upper_bound_us(95), 500_001);
+        assert_eq!(snapshot.percentile_upper_bound_us(99), 500_001);
+
+        let after_reset = metrics.snapshot_and_reset();
+        assert_eq!(after_reset.total, 0);
+        assert_eq!(after_reset.max_us, 0);
+        assert_eq!(after_reset.percentile_upper_bound_us(95), 0);
+    }
+
+    #[test]
+    fn runtime_health_log_format_is_stable() {
+        let mut counts = [0u64; CALLBACK_LATENCY_BUCKETS_US.len() + 1];
+        counts[0] = 1;
+        counts[1] = 1;
+        counts[CALLBACK_LATENCY_BUCKETS_US.len()] = 1;
+        let callback_latency = CallbackLatencySnapshot {
+            counts,
+            total: 3,
+            max_us: 700_000,
+        };
+
+        let line = format_runtime_health_log(1_770_000_000, 4, 1, 2, 9, 3, 5, &callback_latency);
+        assert_eq!(
+            line,
+            "[METRIC] runtime-health ts=1770000000 sigq_pending=4 sigq_drop_full=1 sigq_drop_disconnected=2 logq_pending=9 logq_drop_full=3 logq_drop_disconnected=5 cb_count=3 cb_p50_us=250 cb_p95_us=500001 cb_p99_us=500001 cb_max_us=700000"
+        );
+    }
+
+    #[test]
+    fn write_intent_detection_requires_write_flag() {
+        assert!(!is_write_intent(FFLAG_READ));
+        assert!(is_write_intent(FFLAG_WRITE));
+        assert!(is_write_intent(FFLAG_READ | FFLAG_WRITE));
+    }
+
+    #[test]
+    fn read_intent_detection_handles_zero_and_read_flags() {
+        assert!(is_read_intent(0));
+        assert!(is_read_intent(FFLAG_READ));
+        assert!(is_read_intent(FFLAG_READ | FFLAG_WRITE));
+        assert!(!is_read_intent(FFLAG_WRITE));
+    }
+
+    #[test]
+    fn auth_open_fast_allow_requires_non_sensitive_read_only_path() {
+        let policy = test_sensitive_policy();
+        assert!(should_fast_allow_open(
+            "/Users/jqwang/project/notes.txt",
+            FFLAG_READ,
+            &policy
+        ));
+        assert!(!should_fast_allow_open(
+            "/Users/jqwang/project/notes.txt",
+            FFLAG_WRITE,
+            &policy
+        ));
+        assert!(!should_fast_allow_open(
+            "/Users/jqwang/.codex/config.toml",
+            FFLAG_READ,
+            &policy
+        ));
+    }
+
+    #[test]
+    fn truncate_gate_is_disabled_by_default() {
+        let policy: SecurityPolicy =
+            serde_json::from_str(r#"{"protected_zones":["/Users/jqwang/project"],"temporary_overrides":[]}"#)
+                .expect("policy json");
+        assert!(!policy.truncate_gate_enabled);
+        assert!(!should_gate_protected_truncate(
+            "/Users/jqwang/project/data.db",
+            4096,
+            &policy,
+            "/Users/jqwang"
+        ));
+    }
+
+    #[test]
+    fn truncate_gate_covers_non_empty_protected_files_only() {
+        let mut policy = test_policy();
+        policy.truncate_gate_enabled = true;
+        assert!(should_gate_protected_truncate(
+            "/Users/jqwang/project/data.db",
+            4096,
+            &policy,
+            "/Users/jqwang"
+        ));
+        assert!(!should_gate_protected_truncate(
+            "/Users/jqwang/project/empty.txt",
+            0,
+            &policy,
+            "/Users/jqwang"
+        ));
+        assert!(!should_gate_protected_truncate(
+            "/Users/jqwang/Desktop/data.db",
+            4096,
+            &policy,
+            "/Users/jqwang"
+        ));
+    }
+
+    #[test]
+    fn truncate_gate_respects_zone_list_and_min_bytes() {
+        let mut policy = test_policy();
+        policy.protected_zones.push("/Users/jqwang/scratch".to_string());
+        policy.truncate_gate_enabled = true;
+        policy.truncate_gate_zones = vec!["/Users/jqwang/project".to_string()];
+        policy.truncate_gate_min_bytes = 1024;
+        assert!(should_gate_protected_truncate(
+            "/Users/jqwang/project/data.db",
+            4096,
+            &policy,
+            "/Users/jqwang"
+        ));
+        assert!(!should_gate_protected_truncate(
+            "/Users/jqwang/project/small.txt",
+            100,
+            &policy,
+            "/Users/jqwang"
+        ));
+        assert!(!should_gate_protected_truncate(
+            "/Users/jqwang/scratch/data.db",
+            4096,
+            &policy,
+            "/Users/jqwang"
+        ));
     }
 
-    if idx < args.len() {
-        Some(args[idx].as_str())
-    } else {
-        None
+    #[test]
+    fn truncate_gate_skips_overridden_paths() {
+        let mut policy = test_policy();
+        policy.truncate_gate_enabled = true;
+        policy.temporary_overrides = vec![TemporaryOverrideEntry::Rule(TemporaryOverrideRule {
+            path: "/Users/jqwang/project/data.db".to_string(),
+            expires_at: Some(now_ts().saturating_add(300)),
+            created_at: Some(now_ts()),
+            created_by: Some(OVERRIDE_CREATED_BY_HELPER.to_string()),
+            scope: None,
+            ops: Vec::new(),
+            rename_to: None,
+            max_uses: None,
+            uses: 0,
+            pin: None,
+            id: None,
+        })];
+        assert!(!should_gate_protected_truncate(
+            "/Users/jqwang/project/data.db",
+            4096,
+            &policy,
+            "/Users/jqwang"
+        ));
     }
-}
 
-fn is_git_merge_or_pull_invocation(args: &[String]) -> bool {
-    matches!(git_subcommand_from_args(args), Some("merge" | "pull"))
-}
+    #[test]
+    fn open_truncate_gate_requires_write_and_trunc_flags() {
+        let mut policy = test_policy();
+        policy.truncate_gate_enabled = true;
+        let path = "/Users/jqwang/project/notes.txt";
+        assert!(should_gate_protected_open_truncate(
+            path,
+            FFLAG_WRITE | FFLAG_TRUNC,
+            512,
+            &policy,
+            "/Users/jqwang"
+        ));
+        assert!(!should_gate_protected_open_truncate(
+            path,
+            FFLAG_WRITE,
+            512,
+            &policy,
+            "/Users/jqwang"
+        ));
+        assert!(!should_gate_protected_open_truncate(
+            path,
+            FFLAG_READ | FFLAG_TRUNC,
+            512,
+            &policy,
+            "/Users/jqwang"
+        ));
+    }
 
-fn should_allow_git_merge_pull_worktree_change_in_ai_context(
-    process_name: &str,
-    args: &[String],
-    trusted_process: &TrustedProcessDecision,
-    policy: &SecurityPolicy,
-) -> bool {
-    policy.allow_git_merge_pull_in_ai_context
-        && process_name == "git"
-        && trusted_process.is_trusted()
-        && is_git_merge_or_pull_invocation(args)
-}
+    #[test]
+    fn metadata_mode_change_flags_owner_access_loss_and_setid() {
+        assert!(is_destructive_mode_change(0o644, 0o000));
+        assert!(is_destructive_mode_change(0o644, 0o444));
+        assert!(is_destructive_mode_change(0o755, 0o4755));
+        assert!(!is_destructive_mode_change(0o644, 0o755));
+        assert!(!is_destructive_mode_change(0o644, 0o600));
+    }
 
-fn should_allow_git_merge_pull_for_process(
-    pid: i32,
-    process_name: &str,
-    trusted_process: &TrustedProcessDecision,
-    policy: &SecurityPolicy,
-) -> bool {
-    if process_name != "git" {
-        return false;
+    #[test]
+    fn metadata_flag_change_only_tracks_protection_flags() {
+        assert!(is_protected_flag_change(libc::UF_IMMUTABLE, 0));
+        assert!(is_protected_flag_change(0, libc::UF_HIDDEN));
+        assert!(!is_protected_flag_change(
+            libc::UF_IMMUTABLE,
+            libc::UF_IMMUTABLE
+        ));
+        assert!(!is_protected_flag_change(0, libc::UF_NODUMP));
     }
 
-    let args = match get_process_argv(pid) {
-        Some(args) => args,
-        None => return false,
-    };
-    should_allow_git_merge_pull_worktree_change_in_ai_context(process_name, &args, trusted_process, policy)
-}
+    #[test]
+    fn metadata_owner_change_ignores_unchanged_ids() {
+        assert!(is_owner_change(501, 20, 0, 20));
+        assert!(is_owner_change(501, 20, u32::MAX, 0));
+        assert!(!is_owner_change(501, 20, 501, 20));
+        assert!(!is_owner_change(501, 20, u32::MAX, u32::MAX));
+    }
 
-fn is_vcs_metadata_path(path: &str) -> bool {
-    trim_trailing_slashes(path)
-        .split('/')
-        .any(|component| component == ".git" || component == ".jj")
-}
+    #[test]
+    fn metadata_utimes_backdating_exempts_fresh_files() {
+        let backdated = libc::timespec {
+            tv_sec: 1_000,
+            tv_nsec: 0,
+        };
+        assert!(is_backdated_utimes(5_000, &backdated, 2_000, 10_000));
+        assert!(!is_backdated_utimes(5_000, &backdated, 9_990, 10_000));
+        let forward = libc::timespec {
+            tv_sec: 6_000,
+            tv_nsec: 0,
+        };
+        assert!(!is_backdated_utimes(5_000, &forward, 2_000, 10_000));
+        let now_marker = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: libc::UTIME_NOW,
+        };
+        assert!(!is_backdated_utimes(5_000, &now_marker, 2_000, 10_000));
+    }
 
-fn should_allow_vcs_metadata_unlink_in_ai_context(
-    path: &str,
-    process_name: Option<&str>,
-    trusted_process: &TrustedProcessDecision,
-    policy: &SecurityPolicy,
-) -> bool {
-    if !policy.allow_vcs_metadata_in_ai_context || !is_vcs_metadata_path(path) {
-        return false;
+    #[test]
+    fn metadata_xattr_allowlist_supports_prefix_entries() {
+        let policy = test_policy();
+        assert!(policy.is_metadata_xattr_allowed("com.apple.provenance"));
+        assert!(policy.is_metadata_xattr_allowed("com.apple.metadata:kMDItemWhereFroms"));
+        assert!(!policy.is_metadata_xattr_allowed("com.apple.ResourceFork"));
+        assert!(!policy.is_metadata_xattr_allowed("user.backup"));
     }
-    let process_name = match process_name {
-        Some(name) => name,
-        None => return false,
-    };
 
-    is_vcs_tool(process_name) && trusted_process.is_trusted()
-}
+    #[test]
+    fn metadata_gate_requires_protected_path_and_enabled_op() {
+        let mut policy = test_policy();
+        let change = MetadataChange {
+            op: "setmode",
+            path: "/Users/jqwang/project/data.db".to_string(),
+            file: FileIdentity::default(),
+            destructive: true,
+        };
+        assert!(should_gate_protected_metadata_change(
+            &change,
+            &policy,
+            "/Users/jqwang"
+        ));
 
-fn should_allow_vcs_metadata_rename_in_ai_context(
-    source_path: &str,
-    dest_path: &str,
-    process_name: Option<&str>,
-    trusted_process: &TrustedProcessDecision,
-    policy: &SecurityPolicy,
-) -> bool {
-    if !policy.allow_vcs_metadata_in_ai_context
-        || !is_vcs_metadata_path(source_path)
-        || !is_vcs_metadata_path(dest_path)
-    {
-        return false;
-    }
-    let process_name = match process_name {
-        Some(name) => name,
-        None => return false,
-    };
+        let outside = MetadataChange {
+            op: "setmode",
+            path: "/Users/jqwang/Desktop/data.db".to_string(),
+            file: FileIdentity::default(),
+            destructive: true,
+        };
+        assert!(!should_gate_protected_metadata_change(
+            &outside,
+            &policy,
+            "/Users/jqwang"
+        ));
 
-    is_vcs_tool(process_name) && trusted_process.is_trusted()
-}
+        let benign = MetadataChange {
+            op: "setmode",
+            path: "/Users/jqwang/project/run.sh".to_string(),
+            file: FileIdentity::default(),
+            destructive: false,
+        };
+        assert!(!should_gate_protected_metadata_change(
+            &benign,
+            &policy,
+            "/Users/jqwang"
+        ));
 
-fn is_read_intent(fflag: i32) -> bool {
-    (fflag & FFLAG_READ) != 0 || !is_write_intent(fflag)
-}
+        policy.metadata_gate_ops = vec!["setflags".to_string()];
+        assert!(!should_gate_protected_metadata_change(
+            &change,
+            &policy,
+            "/Users/jqwang"
+        ));
+        policy.metadata_gate_ops = default_metadata_gate_ops();
+        policy.metadata_gate_enabled = false;
+        assert!(!should_gate_protected_metadata_change(
+            &change,
+            &policy,
+            "/Users/jqwang"
+        ));
+    }
 
-fn is_write_intent(fflag: i32) -> bool {
-    (fflag & FFLAG_WRITE) != 0
-}
+    #[test]
+    fn tainted_open_write_outside_allow_zone_is_denied() {
+        let policy = test_sensitive_policy();
+        let mut taint = TaintState::new(600);
+        taint.mark(100, 1_000);
+        assert!(should_deny_tainted_open_write(
+            100,
+            "/Users/jqwang/Desktop/out.txt",
+            FFLAG_WRITE,
+            Some("python3"),
+            &TrustedProcessDecision::NotTrusted,
+            1_100,
+            &taint,
+            &policy
+        ));
+    }
 
-fn should_fast_allow_open(path: &str, fflag: i32, policy: &SecurityPolicy) -> bool {
-    !is_write_intent(fflag) && !policy.is_sensitive_path(path)
-}
+    #[test]
+    fn tainted_open_read_only_outside_allow_zone_is_allowed() {
+        let policy = test_sensitive_policy();
+        let mut taint = TaintState::new(600);
+        taint.mark(100, 1_000);
+        assert!(!should_deny_tainted_open_write(
+            100,
+            "/Users/jqwang/Desktop/out.txt",
+            FFLAG_READ,
+            Some("python3"),
+            &TrustedProcessDecision::NotTrusted,
+            1_100,
+            &taint,
+            &policy
+        ));
+    }
 
-fn should_deny_sensitive_open_for_process(
-    path: &str,
-    is_ai_context: bool,
-    fflag: i32,
-    policy: &SecurityPolicy,
-    is_guard_process: bool,
-) -> bool {
-    policy.read_gate_enabled
-        && policy.is_sensitive_path(path)
-        && is_read_intent(fflag)
-        && !is_ai_context
-        && !is_guard_process
-        && !policy.is_sensitive_read_override_active_for_path(path, now_ts())
-}
+    #[test]
+    fn exfil_tool_is_denied_in_ai_context() {
+        let policy = test_sensitive_policy();
+        assert!(should_deny_exec_in_ai_context("curl", true, &policy));
+    }
 
-fn should_deny_sensitive_open(path: &str, is_ai_context: bool, fflag: i32, policy: &SecurityPolicy) -> bool {
-    should_deny_sensitive_open_for_process(path, is_ai_context, fflag, policy, false)
-}
+    #[test]
+    fn exfil_tool_is_allowed_outside_ai_context() {
+        let policy = test_sensitive_policy();
+        assert!(!should_deny_exec_in_ai_context("curl", false, &policy));
+    }
 
-fn is_sensitive_read_observer_process(process_name: &str) -> bool {
-    process_name == "AgentSmith"
-}
+    #[test]
+    fn denial_record_includes_reason_code() {
+        let record = DenialRecord::for_test_reason("SENSITIVE_READ_NON_AI");
+        let json = serde_json::to_string(&record).expect("serialize");
+        assert!(json.contains("SENSITIVE_READ_NON_AI"));
+    }
 
-fn is_sensitive_read_observer_path(path: &str, home: &str) -> bool {
-    let policy_path = format!("{}/.agentsmith-rs/policy.json", home);
-    let policy_lock_path = format!("{}.lock", policy_path);
-    let guard_dir = format!("{}/.agentsmith-rs/guard", home);
-    path == policy_path
-        || path == policy_lock_path
-        || path_prefix_match(path, &policy_lock_path)
-        || path_prefix_match(path, &guard_dir)
-}
+    #[test]
+    fn audit_only_coalescer_aggregates_same_signature_within_window() {
+        let mut coalescer = AuditOnlyCoalescer::default();
 
-fn should_allow_sensitive_read_observer(path: &str, process_name: &str, home: &str) -> bool {
-    is_sensitive_read_observer_process(process_name) && is_sensitive_read_observer_path(path, home)
-}
+        let mut first = DenialRecord::for_test_reason(REASON_SENSITIVE_READ_NON_AI);
+        first.ts = 1;
+        first.path = "/Users/jqwang/.codex/config.toml".to_string();
+        first.process = "zsh".to_string();
+        first.ancestor = "none".to_string();
 
-fn should_mark_taint_on_sensitive_read(path: &str, policy: &SecurityPolicy, home: &str) -> bool {
-    policy.is_sensitive_path(path) && !is_sensitive_read_observer_path(path, home)
-}
+        let mut second = first.clone();
+        second.ts = 5;
+        second.pid = Some(2001);
+        second.ppid = Some(2000);
 
-fn should_deny_sensitive_transfer(source: &str, dest: &str, policy: &SecurityPolicy) -> bool {
-    policy.transfer_gate_enabled
-        && policy.is_sensitive_path(source)
-        && !policy.is_sensitive_export_allowed(dest)
-        && !policy.is_sensitive_path(dest)
-}
+        assert!(coalescer.ingest(first).is_empty());
+        assert!(coalescer.ingest(second).is_empty());
 
-fn should_allow_vcs_metadata_tainted_write(
-    target_path: &str,
-    process_name: Option<&str>,
-    trusted_process: &TrustedProcessDecision,
-    policy: &SecurityPolicy,
-) -> bool {
-    if !policy.allow_vcs_metadata_in_ai_context || !is_vcs_metadata_path(target_path) {
-        return false;
+        let ready = coalescer.take_ready(11, false);
+        assert_eq!(ready.len(), 1);
+        let entry = &ready[0];
+        assert_eq!(entry.count, 2);
+        assert_eq!(entry.first_ts, 1);
+        assert_eq!(entry.last_ts, 5);
     }
-    let process_name = match process_name {
-        Some(name) => name,
-        None => return false,
-    };
 
-    is_vcs_tool(process_name) && trusted_process.is_trusted()
-}
+    #[test]
+    fn audit_only_coalescer_keeps_distinct_signatures_separate() {
+        let mut coalescer = AuditOnlyCoalescer::default();
 
-fn should_deny_tainted_write(
-    pid: i32,
-    target: &str,
-    process_name: Option<&str>,
-    trusted_process: &TrustedProcessDecision,
-    now: u64,
-    taint: &TaintState,
-    policy: &SecurityPolicy,
-) -> bool {
-    taint.is_tainted(pid, now)
-        && !is_safe_taint_device_path(target)
-        && !policy.is_sensitive_export_allowed(target)
-        && !policy.is_sensitive_path(target)
-        && !should_allow_vcs_metadata_tainted_write(target, process_name, trusted_process, policy)
-}
+        let mut first = DenialRecord::for_test_reason(REASON_SENSITIVE_READ_NON_AI);
+        first.ts = 1;
+        first.path = "/Users/jqwang/.codex/config.toml".to_string();
 
-fn should_deny_tainted_open_write(
-    pid: i32,
-    path: &str,
-    fflag: i32,
-    process_name: Option<&str>,
-    trusted_process: &TrustedProcessDecision,
-    now: u64,
-    taint: &TaintState,
-    policy: &SecurityPolicy,
-) -> bool {
-    is_write_intent(fflag) && should_deny_tainted_write(pid, path, process_name, trusted_process, now, taint, policy)
-}
+        let mut second = first.clone();
+        second.ts = 2;
+        second.path = "/Users/jqwang/.codex/history.jsonl".to_string();
 
-fn should_deny_exec_in_ai_context(proc_name: &str, is_ai_context: bool, policy: &SecurityPolicy) -> bool {
-    policy.exec_gate_enabled && is_ai_context && policy.exec_exfil_tool_blocklist.iter().any(|tool| tool == proc_name)
-}
+        assert!(coalescer.ingest(first).is_empty());
+        assert!(coalescer.ingest(second).is_empty());
 
-fn join_path_component(dir: &str, name: &str) -> String {
-    let normalized_dir = trim_trailing_slashes(dir);
-    if normalized_dir == "/" {
-        format!("/{}", name)
-    } else {
-        format!("{}/{}", normalized_dir, name)
+        let mut ready = coalescer.take_ready(100, true);
+        ready.sort_by(|left, right| left.sample.path.cmp(&right.sample.path));
+        assert_eq!(ready.len(), 2);
+        assert_eq!(ready[0].count, 1);
+        assert_eq!(ready[1].count, 1);
     }
-}
 
-#[derive(Debug)]
-struct GateDenyDecision {
-    process: String,
-    ancestor: String,
-    reason: &'static str,
-}
+    #[test]
+    fn audit_only_log_line_includes_count_only_for_aggregated_entries() {
+        let mut sample = DenialRecord::for_test_reason(REASON_SENSITIVE_READ_NON_AI);
+        sample.ts = 7;
+        sample.path = "/Users/jqwang/.codex/config.toml".to_string();
 
-/// Core decision: should this operation be denied?
-/// Returns Some(decision) if denied, None if allowed.
-fn should_deny(
-    path: &str,
-    pid: i32,
-    home: &str,
-    policy: &SecurityPolicy,
-    cache: &mut HashMap<i32, CachedAncestor>,
-    trust_cache: &Arc<Mutex<TrustedProcessCache>>,
-    signature_cache: &Arc<Mutex<BinarySignatureCache>>,
-    signature_refresh_tx: &mpsc::SyncSender<String>,
-) -> Option<GateDenyDecision> {
-    // 1. Not in protected zone → ALLOW
-    if !policy.is_protected(path, home) {
-        return None;
-    }
+        let single = AuditOnlyAggregateEntry::new(sample.clone());
+        let single_json = serialize_audit_only_entry(&single).expect("single audit json");
+        assert!(!single_json.contains("\"count\""));
+        assert!(!single_json.contains("\"first_ts\""));
+        assert!(!single_json.contains("\"last_ts\""));
 
-    // 2. System temp paths → ALLOW
-    if is_system_temp(path, home) {
-        return None;
+        let mut aggregated = AuditOnlyAggregateEntry::new(sample);
+        aggregated.merge_same_signature(8);
+        aggregated.merge_same_signature(10);
+        let aggregated_json = serialize_audit_only_entry(&aggregated).expect("aggregated audit json");
+        assert!(aggregated_json.contains("\"count\":3"));
+        assert!(aggregated_json.contains("\"first_ts\":7"));
+        assert!(aggregated_json.contains("\"last_ts\":10"));
     }
 
-    // 3. Not in AI agent process tree → ALLOW (user operation)
-    let ai_ancestor = match find_ai_ancestor(pid, policy, cache) {
-        Some(name) => name,
-        None => return None,
-    };
-
-    let process_name = process_name_for_pid(pid).unwrap_or_else(|| format!("pid:{}", pid));
-    let trusted_process = evaluate_trusted_process(
-        pid,
-        process_name.as_str(),
-        policy,
-        trust_cache,
-        signature_cache,
-        signature_refresh_tx,
-    );
+    #[test]
+    fn denial_feedback_for_sensitive_read_has_read_specific_guidance() {
+        let record = DenialRecord::for_test_reason(REASON_SENSITIVE_READ_NON_AI);
+        let feedback = build_denial_feedback("/Users/jqwang", &record);
+        assert!(feedback.contains("non-AI reads are denied"));
+        assert!(feedback.contains("AI context"));
+        assert!(!feedback.contains("agentsmith-override --minutes 3"));
+    }
 
-    // 4. Keep git/jj commit internals workable while still blocking `git rm` on working tree files.
-    if should_allow_vcs_metadata_unlink_in_ai_context(path, Some(process_name.as_str()), &trusted_process, policy) {
-        return None;
+    #[test]
+    fn denial_feedback_for_protected_delete_keeps_override_guidance() {
+        let record = DenialRecord::for_test_reason(REASON_PROTECTED_ZONE_AI_DELETE);
+        let feedback = build_denial_feedback("/Users/jqwang", &record);
+        assert!(feedback.contains("agentsmith-override --minutes 3"));
+        assert!(feedback.contains("agentsmith-quarantine"));
     }
-    let vcs_metadata_mismatch = policy.allow_vcs_metadata_in_ai_context
-        && is_vcs_metadata_path(path)
-        && is_vcs_tool(process_name.as_str())
-        && trusted_process.is_identity_mismatch();
 
-    // 5. Keep `git merge` / `git pull` workflow writable inside protected zones (no rebase/rm bypass).
-    if should_allow_git_merge_pull_for_process(pid, process_name.as_str(), &trusted_process, policy) {
-        return None;
+    #[test]
+    fn denial_feedback_for_protected_truncate_has_overwrite_guidance() {
+        let record = DenialRecord::for_test_reason(REASON_PROTECTED_ZONE_AI_TRUNCATE);
+        let feedback = build_denial_feedback("/Users/jqwang", &record);
+        assert!(feedback.contains("Truncating/overwriting"));
+        assert!(feedback.contains("agentsmith-override --minutes 3"));
     }
-    let merge_pull_mismatch = policy.allow_git_merge_pull_in_ai_context
-        && process_name == "git"
-        && trusted_process.is_identity_mismatch()
-        && get_process_argv(pid).is_some_and(|args| is_git_merge_or_pull_invocation(&args));
 
-    // 5. Optional compatibility mode for trusted tools in AI context.
-    if policy.allow_trusted_tools_in_ai_context && trusted_process.is_trusted() {
-        return None;
+    #[test]
+    fn denial_feedback_for_protected_metadata_names_operation() {
+        let mut record = DenialRecord::for_test_reason(REASON_PROTECTED_ZONE_AI_METADATA);
+        record.op = "setflags".to_string();
+        let feedback = build_denial_feedback("/Users/jqwang", &record);
+        assert!(feedback.contains("metadata change (setflags)"));
+        assert!(feedback.contains("agentsmith-override --minutes 3"));
     }
-    let trusted_tool_mismatch = policy.allow_trusted_tools_in_ai_context
-        && policy.is_trusted_tool(process_name.as_str())
-        && trusted_process.is_identity_mismatch();
 
-    let reason = if vcs_metadata_mismatch || merge_pull_mismatch || trusted_tool_mismatch {
-        REASON_TRUST_IDENTITY_MISMATCH
-    } else {
-        REASON_PROTECTED_ZONE_AI_DELETE
-    };
+    #[test]
+    fn denial_feedback_and_record_include_session_metadata() {
+        let mut record = DenialRecord::for_test_reason(REASON_PROTECTED_ZONE_AI_DELETE);
+        record.session = Some(AgentSession {
+            id: "claude-4242-1700000000".to_string(),
+            agent: "claude".to_string(),
+            pid: 4242,
+            started_at: 1_700_000_000,
+            cwd: "/Users/jqwang/project".to_string(),
+            tty: Some("/dev/ttys003".to_string()),
+            source: SESSION_SOURCE_LAUNCHER,
+        });
+        let feedback = build_denial_feedback("/Users/jqwang", &record);
+        assert!(feedback.contains("Session: claude-4242-1700000000 (agent claude, cwd /Users/jqwang/project)"));
 
-    // 6. In AI agent context and protected path → DENY
-    Some(GateDenyDecision {
-        process: process_name,
-        ancestor: ai_ancestor,
-        reason,
-    })
-}
+        let json = serde_json::to_value(&record).expect("serialize denial");
+        assert_eq!(json["session"]["agent"], "claude");
+        assert_eq!(json["session"]["pid"], 4242);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        record.session = None;
+        let json = serde_json::to_value(&record).expect("serialize denial");
+        assert!(json.get("session").is_none());
+    }
 
     #[test]
-    fn sensitive_policy_defaults_are_safe() {
-        let policy: SecurityPolicy =
-            serde_json::from_str(r#"{"protected_zones":[],"temporary_overrides":[]}"#).expect("default policy json");
-        assert!(policy.sensitive_zones.is_empty());
-        assert!(policy.read_gate_enabled);
-        assert!(policy.transfer_gate_enabled);
-        assert!(policy.exec_gate_enabled);
-        assert!(!policy.audit_only_mode);
-        assert!(policy.trusted_tool_identities.is_empty());
-        assert!(!policy.trusted_identity_require_cdhash);
+    fn keyed_denial_feedback_separates_concurrent_agents() {
+        let tmp_dir = std::env::temp_dir().join(format!(
+            "agentsmith-feedback-{}-{}",
+            std::process::id(),
+            now_ts()
+        ));
+        fs::create_dir_all(&tmp_dir).expect("create feedback temp dir");
+
+        let mut codex = DenialRecord::for_test_reason(REASON_PROTECTED_ZONE_AI_DELETE);
+        codex.path = "/Users/jqwang/01-agent/a.txt".to_string();
+        codex.session = Some(AgentSession {
+            id: "codex-100-10".to_string(),
+            agent: "codex".to_string(),
+            pid: 100,
+            started_at: 10,
+            cwd: "/Users/jqwang/01-agent".to_string(),
+            tty: None,
+            source: SESSION_SOURCE_DETECTOR,
+        });
+        let mut other = DenialRecord::for_test_reason(REASON_SENSITIVE_READ_NON_AI);
+        other.pid = Some(300);
+        other.ppid = Some(200);
+        assert_eq!(denial_feedback_key(&codex), "codex-100-10");
+        assert_eq!(denial_feedback_key(&other), "pid-200");
         assert_eq!(
-            policy.taint_ttl_seconds_or_default(),
-            DEFAULT_TAINT_TTL_SECS
+            denial_feedback_key(&DenialRecord::for_test_reason("x")),
+            "unknown"
         );
+
+        for record in [&codex, &other, &codex] {
+            write_keyed_denial_feedback(
+                &tmp_dir,
+                record,
+                &build_denial_feedback("/Users/jqwang", record),
+                50,
+            )
+            .expect("write keyed feedback");
+        }
+        let codex_dir = tmp_dir.join("feedback").join("codex-100-10");
+        let text = fs::read_to_string(codex_dir.join("last_denial.txt")).expect("read text feedback");
+        assert!(text.contains("/Users/jqwang/01-agent/a.txt"));
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(codex_dir.join("last_denial.json")).expect("read json"))
+                .expect("parse json feedback");
+        assert_eq!(json["session"]["id"], "codex-100-10");
+        let recent = fs::read_to_string(codex_dir.join("denials.jsonl")).expect("read recent denials");
+        assert_eq!(recent.lines().count(), 2);
+        let other_text = fs::read_to_string(tmp_dir.join("feedback").join("pid-200").join("last_denial.txt"))
+            .expect("read other feedback");
+        assert!(other_text.contains(REASON_SENSITIVE_READ_NON_AI));
+
+        let _ = fs::remove_dir_all(tmp_dir);
     }
 
     #[test]
-    fn policy_taint_ttl_seconds_can_be_overridden() {
-        let policy: SecurityPolicy = serde_json::from_str(
+    fn denial_feedback_templates_select_by_reason_agent_and_locale() {
+        let config: FeedbackTemplateConfig = serde_json::from_str(
             r#"{
-                "protected_zones":[],
-                "temporary_overrides":[],
-                "taint_ttl_seconds":42
+                "locale": "zh",
+                "agent_locales": {"codex": "en"},
+                "templates": [
+                    {"reason": "*", "text": "generic {path}\n"},
+                    {"reason": "PROTECTED_ZONE_AI_DELETE", "locale": "zh", "text": "删除 {path}，放行：{override_command}\n"},
+                    {"reason": "PROTECTED_ZONE_AI_DELETE", "agent": "claude", "locale": "zh", "text": "claude {zone} {unknown}\n"}
+                ]
             }"#,
         )
-        .expect("policy json");
-        assert_eq!(policy.taint_ttl_seconds_or_default(), 42);
+        .expect("parse templates");
+
+        let mut record = DenialRecord::for_test_reason(REASON_PROTECTED_ZONE_AI_DELETE);
+        record.ancestor = "Claude".to_string();
+        let feedback = build_denial_feedback_with("/Users/jqwang", &record, &config);
+        assert!(feedback.starts_with("[AGENTSMITH DENIED]"));
+        assert!(feedback.ends_with("claude test-zone {unknown}\n"));
+
+        record.ancestor = "gemini".to_string();
+        let feedback = build_denial_feedback_with("/Users/jqwang", &record, &config);
+        assert!(feedback.contains("删除 /tmp/test，放行：agentsmith-override --minutes 3 /tmp/test"));
+
+        record.ancestor = "codex".to_string();
+        let feedback = build_denial_feedback_with("/Users/jqwang", &record, &config);
+        assert!(feedback.ends_with("generic /tmp/test\n"));
+
+        let zh_only = FeedbackTemplateConfig {
+            locale: Some("zh".to_string()),
+            ..FeedbackTemplateConfig::default()
+        };
+        let record = DenialRecord::for_test_reason(REASON_TAINT_WRITE_OUT);
+        let feedback = build_denial_feedback_with("/Users/jqwang", &record, &zh_only);
+        assert!(feedback.contains("/Users/jqwang/.agentsmith-rs/guard/quarantine"));
+        assert!(feedback.contains("建议下一步"));
+        let fr = FeedbackTemplateConfig {
+            locale: Some("fr".to_string()),
+            ..FeedbackTemplateConfig::default()
+        };
+        assert!(build_denial_feedback_with("/Users/jqwang", &record, &fr).contains("tainted by a prior sensitive read"));
     }
 
     #[test]
-    fn policy_audit_only_mode_defaults_to_false() {
-        let policy: SecurityPolicy =
-            serde_json::from_str(r#"{"protected_zones":[],"temporary_overrides":[]}"#).expect("policy json");
-        assert!(!policy.audit_only_mode);
+    fn control_envelope_checks_peer_uid_and_version() {
+        let request = ControlRequest {
+            version: CONTROL_PROTOCOL_VERSION,
+            id: Some("../x".to_string()),
+            command: "status".to_string(),
+            path: None,
+            minutes: None,
+            approval_id: None,
+            scope_pid: None,
+            scope_session: None,
+            ops: Vec::new(),
+            rename_to: None,
+            max_uses: None,
+            pin: false,
+            paths: Vec::new(),
+            glob: None,
+        };
+        let owner = ControlPeer { uid: 501, pid: 4242 };
+        assert!(validate_control_envelope(&request, &owner, 501).is_ok());
+        assert!(validate_control_envelope(&request, &ControlPeer { uid: 0, pid: 1 }, 501).is_ok());
+        let err = validate_control_envelope(&request, &ControlPeer { uid: 502, pid: 4242 }, 501)
+            .expect_err("other users are rejected");
+        assert!(err.contains("uid 502"));
+
+        let future = ControlRequest {
+            version: CONTROL_PROTOCOL_VERSION + 1,
+            ..request.clone()
+        };
+        assert!(validate_control_envelope(&future, &owner, 501)
+            .expect_err("unknown version")
+            .contains("unsupported protocol version"));
+
+        assert_eq!(
+            control_request_id(&request, &owner),
+            sanitize_component("../x")
+        );
+        let anonymous = ControlRequest { id: None, ..request };
+        assert!(control_request_id(&anonymous, &owner).starts_with("ctl-4242-"));
+        assert!(control_command_mutates_overrides("override.grant"));
+        assert!(!control_command_mutates_overrides("override.list"));
     }
 
     #[test]
-    fn policy_audit_only_mode_can_be_enabled() {
-        let policy: SecurityPolicy = serde_json::from_str(
-            r#"{
-                "protected_zones":[],
-                "temporary_overrides":[],
-                "audit_only_mode":true
-            }"#,
-        )
-        .expect("policy json");
-        assert!(policy.audit_only_mode);
+    fn control_override_list_reports_remaining_ttl_and_rejects_unknown_commands() {
+        let overrides = vec![
+            TemporaryOverrideEntry::Rule(TemporaryOverrideRule {
+                path: "/Users/jqwang/project/build".to_string(),
+                expires_at: Some(1_180),
+                created_at: Some(1_000),
+                created_by: Some(OVERRIDE_CREATED_BY_HELPER.to_string()),
+                scope: None,
+                ops: Vec::new(),
+                rename_to: None,
+                max_uses: None,
+                uses: 0,
+                pin: None,
+                id: None,
+            }),
+            TemporaryOverrideEntry::Rule(TemporaryOverrideRule {
+                path: "/Users/jqwang/project/old".to_string(),
+                expires_at: Some(900),
+                created_at: Some(800),
+                created_by: None,
+                scope: None,
+                ops: Vec::new(),
+                rename_to: None,
+                max_uses: None,
+                uses: 0,
+                pin: None,
+                id: None,
+            }),
+        ];
+        let list = control_override_list(&overrides, 1_000);
+        assert_eq!(list.as_array().map(Vec::len), Some(1));
+        assert_eq!(list[0]["path"], "/Users/jqwang/project/build");
+        assert_eq!(list[0]["remaining_secs"], 180);
+
+        let mut runtime = overrides.clone();
+        let mut window = VecDeque::new();
+        let peer = ControlPeer { uid: 501, pid: 4242 };
+        let request = ControlRequest {
+            version: CONTROL_PROTOCOL_VERSION,
+            id: Some("req-1".to_string()),
+            command: "override.explode".to_string(),
+            path: None,
+            minutes: None,
+            approval_id: None,
+            scope_pid: None,
+            scope_session: None,
+            ops: Vec::new(),
+            rename_to: None,
+            max_uses: None,
+            pin: false,
+            paths: Vec::new(),
+            glob: None,
+        };
+        let (reply, _replies) = mpsc::channel();
+        let mut pending = PendingOverrideQueue::default();
+        let (changed, response) = handle_control_override_command(
+            &request,
+            &peer,
+            &reply,
+            &test_policy(),
+            "/Users/jqwang",
+            &mut runtime,
+            &mut window,
+            &mut pending,
+            &Mutex::default(),
+        );
+        assert!(!changed);
+        assert_eq!(response.status, "invalid");
+        assert!(response.message.contains("unknown command"));
+        assert!(window.is_empty());
+        assert_eq!(runtime.len(), 2);
+    }
+
+    #[test]
+    fn control_socket_round_trip_uses_kernel_peer_credentials() {
+        let dir = std::env::temp_dir().join(format!("as-ctl-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("create socket dir");
+        let socket = dir.join("control.sock");
+        let _ = fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket).expect("bind control socket");
+        let owner_uid = unsafe { libc::getuid() };
+
+        let (commands_tx, commands_rx) = mpsc::sync_channel::<ControlCommand>(1);
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().expect("accept");
+            serve_control_connection(stream, owner_uid, &commands_tx);
+        });
+        let policy_thread = thread::spawn(move || {
+            let command = commands_rx.recv().expect("queued command");
+            let peer = command.peer.clone();
+            let id = control_request_id(&command.request, &peer);
+            // The peer double-forked away from an agent: its live parent is launchd.
+            let policy = test_policy();
+            let process_table = Mutex::new(ProcessTable::default());
+            {
+                let mut table = process_table.lock().expect("process table");
+                table.record_exec(
+                    900_002,
+                    None,
+                    ProcessLinks::from_ppid(1),
+                    "/usr/local/bin/codex".to_string(),
+                    vec![],
+                    &policy,
+                    50,
+                );
+                table.record_exec(
+                    peer.pid,
+                    process_start_time_for_pid(peer.pid),
+                    ProcessLinks {
+                        ppid: 1,
+                        original_ppid: 900_002,
+                        responsible_pid: 0,
+                    },
+                    "/usr/local/bin/agentsmith-rs".to_string(),
+                    vec!["agentsmith-rs".to_string(), "ctl".to_string()],
+                    &policy,
+                    50,
+                );
+            }
+            let origin = validate_control_peer_origin(&peer, &policy, &process_table);
+            let _ = command.reply.send(ControlResponse::ok(
+                &id,
+                command.request.command.clone(),
+                Some(serde_json::json!({
+                    "uid": peer.uid,
+                    "pid": peer.pid,
+                    "origin_error": origin.err(),
+                })),
+            ));
+        });
+
+        let request = ControlRequest {
+            version: CONTROL_PROTOCOL_VERSION,
+            id: Some("status-1".to_string()),
+            command: "status".to_string(),
+            path: None,
+            minutes: None,
+            approval_id: None,
+            scope_pid: None,
+            scope_session: None,
+            ops: Vec::new(),
+            rename_to: None,
+            max_uses: None,
+            pin: false,
+            paths: Vec::new(),
+            glob: None,
+        };
+        let response = send_control_request(&socket, &request, |_| {}).expect("control response");
+        server.join().expect("server thread");
+        policy_thread.join().expect("policy thread");
+        assert_eq!(response.status, "ok");
+        assert_eq!(response.id, "status-1");
+        assert_eq!(response.message, "status");
+        let data = response.data.expect("peer data");
+        assert_eq!(data["uid"], owner_uid);
+        assert_eq!(data["pid"], std::process::id());
+        assert!(data["origin_error"]
+            .as_str()
+            .is_some_and(|err| err.contains("ancestor: codex")));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    /// Approval-required policy protecting `<home>/project` in a fresh home.
+    fn approval_fixture(tag: &str) -> (PathBuf, String, SecurityPolicy) {
+        let home_dir = std::env::temp_dir().join(format!("as-approval-{}-{}", tag, std::process::id()));
+        let _ = fs::remove_dir_all(&home_dir);
+        fs::create_dir_all(&home_dir).expect("create home");
+        let home = home_dir.to_string_lossy().into_owned();
+        let mut policy = test_policy();
+        policy.protected_zones = vec![format!("{}/project", home)];
+        policy.override_approval_required = true;
+        (home_dir, home, policy)
+    }
+
+    fn approval_request(home: &str, id: &str, action: &str, approval_id: Option<&str>) -> OverrideRequest {
+        OverrideRequest {
+            id: id.to_string(),
+            action: action.to_string(),
+            path: approval_id.is_none().then(|| format!("{}/project/build", home)),
+            minutes: approval_id.is_none().then_some(3),
+            requester_pid: Some(std::process::id() as i32),
+            approval_id: approval_id.map(str::to_string),
+            scope_pid: None,
+            scope_session: None,
+            ops: Vec::new(),
+            rename_to: None,
+            max_uses: None,
+            pin: false,
+            paths: Vec::new(),
+            glob: None,
+        }
+    }
+
+    /// `(approval_id, status)` of every audit record.
+    fn override_audit_steps(home_dir: &Path) -> Vec<(String, String)> {
+        let audit = fs::read_to_string(home_dir.join(".agentsmith-rs/guard/override-audit.jsonl")).expect("audit log");
+        audit
+            .lines()
+            .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+            .map(|record| {
+                (
+                    record["approval_id"].as_str().unwrap_or_default().to_string(),
+                    record["status"].as_str().unwrap_or_default().to_string(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn override_approval_parks_grants_until_a_human_decides() {
+        let (home_dir, home, policy) = approval_fixture("approve");
+        let requester = test_party(std::process::id() as i32, 1, "/dev/ttys001");
+        let approver = test_party(requester.pid + 1, 2, "/dev/ttys002");
+        let mut overrides = Vec::new();
+        let mut pending = PendingOverrideQueue::default();
+        let (reply_tx, reply_rx) = mpsc::channel();
+        let mut route = |req: &OverrideRequest, party: &OverrideParty, overrides: &mut Vec<TemporaryOverrideEntry>| {
+            route_override_request(
+                req,
+                party,
+                PendingReply::Socket(reply_tx.clone()),
+                &policy,
+                &home,
+                overrides,
+                &mut pending,
+            )
+        };
+
+        let OverrideRouting::Pending(parked) = route(
+            &approval_request(&home, "req-a", "grant", None),
+            &requester,
+            &mut overrides,
+        ) else {
+            panic!("grant should wait for approval");
+        };
+        assert_eq!(parked.status, "pending");
+        assert!(parked.expires_at.is_some());
+        assert!(overrides.is_empty());
+
+        let OverrideRouting::Done(changed, own) = route(
+            &approval_request(&home, "self", "approve", Some("req-a")),
+            &requester,
+            &mut overrides,
+        ) else {
+            panic!("approve is decided immediately");
+        };
+        assert!(!changed);
+        assert!(own.message.contains("own requester"));
+
+        let OverrideRouting::Done(changed, approved) = route(
+            &approval_request(&home, "approve-a", "approve", Some("req-a")),
+            &approver,
+            &mut overrides,
+        ) else {
+            panic!("approve is decided immediately");
+        };
+        assert!(changed);
+        assert_eq!(approved.status, "ok");
+        assert_eq!(overrides.len(), 1);
+
+        assert!(reply_rx.try_recv().is_err(), "replies wait for the flush");
+        pending.flush_replies();
+        let reply = reply_rx.try_recv().expect("requester is answered");
+        assert_eq!((reply.id.as_str(), reply.status.as_str()), ("req-a", "ok"));
+        assert!(override_audit_steps(&home_dir).contains(&("req-a".to_string(), "ok".to_string())));
+        let _ = fs::remove_dir_all(&home_dir);
     }
 
     #[test]
-    fn save_policy_preserves_taint_ttl_seconds_field() {
-        let tmp_dir = std::env::temp_dir().join(format!(
-            "agentsmith-policy-roundtrip-{}-{}",
-            std::process::id(),
-            now_ts()
-        ));
-        fs::create_dir_all(&tmp_dir).expect("create temp dir");
-        let policy_path = tmp_dir.join("policy.json");
-        fs::write(
-            &policy_path,
-            r#"{
-              "protected_zones": [],
-              "temporary_overrides": [],
-              "taint_ttl_seconds": 42
-            }"#,
-        )
-        .expect("write policy json");
+    fn rejected_override_request_is_answered_with_an_error() {
+        let (home_dir, home, policy) = approval_fixture("reject");
+        let requester = test_party(std::process::id() as i32, 1, "/dev/ttys001");
+        let approver = test_party(requester.pid + 1, 2, "/dev/ttys002");
+        let mut overrides = Vec::new();
+        let mut pending = PendingOverrideQueue::default();
+        let (reply_tx, reply_rx) = mpsc::channel();
+        let routed = route_override_request(
+            &approval_request(&home, "req-b", "grant", None),
+            &requester,
+            PendingReply::Socket(reply_tx),
+            &policy,
+            &home,
+            &mut overrides,
+            &mut pending,
+        );
+        assert!(matches!(routed, OverrideRouting::Pending(_)));
 
-        let policy = load_policy(policy_path.to_str().expect("utf8 path")).expect("load policy");
-        save_policy(policy_path.to_str().expect("utf8 path"), &policy).expect("save policy");
+        let (changed, rejected) = decide_pending_override(
+            &approval_request(&home, "reject-b", "reject", Some("req-b")),
+            false,
+            &approver,
+            &policy,
+            &home,
+            &mut overrides,
+            &mut pending,
+        );
+        assert!(!changed);
+        assert_eq!(rejected.status, "ok");
+        assert!(overrides.is_empty());
+        assert!(pending.entries.is_empty());
 
-        let persisted = fs::read_to_string(&policy_path).expect("read persisted policy");
-        let persisted_json: serde_json::Value = serde_json::from_str(&persisted).expect("decode persisted policy");
+        pending.flush_replies();
+        let reply = reply_rx.try_recv().expect("requester is answered");
         assert_eq!(
-            persisted_json.get("taint_ttl_seconds").and_then(|value| value.as_u64()),
-            Some(42)
+            (reply.id.as_str(), reply.status.as_str()),
+            ("req-b", "error")
         );
-
-        let _ = fs::remove_dir_all(tmp_dir);
+        assert!(reply.message.contains("rejected by pid"));
+        assert!(override_audit_steps(&home_dir).contains(&("req-b".to_string(), "rejected".to_string())));
+        let _ = fs::remove_dir_all(&home_dir);
     }
 
     #[test]
-    fn sensitive_zone_boundary_match_is_correct() {
-        let mut policy = test_policy();
-        policy.sensitive_zones = vec!["/Users/jqwang/.codex".to_string()];
-        assert!(policy.is_sensitive_path("/Users/jqwang/.codex/sessions/a.json"));
-        assert!(!policy.is_sensitive_path("/Users/jqwang/.codexx/sessions/a.json"));
+    fn override_request_expires_without_a_decision() {
+        let (home_dir, home, policy) = approval_fixture("expire");
+        let requester = test_party(std::process::id() as i32, 1, "/dev/ttys001");
+        let mut overrides = Vec::new();
+        let mut pending = PendingOverrideQueue::default();
+        let (reply_tx, reply_rx) = mpsc::channel();
+        let routed = route_override_request(
+            &approval_request(&home, "req-c", "grant", None),
+            &requester,
+            PendingReply::Socket(reply_tx),
+            &policy,
+            &home,
+            &mut overrides,
+            &mut pending,
+        );
+        let OverrideRouting::Pending(parked) = routed else {
+            panic!("grant should wait for approval");
+        };
+        let deadline = parked.expires_at.expect("deadline");
+
+        expire_pending_overrides(&home, &mut pending, deadline - 1);
+        assert_eq!(pending.entries.len(), 1, "still inside its deadline");
+        expire_pending_overrides(&home, &mut pending, deadline);
+        assert!(pending.entries.is_empty());
+        assert!(overrides.is_empty());
+
+        pending.flush_replies();
+        let reply = reply_rx.try_recv().expect("requester is answered");
+        assert_eq!(
+            (reply.id.as_str(), reply.status.as_str()),
+            ("req-c", "error")
+        );
+        assert!(reply.message.contains("expired without approval"));
+        assert!(override_audit_steps(&home_dir).contains(&("req-c".to_string(), "expired".to_string())));
+        let _ = fs::remove_dir_all(&home_dir);
     }
 
     #[test]
-    fn sensitive_destination_allows_only_allowlist() {
-        let mut policy = test_policy();
-        policy.sensitive_export_allow_zones = vec!["/Users/jqwang/.agentsmith-rs/guard/quarantine".to_string()];
-        assert!(policy.is_sensitive_export_allowed("/Users/jqwang/.agentsmith-rs/guard/quarantine/a"));
-        assert!(!policy.is_sensitive_export_allowed("/Users/jqwang/Desktop/a"));
+    fn override_decision_needs_a_known_pending_request() {
+        let (home_dir, home, policy) = approval_fixture("unknown");
+        let approver = test_party(std::process::id() as i32 + 1, 2, "/dev/ttys002");
+        let mut overrides = Vec::new();
+        let mut pending = PendingOverrideQueue::default();
+        let mut decide = |req: &OverrideRequest| {
+            decide_pending_override(
+                req,
+                true,
+                &approver,
+                &policy,
+                &home,
+                &mut overrides,
+                &mut pending,
+            )
+        };
+
+        let (changed, unknown) = decide(&approval_request(
+            &home,
+            "approve-x",
+            "approve",
+            Some("req-x"),
+        ));
+        assert!(!changed);
+        assert_eq!(unknown.status, "error");
+        assert!(unknown.message.contains("no pending override request req-x"));
+
+        let mut missing = approval_request(&home, "approve-y", "approve", Some("req-y"));
+        missing.approval_id = None;
+        let (changed, missing) = decide(&missing);
+        assert!(!changed);
+        assert_eq!(missing.status, "invalid");
+        assert!(missing.message.contains("missing approval_id"));
+        let _ = fs::remove_dir_all(&home_dir);
     }
 
     #[test]
-    fn open_read_on_sensitive_path_denies_non_ai_context() {
-        let policy = test_sensitive_policy();
-        let decision = decide_sensitive_open_for_test(
-            "/Users/jqwang/.codex/chat/history.jsonl",
-            false,
-            FFLAG_READ,
-            &policy,
-        );
-        assert!(decision.deny);
+    fn invalid_or_excess_grants_never_reach_the_approval_queue() {
+        let (home_dir, home, policy) = approval_fixture("bounded");
+        let requester = test_party(std::process::id() as i32, 1, "/dev/ttys001");
+        let mut overrides = Vec::new();
+        let mut pending = PendingOverrideQueue::default();
+        let (reply_tx, _reply_rx) = mpsc::channel();
+        let mut route = |req: &OverrideRequest| {
+            route_override_request(
+                req,
+                &requester,
+                PendingReply::Socket(reply_tx.clone()),
+                &policy,
+                &home,
+                &mut overrides,
+                &mut pending,
+            )
+        };
+
+        let mut no_expire = approval_request(&home, "req-never", "grant", None);
+        no_expire.minutes = Some(0);
+        let OverrideRouting::Done(false, refused) = route(&no_expire) else {
+            panic!("an invalid grant is refused without parking");
+        };
+        assert_eq!(refused.status, "invalid");
+
+        for index in 0..MAX_PENDING_OVERRIDES {
+            let parked = route(&approval_request(
+                &home,
+                &format!("req-{}", index),
+                "grant",
+                None,
+            ));
+            assert!(matches!(parked, OverrideRouting::Pending(_)));
+        }
+        let OverrideRouting::Done(false, full) = route(&approval_request(&home, "req-full", "grant", None)) else {
+            panic!("a full queue refuses new grants");
+        };
+        assert_eq!(full.status, "error");
+        assert!(full.message.contains("too many pending override requests"));
+        assert_eq!(pending.entries.len(), MAX_PENDING_OVERRIDES);
+        let _ = fs::remove_dir_all(&home_dir);
+    }
+
+    fn test_party(pid: i32, parent: i32, tty: &str) -> OverrideParty {
+        OverrideParty {
+            pid,
+            process: "agentsmith-override".to_string(),
+            actor: OverrideActor {
+                ancestry: vec![(pid, pid as u64), (parent, parent as u64)],
+                session_id: None,
+            },
+            tty: Some(tty.to_string()),
+            uid: Some(501),
+            via_menubar: false,
+        }
     }
 
     #[test]
-    fn open_read_on_sensitive_path_denies_non_ai_with_zero_flag() {
-        let policy = test_sensitive_policy();
-        let decision = decide_sensitive_open_for_test("/Users/jqwang/.codex/chat/history.jsonl", false, 0, &policy);
-        assert!(decision.deny);
+    fn override_approver_must_be_outside_the_requesters_tree() {
+        let requester = test_party(700, 600, "/dev/ttys001");
+        assert!(check_override_approver(&requester, &test_party(800, 500, "/dev/ttys002")).is_ok());
+
+        let sibling = test_party(701, 600, "/dev/ttys002");
+        assert!(check_override_approver(&requester, &sibling)
+            .unwrap_err()
+            .contains("process tree"));
+        let mut child = test_party(702, 700, "/dev/ttys002");
+        child.actor.ancestry.push((600, 600));
+        assert!(check_override_approver(&requester, &child).is_err());
+
+        let mut agent_requester = requester.clone();
+        agent_requester.actor.session_id = Some("codex-1".to_string());
+        let mut same_session = test_party(800, 500, "/dev/ttys002");
+        same_session.actor.session_id = Some("codex-1".to_string());
+        assert!(check_override_approver(&agent_requester, &same_session)
+            .unwrap_err()
+            .contains("session"));
+
+        let same_tty = test_party(800, 500, "/dev/ttys001");
+        assert!(check_override_approver(&requester, &same_tty)
+            .unwrap_err()
+            .contains("terminal"));
+        let mut no_tty = test_party(800, 500, "/dev/ttys002");
+        no_tty.tty = None;
+        assert!(check_override_approver(&requester, &no_tty).is_err());
+        no_tty.uid = Some(0);
+        assert!(check_override_approver(&requester, &no_tty).is_ok());
+        let mut menubar = test_party(800, 500, "/dev/ttys002");
+        menubar.tty = None;
+        menubar.via_menubar = true;
+        assert!(check_override_approver(&requester, &menubar).is_ok());
+    }
+
+    fn scope_request(scope_pid: Option<i32>, scope_session: Option<&str>) -> OverrideRequest {
+        OverrideRequest {
+            id: "req-scope".to_string(),
+            action: "grant".to_string(),
+            path: Some("/Users/jqwang/project/build".to_string()),
+            minutes: Some(3),
+            requester_pid: None,
+            approval_id: None,
+            scope_pid,
+            scope_session: scope_session.map(str::to_string),
+            ops: Vec::new(),
+            rename_to: None,
+            max_uses: None,
+            pin: false,
+            paths: Vec::new(),
+            glob: None,
+        }
     }
 
     #[test]
-    fn open_read_on_sensitive_path_allows_ai_context() {
-        let policy = test_sensitive_policy();
-        let decision = decide_sensitive_open_for_test(
-            "/Users/jqwang/.codex/chat/history.jsonl",
-            true,
-            FFLAG_READ,
-            &policy,
+    fn override_scope_resolves_live_pids_and_sessions() {
+        let own_pid = std::process::id() as i32;
+        let own_start = get_process_bsd_info(own_pid).expect("own bsd info").pbi_start_tvsec;
+        let subtree = resolve_override_scope(&scope_request(Some(own_pid), None))
+            .expect("live pid")
+            .expect("scope");
+        assert_eq!(subtree.start_tvsec, own_start);
+        assert_eq!(subtree.session_id, None);
+        let session_id = format!("claude-{}-{}", own_pid, own_start);
+        let session = resolve_override_scope(&scope_request(None, Some(&session_id)))
+            .expect("live session root")
+            .expect("scope");
+        assert_eq!(session.pid, own_pid);
+        assert_eq!(session.session_id.as_deref(), Some(session_id.as_str()));
+        assert_eq!(
+            resolve_override_scope(&scope_request(None, Some("  "))),
+            Ok(None)
         );
-        assert!(!decision.deny);
     }
 
     #[test]
-    fn open_read_on_sensitive_path_allows_temporary_override_for_human_read() {
-        let mut policy = test_sensitive_policy();
-        policy.temporary_overrides = vec![TemporaryOverrideEntry::Rule(TemporaryOverrideRule {
-            path: "/Users/jqwang/.codex/chat".to_string(),
-            expires_at: Some(now_ts().saturating_add(300)),
-            created_at: Some(now_ts()),
-            created_by: Some(OVERRIDE_CREATED_BY_SENSITIVE_READ_HELPER.to_string()),
-        })];
+    fn override_scope_rejects_invalid_requests() {
+        let own_pid = std::process::id() as i32;
+        let own_start = get_process_bsd_info(own_pid).expect("own bsd info").pbi_start_tvsec;
+        let session_id = format!("claude-{}-{}", own_pid, own_start);
+        let rejected = |request: OverrideRequest| resolve_override_scope(&request).expect_err("scope is refused");
+
+        assert!(rejected(scope_request(Some(own_pid), Some(&session_id))).contains("mutually exclusive"));
+        assert_eq!(rejected(scope_request(Some(1), None)), "invalid scope_pid");
+        assert!(rejected(scope_request(Some(i32::MAX), None)).contains("is not alive"));
+        assert!(rejected(scope_request(None, Some("claude-nope"))).contains("malformed session id"));
+        assert!(rejected(scope_request(None, Some(&format!("claude-{}-1", own_pid)))).contains("has ended"));
 
-        let decision = decide_sensitive_open_for_test(
-            "/Users/jqwang/.codex/chat/history.jsonl",
-            false,
-            FFLAG_READ,
-            &policy,
+        let mut overrides = Vec::new();
+        let (changed, response) = apply_override_request(
+            &scope_request(Some(1), None),
+            &test_policy(),
+            "/Users/jqwang",
+            &mut overrides,
         );
-        assert!(!decision.deny);
+        assert!(!changed);
+        assert_eq!(response.status, "invalid");
+        assert!(overrides.is_empty());
+
+        let mut sensitive = scope_request(Some(own_pid), None);
+        sensitive.action = "grant-sensitive-read".to_string();
+        let (changed, response) = apply_override_request(&sensitive, &test_policy(), "/Users/jqwang", &mut overrides);
+        assert!(!changed);
+        assert_eq!(response.status, "invalid");
     }
 
     #[test]
-    fn open_read_on_sensitive_path_allows_guard_process() {
-        let policy = test_sensitive_policy();
-        assert!(!should_deny_sensitive_open_for_process(
-            "/Users/jqwang/.agentsmith-rs/policy.json",
-            false,
-            FFLAG_READ,
+    fn scoped_override_only_applies_inside_its_process_tree() {
+        let own_pid = std::process::id() as i32;
+        let own_start = get_process_bsd_info(own_pid).expect("own bsd info").pbi_start_tvsec;
+        let mut policy = test_policy();
+        let mut overrides = Vec::new();
+        let (changed, response) = apply_override_request(
+            &scope_request(Some(own_pid), None),
             &policy,
-            true,
-        ));
+            "/Users/jqwang",
+            &mut overrides,
+        );
+        assert!(changed);
+        assert!(response.message.contains("limited to pid"));
+        policy.temporary_overrides = overrides;
+
+        let path = "/Users/jqwang/project/build/out.o";
+        let inside = OverrideActor {
+            ancestry: vec![(own_pid + 1, own_start + 5), (own_pid, own_start), (1, 0)],
+            session_id: None,
+        };
+        let recycled = OverrideActor {
+            ancestry: vec![(own_pid, own_start + 1)],
+            session_id: None,
+        };
+        assert!(!policy.is_override_active_for_path(path, now_ts(), None));
+        assert!(policy.is_protected(path, "/Users/jqwang"));
+        assert!(policy.has_restricted_override_for_path(path, now_ts()));
+        assert!(policy.is_override_active_for_path(path, now_ts(), Some(&inside)));
+        assert!(!policy.is_override_active_for_path(path, now_ts(), Some(&recycled)));
     }
 
     #[test]
-    fn sensitive_read_observer_allows_policy_and_guard_logs() {
-        let home = "/Users/jqwang";
-        assert!(should_allow_sensitive_read_observer(
-            "/Users/jqwang/.agentsmith-rs/policy.json",
-            "AgentSmith",
-            home,
-        ));
-        assert!(should_allow_sensitive_read_observer(
-            "/Users/jqwang/.agentsmith-rs/policy.json.lock",
-            "AgentSmith",
-            home,
-        ));
-        assert!(should_allow_sensitive_read_observer(
-            "/Users/jqwang/.agentsmith-rs/guard/denials.jsonl",
-            "AgentSmith",
-            home,
-        ));
+    fn session_scoped_override_matches_by_session_not_subtree() {
+        let own_pid = std::process::id() as i32;
+        let own_start = get_process_bsd_info(own_pid).expect("own bsd info").pbi_start_tvsec;
+        let session_id = format!("claude-{}-{}", own_pid, own_start);
+        let subtree = resolve_override_scope(&scope_request(Some(own_pid), None))
+            .expect("live pid")
+            .expect("scope");
+        let session = resolve_override_scope(&scope_request(None, Some(&session_id)))
+            .expect("live session root")
+            .expect("scope");
+        let same_session = OverrideActor {
+            ancestry: vec![],
+            session_id: Some(session_id.clone()),
+        };
+        let other_session = OverrideActor {
+            ancestry: vec![],
+            session_id: Some(format!("codex-{}-{}", own_pid, own_start)),
+        };
+        assert!(same_session.is_within(&session));
+        assert!(!other_session.is_within(&session));
+        assert!(!same_session.is_within(&subtree));
     }
 
     #[test]
-    fn sensitive_read_observer_does_not_allow_chat_history_or_other_process() {
-        let home = "/Users/jqwang";
-        assert!(!should_allow_sensitive_read_observer(
-            "/Users/jqwang/.codex/chat/history.jsonl",
-            "AgentSmith",
-            home,
-        ));
-        assert!(!should_allow_sensitive_read_observer(
-            "/Users/jqwang/.agentsmith-rs/policy.json",
-            "cat",
-            home,
-        ));
+    fn scoped_and_unscoped_rules_coexist_and_dead_scopes_are_dropped() {
+        let own_pid = std::process::id() as i32;
+        let own_start = get_process_bsd_info(own_pid).expect("own bsd info").pbi_start_tvsec;
+        let rule = |scope: Option<OverrideScope>| {
+            TemporaryOverrideEntry::Rule(TemporaryOverrideRule {
+                path: "/Users/jqwang/project/build".to_string(),
+                expires_at: Some(now_ts() + 60),
+                created_at: Some(now_ts()),
+                created_by: Some(OVERRIDE_CREATED_BY_HELPER.to_string()),
+                scope,
+                ops: Vec::new(),
+                rename_to: None,
+                max_uses: None,
+                uses: 0,
+                pin: None,
+                id: None,
+            })
+        };
+        let mut policy = test_policy();
+        policy.temporary_overrides = vec![
+            rule(Some(OverrideScope {
+                pid: own_pid,
+                start_tvsec: own_start,
+                session_id: None,
+            })),
+            rule(None),
+            // Same pid, other start time: the scoped process is gone.
+            rule(Some(OverrideScope {
+                pid: own_pid,
+                start_tvsec: own_start + 1,
+                session_id: None,
+            })),
+        ];
+        assert!(policy.sanitize_overrides(now_ts(), "/Users/jqwang"));
+        assert_eq!(policy.temporary_overrides.len(), 2);
+        assert!(policy.is_override_active_for_path("/Users/jqwang/project/build/out.o", now_ts(), None));
     }
 
-    #[test]
-    fn observer_metadata_reads_do_not_mark_taint() {
-        let policy = test_sensitive_policy();
-        let home = "/Users/jqwang";
-        assert!(!should_mark_taint_on_sensitive_read(
-            "/Users/jqwang/.agentsmith-rs/policy.json",
-            &policy,
-            home,
-        ));
-        assert!(!should_mark_taint_on_sensitive_read(
-            "/Users/jqwang/.agentsmith-rs/guard/denials.jsonl",
-            &policy,
-            home,
-        ));
-        assert!(should_mark_taint_on_sensitive_read(
-            "/Users/jqwang/.codex/chat/history.jsonl",
-            &policy,
-            home,
-        ));
+    fn limited_request(ops: &[&str], rename_to: Option<&str>, max_uses: Option<u32>) -> OverrideRequest {
+        OverrideRequest {
+            id: "req-op".to_string(),
+            action: "grant".to_string(),
+            path: Some("/Users/jqwang/project/build".to_string()),
+            minutes: Some(3),
+            requester_pid: None,
+            approval_id: None,
+            scope_pid: None,
+            scope_session: None,
+            ops: ops.iter().map(|op| op.to_string()).collect(),
+            rename_to: rename_to.map(str::to_string),
+            max_uses,
+            pin: false,
+            paths: Vec::new(),
+            glob: None,
+        }
     }
 
     #[test]
-    fn sensitive_source_to_desktop_is_denied() {
-        let policy = test_sensitive_policy();
-        assert!(should_deny_sensitive_transfer(
-            "/Users/jqwang/.codex/sessions/a.json",
-            "/Users/jqwang/Desktop/a.json",
-            &policy
-        ));
+    fn override_limits_reject_invalid_operation_requests() {
+        assert!(
+            resolve_override_limits(&limited_request(&["chmod"], None, None))
+                .unwrap_err()
+                .contains("chmod")
+        );
+        assert!(resolve_override_limits(&limited_request(&["unlink"], Some("/tmp/out"), None)).is_err());
+        assert!(resolve_override_limits(&limited_request(&[], None, Some(0))).is_err());
+        assert!(resolve_override_limits(&limited_request(&[], None, Some(MAX_OVERRIDE_USES + 1))).is_err());
+        assert_eq!(
+            resolve_override_limits(&limited_request(
+                &[],
+                Some("/Users/jqwang/project/dist/"),
+                None
+            ))
+            .expect("rename limits"),
+            OverrideLimits {
+                ops: vec!["rename".to_string()],
+                rename_to: Some("/Users/jqwang/project/dist".to_string()),
+                max_uses: None,
+            }
+        );
+
+        let mut overrides = Vec::new();
+        let (changed, response) = apply_override_request(
+            &limited_request(&["chmod"], None, None),
+            &test_policy(),
+            "/Users/jqwang",
+            &mut overrides,
+        );
+        assert!(!changed);
+        assert_eq!(response.status, "invalid");
+        assert!(overrides.is_empty());
     }
 
     #[test]
-    fn sensitive_source_to_quarantine_is_allowed() {
-        let policy = test_sensitive_policy();
-        assert!(!should_deny_sensitive_transfer(
-            "/Users/jqwang/.codex/sessions/a.json",
-            "/Users/jqwang/.agentsmith-rs/guard/quarantine/a.json",
-            &policy
-        ));
+    fn single_use_unlink_override_is_consumed_once() {
+        let mut policy = test_policy();
+        let mut overrides = Vec::new();
+        let (changed, response) = apply_override_request(
+            &limited_request(&["unlink"], None, Some(1)),
+            &policy,
+            "/Users/jqwang",
+            &mut overrides,
+        );
+        assert!(changed);
+        assert!(response.message.ends_with("(unlink, 1 use(s))"));
+        policy.temporary_overrides = overrides;
+
+        let path = "/Users/jqwang/project/build/out.o";
+        let actor = OverrideActor::default();
+        let now = now_ts();
+        assert!(!policy.is_override_active_for_path(path, now, None));
+        assert!(policy.is_protected(path, "/Users/jqwang"));
+        assert!(policy.has_restricted_override_for_path(path, now));
+        assert!(policy.overrides_for_op(path, now, &actor, "truncate", None).is_empty());
+        let rules = policy.overrides_for_op(path, now, &actor, "unlink", None);
+        assert_eq!(rules.len(), 1);
+        assert!(rules[0].id.is_some());
+
+        let mut ledger = OverrideUseLedger::default();
+        assert_eq!(ledger.claim(rules[0]), Some(1));
+        assert_eq!(ledger.claim(rules[0]), None);
+        assert!(ledger.fold_into(&mut policy.temporary_overrides, now));
+        assert!(policy.sanitize_overrides(now, "/Users/jqwang"));
+        assert!(policy.temporary_overrides.is_empty());
+        // The claim outlives the folded rule so a stale snapshot cannot reuse it.
+        assert_eq!(ledger.claimed.len(), 1);
     }
 
     #[test]
-    fn taint_expires_after_ttl() {
-        let mut taint = TaintState::new(60);
-        taint.mark(100, 1_000);
-        assert!(taint.is_tainted(100, 1_030));
-        assert!(!taint.is_tainted(100, 1_061));
+    fn rename_limited_override_only_allows_its_destination() {
+        let now = now_ts();
+        let rename_only = TemporaryOverrideRule {
+            path: "/Users/jqwang/project/build".to_string(),
+            expires_at: Some(now + 60),
+            created_at: Some(now),
+            created_by: Some(OVERRIDE_CREATED_BY_HELPER.to_string()),
+            scope: None,
+            ops: vec!["rename".to_string()],
+            rename_to: Some("/Users/jqwang/project/dist".to_string()),
+            max_uses: None,
+            uses: 0,
+            pin: None,
+            id: None,
+        };
+        assert!(rename_only.allows_op("rename", Some("/Users/jqwang/project/dist")));
+        assert!(!rename_only.allows_op("rename", Some("/tmp/dist")));
+        assert!(!rename_only.allows_op("unlink", None));
+
+        // An uncounted rule is preferred so a counted one is not spent needlessly.
+        let mut counted = rename_only.clone();
+        counted.max_uses = Some(2);
+        counted.id = Some("counted".to_string());
+        let mut policy = test_policy();
+        policy.temporary_overrides = vec![
+            TemporaryOverrideEntry::Rule(counted),
+            TemporaryOverrideEntry::Rule(rename_only),
+        ];
+        let rules = policy.overrides_for_op(
+            "/Users/jqwang/project/build/out.o",
+            now,
+            &OverrideActor::default(),
+            "rename",
+            Some("/Users/jqwang/project/dist"),
+        );
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].max_uses, None);
     }
 
     #[test]
-    fn taint_state_accepts_matching_pid_start_time_identity() {
-        let mut taint = TaintState::new(60);
+    fn counted_override_is_exhausted_and_each_use_is_audited() {
+        let home_dir = std::env::temp_dir().join(format!("as-consume-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&home_dir);
+        fs::create_dir_all(&home_dir).expect("create home");
+        let home = home_dir.to_string_lossy().into_owned();
+        let rule_id = format!("exhaust-{}", std::process::id());
+        let now = now_ts();
+        let mut policy = test_policy();
+        policy.protected_zones = vec![format!("{}/project", home)];
+        policy.temporary_overrides = vec![TemporaryOverrideEntry::Rule(TemporaryOverrideRule {
+            path: format!("{}/project/build", home),
+            expires_at: Some(now + 60),
+            created_at: Some(now),
+            created_by: Some(OVERRIDE_CREATED_BY_HELPER.to_string()),
+            scope: None,
+            ops: vec!["unlink".to_string()],
+            rename_to: None,
+            max_uses: Some(2),
+            uses: 0,
+            pin: None,
+            id: Some(rule_id.clone()),
+        })];
+        let path = format!("{}/project/build/out.o", home);
         let pid = std::process::id() as i32;
-        let process_start = process_start_time_for_pid(pid).expect("current process start time must be available");
-        taint.mark_with_process_start(pid, 1_000, Some(process_start));
-        assert!(taint.is_tainted(pid, 1_030));
-    }
+        let mut table = ProcessTable::default();
+        let mut claim =
+            |op: &str| claim_restricted_override_for_pid(&path, GateOp::new(op, None), pid, &policy, &mut table);
+
+        assert_eq!(claim("truncate"), OverrideClaim::NotCovered);
+        assert_eq!(claim("unlink"), OverrideClaim::Allowed);
+        assert_eq!(claim("unlink"), OverrideClaim::Allowed);
+        assert_eq!(
+            claim("unlink"),
+            OverrideClaim::NotCovered,
+            "both uses are spent"
+        );
+
+        let mut overrides = policy.temporary_overrides.clone();
+        assert!(sync_override_uses(&home, &mut overrides, now));
+        let TemporaryOverrideEntry::Rule(rule) = &overrides[0] else {
+            panic!("counted overrides are rules");
+        };
+        assert_eq!(rule.uses, 2);
+        policy.temporary_overrides = overrides;
+        assert!(policy.sanitize_overrides(now, &home));
+        assert!(policy.temporary_overrides.is_empty());
 
-    #[test]
-    fn taint_state_rejects_pid_start_time_mismatch() {
-        let mut taint = TaintState::new(60);
-        let pid = std::process::id() as i32;
-        taint.mark_with_process_start(
-            pid,
-            1_000,
-            Some(ProcessStartTimeKey {
-                start_tvsec: u64::MAX,
-                start_tvusec: u64::MAX,
-            }),
+        let audit = fs::read_to_string(home_dir.join(".agentsmith-rs/guard/override-audit.jsonl")).expect("audit log");
+        let uses: Vec<String> = audit
+            .lines()
+            .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+            .filter(|record| record["action"] == "consume" && record["request_id"] == rule_id.as_str())
+            .map(|record| record["message"].as_str().unwrap_or_default().to_string())
+            .collect();
+        assert_eq!(
+            uses,
+            vec![
+                format!("unlink of {} allowed, use 1 of 2", path),
+                format!("unlink of {} allowed, use 2 of 2", path),
+            ]
         );
-        assert!(!taint.is_tainted(pid, 1_030));
+        let _ = fs::remove_dir_all(&home_dir);
     }
 
     #[test]
-    fn taint_state_prune_expired_entries_keeps_recent_entries() {
-        let mut taint = TaintState::new(60);
-        taint.mark(100, 1_000);
-        taint.mark(200, 1_150);
-        taint.prune_expired(1_200);
-        assert!(!taint.is_tainted(100, 1_200));
-        assert!(taint.is_tainted(200, 1_200));
+    fn pinned_override_only_matches_the_unchanged_file() {
+        let dir = std::env::temp_dir().join(format!("agentsmith-pin-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("create temp dir");
+        let dir = fs::canonicalize(&dir).expect("canonical temp dir");
+        let file = dir.join("data.db");
+        fs::write(&file, b"approved content").expect("write file");
+        let file_path = file.to_string_lossy().into_owned();
+        let identity = |path: &Path| FileIdentity::from_metadata(&fs::symlink_metadata(path).expect("stat"));
+
+        let mut policy = test_policy();
+        policy.protected_zones = vec![dir.to_string_lossy().into_owned()];
+        let request = |path: &str| OverrideRequest {
+            id: "req-pin".to_string(),
+            action: "grant".to_string(),
+            path: Some(path.to_string()),
+            minutes: Some(3),
+            requester_pid: None,
+            approval_id: None,
+            scope_pid: None,
+            scope_session: None,
+            ops: Vec::new(),
+            rename_to: None,
+            max_uses: None,
+            pin: true,
+            paths: Vec::new(),
+            glob: None,
+        };
+        let mut overrides = Vec::new();
+        let (changed, response) = apply_override_request(
+            &request(&dir.join("sub").to_string_lossy()),
+            &policy,
+            "/Users/jqwang",
+            &mut overrides,
+        );
+        assert!(!changed, "{}", response.message);
+        let (changed, response) = apply_override_request(
+            &request(&file_path),
+            &policy,
+            "/Users/jqwang",
+            &mut overrides,
+        );
+        assert!(changed, "{}", response.message);
+        assert!(response.message.ends_with("(pinned to current content)"));
+        policy.temporary_overrides = overrides;
+
+        let now = now_ts();
+        let actor = OverrideActor::default();
+        assert!(policy.is_protected(&file_path, "/Users/jqwang"));
+        let rules = policy.overrides_for_op(&file_path, now, &actor, "unlink", None);
+        assert_eq!(rules.len(), 1);
+        let pin = rules[0].pin.clone().expect("pin");
+        assert_eq!(pin.file.size, 16);
+        assert!(pin.matches(&file_path, Some(identity(&file))));
+        assert!(!pin.matches(&file_path, None));
+        // A file created under the pinned path later is not covered.
+        assert!(policy
+            .overrides_for_op(&format!("{}/later", file_path), now, &actor, "unlink", None)
+            .is_empty());
+
+        // Same size and inode, different bytes.
+        fs::write(&file, b"replaced content").expect("rewrite file");
+        assert!(!pin.matches(&file_path, Some(identity(&file))));
+        // Swapped for a new inode with the original bytes.
+        let replacement = dir.join("data.db.new");
+        fs::write(&replacement, b"approved content").expect("write replacement");
+        fs::rename(&replacement, &file).expect("swap file");
+        assert!(!pin.matches(&file_path, Some(identity(&file))));
+        let _ = fs::remove_dir_all(&dir);
     }
 
     #[test]
-    fn taint_state_inherits_to_child_pid_from_tainted_parent() {
-        let mut taint = TaintState::new(60);
-        taint.mark(100, 1_000);
-        assert!(taint.inherit_from_parent(100, 200, 1_020));
-        assert!(taint.is_tainted(200, 1_020));
+    fn pinned_override_rehashes_a_file_whose_stat_looks_unchanged() {
+        let dir = std::env::temp_dir().join(format!("agentsmith-pin-rehash-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("create temp dir");
+        let file = dir.join("data.db");
+        fs::write(&file, b"approved content").expect("write file");
+        let file_path = file.to_string_lossy().into_owned();
+        let pin = OverridePin::capture(&file_path).expect("pin file");
+
+        // Same-size rewrite whose stat is made to look like the pinned one.
+        fs::write(&file, b"replaced content").expect("rewrite file");
+        let forged = FileIdentity::from_metadata(&fs::symlink_metadata(&file).expect("stat"));
+        let stale = OverridePin {
+            file: forged,
+            sha256: pin.sha256.clone(),
+        };
+        assert!(!stale.matches(&file_path, Some(forged)));
+        let current = OverridePin::capture(&file_path).expect("re-pin file");
+        assert!(current.matches(&file_path, Some(forged)));
+        let _ = fs::remove_dir_all(&dir);
     }
 
     #[test]
-    fn taint_state_does_not_inherit_from_clean_parent() {
-        let mut taint = TaintState::new(60);
-        assert!(!taint.inherit_from_parent(100, 200, 1_020));
-        assert!(!taint.is_tainted(200, 1_020));
+    fn pinned_override_denies_a_changed_file_with_the_pin_mismatch_reason() {
+        let dir = std::env::temp_dir().join(format!("agentsmith-pin-gate-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("create temp dir");
+        let file = dir.join("data.db");
+        fs::write(&file, b"approved content").expect("write file");
+        let file_path = file.to_string_lossy().into_owned();
+        let now = now_ts();
+        let mut policy = test_policy();
+        policy.protected_zones = vec![dir.to_string_lossy().into_owned()];
+        policy.temporary_overrides = vec![TemporaryOverrideEntry::Rule(TemporaryOverrideRule {
+            path: file_path.clone(),
+            expires_at: Some(now + 60),
+            created_at: Some(now),
+            created_by: Some(OVERRIDE_CREATED_BY_HELPER.to_string()),
+            scope: None,
+            ops: Vec::new(),
+            rename_to: None,
+            max_uses: None,
+            uses: 0,
+            pin: Some(OverridePin::capture(&file_path).expect("pin file")),
+            id: None,
+        })];
+        let mut table = ProcessTable::default();
+        table.record_exec(
+            4600,
+            start_key(46),
+            ProcessLinks::from_ppid(1),
+            "/usr/local/bin/codex".to_string(),
+            vec!["codex".to_string()],
+            &policy,
+            50,
+        );
+        let trust_cache = Arc::new(Mutex::new(TrustedProcessCache::default()));
+        let signature_cache = Arc::new(Mutex::new(BinarySignatureCache::default()));
+        let (signature_refresh_tx, _signature_refresh_rx) = mpsc::sync_channel::<String>(4);
+        let gate = |table: &mut ProcessTable| {
+            let identity = FileIdentity::from_metadata(&fs::symlink_metadata(&file).expect("stat"));
+            should_deny_protected_zone_op(
+                &file_path,
+                4600,
+                "/Users/jqwang",
+                &policy,
+                table,
+                &trust_cache,
+                &signature_cache,
+                &signature_refresh_tx,
+                GateOp::new("unlink", Some(identity)),
+                REASON_PROTECTED_ZONE_AI_DELETE,
+            )
+        };
+
+        assert!(gate(&mut table).is_none(), "the pinned file is unchanged");
+        fs::write(&file, b"replaced content").expect("rewrite file");
+        let decision = gate(&mut table).expect("changed file is denied");
+        assert_eq!(decision.reason, REASON_OVERRIDE_PIN_MISMATCH);
+        assert_eq!(decision.ancestor, "codex");
+        let _ = fs::remove_dir_all(&dir);
     }
 
     #[test]
-    fn tainted_process_write_outside_allow_zone_is_denied() {
-        let policy = test_sensitive_policy();
-        let mut taint = TaintState::new(600);
-        taint.mark(100, 1_000);
-        assert!(should_deny_tainted_write(
-            100,
-            "/Users/jqwang/Desktop/out.txt",
-            Some("python3"),
-            &TrustedProcessDecision::NotTrusted,
-            1_100,
-            &taint,
-            &policy
+    fn override_policy_limits_grants_and_auto_approves_repo_build_output() {
+        let home_dir = std::env::temp_dir().join(format!("as-override-policy-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&home_dir);
+        fs::create_dir_all(home_dir.join("project/repo/.git")).expect("create repo");
+        fs::create_dir_all(home_dir.join("project/scratch")).expect("create scratch");
+        let home = home_dir.to_string_lossy().into_owned();
+        let mut policy = test_policy();
+        policy.protected_zones = vec![format!("{}/project", home)];
+        policy.override_approval_required = true;
+        policy.override_policy = OverridePolicy {
+            never_override: vec![format!("{}/project/keys", home), format!("{}/project/**/*.pem", home)],
+            zones: vec![OverrideZoneRule {
+                zone: format!("{}/project", home),
+                max_minutes: Some(10),
+                min_depth: Some(2),
+            }],
+            auto_approve: vec![OverrideAutoApproveRule {
+                name: "build-output".to_string(),
+                paths: vec![format!("{}/project/*/target", home)],
+                inside_repo: true,
+                max_minutes: Some(5),
+            }],
+            ..OverridePolicy::default()
+        };
+        let grant = |path: &str, minutes: u64| OverrideRequest {
+            id: "req".to_string(),
+            action: "grant".to_string(),
+            path: Some(format!("{}/project/{}", home, path)),
+            minutes: Some(minutes),
+            requester_pid: Some(std::process::id() as i32),
+            approval_id: None,
+            scope_pid: None,
+            scope_session: None,
+            ops: Vec::new(),
+            rename_to: None,
+            max_uses: None,
+            pin: false,
+            paths: Vec::new(),
+            glob: None,
+        };
+        let mut overrides = Vec::new();
+        let mut pending = PendingOverrideQueue::default();
+        let (reply_tx, _reply_rx) = mpsc::channel();
+        let mut route = |req: &OverrideRequest, overrides: &mut Vec<TemporaryOverrideEntry>| {
+            route_override_request(
+                req,
+                &test_party(std::process::id() as i32, 1, "/dev/ttys001"),
+                PendingReply::Socket(reply_tx.clone()),
+                &policy,
+                &home,
+                overrides,
+                &mut pending,
+            )
+        };
+        let refused = |routing: OverrideRouting| match routing {
+            OverrideRouting::Done(false, response) if response.status == "error" => response,
+            _ => panic!("grant should be refused"),
+        };
+
+        let never = refused(route(&grant("keys", 3), &mut overrides));
+        assert!(never.message.contains("can never be overridden"));
+        assert!(never.explanation[0].contains("never_override"));
+        let shallow = refused(route(&grant("repo", 3), &mut overrides));
+        assert!(shallow.message.contains("min 2"));
+        let long = refused(route(&grant("repo/target", 20), &mut overrides));
+        assert_eq!(long.message, "minutes exceeds max allowed (10)");
+        assert!(long.explanation[0].contains("caps grants at 10 minute(s)"));
+
+        let OverrideRouting::Done(true, auto) = route(&grant("repo/target", 3), &mut overrides) else {
+            panic!("build output in a repo should be auto-approved");
+        };
+        assert_eq!(auto.status, "ok");
+        assert!(auto.explanation.iter().any(|line| line.contains("build-output")));
+        assert_eq!(overrides.len(), 1);
+        assert!(matches!(
+            route(&grant("scratch/target", 3), &mut overrides),
+            OverrideRouting::Pending(_)
         ));
+
+        policy.temporary_overrides = overrides;
+        assert!(!policy.is_protected(&format!("{}/project/repo/target/out.o", home), &home));
+        assert!(policy.is_protected(&format!("{}/project/repo/target/cert.pem", home), &home));
+        let _ = fs::remove_dir_all(&home_dir);
     }
 
     #[test]
-    fn tainted_git_metadata_write_is_allowed() {
-        let policy = test_sensitive_policy();
-        let mut taint = TaintState::new(600);
-        taint.mark(100, 1_000);
-        assert!(!should_deny_tainted_write(
-            100,
-            "/Users/jqwang/00-nixos-config/nixos-config/.git/index.lock",
-            Some("git"),
-            &TrustedProcessDecision::Trusted,
-            1_100,
-            &taint,
-            &policy
-        ));
+    fn override_policy_refuses_covering_grants_and_enforces_the_tightest_caps() {
+        let home_dir = std::env::temp_dir().join(format!("as-override-refuse-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&home_dir);
+        fs::create_dir_all(home_dir.join("project/repo/.git")).expect("create repo");
+        let home = home_dir.to_string_lossy().into_owned();
+        let mut policy = test_policy();
+        policy.protected_zones = vec![format!("{}/project", home)];
+        policy.override_policy = OverridePolicy {
+            max_minutes: Some(4),
+            max_active: Some(1),
+            never_override: vec![format!("{}/project/repo/secrets", home)],
+            zones: vec![OverrideZoneRule {
+                zone: format!("{}/project", home),
+                max_minutes: Some(10),
+                min_depth: None,
+            }],
+            auto_approve: vec![OverrideAutoApproveRule {
+                name: "build-output".to_string(),
+                paths: vec![format!("{}/project/*/target", home)],
+                inside_repo: true,
+                max_minutes: Some(2),
+            }],
+            ..OverridePolicy::default()
+        };
+        let grant = |path: &str, minutes: u64| OverrideRequest {
+            id: "req".to_string(),
+            action: "grant".to_string(),
+            path: Some(format!("{}/project/{}", home, path)),
+            minutes: Some(minutes),
+            requester_pid: Some(std::process::id() as i32),
+            approval_id: None,
+            scope_pid: None,
+            scope_session: None,
+            ops: Vec::new(),
+            rename_to: None,
+            max_uses: None,
+            pin: false,
+            paths: Vec::new(),
+            glob: None,
+        };
+        let mut overrides = Vec::new();
+
+        // A grant on a parent would cover the never_override entry.
+        let (changed, covering) = apply_override_request(&grant("repo", 3), &policy, &home, &mut overrides);
+        assert!(!changed);
+        assert_eq!(covering.status, "error");
+        assert!(covering.message.contains("can never be overridden"));
+        assert!(covering.explanation[0].contains("repo/secrets"));
+
+        // The global cap is tighter than the zone cap, so it wins.
+        let (changed, long) = apply_override_request(&grant("repo/target", 5), &policy, &home, &mut overrides);
+        assert!(!changed);
+        assert_eq!(long.message, "minutes exceeds max allowed (4)");
+        assert!(long.explanation.iter().all(|line| !line.contains("caps grants at 10")));
+
+        let (changed, first) = apply_override_request(&grant("repo/target", 3), &policy, &home, &mut overrides);
+        assert!(changed, "{}", first.message);
+        let (changed, second) = apply_override_request(&grant("repo/build", 3), &policy, &home, &mut overrides);
+        assert!(!changed);
+        assert_eq!(second.message, "too many active overrides (max 1)");
+        assert_eq!(overrides.len(), 1);
+
+        // Above the rule's own cap the grant still needs a human.
+        policy.override_approval_required = true;
+        let mut pending = PendingOverrideQueue::default();
+        let (reply_tx, _reply_rx) = mpsc::channel();
+        let routing = route_override_request(
+            &grant("repo/target", 3),
+            &test_party(std::process::id() as i32, 1, "/dev/ttys001"),
+            PendingReply::Socket(reply_tx),
+            &policy,
+            &home,
+            &mut Vec::new(),
+            &mut pending,
+        );
+        assert!(matches!(routing, OverrideRouting::Pending(_)));
+        let _ = fs::remove_dir_all(&home_dir);
     }
 
     #[test]
-    fn tainted_non_vcs_metadata_write_is_denied() {
-        let policy = test_sensitive_policy();
-        let mut taint = TaintState::new(600);
-        taint.mark(100, 1_000);
-        assert!(should_deny_tainted_write(
-            100,
-            "/Users/jqwang/00-nixos-config/nixos-config/.git/index.lock",
-            Some("python3"),
-            &TrustedProcessDecision::NotTrusted,
-            1_100,
-            &taint,
-            &policy
-        ));
+    fn batch_override_expands_glob_and_is_granted_or_refused_as_a_unit() {
+        let home_dir = std::env::temp_dir().join(format!("as-override-batch-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&home_dir);
+        let gen = home_dir.join("project/gen");
+        fs::create_dir_all(&gen).expect("create gen dir");
+        for name in ["a.pb.go", "b.pb.go", ".c.pb.go", "keep.txt"] {
+            fs::write(gen.join(name), b"generated").expect("write file");
+        }
+        let home = home_dir.to_string_lossy().into_owned();
+        let gen_path = |name: &str| format!("{}/project/gen/{}", home, name);
+        let mut policy = test_policy();
+        policy.protected_zones = vec![format!("{}/project", home)];
+        let requester = std::process::id() as i32;
+        let request = |path: Option<String>, paths: Vec<String>, glob: Option<String>| OverrideRequest {
+            id: "req-batch".to_string(),
+            action: "grant".to_string(),
+            path,
+            minutes: Some(3),
+            requester_pid: Some(requester),
+            approval_id: None,
+            scope_pid: None,
+            scope_session: None,
+            ops: Vec::new(),
+            rename_to: None,
+            max_uses: None,
+            pin: false,
+            paths,
+            glob,
+        };
+        let glob = Some(gen_path("*.pb.go"));
+
+        let mut overrides = Vec::new();
+        let (changed, granted) = apply_override_request(
+            &request(None, Vec::new(), glob.clone()),
+            &policy,
+            &home,
+            &mut overrides,
+        );
+        assert!(changed, "{}", granted.message);
+        assert_eq!(
+            granted.paths,
+            vec![gen_path("a.pb.go"), gen_path("b.pb.go")]
+        );
+        assert_eq!(overrides.len(), 2);
+
+        let (changed, refused) = apply_override_request(
+            &request(
+                Some(gen_path("keep.txt")),
+                vec![format!("{}/project", home)],
+                None,
+            ),
+            &policy,
+            &home,
+            &mut overrides,
+        );
+        assert!(!changed);
+        assert!(refused.message.starts_with(&format!("{}/project: broad path", home)));
+        assert_eq!(overrides.len(), 2);
+        let (_, nested) = apply_override_request(
+            &request(None, Vec::new(), Some(format!("{}/*/gen/*.go", home))),
+            &policy,
+            &home,
+            &mut overrides,
+        );
+        assert!(nested.message.contains("last path component"));
+
+        policy.override_approval_required = true;
+        let mut overrides = Vec::new();
+        let mut pending = PendingOverrideQueue::default();
+        let (reply_tx, _reply_rx) = mpsc::channel();
+        let mut route = |req: &OverrideRequest, party: &OverrideParty, overrides: &mut Vec<TemporaryOverrideEntry>| {
+            route_override_request(
+                req,
+                party,
+                PendingReply::Socket(reply_tx.clone()),
+                &policy,
+                &home,
+                overrides,
+                &mut pending,
+            )
+        };
+        let OverrideRouting::Pending(parked) = route(
+            &request(None, Vec::new(), glob),
+            &test_party(requester, 1, "/dev/ttys001"),
+            &mut overrides,
+        ) else {
+            panic!("batch grant should wait for approval");
+        };
+        assert_eq!(parked.paths.len(), 2);
+        fs::write(gen.join("late.pb.go"), b"generated").expect("write late file");
+        let approve = OverrideRequest {
+            id: "approve-batch".to_string(),
+            action: "approve".to_string(),
+            approval_id: Some("req-batch".to_string()),
+            ..request(None, Vec::new(), None)
+        };
+        let OverrideRouting::Done(changed, approved) = route(
+            &approve,
+            &test_party(requester + 1, 2, "/dev/ttys002"),
+            &mut overrides,
+        ) else {
+            panic!("approve is decided immediately");
+        };
+        assert!(changed, "{}", approved.message);
+        assert_eq!(
+            overrides.len(),
+            2,
+            "files created after the request are not granted"
+        );
+        let _ = fs::remove_dir_all(&home_dir);
     }
 
-    #[test]
-    fn tainted_write_to_safe_device_paths_is_allowed() {
-        let policy = test_sensitive_policy();
-        let mut taint = TaintState::new(600);
-        taint.mark(100, 1_000);
+    fn batch_fixture(tag: &str) -> (PathBuf, String, SecurityPolicy) {
+        let home_dir = std::env::temp_dir().join(format!("as-batch-{}-{}", tag, std::process::id()));
+        let _ = fs::remove_dir_all(&home_dir);
+        let gen = home_dir.join("project/gen");
+        fs::create_dir_all(&gen).expect("create gen dir");
+        for name in ["a.pb.go", "b.pb.go"] {
+            fs::write(gen.join(name), b"generated").expect("write file");
+        }
+        let home = home_dir.to_string_lossy().into_owned();
+        let mut policy = test_policy();
+        policy.protected_zones = vec![format!("{}/project", home)];
+        policy.override_approval_required = true;
+        (home_dir, home, policy)
+    }
 
-        for path in ["/dev/null", "/dev/tty", "/dev/dtracehelper"] {
-            assert!(!should_deny_tainted_write(
-                100,
-                path,
-                Some("bash"),
-                &TrustedProcessDecision::NotTrusted,
-                1_100,
-                &taint,
-                &policy
-            ));
+    fn batch_request(id: &str, paths: Vec<String>, glob: Option<String>) -> OverrideRequest {
+        OverrideRequest {
+            id: id.to_string(),
+            action: "grant".to_string(),
+            path: None,
+            minutes: Some(3),
+            requester_pid: Some(std::process::id() as i32),
+            approval_id: None,
+            scope_pid: None,
+            scope_session: None,
+            ops: Vec::new(),
+            rename_to: None,
+            max_uses: None,
+            pin: false,
+            paths,
+            glob,
         }
     }
 
     #[test]
-    fn tainted_write_to_other_device_paths_is_denied() {
-        let policy = test_sensitive_policy();
-        let mut taint = TaintState::new(600);
-        taint.mark(100, 1_000);
-        assert!(should_deny_tainted_write(
-            100,
-            "/dev/random",
-            Some("bash"),
-            &TrustedProcessDecision::NotTrusted,
-            1_100,
-            &taint,
-            &policy
-        ));
+    fn pending_batch_override_is_rejected_or_expires_as_a_unit() {
+        let (home_dir, home, policy) = batch_fixture("pending");
+        let requester = test_party(std::process::id() as i32, 1, "/dev/ttys001");
+        let approver = test_party(requester.pid + 1, 2, "/dev/ttys002");
+        let mut overrides = Vec::new();
+        let mut pending = PendingOverrideQueue::default();
+        let (reply_tx, reply_rx) = mpsc::channel();
+        for request in [
+            batch_request(
+                "req-glob",
+                Vec::new(),
+                Some(format!("{}/project/gen/*.pb.go", home)),
+            ),
+            batch_request(
+                "req-paths",
+                vec![
+                    format!("{}/project/gen/a.pb.go", home),
+                    format!("{}/project/gen/b.pb.go", home),
+                ],
+                None,
+            ),
+        ] {
+            let routed = route_override_request(
+                &request,
+                &requester,
+                PendingReply::Socket(reply_tx.clone()),
+                &policy,
+                &home,
+                &mut overrides,
+                &mut pending,
+            );
+            let OverrideRouting::Pending(parked) = routed else {
+                panic!("batch grant should wait for approval");
+            };
+            assert_eq!(parked.paths.len(), 2);
+        }
+
+        let reject = OverrideRequest {
+            action: "reject".to_string(),
+            approval_id: Some("req-glob".to_string()),
+            ..batch_request("reject-glob", Vec::new(), None)
+        };
+        let (changed, rejected) = decide_pending_override(
+            &reject,
+            false,
+            &approver,
+            &policy,
+            &home,
+            &mut overrides,
+            &mut pending,
+        );
+        assert!(!changed);
+        assert_eq!(rejected.status, "ok");
+        let deadline = pending.entries[0].deadline;
+        expire_pending_overrides(&home, &mut pending, deadline);
+        assert!(pending.entries.is_empty());
+        assert!(overrides.is_empty(), "no path of either batch is granted");
+
+        pending.flush_replies();
+        let mut replies: Vec<_> = reply_rx
+            .try_iter()
+            .map(|reply| (reply.id, reply.status, reply.message))
+            .collect();
+        replies.sort();
+        assert_eq!(replies.len(), 2);
+        assert_eq!(
+            (replies[0].0.as_str(), replies[0].1.as_str()),
+            ("req-glob", "error")
+        );
+        assert!(replies[0].2.contains("rejected by pid"));
+        assert_eq!(
+            (replies[1].0.as_str(), replies[1].1.as_str()),
+            ("req-paths", "error")
+        );
+        assert!(replies[1].2.contains("expired without approval"));
+        let steps = override_audit_steps(&home_dir);
+        assert!(steps.contains(&("req-glob".to_string(), "rejected".to_string())));
+        assert!(steps.contains(&("req-paths".to_string(), "expired".to_string())));
+        let _ = fs::remove_dir_all(&home_dir);
     }
 
     #[test]
-    fn cache_watermark_highs_track_peaks() {
-        let mut highs = CacheWatermarkHighs::default();
-        assert_eq!(highs.update(2, 3, 1), (2, 3, 1));
-        assert_eq!(highs.update(1, 4, 0), (2, 4, 1));
-        assert_eq!(highs.update(8, 2, 9), (8, 4, 9));
+    fn malformed_batch_override_is_invalid_and_never_parked() {
+        let (home_dir, home, policy) = batch_fixture("invalid");
+        let gen = |pattern: &str| Some(format!("{}/project/gen/{}", home, pattern));
+        let cases = [
+            (
+                batch_request("no-wildcard", Vec::new(), gen("a.pb.go")),
+                "has no wildcard",
+            ),
+            (
+                batch_request("no-match", Vec::new(), gen("*.rs")),
+                "matched nothing",
+            ),
+            (
+                batch_request("recursive", Vec::new(), gen("**.go")),
+                "last path component",
+            ),
+            (
+                batch_request("relative", Vec::new(), Some("gen/*.go".to_string())),
+                "invalid glob",
+            ),
+            (
+                batch_request("missing-dir", Vec::new(), gen("missing/*.go")),
+                "cannot read",
+            ),
+            (
+                batch_request("relative-path", vec!["gen/a.pb.go".to_string()], None),
+                "invalid absolute path",
+            ),
+        ];
+        let mut pending = PendingOverrideQueue::default();
+        let (reply_tx, _reply_rx) = mpsc::channel();
+        for (request, expected) in cases {
+            let routed = route_override_request(
+                &request,
+                &test_party(std::process::id() as i32, 1, "/dev/ttys001"),
+                PendingReply::Socket(reply_tx.clone()),
+                &policy,
+                &home,
+                &mut Vec::new(),
+                &mut pending,
+            );
+            let OverrideRouting::Done(false, response) = routed else {
+                panic!("{} should be refused before approval", request.id);
+            };
+            assert_eq!(response.status, "invalid", "{}", request.id);
+            assert!(
+                response.message.contains(expected),
+                "{}: {}",
+                request.id,
+                response.message
+            );
+        }
+        assert!(pending.entries.is_empty());
+        let _ = fs::remove_dir_all(&home_dir);
     }
 
     #[test]
-    fn cache_watermark_log_format_is_stable() {
-        let line = format_cache_watermark_log(1_770_000_000, 2, 5, 3, 7, 1, 4);
+    fn keyed_feedback_history_rotates_and_is_pruned_with_its_directory() {
+        let tmp_dir = std::env::temp_dir().join(format!("as-feedback-rotate-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&tmp_dir);
+        fs::create_dir_all(&tmp_dir).expect("temp dir");
+        let mut record = DenialRecord::for_test_reason(REASON_PROTECTED_ZONE_AI_DELETE);
+        record.ppid = Some(4400);
+        let key_dir = tmp_dir.join("feedback").join("pid-4400");
+        let log_path = key_dir.join("denials.jsonl");
+
+        write_keyed_denial_feedback(&tmp_dir, &record, "denied", 50).expect("first feedback");
+        let history = format!("{}\n", "x".repeat(FEEDBACK_DENIALS_MAX_BYTES as usize));
+        fs::write(&log_path, &history).expect("full feedback history");
+        write_keyed_denial_feedback(&tmp_dir, &record, "denied", 60).expect("second feedback");
+
+        let segments = log_segment_paths(&log_path);
         assert_eq!(
-            line,
-            "[METRIC] cache-watermark ts=1770000000 ancestor=2/5 trusted=3/7 taint=1/4"
+            segments,
+            vec![key_dir.join("denials.jsonl.19700101T000100Z")]
+        );
+        assert_eq!(
+            fs::read_to_string(&segments[0]).expect("rotated segment"),
+            history
+        );
+        assert_eq!(
+            fs::read_to_string(&log_path).expect("current history").lines().count(),
+            1
+        );
+
+        prune_stale_feedback_dirs(
+            &tmp_dir.join("feedback"),
+            now_ts() + FEEDBACK_RETENTION_SECS + 60,
         );
+        assert!(!key_dir.exists());
+        let _ = fs::remove_dir_all(&tmp_dir);
+    }
+
+    fn rotated_log(tag: &str, rotation: &LogRotationPolicy, records: u64) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("as-log-rotate-{}-{}", tag, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("temp dir");
+        let log_path = dir.join("audit-only.jsonl");
+        for n in 1..=records {
+            let mut file = open_rotating_log(&log_path, 1, rotation, 1_000 + n, || {}).expect("open log");
+            writeln!(file, "{{\"n\":{}}}", n).expect("write record");
+        }
+        (dir, log_path)
     }
 
     #[test]
-    fn callback_latency_metrics_snapshot_tracks_percentiles_and_resets() {
-        let metrics = CallbackLatencyMetrics::new();
-        metrics.observe(Duration::from_micros(80));
-        metrics.observe(Duration::from_micros(1_200));
-        metrics.observe(Duration::from_micros(700_000));
-
-        let snapshot = metrics.snapshot_and_reset();
-        assert_eq!(snapshot.total, 3);
-        assert_eq!(snapshot.max_us, 700_000);
-        assert_eq!(snapshot.percentile_upper_bound_us(50), 2_000);
-        assert_eq!(snapshot.percentile_upper_bound_us(95), 500_001);
-        assert_eq!(snapshot.percentile_upper_bound_us(99), 500_001);
+    fn rotating_log_keeps_dated_segments_and_readers_follow_them() {
+        let rotation = LogRotationPolicy {
+            keep_segments: Some(2),
+            compress: true,
+            ..LogRotationPolicy::default()
+        };
+        let (dir, log_path) = rotated_log("follow", &rotation, 4);
+        // Compression runs in the background; a pass under the lock settles it.
+        let _maintenance = LOG_MAINTENANCE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        maintain_log_segments(&log_path, &rotation, 1_004);
 
-        let after_reset = metrics.snapshot_and_reset();
-        assert_eq!(after_reset.total, 0);
-        assert_eq!(after_reset.max_us, 0);
-        assert_eq!(after_reset.percentile_upper_bound_us(95), 0);
+        let segments = log_segment_paths(&log_path);
+        assert_eq!(
+            segments,
+            vec![
+                dir.join("audit-only.jsonl.19700101T001643Z.gz"),
+                dir.join("audit-only.jsonl.19700101T001644Z.gz"),
+            ]
+        );
+        assert_eq!(
+            read_guard_log(&log_path, 0).expect("full history"),
+            "{\"n\":2}\n{\"n\":3}\n{\"n\":4}\n"
+        );
+        assert_eq!(
+            read_guard_log(&log_path, u64::MAX).expect("current segment"),
+            "{\"n\":4}\n"
+        );
+        let _ = fs::remove_dir_all(&dir);
     }
 
-    #[test]
-    fn runtime_health_log_format_is_stable() {
-        let mut counts = [0u64; CALLBACK_LATENCY_BUCKETS_US.len() + 1];
-        counts[0] = 1;
-        counts[1] = 1;
-        counts[CALLBACK_LATENCY_BUCKETS_US.len()] = 1;
-        let callback_latency = CallbackLatencySnapshot {
-            counts,
-            total: 3,
-            max_us: 700_000,
+    #[test]
+    fn rotation_retention_drops_the_oldest_segments_past_the_limits() {
+        let rotation = LogRotationPolicy {
+            keep_segments: Some(3),
+            ..LogRotationPolicy::default()
+        };
+        let (dir, log_path) = rotated_log("retain", &rotation, 6);
+        let _maintenance = LOG_MAINTENANCE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        maintain_log_segments(&log_path, &rotation, 1_006);
+        assert_eq!(
+            log_segment_paths(&log_path),
+            vec![
+                dir.join("audit-only.jsonl.19700101T001644Z"),
+                dir.join("audit-only.jsonl.19700101T001645Z"),
+                dir.join("audit-only.jsonl.19700101T001646Z"),
+            ]
+        );
+
+        // Age-based retention also applies; the newest segments stay.
+        let oldest = dir.join("audit-only.jsonl.19700101T001644Z");
+        File::options()
+            .write(true)
+            .open(&oldest)
+            .and_then(|file| file.set_modified(SystemTime::now() - Duration::from_secs(2 * 86_400)))
+            .expect("age segment");
+        let by_age = LogRotationPolicy {
+            keep_days: Some(1),
+            ..rotation
         };
+        maintain_log_segments(&log_path, &by_age, now_ts());
+        assert_eq!(log_segment_paths(&log_path).len(), 2);
+        assert!(!oldest.exists());
 
-        let line = format_runtime_health_log(1_770_000_000, 4, 1, 2, 9, 3, 5, &callback_latency);
+        let unbounded = LogRotationPolicy {
+            keep_segments: Some(usize::MAX),
+            ..LogRotationPolicy::default()
+        };
         assert_eq!(
-            line,
-            "[METRIC] runtime-health ts=1770000000 sigq_pending=4 sigq_drop_full=1 sigq_drop_disconnected=2 logq_pending=9 logq_drop_full=3 logq_drop_disconnected=5 cb_count=3 cb_p50_us=250 cb_p95_us=500001 cb_p99_us=500001 cb_max_us=700000"
+            unbounded.keep_segments_or_default(),
+            LOG_ROTATION_MAX_KEEP_SEGMENTS
         );
+        let _ = fs::remove_dir_all(&dir);
     }
 
     #[test]
-    fn write_intent_detection_requires_write_flag() {
-        assert!(!is_write_intent(FFLAG_READ));
-        assert!(is_write_intent(FFLAG_WRITE));
-        assert!(is_write_intent(FFLAG_READ | FFLAG_WRITE));
+    fn compressed_segments_are_gzip_and_replace_the_plain_copy() {
+        let dir = std::env::temp_dir().join(format!("as-log-gzip-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("temp dir");
+        let segment = dir.join("denials.jsonl.19700101T001640Z");
+        fs::write(&segment, "{\"n\":1}\n{\"n\":2}\n").expect("segment");
+
+        let gzipped = compress_log_segment(&segment).expect("compress");
+        assert_eq!(gzipped, dir.join("denials.jsonl.19700101T001640Z.gz"));
+        assert!(!segment.exists());
+        assert!(!dir.join("denials.jsonl.19700101T001640Z.gz.tmp").exists());
+        let bytes = fs::read(&gzipped).expect("gzip file");
+        assert_eq!(&bytes[..2], &[0x1f, 0x8b], "gzip magic");
+        assert_eq!(
+            read_log_segment(&gzipped).expect("decompress"),
+            "{\"n\":1}\n{\"n\":2}\n"
+        );
+
+        fs::write(&gzipped, "not gzip").expect("corrupt");
+        assert!(read_log_segment(&gzipped).is_err());
+        let _ = fs::remove_dir_all(&dir);
     }
 
     #[test]
-    fn read_intent_detection_handles_zero_and_read_flags() {
-        assert!(is_read_intent(0));
-        assert!(is_read_intent(FFLAG_READ));
-        assert!(is_read_intent(FFLAG_READ | FFLAG_WRITE));
-        assert!(!is_read_intent(FFLAG_WRITE));
+    fn segment_maintenance_cleans_interrupted_compressions_and_refuses_linked_files() {
+        let rotation = LogRotationPolicy {
+            keep_segments: Some(2),
+            compress: true,
+            ..LogRotationPolicy::default()
+        };
+        let (dir, log_path) = rotated_log("leftover", &rotation, 4);
+        let _maintenance = LOG_MAINTENANCE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        maintain_log_segments(&log_path, &rotation, 1_004);
+        let segments = log_segment_paths(&log_path);
+
+        // Leftovers of a compression interrupted after its rename, and before it.
+        let plain_copy = dir.join("audit-only.jsonl.19700101T001643Z");
+        let partial = dir.join("audit-only.jsonl.19700101T001644Z.gz.tmp");
+        fs::write(&plain_copy, "{\"n\":2}\n").expect("plain copy");
+        fs::write(&partial, "partial").expect("partial gzip");
+        assert_eq!(log_segment_paths(&log_path), segments);
+        maintain_log_segments(&log_path, &rotation, 2_000);
+        assert!(!plain_copy.exists());
+        assert!(!partial.exists());
+        assert_eq!(log_segment_paths(&log_path), segments);
+
+        // A segment hard-linked elsewhere may be someone else's file.
+        let linked = dir.join("audit-only.jsonl.19700101T001700Z");
+        fs::write(&linked, "{\"n\":5}\n").expect("linked segment");
+        fs::hard_link(&linked, dir.join("elsewhere")).expect("hard link");
+        assert!(compress_log_segment(&linked).is_err());
+        assert!(linked.exists());
+        assert!(!dir.join("audit-only.jsonl.19700101T001700Z.gz.tmp").exists());
+        let _ = fs::remove_dir_all(&dir);
     }
 
     #[test]
-    fn auth_open_fast_allow_requires_non_sensitive_read_only_path() {
-        let policy = test_sensitive_policy();
-        assert!(should_fast_allow_open(
-            "/Users/jqwang/project/notes.txt",
-            FFLAG_READ,
-            &policy
-        ));
-        assert!(!should_fast_allow_open(
-            "/Users/jqwang/project/notes.txt",
-            FFLAG_WRITE,
-            &policy
-        ));
-        assert!(!should_fast_allow_open(
-            "/Users/jqwang/.codex/config.toml",
-            FFLAG_READ,
-            &policy
-        ));
+    fn denial_feedback_for_trust_mismatch_has_identity_guidance() {
+        let mut record = DenialRecord::for_test_reason(REASON_TRUST_IDENTITY_MISMATCH);
+        record.process = "git".to_string();
+        let feedback = build_denial_feedback("/Users/jqwang", &record);
+        assert!(feedback.contains("identity verification failed"));
+        assert!(feedback.contains("trusted_tool_identities"));
     }
 
     #[test]
-    fn tainted_open_write_outside_allow_zone_is_denied() {
-        let policy = test_sensitive_policy();
-        let mut taint = TaintState::new(600);
-        taint.mark(100, 1_000);
-        assert!(should_deny_tainted_open_write(
-            100,
-            "/Users/jqwang/Desktop/out.txt",
-            FFLAG_WRITE,
-            Some("python3"),
-            &TrustedProcessDecision::NotTrusted,
-            1_100,
-            &taint,
-            &policy
-        ));
+    fn trusted_identity_self_check_warns_when_identities_missing() {
+        let mut policy = test_policy();
+        policy.trusted_tools = vec!["git".to_string(), "cargo".to_string()];
+        policy.trusted_tool_identities = vec![];
+
+        let warning = trusted_identity_configuration_warning(&policy).expect("warning expected");
+        assert!(warning.contains("trusted_tool_identities"));
+        assert!(warning.contains("TRUST_IDENTITY_MISMATCH"));
+        assert!(warning.contains("git"));
+        assert!(!warning.contains("cargo"));
     }
 
     #[test]
-    fn tainted_open_read_only_outside_allow_zone_is_allowed() {
-        let policy = test_sensitive_policy();
-        let mut taint = TaintState::new(600);
-        taint.mark(100, 1_000);
-        assert!(!should_deny_tainted_open_write(
-            100,
-            "/Users/jqwang/Desktop/out.txt",
-            FFLAG_READ,
-            Some("python3"),
-            &TrustedProcessDecision::NotTrusted,
-            1_100,
-            &taint,
-            &policy
-        ));
+    fn trusted_identity_self_check_ignores_unused_tools_for_warning_scope() {
+        let mut policy = test_policy();
+        policy.trusted_tools = vec!["git".to_string(), "cargo".to_string()];
+        policy.trusted_tool_identities = vec![TrustedToolIdentity {
+            path: "/usr/bin/git".to_string(),
+            signing_identifier: "com.apple.git".to_string(),
+            team_identifier: None,
+            cdhash: None,
+        }];
+
+        assert!(trusted_identity_configuration_warning(&policy).is_none());
     }
 
     #[test]
-    fn exfil_tool_is_denied_in_ai_context() {
-        let policy = test_sensitive_policy();
-        assert!(should_deny_exec_in_ai_context("curl", true, &policy));
+    fn trusted_identity_self_check_warns_for_missing_tools_when_trusted_gate_enabled() {
+        let mut policy = test_policy();
+        policy.allow_trusted_tools_in_ai_context = true;
+        policy.trusted_tools = vec!["git".to_string(), "cargo".to_string()];
+        policy.trusted_tool_identities = vec![TrustedToolIdentity {
+            path: "/usr/bin/git".to_string(),
+            signing_identifier: "com.apple.git".to_string(),
+            team_identifier: None,
+            cdhash: None,
+        }];
+
+        let warning = trusted_identity_configuration_warning(&policy).expect("warning expected");
+        assert!(warning.contains("cargo"));
+        assert!(warning.contains("TRUST_IDENTITY_MISMATCH"));
     }
 
     #[test]
-    fn exfil_tool_is_allowed_outside_ai_context() {
-        let policy = test_sensitive_policy();
-        assert!(!should_deny_exec_in_ai_context("curl", false, &policy));
+    fn trusted_identity_self_check_is_quiet_when_no_trusted_gates_are_active() {
+        let mut policy = test_policy();
+        policy.allow_vcs_metadata_in_ai_context = false;
+        policy.allow_trusted_tools_in_ai_context = false;
+        policy.trusted_tools = vec!["git".to_string()];
+        policy.trusted_tool_identities = vec![];
+
+        assert!(trusted_identity_configuration_warning(&policy).is_none());
     }
 
     #[test]
-    fn denial_record_includes_reason_code() {
-        let record = DenialRecord::for_test_reason("SENSITIVE_READ_NON_AI");
-        let json = serde_json::to_string(&record).expect("serialize");
-        assert!(json.contains("SENSITIVE_READ_NON_AI"));
+    fn trusted_identity_self_check_is_quiet_when_identities_present() {
+        let mut policy = test_policy();
+        policy.trusted_tools = vec!["git".to_string()];
+        policy.trusted_tool_identities = vec![TrustedToolIdentity {
+            path: "/usr/bin/git".to_string(),
+            signing_identifier: "com.apple.git".to_string(),
+            team_identifier: Some("not-real-team".to_string()),
+            cdhash: None,
+        }];
+
+        assert!(trusted_identity_configuration_warning(&policy).is_none());
     }
 
     #[test]
-    fn audit_only_coalescer_aggregates_same_signature_within_window() {
-        let mut coalescer = AuditOnlyCoalescer::default();
+    fn policy_self_check_warning_is_throttled_for_duplicate_messages() {
+        let mut policy = test_policy();
+        policy.trusted_tools = vec!["git".to_string()];
+        policy.trusted_tool_identities = vec![];
+        let mut warning_state = None;
 
-        let mut first = DenialRecord::for_test_reason(REASON_SENSITIVE_READ_NON_AI);
-        first.ts = 1;
-        first.path = "/Users/jqwang/.codex/config.toml".to_string();
-        first.process = "zsh".to_string();
-        first.ancestor = "none".to_string();
+        log_policy_self_checks(&policy, &mut warning_state, 1_000);
+        let (first_warning, first_ts) = warning_state.clone().expect("warning state should be set");
+        assert_eq!(first_ts, 1_000);
 
-        let mut second = first.clone();
-        second.ts = 5;
-        second.pid = Some(2001);
-        second.ppid = Some(2000);
+        log_policy_self_checks(&policy, &mut warning_state, 1_050);
+        let (second_warning, second_ts) = warning_state.clone().expect("warning state should still be set");
+        assert_eq!(first_warning, second_warning);
+        assert_eq!(second_ts, 1_000);
 
-        assert!(coalescer.ingest(first).is_empty());
-        assert!(coalescer.ingest(second).is_empty());
+        log_policy_self_checks(&policy, &mut warning_state, 1_401);
+        let (_, third_ts) = warning_state.expect("warning state should remain set");
+        assert_eq!(third_ts, 1_401);
+    }
+
+    #[derive(Debug)]
+    struct SensitiveOpenDecision {
+        deny: bool,
+    }
+
+    fn decide_sensitive_open_for_test(
+        path: &str,
+        is_ai_context: bool,
+        fflag: i32,
+        policy: &SecurityPolicy,
+    ) -> SensitiveOpenDecision {
+        SensitiveOpenDecision {
+            deny: should_deny_sensitive_open(path, is_ai_context, fflag, policy),
+        }
+    }
+
+    fn test_sensitive_policy() -> SecurityPolicy {
+        let mut policy = test_policy();
+        policy.sensitive_zones = vec!["/Users/jqwang/.codex".to_string()];
+        policy.sensitive_export_allow_zones = vec!["/Users/jqwang/.agentsmith-rs/guard/quarantine".to_string()];
+        policy
+    }
+
+    pub(crate) fn test_policy() -> SecurityPolicy {
+        SecurityPolicy {
+            protected_zones: vec!["/Users/jqwang/project".to_string()],
+            temporary_overrides: vec![],
+            auto_protect_home_digit_children: true,
+            allow_vcs_metadata_in_ai_context: true,
+            allow_git_merge_pull_in_ai_context: true,
+            trusted_tools: default_trusted_tools(),
+            trusted_tool_identities: vec![],
+            ai_agent_patterns: default_ai_agent_patterns(),
+            ai_agent_detectors: vec![],
+            ai_agent_profiles: vec![],
+            ai_agent_min_confidence: DetectorConfidence::Low,
+            allow_trusted_tools_in_ai_context: false,
+            exec_exfil_tool_blocklist: default_exec_exfil_tool_blocklist(),
+            sensitive_zones: vec![],
+            sensitive_export_allow_zones: vec![],
+            read_gate_enabled: true,
+            transfer_gate_enabled: true,
+            exec_gate_enabled: true,
+            audit_only_mode: false,
+            taint_ttl_seconds: None,
+            trusted_identity_require_cdhash: false,
+            override_approval_required: false,
+            override_approval_timeout_seconds: None,
+            override_policy: OverridePolicy::default(),
+            log_rotation: LogRotationPolicy::default(),
+            truncate_gate_enabled: false,
+            truncate_gate_zones: vec![],
+            truncate_gate_min_bytes: 0,
+            metadata_gate_enabled: true,
+            metadata_gate_ops: default_metadata_gate_ops(),
+            metadata_gate_xattr_allowlist: default_metadata_gate_xattr_allowlist(),
+        }
+    }
+
+    #[test]
+    fn legacy_override_path_still_decodes_and_applies() {
+        let json = r#"{
+            "protected_zones": ["/Users/jqwang/0"],
+            "temporary_overrides": ["/Users/jqwang/00-nixos-config/nixos-config"]
+        }"#;
+        let policy: SecurityPolicy = serde_json::from_str(json).expect("policy json should decode");
+        assert!(
+            !policy.is_protected(
+                "/Users/jqwang/00-nixos-config/nixos-config/file.txt",
+                "/Users/jqwang"
+            ),
+            "legacy string override should still bypass protection"
+        );
+    }
+
+    #[test]
+    fn sanitize_overrides_removes_expired_entries() {
+        let mut policy = SecurityPolicy {
+            protected_zones: vec!["/Users/jqwang/00-nixos-config".to_string()],
+            temporary_overrides: vec![TemporaryOverrideEntry::Rule(TemporaryOverrideRule {
+                path: "/Users/jqwang/00-nixos-config".to_string(),
+                expires_at: Some(100),
+                created_at: Some(1),
+                created_by: Some("test".to_string()),
+                scope: None,
+                ops: Vec::new(),
+                rename_to: None,
+                max_uses: None,
+                uses: 0,
+                pin: None,
+                id: None,
+            })],
+            auto_protect_home_digit_children: false,
+            allow_vcs_metadata_in_ai_context: true,
+            allow_git_merge_pull_in_ai_context: true,
+            trusted_tools: default_trusted_tools(),
+            trusted_tool_identities: vec![],
+            ai_agent_patterns: default_ai_agent_patterns(),
+            ai_agent_detectors: vec![],
+            ai_agent_profiles: vec![],
+            ai_agent_min_confidence: DetectorConfidence::Low,
+            allow_trusted_tools_in_ai_context: false,
+            exec_exfil_tool_blocklist: default_exec_exfil_tool_blocklist(),
+            sensitive_zones: vec![],
+            sensitive_export_allow_zones: vec![],
+            read_gate_enabled: true,
+            transfer_gate_enabled: true,
+            exec_gate_enabled: true,
+            audit_only_mode: false,
+            taint_ttl_seconds: None,
+            trusted_identity_require_cdhash: false,
+            override_approval_required: false,
+            override_approval_timeout_seconds: None,
+            override_policy: OverridePolicy::default(),
+            log_rotation: LogRotationPolicy::default(),
+            truncate_gate_enabled: false,
+            truncate_gate_zones: vec![],
+            truncate_gate_min_bytes: 0,
+            metadata_gate_enabled: true,
+            metadata_gate_ops: default_metadata_gate_ops(),
+            metadata_gate_xattr_allowlist: default_metadata_gate_xattr_allowlist(),
+        };
 
-        let ready = coalescer.take_ready(11, false);
-        assert_eq!(ready.len(), 1);
-        let entry = &ready[0];
-        assert_eq!(entry.count, 2);
-        assert_eq!(entry.first_ts, 1);
-        assert_eq!(entry.last_ts, 5);
+        let changed = policy.sanitize_overrides(100, "/Users/jqwang");
+        assert!(changed, "expired override should be removed");
+        assert!(policy.temporary_overrides.is_empty());
     }
 
     #[test]
-    fn audit_only_coalescer_keeps_distinct_signatures_separate() {
-        let mut coalescer = AuditOnlyCoalescer::default();
-
-        let mut first = DenialRecord::for_test_reason(REASON_SENSITIVE_READ_NON_AI);
-        first.ts = 1;
-        first.path = "/Users/jqwang/.codex/config.toml".to_string();
-
-        let mut second = first.clone();
-        second.ts = 2;
-        second.path = "/Users/jqwang/.codex/history.jsonl".to_string();
-
-        assert!(coalescer.ingest(first).is_empty());
-        assert!(coalescer.ingest(second).is_empty());
+    fn sanitize_overrides_deduplicates_paths_by_last_entry() {
+        let mut policy = SecurityPolicy {
+            protected_zones: vec!["/Users/jqwang/00-nixos-config".to_string()],
+            temporary_overrides: vec![
+                TemporaryOverrideEntry::Path("/Users/jqwang/00-nixos-config/nixos-config".to_string()),
+                TemporaryOverrideEntry::Rule(TemporaryOverrideRule {
+                    path: "/Users/jqwang/00-nixos-config/nixos-config".to_string(),
+                    expires_at: Some(9_999_999_999),
+                    created_at: Some(2),
+                    created_by: Some("newer".to_string()),
+                    scope: None,
+                    ops: Vec::new(),
+                    rename_to: None,
+                    max_uses: None,
+                    uses: 0,
+                    pin: None,
+                    id: None,
+                }),
+            ],
+            auto_protect_home_digit_children: false,
+            allow_vcs_metadata_in_ai_context: true,
+            allow_git_merge_pull_in_ai_context: true,
+            trusted_tools: default_trusted_tools(),
+            trusted_tool_identities: vec![],
+            ai_agent_patterns: default_ai_agent_patterns(),
+            ai_agent_detectors: vec![],
+            ai_agent_profiles: vec![],
+            ai_agent_min_confidence: DetectorConfidence::Low,
+            allow_trusted_tools_in_ai_context: false,
+            exec_exfil_tool_blocklist: default_exec_exfil_tool_blocklist(),
+            sensitive_zones: vec![],
+            sensitive_export_allow_zones: vec![],
+            read_gate_enabled: true,
+            transfer_gate_enabled: true,
+            exec_gate_enabled: true,
+            audit_only_mode: false,
+            taint_ttl_seconds: None,
+            trusted_identity_require_cdhash: false,
+            override_approval_required: false,
+            override_approval_timeout_seconds: None,
+            override_policy: OverridePolicy::default(),
+            log_rotation: LogRotationPolicy::default(),
+            truncate_gate_enabled: false,
+            truncate_gate_zones: vec![],
+            truncate_gate_min_bytes: 0,
+            metadata_gate_enabled: true,
+            metadata_gate_ops: default_metadata_gate_ops(),
+            metadata_gate_xattr_allowlist: default_metadata_gate_xattr_allowlist(),
+        };
 
-        let mut ready = coalescer.take_ready(100, true);
-        ready.sort_by(|left, right| left.sample.path.cmp(&right.sample.path));
-        assert_eq!(ready.len(), 2);
-        assert_eq!(ready[0].count, 1);
-        assert_eq!(ready[1].count, 1);
+        let changed = policy.sanitize_overrides(1, "/Users/jqwang");
+        assert!(changed, "duplicate overrides should be compacted");
+        assert_eq!(policy.temporary_overrides.len(), 1);
+        assert_eq!(
+            policy.temporary_overrides[0].expires_at(),
+            Some(9_999_999_999),
+            "newest override entry should win after dedup"
+        );
     }
 
     #[test]
-    fn audit_only_log_line_includes_count_only_for_aggregated_entries() {
-        let mut sample = DenialRecord::for_test_reason(REASON_SENSITIVE_READ_NON_AI);
-        sample.ts = 7;
-        sample.path = "/Users/jqwang/.codex/config.toml".to_string();
-
-        let single = AuditOnlyAggregateEntry::new(sample.clone());
-        let single_json = serialize_audit_only_entry(&single).expect("single audit json");
-        assert!(!single_json.contains("\"count\""));
-        assert!(!single_json.contains("\"first_ts\""));
-        assert!(!single_json.contains("\"last_ts\""));
-
-        let mut aggregated = AuditOnlyAggregateEntry::new(sample);
-        aggregated.merge_same_signature(8);
-        aggregated.merge_same_signature(10);
-        let aggregated_json = serialize_audit_only_entry(&aggregated).expect("aggregated audit json");
-        assert!(aggregated_json.contains("\"count\":3"));
-        assert!(aggregated_json.contains("\"first_ts\":7"));
-        assert!(aggregated_json.contains("\"last_ts\":10"));
+    fn path_prefix_match_respects_directory_boundaries() {
+        assert!(path_prefix_match(
+            "/Users/jqwang/project/a.txt",
+            "/Users/jqwang/project"
+        ));
+        assert!(path_prefix_match(
+            "/Users/jqwang/project",
+            "/Users/jqwang/project"
+        ));
+        assert!(!path_prefix_match(
+            "/Users/jqwang/project-backup/a.txt",
+            "/Users/jqwang/project"
+        ));
+        assert!(!path_prefix_match(
+            "/Users/jqwang/projectx",
+            "/Users/jqwang/project"
+        ));
     }
 
     #[test]
-    fn denial_feedback_for_sensitive_read_has_read_specific_guidance() {
-        let record = DenialRecord::for_test_reason(REASON_SENSITIVE_READ_NON_AI);
-        let feedback = build_denial_feedback("/Users/jqwang", &record);
-        assert!(feedback.contains("non-AI reads are denied"));
-        assert!(feedback.contains("AI context"));
-        assert!(!feedback.contains("agentsmith-override --minutes 3"));
+    fn path_prefix_match_supports_system_volume_and_private_aliases() {
+        assert!(path_prefix_match(
+            "/System/Volumes/Data/Users/jqwang/.codex/config.toml",
+            "/Users/jqwang/.codex"
+        ));
+        assert!(path_prefix_match(
+            "/Users/jqwang/.codex/config.toml",
+            "/System/Volumes/Data/Users/jqwang/.codex"
+        ));
+        assert!(path_prefix_match("/private/tmp/agentsmith-rs.tmp", "/tmp"));
+        assert!(path_prefix_match("/tmp/agentsmith-rs.tmp", "/private/tmp"));
+        assert!(!path_prefix_match(
+            "/System/Volumes/Data/Users/jqwang/.codex-backup/config.toml",
+            "/Users/jqwang/.codex"
+        ));
     }
 
     #[test]
-    fn denial_feedback_for_protected_delete_keeps_override_guidance() {
-        let record = DenialRecord::for_test_reason(REASON_PROTECTED_ZONE_AI_DELETE);
-        let feedback = build_denial_feedback("/Users/jqwang", &record);
-        assert!(feedback.contains("agentsmith-override --minutes 3"));
-        assert!(feedback.contains("agentsmith-quarantine"));
+    fn normalize_absolute_path_collapses_dot_segments() {
+        assert_eq!(
+            normalize_absolute_path("/Users/jqwang/project/./a/../b.txt"),
+            Some("/Users/jqwang/project/b.txt".to_string())
+        );
+        assert_eq!(
+            normalize_absolute_path("/Users/jqwang/project/../../.ssh/id_rsa"),
+            Some("/Users/.ssh/id_rsa".to_string())
+        );
+        assert_eq!(normalize_absolute_path("relative/path"), None);
     }
 
-    #[test]
-    fn denial_feedback_for_trust_mismatch_has_identity_guidance() {
-        let mut record = DenialRecord::for_test_reason(REASON_TRUST_IDENTITY_MISMATCH);
-        record.process = "git".to_string();
-        let feedback = build_denial_feedback("/Users/jqwang", &record);
-        assert!(feedback.contains("identity verification failed"));
-        assert!(feedback.contains("trusted_tool_identities"));
+    fn trusted_identity_for_path(path: &str) -> TrustedToolIdentity {
+        let signature = read_binary_signature(path).expect("signed binary");
+        TrustedToolIdentity {
+            path: path.to_string(),
+            signing_identifier: signature.signing_identifier,
+            team_identifier: signature.team_identifier,
+            cdhash: signature.cdhash,
+        }
     }
 
     #[test]
-    fn trusted_identity_self_check_warns_when_identities_missing() {
+    fn trusted_identity_accepts_real_system_git_and_xcrun() {
+        let git_path = canonicalize_executable_path_strict("/usr/bin/git").expect("canonical git");
+        let xcrun_path = canonicalize_executable_path_strict("/usr/bin/xcrun").expect("canonical xcrun");
+
         let mut policy = test_policy();
-        policy.trusted_tools = vec!["git".to_string(), "cargo".to_string()];
-        policy.trusted_tool_identities = vec![];
+        policy.trusted_tools = vec!["git".to_string(), "xcrun".to_string()];
+        policy.trusted_tool_identities = vec![
+            trusted_identity_for_path(&git_path),
+            trusted_identity_for_path(&xcrun_path),
+        ];
 
-        let warning = trusted_identity_configuration_warning(&policy).expect("warning expected");
-        assert!(warning.contains("trusted_tool_identities"));
-        assert!(warning.contains("TRUST_IDENTITY_MISMATCH"));
-        assert!(warning.contains("git"));
-        assert!(!warning.contains("cargo"));
+        assert_eq!(
+            evaluate_trusted_process_from_path("git", &git_path, &policy),
+            TrustedProcessDecision::Trusted
+        );
+        assert_eq!(
+            evaluate_trusted_process_from_path("xcrun", &xcrun_path, &policy),
+            TrustedProcessDecision::Trusted
+        );
     }
 
     #[test]
-    fn trusted_identity_self_check_ignores_unused_tools_for_warning_scope() {
+    fn trusted_identity_rejects_basename_spoof_binary() {
+        let git_path = canonicalize_executable_path_strict("/usr/bin/git").expect("canonical git");
         let mut policy = test_policy();
-        policy.trusted_tools = vec!["git".to_string(), "cargo".to_string()];
-        policy.trusted_tool_identities = vec![TrustedToolIdentity {
-            path: "/usr/bin/git".to_string(),
-            signing_identifier: "com.apple.git".to_string(),
-            team_identifier: None,
-            cdhash: None,
-        }];
+        policy.trusted_tools = vec!["git".to_string()];
+        policy.trusted_tool_identities = vec![trusted_identity_for_path(&git_path)];
 
-        assert!(trusted_identity_configuration_warning(&policy).is_none());
-    }
+        let tmp_dir = std::env::temp_dir().join(format!(
+            "agentsmith-trust-spoof-{}-{}",
+            std::process::id(),
+            now_ts()
+        ));
+        fs::create_dir_all(&tmp_dir).expect("create spoof temp dir");
+        let fake_git = tmp_dir.join("git");
+        fs::write(&fake_git, "#!/bin/sh\necho fake-git\n").expect("write fake git");
+        let mut perms = fs::metadata(&fake_git).expect("metadata").permissions();
+        perms.set_mode(0o755);
+        fs::set_permissions(&fake_git, perms).expect("chmod fake git");
 
-    #[test]
-    fn trusted_identity_self_check_warns_for_missing_tools_when_trusted_gate_enabled() {
-        let mut policy = test_policy();
-        policy.allow_trusted_tools_in_ai_context = true;
-        policy.trusted_tools = vec!["git".to_string(), "cargo".to_string()];
-        policy.trusted_tool_identities = vec![TrustedToolIdentity {
-            path: "/usr/bin/git".to_string(),
-            signing_identifier: "com.apple.git".to_string(),
-            team_identifier: None,
-            cdhash: None,
-        }];
+        let decision = evaluate_trusted_process_from_path("git", fake_git.to_str().expect("utf8 path"), &policy);
+        assert!(matches!(
+            decision,
+            TrustedProcessDecision::IdentityMismatch(_)
+        ));
 
-        let warning = trusted_identity_configuration_warning(&policy).expect("warning expected");
-        assert!(warning.contains("cargo"));
-        assert!(warning.contains("TRUST_IDENTITY_MISMATCH"));
+        let _ = fs::remove_dir_all(tmp_dir);
     }
 
     #[test]
-    fn trusted_identity_self_check_is_quiet_when_no_trusted_gates_are_active() {
+    fn trusted_identity_rejects_signed_binary_with_wrong_identity() {
+        let git_path = canonicalize_executable_path_strict("/usr/bin/git").expect("canonical git");
+        let xcrun_path = canonicalize_executable_path_strict("/usr/bin/xcrun").expect("canonical xcrun");
+
         let mut policy = test_policy();
-        policy.allow_vcs_metadata_in_ai_context = false;
-        policy.allow_trusted_tools_in_ai_context = false;
         policy.trusted_tools = vec!["git".to_string()];
-        policy.trusted_tool_identities = vec![];
+        policy.trusted_tool_identities = vec![trusted_identity_for_path(&git_path)];
 
-        assert!(trusted_identity_configuration_warning(&policy).is_none());
+        let decision = evaluate_trusted_process_from_path("git", &xcrun_path, &policy);
+        assert!(matches!(
+            decision,
+            TrustedProcessDecision::IdentityMismatch(_)
+        ));
     }
 
     #[test]
-    fn trusted_identity_self_check_is_quiet_when_identities_present() {
+    fn trusted_identity_requires_trusted_tools_membership() {
+        let git_path = canonicalize_executable_path_strict("/usr/bin/git").expect("canonical git");
         let mut policy = test_policy();
-        policy.trusted_tools = vec!["git".to_string()];
-        policy.trusted_tool_identities = vec![TrustedToolIdentity {
-            path: "/usr/bin/git".to_string(),
-            signing_identifier: "com.apple.git".to_string(),
-            team_identifier: Some("not-real-team".to_string()),
-            cdhash: None,
-        }];
+        policy.trusted_tools = vec![];
+        policy.trusted_tool_identities = vec![trusted_identity_for_path(&git_path)];
 
-        assert!(trusted_identity_configuration_warning(&policy).is_none());
+        assert_eq!(
+            evaluate_trusted_process_from_path("git", &git_path, &policy),
+            TrustedProcessDecision::NotTrusted
+        );
     }
 
     #[test]
-    fn policy_self_check_warning_is_throttled_for_duplicate_messages() {
+    fn trusted_identity_rejects_symlink_executable_path() {
+        let git_path = canonicalize_executable_path_strict("/usr/bin/git").expect("canonical git");
         let mut policy = test_policy();
         policy.trusted_tools = vec!["git".to_string()];
-        policy.trusted_tool_identities = vec![];
-        let mut warning_state = None;
+        policy.trusted_tool_identities = vec![trusted_identity_for_path(&git_path)];
 
-        log_policy_self_checks(&policy, &mut warning_state, 1_000);
-        let (first_warning, first_ts) = warning_state.clone().expect("warning state should be set");
-        assert_eq!(first_ts, 1_000);
+        let tmp_dir = std::env::temp_dir().join(format!(
+            "agentsmith-trust-symlink-{}-{}",
+            std::process::id(),
+            now_ts()
+        ));
+        fs::create_dir_all(&tmp_dir).expect("create symlink temp dir");
+        let link_path = tmp_dir.join("git");
+        std::os::unix::fs::symlink("/usr/bin/git", &link_path).expect("create symlink");
 
-        log_policy_self_checks(&policy, &mut warning_state, 1_050);
-        let (second_warning, second_ts) = warning_state.clone().expect("warning state should still be set");
-        assert_eq!(first_warning, second_warning);
-        assert_eq!(second_ts, 1_000);
+        let decision = evaluate_trusted_process_from_path("git", link_path.to_str().expect("utf8 path"), &policy);
+        assert!(matches!(
+            decision,
+            TrustedProcessDecision::IdentityMismatch(_)
+        ));
 
-        log_policy_self_checks(&policy, &mut warning_state, 1_401);
-        let (_, third_ts) = warning_state.expect("warning state should remain set");
-        assert_eq!(third_ts, 1_401);
+        let _ = fs::remove_dir_all(tmp_dir);
     }
 
-    #[derive(Debug)]
-    struct SensitiveOpenDecision {
-        deny: bool,
-    }
+    #[test]
+    fn sanitize_overrides_drops_invalid_or_outside_zone_paths() {
+        let mut policy = SecurityPolicy {
+            protected_zones: vec!["/Users/jqwang/project".to_string()],
+            temporary_overrides: vec![
+                TemporaryOverrideEntry::Path("/".to_string()),
+                TemporaryOverrideEntry::Path("relative/path".to_string()),
+                TemporaryOverrideEntry::Path("/Users/jqwang/other/file".to_string()),
+                TemporaryOverrideEntry::Path("/Users/jqwang/project/file.txt".to_string()),
+            ],
+            auto_protect_home_digit_children: false,
+            allow_vcs_metadata_in_ai_context: true,
+            allow_git_merge_pull_in_ai_context: true,
+            trusted_tools: default_trusted_tools(),
+            trusted_tool_identities: vec![],
+            ai_agent_patterns: default_ai_agent_patterns(),
+            ai_agent_detectors: vec![],
+            ai_agent_profiles: vec![],
+            ai_agent_min_confidence: DetectorConfidence::Low,
+            allow_trusted_tools_in_ai_context: false,
+            exec_exfil_tool_blocklist: default_exec_exfil_tool_blocklist(),
+            sensitive_zones: vec![],
+            sensitive_export_allow_zones: vec![],
+            read_gate_enabled: true,
+            transfer_gate_enabled: true,
+            exec_gate_enabled: true,
+            audit_only_mode: false,
+            taint_ttl_seconds: None,
+            trusted_identity_require_cdhash: false,
+            override_approval_required: false,
+            override_approval_timeout_seconds: None,
+            override_policy: OverridePolicy::default(),
+            log_rotation: LogRotationPolicy::default(),
+            truncate_gate_enabled: false,
+            truncate_gate_zones: vec![],
+            truncate_gate_min_bytes: 0,
+            metadata_gate_enabled: true,
+            metadata_gate_ops: default_metadata_gate_ops(),
+            metadata_gate_xattr_allowlist: default_metadata_gate_xattr_allowlist(),
+        };
 
-    fn decide_sensitive_open_for_test(
-        path: &str,
-        is_ai_context: bool,
-        fflag: i32,
-        policy: &SecurityPolicy,
-    ) -> SensitiveOpenDecision {
-        SensitiveOpenDecision {
-            deny: should_deny_sensitive_open(path, is_ai_context, fflag, policy),
-        }
+        let changed = policy.sanitize_overrides(1, "/Users/jqwang");
+        assert!(changed, "invalid paths should be removed");
+        assert_eq!(policy.temporary_overrides.len(), 1);
+        assert_eq!(
+            policy.temporary_overrides[0].path(),
+            "/Users/jqwang/project/file.txt"
+        );
     }
 
-    fn test_sensitive_policy() -> SecurityPolicy {
-        let mut policy = test_policy();
-        policy.sensitive_zones = vec!["/Users/jqwang/.codex".to_string()];
-        policy.sensitive_export_allow_zones = vec!["/Users/jqwang/.agentsmith-rs/guard/quarantine".to_string()];
-        policy
+    #[test]
+    fn home_digit_root_matches_first_path_component() {
+        assert_eq!(
+            home_digit_root("/Users/jqwang/01-agent/new-codex", "/Users/jqwang"),
+            Some("/Users/jqwang/01-agent".to_string())
+        );
+        assert_eq!(
+            home_digit_root("/Users/jqwang/0x-lab/demo.txt", "/Users/jqwang"),
+            Some("/Users/jqwang/0x-lab".to_string())
+        );
+        assert_eq!(
+            home_digit_root("/Users/jqwang/dev/project", "/Users/jqwang"),
+            None
+        );
+        assert_eq!(home_digit_root("/tmp/01-agent", "/Users/jqwang"), None);
     }
 
-    fn test_policy() -> SecurityPolicy {
-        SecurityPolicy {
-            protected_zones: vec!["/Users/jqwang/project".to_string()],
+    #[test]
+    fn auto_home_digit_zone_applies_protection() {
+        let policy = SecurityPolicy {
+            protected_zones: vec![],
             temporary_overrides: vec![],
             auto_protect_home_digit_children: true,
             allow_vcs_metadata_in_ai_context: true,
@@ -4283,6 +11766,9 @@ mod tests {
             trusted_tools: default_trusted_tools(),
             trusted_tool_identities: vec![],
             ai_agent_patterns: default_ai_agent_patterns(),
+            ai_agent_detectors: vec![],
+            ai_agent_profiles: vec![],
+            ai_agent_min_confidence: DetectorConfidence::Low,
             allow_trusted_tools_in_ai_context: false,
             exec_exfil_tool_blocklist: default_exec_exfil_tool_blocklist(),
             sensitive_zones: vec![],
@@ -4293,41 +11779,41 @@ mod tests {
             audit_only_mode: false,
             taint_ttl_seconds: None,
             trusted_identity_require_cdhash: false,
-        }
-    }
+            override_approval_required: false,
+            override_approval_timeout_seconds: None,
+            override_policy: OverridePolicy::default(),
+            log_rotation: LogRotationPolicy::default(),
+            truncate_gate_enabled: false,
+            truncate_gate_zones: vec![],
+            truncate_gate_min_bytes: 0,
+            metadata_gate_enabled: true,
+            metadata_gate_ops: default_metadata_gate_ops(),
+            metadata_gate_xattr_allowlist: default_metadata_gate_xattr_allowlist(),
+        };
 
-    #[test]
-    fn legacy_override_path_still_decodes_and_applies() {
-        let json = r#"{
-            "protected_zones": ["/Users/jqwang/0"],
-            "temporary_overrides": ["/Users/jqwang/00-nixos-config/nixos-config"]
-        }"#;
-        let policy: SecurityPolicy = serde_json::from_str(json).expect("policy json should decode");
-        assert!(
-            !policy.is_protected(
-                "/Users/jqwang/00-nixos-config/nixos-config/file.txt",
-                "/Users/jqwang"
-            ),
-            "legacy string override should still bypass protection"
-        );
+        assert!(policy.is_protected("/Users/jqwang/01-agent/file.txt", "/Users/jqwang"));
+        assert!(policy.is_protected("/Users/jqwang/0x-lab/file.txt", "/Users/jqwang"));
+        assert!(!policy.is_protected("/Users/jqwang/dev/file.txt", "/Users/jqwang"));
+        assert!(!policy.is_protected("/tmp/01-agent/file.txt", "/Users/jqwang"));
     }
 
     #[test]
-    fn sanitize_overrides_removes_expired_entries() {
+    fn sanitize_overrides_keeps_auto_home_digit_entries() {
         let mut policy = SecurityPolicy {
-            protected_zones: vec!["/Users/jqwang/00-nixos-config".to_string()],
-            temporary_overrides: vec![TemporaryOverrideEntry::Rule(TemporaryOverrideRule {
-                path: "/Users/jqwang/00-nixos-config".to_string(),
-                expires_at: Some(100),
-                created_at: Some(1),
-                created_by: Some("test".to_string()),
-            })],
-            auto_protect_home_digit_children: false,
+            protected_zones: vec![],
+            temporary_overrides: vec![
+                TemporaryOverrideEntry::Path("/Users/jqwang/01-agent/file.txt".to_string()),
+                TemporaryOverrideEntry::Path("/Users/jqwang/dev/file.txt".to_string()),
+            ],
+            auto_protect_home_digit_children: true,
             allow_vcs_metadata_in_ai_context: true,
             allow_git_merge_pull_in_ai_context: true,
             trusted_tools: default_trusted_tools(),
             trusted_tool_identities: vec![],
             ai_agent_patterns: default_ai_agent_patterns(),
+            ai_agent_detectors: vec![],
+            ai_agent_profiles: vec![],
+            ai_agent_min_confidence: DetectorConfidence::Low,
             allow_trusted_tools_in_ai_context: false,
             exec_exfil_tool_blocklist: default_exec_exfil_tool_blocklist(),
             sensitive_zones: vec![],
@@ -4338,25 +11824,34 @@ mod tests {
             audit_only_mode: false,
             taint_ttl_seconds: None,
             trusted_identity_require_cdhash: false,
+            override_approval_required: false,
+            override_approval_timeout_seconds: None,
+            override_policy: OverridePolicy::default(),
+            log_rotation: LogRotationPolicy::default(),
+            truncate_gate_enabled: false,
+            truncate_gate_zones: vec![],
+            truncate_gate_min_bytes: 0,
+            metadata_gate_enabled: true,
+            metadata_gate_ops: default_metadata_gate_ops(),
+            metadata_gate_xattr_allowlist: default_metadata_gate_xattr_allowlist(),
         };
 
-        let changed = policy.sanitize_overrides(100, "/Users/jqwang");
-        assert!(changed, "expired override should be removed");
-        assert!(policy.temporary_overrides.is_empty());
+        let changed = policy.sanitize_overrides(1, "/Users/jqwang");
+        assert!(changed, "outside auto-zone entries should be removed");
+        assert_eq!(policy.temporary_overrides.len(), 1);
+        assert_eq!(
+            policy.temporary_overrides[0].path(),
+            "/Users/jqwang/01-agent/file.txt"
+        );
     }
 
     #[test]
-    fn sanitize_overrides_deduplicates_paths_by_last_entry() {
+    fn sanitize_overrides_keeps_sensitive_zone_entries() {
         let mut policy = SecurityPolicy {
-            protected_zones: vec!["/Users/jqwang/00-nixos-config".to_string()],
+            protected_zones: vec![],
             temporary_overrides: vec![
-                TemporaryOverrideEntry::Path("/Users/jqwang/00-nixos-config/nixos-config".to_string()),
-                TemporaryOverrideEntry::Rule(TemporaryOverrideRule {
-                    path: "/Users/jqwang/00-nixos-config/nixos-config".to_string(),
-                    expires_at: Some(9_999_999_999),
-                    created_at: Some(2),
-                    created_by: Some("newer".to_string()),
-                }),
+                TemporaryOverrideEntry::Path("/Users/jqwang/.codex/chat/history.jsonl".to_string()),
+                TemporaryOverrideEntry::Path("/Users/jqwang/other/file.txt".to_string()),
             ],
             auto_protect_home_digit_children: false,
             allow_vcs_metadata_in_ai_context: true,
@@ -4364,9 +11859,12 @@ mod tests {
             trusted_tools: default_trusted_tools(),
             trusted_tool_identities: vec![],
             ai_agent_patterns: default_ai_agent_patterns(),
+            ai_agent_detectors: vec![],
+            ai_agent_profiles: vec![],
+            ai_agent_min_confidence: DetectorConfidence::Low,
             allow_trusted_tools_in_ai_context: false,
             exec_exfil_tool_blocklist: default_exec_exfil_tool_blocklist(),
-            sensitive_zones: vec![],
+            sensitive_zones: vec!["/Users/jqwang/.codex".to_string()],
             sensitive_export_allow_zones: vec![],
             read_gate_enabled: true,
             transfer_gate_enabled: true,
@@ -4374,929 +11872,1698 @@ mod tests {
             audit_only_mode: false,
             taint_ttl_seconds: None,
             trusted_identity_require_cdhash: false,
+            override_approval_required: false,
+            override_approval_timeout_seconds: None,
+            override_policy: OverridePolicy::default(),
+            log_rotation: LogRotationPolicy::default(),
+            truncate_gate_enabled: false,
+            truncate_gate_zones: vec![],
+            truncate_gate_min_bytes: 0,
+            metadata_gate_enabled: true,
+            metadata_gate_ops: default_metadata_gate_ops(),
+            metadata_gate_xattr_allowlist: default_metadata_gate_xattr_allowlist(),
         };
 
         let changed = policy.sanitize_overrides(1, "/Users/jqwang");
-        assert!(changed, "duplicate overrides should be compacted");
+        assert!(changed, "outside sensitive-zone entries should be removed");
         assert_eq!(policy.temporary_overrides.len(), 1);
         assert_eq!(
-            policy.temporary_overrides[0].expires_at(),
-            Some(9_999_999_999),
-            "newest override entry should win after dedup"
+            policy.temporary_overrides[0].path(),
+            "/Users/jqwang/.codex/chat/history.jsonl"
         );
     }
 
     #[test]
-    fn path_prefix_match_respects_directory_boundaries() {
-        assert!(path_prefix_match(
-            "/Users/jqwang/project/a.txt",
-            "/Users/jqwang/project"
+    fn vcs_metadata_path_detection_respects_component_boundary() {
+        assert!(is_vcs_metadata_path("/Users/jqwang/repo/.git/index.lock"));
+        assert!(is_vcs_metadata_path("/Users/jqwang/repo/.jj/working_copy"));
+        assert!(!is_vcs_metadata_path("/Users/jqwang/repo/.gitignore"));
+        assert!(!is_vcs_metadata_path("/Users/jqwang/repo/git/.git-backup"));
+    }
+
+    #[test]
+    fn vcs_metadata_unlink_allow_requires_vcs_tool_and_flag() {
+        let mut policy = SecurityPolicy::default();
+        policy.trusted_tools = default_trusted_tools();
+        policy.allow_vcs_metadata_in_ai_context = true;
+
+        assert!(should_allow_vcs_metadata_unlink_in_ai_context(
+            "/Users/jqwang/repo/.git/index.lock",
+            Some("git"),
+            &TrustedProcessDecision::Trusted,
+            &policy
         ));
-        assert!(path_prefix_match(
-            "/Users/jqwang/project",
-            "/Users/jqwang/project"
+        assert!(!should_allow_vcs_metadata_unlink_in_ai_context(
+            "/Users/jqwang/repo/src/main.rs",
+            Some("git"),
+            &TrustedProcessDecision::Trusted,
+            &policy
         ));
-        assert!(!path_prefix_match(
-            "/Users/jqwang/project-backup/a.txt",
-            "/Users/jqwang/project"
+        assert!(!should_allow_vcs_metadata_unlink_in_ai_context(
+            "/Users/jqwang/repo/.git/index.lock",
+            Some("rm"),
+            &TrustedProcessDecision::Trusted,
+            &policy
         ));
-        assert!(!path_prefix_match(
-            "/Users/jqwang/projectx",
-            "/Users/jqwang/project"
+        assert!(!should_allow_vcs_metadata_unlink_in_ai_context(
+            "/Users/jqwang/repo/.git/index.lock",
+            Some("git"),
+            &TrustedProcessDecision::IdentityMismatch("sig mismatch".to_string()),
+            &policy
+        ));
+
+        policy.allow_vcs_metadata_in_ai_context = false;
+        assert!(!should_allow_vcs_metadata_unlink_in_ai_context(
+            "/Users/jqwang/repo/.git/index.lock",
+            Some("git"),
+            &TrustedProcessDecision::Trusted,
+            &policy
+        ));
+    }
+
+    #[test]
+    fn vcs_metadata_rename_allow_requires_both_paths_in_metadata() {
+        let mut policy = SecurityPolicy::default();
+        policy.trusted_tools = default_trusted_tools();
+        policy.allow_vcs_metadata_in_ai_context = true;
+
+        assert!(should_allow_vcs_metadata_rename_in_ai_context(
+            "/Users/jqwang/repo/.git/index.lock",
+            "/Users/jqwang/repo/.git/index",
+            Some("git"),
+            &TrustedProcessDecision::Trusted,
+            &policy
+        ));
+        assert!(!should_allow_vcs_metadata_rename_in_ai_context(
+            "/Users/jqwang/repo/.git/index.lock",
+            "/private/tmp/index.lock",
+            Some("git"),
+            &TrustedProcessDecision::Trusted,
+            &policy
+        ));
+        assert!(!should_allow_vcs_metadata_rename_in_ai_context(
+            "/Users/jqwang/repo/.git/index.lock",
+            "/Users/jqwang/repo/.git/index",
+            Some("git"),
+            &TrustedProcessDecision::IdentityMismatch("sig mismatch".to_string()),
+            &policy
         ));
     }
 
     #[test]
-    fn path_prefix_match_supports_system_volume_and_private_aliases() {
-        assert!(path_prefix_match(
-            "/System/Volumes/Data/Users/jqwang/.codex/config.toml",
-            "/Users/jqwang/.codex"
-        ));
-        assert!(path_prefix_match(
-            "/Users/jqwang/.codex/config.toml",
-            "/System/Volumes/Data/Users/jqwang/.codex"
-        ));
-        assert!(path_prefix_match("/private/tmp/agentsmith-rs.tmp", "/tmp"));
-        assert!(path_prefix_match("/tmp/agentsmith-rs.tmp", "/private/tmp"));
-        assert!(!path_prefix_match(
-            "/System/Volumes/Data/Users/jqwang/.codex-backup/config.toml",
-            "/Users/jqwang/.codex"
-        ));
+    fn apply_override_request_rejects_no_expire() {
+        let policy = test_policy();
+        let mut overrides = Vec::new();
+        let request = OverrideRequest {
+            id: "req-1".to_string(),
+            action: "grant".to_string(),
+            path: Some("/Users/jqwang/project/file.txt".to_string()),
+            minutes: Some(0),
+            requester_pid: None,
+            approval_id: None,
+            scope_pid: None,
+            scope_session: None,
+            ops: Vec::new(),
+            rename_to: None,
+            max_uses: None,
+            pin: false,
+            paths: Vec::new(),
+            glob: None,
+        };
+
+        let (changed, response) = apply_override_request(&request, &policy, "/Users/jqwang", &mut overrides);
+        assert!(!changed);
+        assert_eq!(response.status, "invalid");
+        assert!(response.message.contains("no-expire"));
+        assert!(overrides.is_empty());
     }
 
     #[test]
-    fn normalize_absolute_path_collapses_dot_segments() {
-        assert_eq!(
-            normalize_absolute_path("/Users/jqwang/project/./a/../b.txt"),
-            Some("/Users/jqwang/project/b.txt".to_string())
-        );
-        assert_eq!(
-            normalize_absolute_path("/Users/jqwang/project/../../.ssh/id_rsa"),
-            Some("/Users/.ssh/id_rsa".to_string())
-        );
-        assert_eq!(normalize_absolute_path("relative/path"), None);
-    }
+    fn apply_override_request_rejects_zone_root_path() {
+        let policy = test_policy();
+        let mut overrides = Vec::new();
+        let request = OverrideRequest {
+            id: "req-2".to_string(),
+            action: "grant".to_string(),
+            path: Some("/Users/jqwang/project".to_string()),
+            minutes: Some(3),
+            requester_pid: None,
+            approval_id: None,
+            scope_pid: None,
+            scope_session: None,
+            ops: Vec::new(),
+            rename_to: None,
+            max_uses: None,
+            pin: false,
+            paths: Vec::new(),
+            glob: None,
+        };
 
-    fn trusted_identity_for_path(path: &str) -> TrustedToolIdentity {
-        let signature = read_binary_signature(path).expect("signed binary");
-        TrustedToolIdentity {
-            path: path.to_string(),
-            signing_identifier: signature.signing_identifier,
-            team_identifier: signature.team_identifier,
-            cdhash: signature.cdhash,
-        }
+        let (changed, response) = apply_override_request(&request, &policy, "/Users/jqwang", &mut overrides);
+        assert!(!changed);
+        assert_eq!(response.status, "error");
+        assert!(response.message.contains("broad path"));
+        assert!(overrides.is_empty());
     }
 
     #[test]
-    fn trusted_identity_accepts_real_system_git_and_xcrun() {
-        let git_path = canonicalize_executable_path_strict("/usr/bin/git").expect("canonical git");
-        let xcrun_path = canonicalize_executable_path_strict("/usr/bin/xcrun").expect("canonical xcrun");
-
-        let mut policy = test_policy();
-        policy.trusted_tools = vec!["git".to_string(), "xcrun".to_string()];
-        policy.trusted_tool_identities = vec![
-            trusted_identity_for_path(&git_path),
-            trusted_identity_for_path(&xcrun_path),
-        ];
+    fn apply_override_request_allows_sensitive_zone_subpath() {
+        let policy = test_sensitive_policy();
+        let mut overrides = Vec::new();
+        let request = OverrideRequest {
+            id: "req-sensitive-allow".to_string(),
+            action: "grant-sensitive-read".to_string(),
+            path: Some("/Users/jqwang/.codex/chat/history.jsonl".to_string()),
+            minutes: Some(3),
+            requester_pid: None,
+            approval_id: None,
+            scope_pid: None,
+            scope_session: None,
+            ops: Vec::new(),
+            rename_to: None,
+            max_uses: None,
+            pin: false,
+            paths: Vec::new(),
+            glob: None,
+        };
 
+        let (changed, response) = apply_override_request(&request, &policy, "/Users/jqwang", &mut overrides);
+        assert!(changed);
+        assert_eq!(response.status, "ok");
+        assert_eq!(overrides.len(), 1);
         assert_eq!(
-            evaluate_trusted_process_from_path("git", &git_path, &policy),
-            TrustedProcessDecision::Trusted
-        );
-        assert_eq!(
-            evaluate_trusted_process_from_path("xcrun", &xcrun_path, &policy),
-            TrustedProcessDecision::Trusted
+            overrides[0].path(),
+            "/Users/jqwang/.codex/chat/history.jsonl"
         );
     }
 
     #[test]
-    fn trusted_identity_rejects_basename_spoof_binary() {
-        let git_path = canonicalize_executable_path_strict("/usr/bin/git").expect("canonical git");
-        let mut policy = test_policy();
-        policy.trusted_tools = vec!["git".to_string()];
-        policy.trusted_tool_identities = vec![trusted_identity_for_path(&git_path)];
-
-        let tmp_dir = std::env::temp_dir().join(format!(
-            "agentsmith-trust-spoof-{}-{}",
-            std::process::id(),
-            now_ts()
-        ));
-        fs::create_dir_all(&tmp_dir).expect("create spoof temp dir");
-        let fake_git = tmp_dir.join("git");
-        fs::write(&fake_git, "#!/bin/sh\necho fake-git\n").expect("write fake git");
-        let mut perms = fs::metadata(&fake_git).expect("metadata").permissions();
-        perms.set_mode(0o755);
-        fs::set_permissions(&fake_git, perms).expect("chmod fake git");
-
-        let decision = evaluate_trusted_process_from_path("git", fake_git.to_str().expect("utf8 path"), &policy);
-        assert!(matches!(
-            decision,
-            TrustedProcessDecision::IdentityMismatch(_)
-        ));
+    fn apply_override_request_rejects_sensitive_zone_root_path() {
+        let policy = test_sensitive_policy();
+        let mut overrides = Vec::new();
+        let request = OverrideRequest {
+            id: "req-sensitive-root".to_string(),
+            action: "grant-sensitive-read".to_string(),
+            path: Some("/Users/jqwang/.codex".to_string()),
+            minutes: Some(3),
+            requester_pid: None,
+            approval_id: None,
+            scope_pid: None,
+            scope_session: None,
+            ops: Vec::new(),
+            rename_to: None,
+            max_uses: None,
+            pin: false,
+            paths: Vec::new(),
+            glob: None,
+        };
 
-        let _ = fs::remove_dir_all(tmp_dir);
+        let (changed, response) = apply_override_request(&request, &policy, "/Users/jqwang", &mut overrides);
+        assert!(!changed);
+        assert_eq!(response.status, "error");
+        assert!(response.message.contains("broad path"));
+        assert!(overrides.is_empty());
     }
 
     #[test]
-    fn trusted_identity_rejects_signed_binary_with_wrong_identity() {
-        let git_path = canonicalize_executable_path_strict("/usr/bin/git").expect("canonical git");
-        let xcrun_path = canonicalize_executable_path_strict("/usr/bin/xcrun").expect("canonical xcrun");
-
-        let mut policy = test_policy();
-        policy.trusted_tools = vec!["git".to_string()];
-        policy.trusted_tool_identities = vec![trusted_identity_for_path(&git_path)];
+    fn apply_override_request_rejects_sensitive_read_grant_outside_sensitive_zone() {
+        let policy = test_sensitive_policy();
+        let mut overrides = Vec::new();
+        let request = OverrideRequest {
+            id: "req-sensitive-outside".to_string(),
+            action: "grant-sensitive-read".to_string(),
+            path: Some("/Users/jqwang/project/file.txt".to_string()),
+            minutes: Some(3),
+            requester_pid: None,
+            approval_id: None,
+            scope_pid: None,
+            scope_session: None,
+            ops: Vec::new(),
+            rename_to: None,
+            max_uses: None,
+            pin: false,
+            paths: Vec::new(),
+            glob: None,
+        };
 
-        let decision = evaluate_trusted_process_from_path("git", &xcrun_path, &policy);
-        assert!(matches!(
-            decision,
-            TrustedProcessDecision::IdentityMismatch(_)
-        ));
+        let (changed, response) = apply_override_request(&request, &policy, "/Users/jqwang", &mut overrides);
+        assert!(!changed);
+        assert_eq!(response.status, "error");
+        assert!(response.message.contains("outside sensitive"));
+        assert!(overrides.is_empty());
     }
 
     #[test]
-    fn trusted_identity_requires_trusted_tools_membership() {
-        let git_path = canonicalize_executable_path_strict("/usr/bin/git").expect("canonical git");
+    fn sensitive_read_override_does_not_disable_protected_delete_gate() {
         let mut policy = test_policy();
-        policy.trusted_tools = vec![];
-        policy.trusted_tool_identities = vec![trusted_identity_for_path(&git_path)];
+        policy.temporary_overrides = vec![TemporaryOverrideEntry::Rule(TemporaryOverrideRule {
+            path: "/Users/jqwang/project/file.txt".to_string(),
+            expires_at: Some(now_ts().saturating_add(300)),
+            created_at: Some(now_ts()),
+            created_by: Some(OVERRIDE_CREATED_BY_SENSITIVE_READ_HELPER.to_string()),
+            scope: None,
+            ops: Vec::new(),
+            rename_to: None,
+            max_uses: None,
+            uses: 0,
+            pin: None,
+            id: None,
+        })];
 
-        assert_eq!(
-            evaluate_trusted_process_from_path("git", &git_path, &policy),
-            TrustedProcessDecision::NotTrusted
+        assert!(
+            policy.is_protected("/Users/jqwang/project/file.txt", "/Users/jqwang"),
+            "sensitive-read override should not bypass protected delete/move gate"
         );
     }
 
     #[test]
-    fn trusted_identity_rejects_symlink_executable_path() {
-        let git_path = canonicalize_executable_path_strict("/usr/bin/git").expect("canonical git");
-        let mut policy = test_policy();
-        policy.trusted_tools = vec!["git".to_string()];
-        policy.trusted_tool_identities = vec![trusted_identity_for_path(&git_path)];
-
-        let tmp_dir = std::env::temp_dir().join(format!(
-            "agentsmith-trust-symlink-{}-{}",
-            std::process::id(),
-            now_ts()
-        ));
-        fs::create_dir_all(&tmp_dir).expect("create symlink temp dir");
-        let link_path = tmp_dir.join("git");
-        std::os::unix::fs::symlink("/usr/bin/git", &link_path).expect("create symlink");
-
-        let decision = evaluate_trusted_process_from_path("git", link_path.to_str().expect("utf8 path"), &policy);
-        assert!(matches!(
-            decision,
-            TrustedProcessDecision::IdentityMismatch(_)
-        ));
+    fn apply_override_request_rejects_too_long_path() {
+        let policy = test_policy();
+        let mut overrides = Vec::new();
+        let long_suffix = "a".repeat(MAX_OVERRIDE_PATH_LEN);
+        let request = OverrideRequest {
+            id: "req-long".to_string(),
+            action: "grant".to_string(),
+            path: Some(format!("/Users/jqwang/project/{}", long_suffix)),
+            minutes: Some(1),
+            requester_pid: None,
+            approval_id: None,
+            scope_pid: None,
+            scope_session: None,
+            ops: Vec::new(),
+            rename_to: None,
+            max_uses: None,
+            pin: false,
+            paths: Vec::new(),
+            glob: None,
+        };
 
-        let _ = fs::remove_dir_all(tmp_dir);
+        let (changed, response) = apply_override_request(&request, &policy, "/Users/jqwang", &mut overrides);
+        assert!(!changed);
+        assert_eq!(response.status, "invalid");
+        assert!(response.message.contains("path too long"));
+        assert!(overrides.is_empty());
     }
 
     #[test]
-    fn sanitize_overrides_drops_invalid_or_outside_zone_paths() {
-        let mut policy = SecurityPolicy {
-            protected_zones: vec!["/Users/jqwang/project".to_string()],
-            temporary_overrides: vec![
-                TemporaryOverrideEntry::Path("/".to_string()),
-                TemporaryOverrideEntry::Path("relative/path".to_string()),
-                TemporaryOverrideEntry::Path("/Users/jqwang/other/file".to_string()),
-                TemporaryOverrideEntry::Path("/Users/jqwang/project/file.txt".to_string()),
-            ],
-            auto_protect_home_digit_children: false,
-            allow_vcs_metadata_in_ai_context: true,
-            allow_git_merge_pull_in_ai_context: true,
-            trusted_tools: default_trusted_tools(),
-            trusted_tool_identities: vec![],
-            ai_agent_patterns: default_ai_agent_patterns(),
-            allow_trusted_tools_in_ai_context: false,
-            exec_exfil_tool_blocklist: default_exec_exfil_tool_blocklist(),
-            sensitive_zones: vec![],
-            sensitive_export_allow_zones: vec![],
-            read_gate_enabled: true,
-            transfer_gate_enabled: true,
-            exec_gate_enabled: true,
-            audit_only_mode: false,
-            taint_ttl_seconds: None,
-            trusted_identity_require_cdhash: false,
+    fn apply_override_request_rejects_when_override_pool_full() {
+        let policy = test_policy();
+        let mut overrides = (0..MAX_RUNTIME_OVERRIDES)
+            .map(|idx| {
+                TemporaryOverrideEntry::Rule(TemporaryOverrideRule {
+                    path: format!("/Users/jqwang/project/file-{}", idx),
+                    expires_at: Some(now_ts().saturating_add(60)),
+                    created_at: Some(now_ts()),
+                    created_by: Some("seed".to_string()),
+                    scope: None,
+                    ops: Vec::new(),
+                    rename_to: None,
+                    max_uses: None,
+                    uses: 0,
+                    pin: None,
+                    id: None,
+                })
+            })
+            .collect::<Vec<_>>();
+        let request = OverrideRequest {
+            id: "req-full".to_string(),
+            action: "grant".to_string(),
+            path: Some("/Users/jqwang/project/new-file".to_string()),
+            minutes: Some(1),
+            requester_pid: None,
+            approval_id: None,
+            scope_pid: None,
+            scope_session: None,
+            ops: Vec::new(),
+            rename_to: None,
+            max_uses: None,
+            pin: false,
+            paths: Vec::new(),
+            glob: None,
         };
 
-        let changed = policy.sanitize_overrides(1, "/Users/jqwang");
-        assert!(changed, "invalid paths should be removed");
-        assert_eq!(policy.temporary_overrides.len(), 1);
-        assert_eq!(
-            policy.temporary_overrides[0].path(),
-            "/Users/jqwang/project/file.txt"
-        );
+        let (changed, response) = apply_override_request(&request, &policy, "/Users/jqwang", &mut overrides);
+        assert!(!changed);
+        assert_eq!(response.status, "error");
+        assert!(response.message.contains("too many active overrides"));
+        assert_eq!(overrides.len(), MAX_RUNTIME_OVERRIDES);
     }
 
     #[test]
-    fn home_digit_root_matches_first_path_component() {
-        assert_eq!(
-            home_digit_root("/Users/jqwang/01-agent/new-codex", "/Users/jqwang"),
-            Some("/Users/jqwang/01-agent".to_string())
-        );
-        assert_eq!(
-            home_digit_root("/Users/jqwang/0x-lab/demo.txt", "/Users/jqwang"),
-            Some("/Users/jqwang/0x-lab".to_string())
-        );
-        assert_eq!(
-            home_digit_root("/Users/jqwang/dev/project", "/Users/jqwang"),
-            None
-        );
-        assert_eq!(home_digit_root("/tmp/01-agent", "/Users/jqwang"), None);
+    fn override_request_budget_limits_per_minute() {
+        let mut window = std::collections::VecDeque::new();
+        for _ in 0..MAX_OVERRIDE_REQUESTS_PER_MINUTE {
+            assert!(consume_override_request_budget(
+                &mut window,
+                100,
+                MAX_OVERRIDE_REQUESTS_PER_MINUTE
+            ));
+        }
+        assert!(!consume_override_request_budget(
+            &mut window,
+            100,
+            MAX_OVERRIDE_REQUESTS_PER_MINUTE
+        ));
+        assert!(consume_override_request_budget(
+            &mut window,
+            161,
+            MAX_OVERRIDE_REQUESTS_PER_MINUTE
+        ));
     }
 
     #[test]
-    fn auto_home_digit_zone_applies_protection() {
-        let policy = SecurityPolicy {
-            protected_zones: vec![],
-            temporary_overrides: vec![],
-            auto_protect_home_digit_children: true,
-            allow_vcs_metadata_in_ai_context: true,
-            allow_git_merge_pull_in_ai_context: true,
-            trusted_tools: default_trusted_tools(),
-            trusted_tool_identities: vec![],
-            ai_agent_patterns: default_ai_agent_patterns(),
-            allow_trusted_tools_in_ai_context: false,
-            exec_exfil_tool_blocklist: default_exec_exfil_tool_blocklist(),
-            sensitive_zones: vec![],
-            sensitive_export_allow_zones: vec![],
-            read_gate_enabled: true,
-            transfer_gate_enabled: true,
-            exec_gate_enabled: true,
-            audit_only_mode: false,
-            taint_ttl_seconds: None,
-            trusted_identity_require_cdhash: false,
+    fn validate_override_request_origin_requires_pid() {
+        let policy = test_policy();
+        let missing_pid = OverrideRequest {
+            id: "req-origin-1".to_string(),
+            action: "grant".to_string(),
+            path: Some("/Users/jqwang/project/file.txt".to_string()),
+            minutes: Some(1),
+            requester_pid: None,
+            approval_id: None,
+            scope_pid: None,
+            scope_session: None,
+            ops: Vec::new(),
+            rename_to: None,
+            max_uses: None,
+            pin: false,
+            paths: Vec::new(),
+            glob: None,
         };
+        let err =
+            validate_override_request_origin(&missing_pid, &policy, &Mutex::default()).expect_err("pid is required");
+        assert!(err.contains("missing requester_pid"));
 
-        assert!(policy.is_protected("/Users/jqwang/01-agent/file.txt", "/Users/jqwang"));
-        assert!(policy.is_protected("/Users/jqwang/0x-lab/file.txt", "/Users/jqwang"));
-        assert!(!policy.is_protected("/Users/jqwang/dev/file.txt", "/Users/jqwang"));
-        assert!(!policy.is_protected("/tmp/01-agent/file.txt", "/Users/jqwang"));
+        let invalid_pid = OverrideRequest {
+            id: "req-origin-2".to_string(),
+            action: "grant".to_string(),
+            path: Some("/Users/jqwang/project/file.txt".to_string()),
+            minutes: Some(1),
+            requester_pid: Some(0),
+            approval_id: None,
+            scope_pid: None,
+            scope_session: None,
+            ops: Vec::new(),
+            rename_to: None,
+            max_uses: None,
+            pin: false,
+            paths: Vec::new(),
+            glob: None,
+        };
+        let err = validate_override_request_origin(&invalid_pid, &policy, &Mutex::default())
+            .expect_err("pid must be positive");
+        assert!(err.contains("invalid requester_pid"));
     }
 
     #[test]
-    fn sanitize_overrides_keeps_auto_home_digit_entries() {
-        let mut policy = SecurityPolicy {
-            protected_zones: vec![],
-            temporary_overrides: vec![
-                TemporaryOverrideEntry::Path("/Users/jqwang/01-agent/file.txt".to_string()),
-                TemporaryOverrideEntry::Path("/Users/jqwang/dev/file.txt".to_string()),
-            ],
-            auto_protect_home_digit_children: true,
-            allow_vcs_metadata_in_ai_context: true,
-            allow_git_merge_pull_in_ai_context: true,
-            trusted_tools: default_trusted_tools(),
-            trusted_tool_identities: vec![],
-            ai_agent_patterns: default_ai_agent_patterns(),
-            allow_trusted_tools_in_ai_context: false,
-            exec_exfil_tool_blocklist: default_exec_exfil_tool_blocklist(),
-            sensitive_zones: vec![],
-            sensitive_export_allow_zones: vec![],
-            read_gate_enabled: true,
-            transfer_gate_enabled: true,
-            exec_gate_enabled: true,
-            audit_only_mode: false,
-            taint_ttl_seconds: None,
-            trusted_identity_require_cdhash: false,
-        };
+    fn parse_procargs_argv_extracts_expected_arguments() {
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&(3_i32).to_ne_bytes());
+        payload.extend_from_slice(b"/bin/bash");
+        payload.push(0);
+        payload.push(0);
+        payload.extend_from_slice(b"/bin/bash");
+        payload.push(0);
+        payload.extend_from_slice(b"/usr/local/bin/agentsmith-override");
+        payload.push(0);
+        payload.extend_from_slice(b"--clear");
+        payload.push(0);
 
-        let changed = policy.sanitize_overrides(1, "/Users/jqwang");
-        assert!(changed, "outside auto-zone entries should be removed");
-        assert_eq!(policy.temporary_overrides.len(), 1);
-        assert_eq!(
-            policy.temporary_overrides[0].path(),
-            "/Users/jqwang/01-agent/file.txt"
-        );
+        let args = parse_procargs_argv(&payload);
+        assert_eq!(args.len(), 3);
+        assert_eq!(args[0], "/bin/bash");
+        assert_eq!(args[1], "/usr/local/bin/agentsmith-override");
+        assert_eq!(args[2], "--clear");
     }
 
     #[test]
-    fn sanitize_overrides_keeps_sensitive_zone_entries() {
-        let mut policy = SecurityPolicy {
-            protected_zones: vec![],
-            temporary_overrides: vec![
-                TemporaryOverrideEntry::Path("/Users/jqwang/.codex/chat/history.jsonl".to_string()),
-                TemporaryOverrideEntry::Path("/Users/jqwang/other/file.txt".to_string()),
-            ],
-            auto_protect_home_digit_children: false,
-            allow_vcs_metadata_in_ai_context: true,
-            allow_git_merge_pull_in_ai_context: true,
-            trusted_tools: default_trusted_tools(),
-            trusted_tool_identities: vec![],
-            ai_agent_patterns: default_ai_agent_patterns(),
-            allow_trusted_tools_in_ai_context: false,
-            exec_exfil_tool_blocklist: default_exec_exfil_tool_blocklist(),
-            sensitive_zones: vec!["/Users/jqwang/.codex".to_string()],
-            sensitive_export_allow_zones: vec![],
-            read_gate_enabled: true,
-            transfer_gate_enabled: true,
-            exec_gate_enabled: true,
-            audit_only_mode: false,
-            taint_ttl_seconds: None,
-            trusted_identity_require_cdhash: false,
-        };
+    fn helper_argv_detection_requires_override_binary_argument() {
+        let helper_args = vec![
+            "/bin/bash".to_string(),
+            "/usr/local/bin/agentsmith-override".to_string(),
+            "--minutes".to_string(),
+            "3".to_string(),
+        ];
+        assert!(is_override_helper_argv(&helper_args));
 
-        let changed = policy.sanitize_overrides(1, "/Users/jqwang");
-        assert!(changed, "outside sensitive-zone entries should be removed");
-        assert_eq!(policy.temporary_overrides.len(), 1);
-        assert_eq!(
-            policy.temporary_overrides[0].path(),
-            "/Users/jqwang/.codex/chat/history.jsonl"
-        );
+        let unrelated_args = vec!["/bin/bash".to_string(), "/tmp/custom-script.sh".to_string()];
+        assert!(!is_override_helper_argv(&unrelated_args));
+        assert!(argv_runs_helper(
+            &["/bin/bash".to_string(), "/usr/local/bin/agentsmith-run".to_string()],
+            "agentsmith-run"
+        ));
+        assert!(!argv_runs_helper(&helper_args, "agentsmith-run"));
     }
 
     #[test]
-    fn vcs_metadata_path_detection_respects_component_boundary() {
-        assert!(is_vcs_metadata_path("/Users/jqwang/repo/.git/index.lock"));
-        assert!(is_vcs_metadata_path("/Users/jqwang/repo/.jj/working_copy"));
-        assert!(!is_vcs_metadata_path("/Users/jqwang/repo/.gitignore"));
-        assert!(!is_vcs_metadata_path("/Users/jqwang/repo/git/.git-backup"));
+    fn git_merge_or_pull_invocation_detects_allowed_subcommands() {
+        let merge_args = vec![
+            "git".to_string(),
+            "-C".to_string(),
+            "/Users/jqwang/00-nixos-config/agentsmith-rs-core".to_string(),
+            "merge".to_string(),
+            "feature/codex-sensitive-dlp-c".to_string(),
+        ];
+        assert!(is_git_merge_or_pull_invocation(&merge_args));
+
+        let pull_args = vec![
+            "git".to_string(),
+            "--work-tree=/Users/jqwang/00-nixos-config/agentsmith-rs-core".to_string(),
+            "pull".to_string(),
+            "--ff-only".to_string(),
+        ];
+        assert!(is_git_merge_or_pull_invocation(&pull_args));
     }
 
     #[test]
-    fn vcs_metadata_unlink_allow_requires_vcs_tool_and_flag() {
-        let mut policy = SecurityPolicy::default();
-        policy.trusted_tools = default_trusted_tools();
-        policy.allow_vcs_metadata_in_ai_context = true;
+    fn git_merge_or_pull_invocation_rejects_other_subcommands() {
+        let rebase_args = vec!["git".to_string(), "rebase".to_string(), "main".to_string()];
+        assert!(!is_git_merge_or_pull_invocation(&rebase_args));
 
-        assert!(should_allow_vcs_metadata_unlink_in_ai_context(
-            "/Users/jqwang/repo/.git/index.lock",
-            Some("git"),
+        let rm_args = vec!["git".to_string(), "rm".to_string(), "README.md".to_string()];
+        assert!(!is_git_merge_or_pull_invocation(&rm_args));
+    }
+
+    #[test]
+    fn git_merge_or_pull_allow_requires_git_process_and_policy_flag() {
+        let mut policy = test_policy();
+        policy.allow_git_merge_pull_in_ai_context = true;
+
+        let merge_args = vec!["git".to_string(), "merge".to_string(), "topic".to_string()];
+        assert!(should_allow_git_merge_pull_worktree_change_in_ai_context(
+            "git",
+            &merge_args,
             &TrustedProcessDecision::Trusted,
             &policy
         ));
-        assert!(!should_allow_vcs_metadata_unlink_in_ai_context(
-            "/Users/jqwang/repo/src/main.rs",
-            Some("git"),
+        assert!(!should_allow_git_merge_pull_worktree_change_in_ai_context(
+            "bash",
+            &merge_args,
             &TrustedProcessDecision::Trusted,
             &policy
         ));
-        assert!(!should_allow_vcs_metadata_unlink_in_ai_context(
-            "/Users/jqwang/repo/.git/index.lock",
-            Some("rm"),
-            &TrustedProcessDecision::Trusted,
+        assert!(!should_allow_git_merge_pull_worktree_change_in_ai_context(
+            "git",
+            &merge_args,
+            &TrustedProcessDecision::IdentityMismatch("sig mismatch".to_string()),
             &policy
         ));
-        assert!(!should_allow_vcs_metadata_unlink_in_ai_context(
-            "/Users/jqwang/repo/.git/index.lock",
-            Some("git"),
-            &TrustedProcessDecision::IdentityMismatch("sig mismatch".to_string()),
+
+        policy.allow_git_merge_pull_in_ai_context = false;
+        assert!(!should_allow_git_merge_pull_worktree_change_in_ai_context(
+            "git",
+            &merge_args,
+            &TrustedProcessDecision::Trusted,
             &policy
         ));
+    }
+
+    #[test]
+    fn invalidate_trust_cache_for_pid_removes_all_cached_identities_for_pid() {
+        let mut cache = HashMap::new();
+        cache.insert(
+            ProcessIdentityKey {
+                pid: 123,
+                start_tvsec: 11,
+                start_tvusec: 22,
+                executable_path: "/usr/bin/git".to_string(),
+            },
+            CachedTrustedProcess {
+                decision: TrustedProcessDecision::Trusted,
+                updated_at: 100,
+            },
+        );
+        cache.insert(
+            ProcessIdentityKey {
+                pid: 123,
+                start_tvsec: 11,
+                start_tvusec: 22,
+                executable_path: "/usr/bin/xcrun".to_string(),
+            },
+            CachedTrustedProcess {
+                decision: TrustedProcessDecision::IdentityMismatch("mismatch".to_string()),
+                updated_at: 100,
+            },
+        );
+        cache.insert(
+            ProcessIdentityKey {
+                pid: 456,
+                start_tvsec: 33,
+                start_tvusec: 44,
+                executable_path: "/usr/bin/git".to_string(),
+            },
+            CachedTrustedProcess {
+                decision: TrustedProcessDecision::Trusted,
+                updated_at: 100,
+            },
+        );
 
-        policy.allow_vcs_metadata_in_ai_context = false;
-        assert!(!should_allow_vcs_metadata_unlink_in_ai_context(
-            "/Users/jqwang/repo/.git/index.lock",
-            Some("git"),
-            &TrustedProcessDecision::Trusted,
-            &policy
-        ));
+        invalidate_trust_cache_for_pid(&mut cache, 123);
+
+        assert_eq!(cache.len(), 1);
+        assert!(cache.keys().all(|key| key.pid != 123));
     }
 
     #[test]
-    fn vcs_metadata_rename_allow_requires_both_paths_in_metadata() {
-        let mut policy = SecurityPolicy::default();
-        policy.trusted_tools = default_trusted_tools();
-        policy.allow_vcs_metadata_in_ai_context = true;
+    fn trusted_process_cache_get_removes_expired_entry_without_full_prune() {
+        let key = ProcessIdentityKey {
+            pid: 321,
+            start_tvsec: 11,
+            start_tvusec: 22,
+            executable_path: "/usr/bin/git".to_string(),
+        };
+        let mut cache = TrustedProcessCache {
+            entries: HashMap::from([(
+                key.clone(),
+                CachedTrustedProcess {
+                    decision: TrustedProcessDecision::Trusted,
+                    updated_at: 1_000,
+                },
+            )]),
+            last_prune_at: 1_301,
+        };
 
-        assert!(should_allow_vcs_metadata_rename_in_ai_context(
-            "/Users/jqwang/repo/.git/index.lock",
-            "/Users/jqwang/repo/.git/index",
-            Some("git"),
-            &TrustedProcessDecision::Trusted,
-            &policy
-        ));
-        assert!(!should_allow_vcs_metadata_rename_in_ai_context(
-            "/Users/jqwang/repo/.git/index.lock",
-            "/private/tmp/index.lock",
-            Some("git"),
-            &TrustedProcessDecision::Trusted,
-            &policy
-        ));
-        assert!(!should_allow_vcs_metadata_rename_in_ai_context(
-            "/Users/jqwang/repo/.git/index.lock",
-            "/Users/jqwang/repo/.git/index",
-            Some("git"),
-            &TrustedProcessDecision::IdentityMismatch("sig mismatch".to_string()),
-            &policy
-        ));
+        let now = 1_301;
+        let decision = cache.get(&key, now);
+        assert!(decision.is_none());
+        assert!(!cache.entries.contains_key(&key));
     }
 
     #[test]
-    fn apply_override_request_rejects_no_expire() {
-        let policy = test_policy();
-        let mut overrides = Vec::new();
-        let request = OverrideRequest {
-            id: "req-1".to_string(),
-            action: "grant".to_string(),
-            path: Some("/Users/jqwang/project/file.txt".to_string()),
-            minutes: Some(0),
-            requester_pid: None,
+    fn trusted_process_cache_periodic_prune_compacts_other_expired_entries() {
+        let fresh_key = ProcessIdentityKey {
+            pid: 111,
+            start_tvsec: 11,
+            start_tvusec: 22,
+            executable_path: "/usr/bin/git".to_string(),
+        };
+        let stale_key = ProcessIdentityKey {
+            pid: 222,
+            start_tvsec: 33,
+            start_tvusec: 44,
+            executable_path: "/usr/bin/xcrun".to_string(),
+        };
+        let now = 2_000;
+        let mut cache = TrustedProcessCache {
+            entries: HashMap::from([
+                (
+                    fresh_key.clone(),
+                    CachedTrustedProcess {
+                        decision: TrustedProcessDecision::Trusted,
+                        updated_at: now,
+                    },
+                ),
+                (
+                    stale_key.clone(),
+                    CachedTrustedProcess {
+                        decision: TrustedProcessDecision::Trusted,
+                        updated_at: now.saturating_sub(TRUST_CACHE_TTL_SECS + 1),
+                    },
+                ),
+            ]),
+            last_prune_at: now.saturating_sub(TRUST_CACHE_PRUNE_INTERVAL_SECS),
         };
 
-        let (changed, response) = apply_override_request(&request, &policy, "/Users/jqwang", &mut overrides);
-        assert!(!changed);
-        assert_eq!(response.status, "error");
-        assert!(response.message.contains("no-expire"));
-        assert!(overrides.is_empty());
+        let decision = cache.get(&fresh_key, now);
+        assert!(decision.is_some());
+        assert!(!cache.entries.contains_key(&stale_key));
     }
 
     #[test]
-    fn apply_override_request_rejects_zone_root_path() {
-        let policy = test_policy();
-        let mut overrides = Vec::new();
-        let request = OverrideRequest {
-            id: "req-2".to_string(),
-            action: "grant".to_string(),
-            path: Some("/Users/jqwang/project".to_string()),
-            minutes: Some(3),
-            requester_pid: None,
+    fn binary_signature_cache_get_removes_expired_entry_without_full_prune() {
+        let path = "/usr/bin/git".to_string();
+        let now: u64 = 5_000;
+        let mut cache = BinarySignatureCache {
+            entries: HashMap::from([(
+                path.clone(),
+                CachedBinarySignature {
+                    signature: Ok(BinaryCodeSignature {
+                        signing_identifier: "com.apple.git".to_string(),
+                        team_identifier: Some("APPLE123".to_string()),
+                        cdhash: Some("abcd".to_string()),
+                    }),
+                    updated_at: now.saturating_sub(SIGNATURE_CACHE_TTL_SECS + 1),
+                },
+            )]),
+            in_flight: HashSet::new(),
+            last_prune_at: now,
         };
 
-        let (changed, response) = apply_override_request(&request, &policy, "/Users/jqwang", &mut overrides);
-        assert!(!changed);
-        assert_eq!(response.status, "error");
-        assert!(response.message.contains("broad path"));
-        assert!(overrides.is_empty());
+        let signature = cache.get(path.as_str(), now);
+        assert!(signature.is_none());
+        assert!(!cache.entries.contains_key(path.as_str()));
     }
 
     #[test]
-    fn apply_override_request_allows_sensitive_zone_subpath() {
-        let policy = test_sensitive_policy();
-        let mut overrides = Vec::new();
-        let request = OverrideRequest {
-            id: "req-sensitive-allow".to_string(),
-            action: "grant-sensitive-read".to_string(),
-            path: Some("/Users/jqwang/.codex/chat/history.jsonl".to_string()),
-            minutes: Some(3),
-            requester_pid: None,
-        };
+    fn binary_signature_cache_in_flight_refresh_is_debounced_and_released() {
+        let path = "/usr/bin/git";
+        let now = 100;
+        let mut cache = BinarySignatureCache::default();
 
-        let (changed, response) = apply_override_request(&request, &policy, "/Users/jqwang", &mut overrides);
-        assert!(changed);
-        assert_eq!(response.status, "ok");
-        assert_eq!(overrides.len(), 1);
-        assert_eq!(
-            overrides[0].path(),
-            "/Users/jqwang/.codex/chat/history.jsonl"
+        assert!(cache.should_enqueue_refresh(path));
+        assert!(!cache.should_enqueue_refresh(path));
+
+        cache.cancel_in_flight_refresh(path);
+        assert!(cache.should_enqueue_refresh(path));
+
+        cache.insert(
+            path.to_string(),
+            Ok(BinaryCodeSignature {
+                signing_identifier: "com.apple.git".to_string(),
+                team_identifier: Some("APPLE123".to_string()),
+                cdhash: Some("abcd".to_string()),
+            }),
+            now,
         );
+        assert!(cache.should_enqueue_refresh(path));
     }
 
     #[test]
-    fn apply_override_request_rejects_sensitive_zone_root_path() {
-        let policy = test_sensitive_policy();
-        let mut overrides = Vec::new();
-        let request = OverrideRequest {
-            id: "req-sensitive-root".to_string(),
-            action: "grant-sensitive-read".to_string(),
-            path: Some("/Users/jqwang/.codex".to_string()),
-            minutes: Some(3),
-            requester_pid: None,
+    fn binary_signature_cache_expires_error_entries_faster_than_success_entries() {
+        let now: u64 = 8_000;
+        let ok_path = "/usr/bin/git".to_string();
+        let err_path = "/usr/bin/xcrun".to_string();
+        let stale_age = SIGNATURE_ERROR_CACHE_TTL_SECS + 1;
+        let mut cache = BinarySignatureCache {
+            entries: HashMap::from([
+                (
+                    ok_path.clone(),
+                    CachedBinarySignature {
+                        signature: Ok(BinaryCodeSignature {
+                            signing_identifier: "com.apple.git".to_string(),
+                            team_identifier: Some("APPLE123".to_string()),
+                            cdhash: Some("abcd".to_string()),
+                        }),
+                        updated_at: now.saturating_sub(stale_age),
+                    },
+                ),
+                (
+                    err_path.clone(),
+                    CachedBinarySignature {
+                        signature: Err("codesign failed".to_string()),
+                        updated_at: now.saturating_sub(stale_age),
+                    },
+                ),
+            ]),
+            in_flight: HashSet::new(),
+            last_prune_at: 0,
         };
 
-        let (changed, response) = apply_override_request(&request, &policy, "/Users/jqwang", &mut overrides);
-        assert!(!changed);
-        assert_eq!(response.status, "error");
-        assert!(response.message.contains("broad path"));
-        assert!(overrides.is_empty());
+        assert!(cache.get(err_path.as_str(), now).is_none());
+        assert!(cache.entries.contains_key(ok_path.as_str()));
+        assert!(!cache.entries.contains_key(err_path.as_str()));
+    }
+
+    #[test]
+    fn signature_pending_decision_uses_stable_prefix_marker() {
+        let pending =
+            TrustedProcessDecision::IdentityMismatch(format!("{}: /usr/bin/git", TRUST_SIGNATURE_PENDING_PREFIX));
+        assert!(trust_decision_is_signature_pending(&pending));
+        assert!(!trust_decision_is_signature_pending(
+            &TrustedProcessDecision::IdentityMismatch("codesign failed".to_string())
+        ));
+    }
+
+    fn start_key(start_tvsec: u64) -> Option<ProcessStartTimeKey> {
+        Some(ProcessStartTimeKey {
+            start_tvsec,
+            start_tvusec: 0,
+        })
     }
 
     #[test]
-    fn apply_override_request_rejects_sensitive_read_grant_outside_sensitive_zone() {
-        let policy = test_sensitive_policy();
-        let mut overrides = Vec::new();
-        let request = OverrideRequest {
-            id: "req-sensitive-outside".to_string(),
-            action: "grant-sensitive-read".to_string(),
-            path: Some("/Users/jqwang/project/file.txt".to_string()),
-            minutes: Some(3),
-            requester_pid: None,
-        };
+    fn ai_agent_label_matches_executable_or_argv0_basename() {
+        let policy = test_policy();
+        assert_eq!(
+            ai_agent_label(&policy, "/opt/homebrew/bin/node", Some("claude")),
+            Some("claude".to_string())
+        );
+        assert_eq!(
+            ai_agent_label(
+                &policy,
+                "/usr/local/bin/codex",
+                Some("/usr/local/bin/codex")
+            ),
+            Some("codex".to_string())
+        );
+        assert_eq!(
+            ai_agent_label(
+                &policy,
+                "/Applications/Codex.app/Contents/MacOS/codex-cli",
+                Some("-zsh")
+            ),
+            Some("codex-cli".to_string())
+        );
+        assert_eq!(ai_agent_label(&policy, "/bin/zsh", Some("zsh")), None);
+    }
 
-        let (changed, response) = apply_override_request(&request, &policy, "/Users/jqwang", &mut overrides);
-        assert!(!changed);
-        assert_eq!(response.status, "error");
-        assert!(response.message.contains("outside sensitive"));
-        assert!(overrides.is_empty());
+    fn detector_rule(json: serde_json::Value) -> AgentDetectorRule {
+        serde_json::from_value(json).expect("valid detector rule")
     }
 
     #[test]
-    fn sensitive_read_override_does_not_disable_protected_delete_gate() {
-        let mut policy = test_policy();
-        policy.temporary_overrides = vec![TemporaryOverrideEntry::Rule(TemporaryOverrideRule {
-            path: "/Users/jqwang/project/file.txt".to_string(),
-            expires_at: Some(now_ts().saturating_add(300)),
-            created_at: Some(now_ts()),
-            created_by: Some(OVERRIDE_CREATED_BY_SENSITIVE_READ_HELPER.to_string()),
-        })];
+    fn detector_rules_match_exe_argv0_env_and_signing_criteria() {
+        let evidence = ExecEvidence {
+            env: vec!["CLAUDECODE=1".to_string(), "CODEX_SANDBOX=seatbelt".to_string()],
+            signing_id: "com.openai.codex".to_string(),
+            team_id: "2DC432GLL2".to_string(),
+        };
+        let glob = detector_rule(serde_json::json!({"name": "codex-glob", "exe_glob": "/opt/**/bin/codex*"}));
+        assert!(glob.matches("/opt/homebrew/Cellar/x/bin/codex-cli", None, &[], &evidence));
+        assert!(!glob.matches("/usr/local/bin/codex", None, &[], &evidence));
+
+        let regex = detector_rule(serde_json::json!({"name": "re", "exe_regex": "/(aider|goose)$"}));
+        assert!(regex.matches("/usr/local/bin/goose", None, &[], &evidence));
+        assert!(!regex.matches("/usr/local/bin/goosey", None, &[], &evidence));
+
+        let exact =
+            detector_rule(serde_json::json!({"name": "exact", "exe_exact": "/usr/local/bin/node", "argv0": "claude"}));
+        assert!(exact.matches(
+            "/usr/local/bin/node",
+            Some("/usr/local/bin/claude"),
+            &[],
+            &evidence
+        ));
+        assert!(!exact.matches("/usr/local/bin/node", Some("claudette"), &[], &evidence));
 
-        assert!(
-            policy.is_protected("/Users/jqwang/project/file.txt", "/Users/jqwang"),
-            "sensitive-read override should not bypass protected delete/move gate"
+        let env = detector_rule(serde_json::json!({"name": "env", "env": ["CLAUDECODE=1", "CODEX_SANDBOX"]}));
+        assert!(env.matches("/bin/zsh", None, &[], &evidence));
+        assert!(!env.matches("/bin/zsh", None, &[], &ExecEvidence::default()));
+
+        let signing = detector_rule(
+            serde_json::json!({"name": "sig", "signing_id": "com.openai.codex", "team_id": "2DC432GLL2"}),
         );
+        assert!(signing.matches("/anything", None, &[], &evidence));
+        assert!(!signing.matches("/anything", None, &[], &ExecEvidence::default()));
+
+        let empty = detector_rule(serde_json::json!({"name": "empty"}));
+        assert!(!empty.matches("/usr/local/bin/codex", Some("codex"), &[], &evidence));
+        assert!(empty.validation_error().is_some());
+
+        let invalid = detector_rule(serde_json::json!({"name": "bad", "exe_regex": "("}));
+        assert!(!invalid.matches("(", None, &[], &evidence));
+        assert!(invalid
+            .validation_error()
+            .is_some_and(|err| err.contains("invalid exe_regex")));
     }
 
     #[test]
-    fn apply_override_request_rejects_too_long_path() {
-        let policy = test_policy();
-        let mut overrides = Vec::new();
-        let long_suffix = "a".repeat(MAX_OVERRIDE_PATH_LEN);
-        let request = OverrideRequest {
-            id: "req-long".to_string(),
-            action: "grant".to_string(),
-            path: Some(format!("/Users/jqwang/project/{}", long_suffix)),
-            minutes: Some(1),
-            requester_pid: None,
+    fn detect_ai_agent_prefers_highest_confidence_and_honors_minimum() {
+        let mut policy = test_policy();
+        policy.ai_agent_detectors = vec![
+            detector_rule(
+                serde_json::json!({"name": "node-host", "label": "node-agent", "confidence": "low", "argv0": "claude"}),
+            ),
+            detector_rule(
+                serde_json::json!({"name": "claude-env", "label": "claude", "confidence": "high", "env": ["CLAUDECODE=1"]}),
+            ),
+        ];
+        let evidence = ExecEvidence {
+            env: vec!["CLAUDECODE=1".to_string()],
+            ..ExecEvidence::default()
         };
+        let detection = detect_ai_agent(
+            &policy,
+            "/usr/local/bin/node",
+            Some("claude"),
+            &[],
+            &evidence,
+        )
+        .expect("detected");
+        assert_eq!(detection.label, "claude");
+        assert_eq!(detection.rule, "claude-env");
+        assert_eq!(detection.confidence, DetectorConfidence::High);
 
-        let (changed, response) = apply_override_request(&request, &policy, "/Users/jqwang", &mut overrides);
-        assert!(!changed);
-        assert_eq!(response.status, "error");
-        assert!(response.message.contains("path too long"));
-        assert!(overrides.is_empty());
+        let fallback = detect_ai_agent(
+            &policy,
+            "/usr/local/bin/codex",
+            None,
+            &[],
+            &ExecEvidence::default(),
+        )
+        .expect("pattern");
+        assert_eq!(fallback.rule, "ai_agent_patterns");
+        assert_eq!(fallback.confidence, DetectorConfidence::Low);
+
+        policy.ai_agent_min_confidence = DetectorConfidence::Medium;
+        assert!(detect_ai_agent(
+            &policy,
+            "/usr/local/bin/codex",
+            None,
+            &[],
+            &ExecEvidence::default()
+        )
+        .is_none());
+        assert!(detect_ai_agent(
+            &policy,
+            "/usr/local/bin/node",
+            Some("claude"),
+            &[],
+            &ExecEvidence::default()
+        )
+        .is_none());
+        assert_eq!(
+            detect_ai_agent(
+                &policy,
+                "/usr/local/bin/node",
+                Some("claude"),
+                &[],
+                &evidence
+            )
+            .map(|d| d.label),
+            Some("claude".to_string())
+        );
     }
 
     #[test]
-    fn apply_override_request_rejects_when_override_pool_full() {
-        let policy = test_policy();
-        let mut overrides = (0..MAX_RUNTIME_OVERRIDES)
-            .map(|idx| {
-                TemporaryOverrideEntry::Rule(TemporaryOverrideRule {
-                    path: format!("/Users/jqwang/project/file-{}", idx),
-                    expires_at: Some(now_ts().saturating_add(60)),
-                    created_at: Some(now_ts()),
-                    created_by: Some("seed".to_string()),
-                })
-            })
-            .collect::<Vec<_>>();
-        let request = OverrideRequest {
-            id: "req-full".to_string(),
-            action: "grant".to_string(),
-            path: Some("/Users/jqwang/project/new-file".to_string()),
-            minutes: Some(1),
-            requester_pid: None,
-        };
-
-        let (changed, response) = apply_override_request(&request, &policy, "/Users/jqwang", &mut overrides);
-        assert!(!changed);
-        assert_eq!(response.status, "error");
-        assert!(response.message.contains("too many active overrides"));
-        assert_eq!(overrides.len(), MAX_RUNTIME_OVERRIDES);
+    fn policy_parses_detectors_and_collects_env_names() {
+        let policy: SecurityPolicy = serde_json::from_str(
+            r#"{
+                "protected_zones": [],
+                "temporary_overrides": [],
+                "ai_agent_min_confidence": "medium",
+                "ai_agent_detectors": [
+                    {"name": "codex-sandbox", "env": ["CODEX_SANDBOX"], "confidence": "high"},
+                    {"name": "claude-code", "env": ["CLAUDECODE=1"]}
+                ]
+            }"#,
+        )
+        .expect("policy parses");
+        assert_eq!(policy.ai_agent_min_confidence, DetectorConfidence::Medium);
+        assert_eq!(
+            policy.ai_agent_detectors[1].confidence,
+            DetectorConfidence::Medium
+        );
+        let names = policy.detector_env_names();
+        assert!(names.contains("CODEX_SANDBOX"));
+        assert!(names.contains("CLAUDECODE"));
+        assert_eq!(names.len(), 2);
     }
 
     #[test]
-    fn override_request_budget_limits_per_minute() {
-        let mut window = std::collections::VecDeque::new();
-        for _ in 0..MAX_OVERRIDE_REQUESTS_PER_MINUTE {
-            assert!(consume_override_request_budget(&mut window, 100));
+    fn agent_profile_catalog_rules_are_valid_and_unique() {
+        let mut names = HashSet::new();
+        for profile in AGENT_PROFILE_CATALOG {
+            assert!(
+                names.insert(profile.name),
+                "duplicate profile {}",
+                profile.name
+            );
+            let rules = agent_profile_rules(profile.name);
+            assert_eq!(rules.len(), profile.signatures.len());
+            for rule in rules {
+                assert_eq!(rule.validation_error(), None, "{}", rule.name);
+                assert!(rule.name.starts_with(&format!("{}:", profile.name)));
+            }
         }
-        assert!(!consume_override_request_budget(&mut window, 100));
-        assert!(consume_override_request_budget(&mut window, 161));
+        assert!(agent_profile_rules("no-such-agent").is_empty());
     }
 
     #[test]
-    fn validate_override_request_origin_requires_pid() {
-        let policy = test_policy();
-        let missing_pid = OverrideRequest {
-            id: "req-origin-1".to_string(),
-            action: "grant".to_string(),
-            path: Some("/Users/jqwang/project/file.txt".to_string()),
-            minutes: Some(1),
-            requester_pid: None,
-        };
-        let err = validate_override_request_origin(&missing_pid, &policy).expect_err("pid is required");
-        assert!(err.contains("missing requester_pid"));
+    fn enabled_agent_profiles_detect_hosted_agents() {
+        let mut policy = test_policy();
+        policy.ai_agent_patterns.clear();
+        let argv = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let aider_args = argv(&["python3.12", "/Users/me/.local/bin/aider", "--yes"]);
+        assert!(detect_ai_agent(
+            &policy,
+            "/opt/homebrew/bin/python3.12",
+            Some("python3.12"),
+            &aider_args,
+            &ExecEvidence::default()
+        )
+        .is_none());
 
-        let invalid_pid = OverrideRequest {
-            id: "req-origin-2".to_string(),
-            action: "grant".to_string(),
-            path: Some("/Users/jqwang/project/file.txt".to_string()),
-            minutes: Some(1),
-            requester_pid: Some(0),
-        };
-        let err = validate_override_request_origin(&invalid_pid, &policy).expect_err("pid must be positive");
-        assert!(err.contains("invalid requester_pid"));
-    }
+        policy.ai_agent_profiles = vec!["aider".to_string(), "gemini-cli".to_string(), "unknown".to_string()];
+        let detection = detect_ai_agent(
+            &policy,
+            "/opt/homebrew/bin/python3.12",
+            Some("python3.12"),
+            &aider_args,
+            &ExecEvidence::default(),
+        )
+        .expect("aider detected");
+        assert_eq!(detection.label, "aider");
+        assert_eq!(detection.rule, "aider:python-host");
 
-    #[test]
-    fn parse_procargs_argv_extracts_expected_arguments() {
-        let mut payload = Vec::new();
-        payload.extend_from_slice(&(3_i32).to_ne_bytes());
-        payload.extend_from_slice(b"/bin/bash");
-        payload.push(0);
-        payload.push(0);
-        payload.extend_from_slice(b"/bin/bash");
-        payload.push(0);
-        payload.extend_from_slice(b"/usr/local/bin/agentsmith-override");
-        payload.push(0);
-        payload.extend_from_slice(b"--clear");
-        payload.push(0);
+        let gemini = detect_ai_agent(
+            &policy,
+            "/bin/zsh",
+            Some("zsh"),
+            &[],
+            &ExecEvidence {
+                env: vec!["GEMINI_CLI=1".to_string()],
+                ..ExecEvidence::default()
+            },
+        )
+        .expect("gemini detected");
+        assert_eq!(gemini.rule, "gemini-cli:env");
+        assert_eq!(gemini.confidence, DetectorConfidence::High);
+        assert!(policy.detector_env_names().contains("GEMINI_CLI"));
 
-        let args = parse_procargs_argv(&payload);
-        assert_eq!(args.len(), 3);
-        assert_eq!(args[0], "/bin/bash");
-        assert_eq!(args[1], "/usr/local/bin/agentsmith-override");
-        assert_eq!(args[2], "--clear");
+        // A bare node process is not enough for the node-host signatures.
+        assert!(detect_ai_agent(
+            &policy,
+            "/opt/homebrew/bin/node",
+            Some("node"),
+            &argv(&["node", "server.js"]),
+            &ExecEvidence::default()
+        )
+        .is_none());
     }
 
     #[test]
-    fn helper_argv_detection_requires_override_binary_argument() {
-        let helper_args = vec![
-            "/bin/bash".to_string(),
-            "/usr/local/bin/agentsmith-override".to_string(),
-            "--minutes".to_string(),
-            "3".to_string(),
-        ];
-        assert!(is_override_helper_argv(&helper_args));
+    fn cursor_electron_helper_is_only_a_low_confidence_match() {
+        let mut policy = test_policy();
+        policy.ai_agent_patterns.clear();
+        policy.ai_agent_profiles = vec!["cursor".to_string()];
+        let helper = "/Applications/Cursor.app/Contents/Frameworks/Cursor Helper (Plugin).app/Contents/MacOS/Cursor Helper (Plugin)";
+        let detect =
+            |policy: &SecurityPolicy, exe: &str| detect_ai_agent(policy, exe, None, &[], &ExecEvidence::default());
 
-        let unrelated_args = vec!["/bin/bash".to_string(), "/tmp/custom-script.sh".to_string()];
-        assert!(!is_override_helper_argv(&unrelated_args));
+        let hint = detect(&policy, helper).expect("helper matches at low confidence");
+        assert_eq!(hint.rule, "cursor:electron-helper");
+        assert_eq!(hint.confidence, DetectorConfidence::Low);
+
+        policy.ai_agent_min_confidence = DetectorConfidence::Medium;
+        assert!(
+            detect(&policy, helper).is_none(),
+            "terminal shells stay user context"
+        );
+        let agent = detect(&policy, "/Users/me/.local/bin/cursor-agent").expect("cursor-agent detected");
+        assert_eq!(agent.rule, "cursor:agent-cli");
+        assert_eq!(agent.confidence, DetectorConfidence::Medium);
     }
 
     #[test]
-    fn git_merge_or_pull_invocation_detects_allowed_subcommands() {
-        let merge_args = vec![
-            "git".to_string(),
-            "-C".to_string(),
-            "/Users/jqwang/00-nixos-config/agentsmith-rs-core".to_string(),
-            "merge".to_string(),
-            "feature/codex-sensitive-dlp-c".to_string(),
-        ];
-        assert!(is_git_merge_or_pull_invocation(&merge_args));
+    fn process_table_labels_exec_from_detector_evidence() {
+        let mut policy = test_policy();
+        policy.ai_agent_detectors = vec![detector_rule(
+            serde_json::json!({"name": "claude-env", "label": "claude", "env": ["CLAUDECODE=1"]}),
+        )];
+        let mut table = ProcessTable::default();
+        table.record_exec_with_evidence(
+            100,
+            start_key(10),
+            ProcessLinks::from_ppid(1),
+            "/opt/homebrew/bin/node".to_string(),
+            vec!["node".to_string()],
+            ExecEvidence {
+                env: vec!["CLAUDECODE=1".to_string()],
+                ..ExecEvidence::default()
+            },
+            &policy,
+            50,
+        );
+        assert_eq!(
+            table.ai_lineage_for_pid(100, &policy, 50),
+            Some("claude".to_string())
+        );
+        table.refresh_ai_labels(&policy, 50);
+        assert_eq!(
+            table.ai_lineage_for_pid(100, &policy, 50),
+            Some("claude".to_string())
+        );
+    }
 
-        let pull_args = vec![
-            "git".to_string(),
-            "--work-tree=/Users/jqwang/00-nixos-config/agentsmith-rs-core".to_string(),
-            "pull".to_string(),
-            "--ff-only".to_string(),
-        ];
-        assert!(is_git_merge_or_pull_invocation(&pull_args));
+    #[test]
+    fn process_table_fork_and_exec_inherit_ai_lineage() {
+        let policy = test_policy();
+        let mut table = ProcessTable::default();
+        table.record_exec(
+            100,
+            start_key(10),
+            ProcessLinks::from_ppid(1),
+            "/usr/local/bin/codex".to_string(),
+            vec![],
+            &policy,
+            50,
+        );
+        table.record_fork(100, 200, start_key(20), &policy, 50);
+        table.record_exec(
+            200,
+            start_key(20),
+            ProcessLinks::from_ppid(100),
+            "/bin/rm".to_string(),
+            vec!["rm".to_string(), "-rf".to_string()],
+            &policy,
+            50,
+        );
+        assert_eq!(
+            table.ai_lineage_for_pid(200, &policy, 50),
+            Some("codex".to_string())
+        );
+        assert_eq!(
+            table.argv_for_pid(200),
+            Some(vec!["rm".to_string(), "-rf".to_string()])
+        );
     }
 
     #[test]
-    fn git_merge_or_pull_invocation_rejects_other_subcommands() {
-        let rebase_args = vec!["git".to_string(), "rebase".to_string(), "main".to_string()];
-        assert!(!is_git_merge_or_pull_invocation(&rebase_args));
+    fn process_table_does_not_carry_ai_label_across_pid_reuse() {
+        let policy = test_policy();
+        let mut table = ProcessTable::default();
+        table.record_exec(
+            100,
+            start_key(10),
+            ProcessLinks::from_ppid(1),
+            "/usr/local/bin/codex".to_string(),
+            vec![],
+            &policy,
+            50,
+        );
+        assert!(table.is_current(100, start_key(10)));
+        assert!(!table.is_current(100, start_key(30)));
 
-        let rm_args = vec!["git".to_string(), "rm".to_string(), "README.md".to_string()];
-        assert!(!is_git_merge_or_pull_invocation(&rm_args));
+        table.record_exec(
+            100,
+            start_key(30),
+            ProcessLinks::from_ppid(1),
+            "/bin/zsh".to_string(),
+            vec![],
+            &policy,
+            60,
+        );
+        assert_eq!(table.ai_lineage_for_pid(100, &policy, 60), None);
     }
 
     #[test]
-    fn git_merge_or_pull_allow_requires_git_process_and_policy_flag() {
-        let mut policy = test_policy();
-        policy.allow_git_merge_pull_in_ai_context = true;
-
-        let merge_args = vec!["git".to_string(), "merge".to_string(), "topic".to_string()];
-        assert!(should_allow_git_merge_pull_worktree_change_in_ai_context(
-            "git",
-            &merge_args,
-            &TrustedProcessDecision::Trusted,
-            &policy
-        ));
-        assert!(!should_allow_git_merge_pull_worktree_change_in_ai_context(
-            "bash",
-            &merge_args,
-            &TrustedProcessDecision::Trusted,
-            &policy
-        ));
-        assert!(!should_allow_git_merge_pull_worktree_change_in_ai_context(
-            "git",
-            &merge_args,
-            &TrustedProcessDecision::IdentityMismatch("sig mismatch".to_string()),
-            &policy
-        ));
+    fn process_table_ignores_parent_started_after_child() {
+        let policy = test_policy();
+        let mut table = ProcessTable::default();
+        table.record_exec(
+            100,
+            start_key(50),
+            ProcessLinks::from_ppid(1),
+            "/usr/local/bin/codex".to_string(),
+            vec![],
+            &policy,
+            60,
+        );
+        table.record_exec(
+            200,
+            start_key(20),
+            ProcessLinks::from_ppid(100),
+            "/bin/cat".to_string(),
+            vec![],
+            &policy,
+            60,
+        );
+        assert_eq!(table.ai_lineage_for_pid(200, &policy, 60), None);
+    }
 
-        policy.allow_git_merge_pull_in_ai_context = false;
-        assert!(!should_allow_git_merge_pull_worktree_change_in_ai_context(
-            "git",
-            &merge_args,
-            &TrustedProcessDecision::Trusted,
-            &policy
-        ));
+    #[test]
+    fn process_table_refresh_recomputes_lineage_for_new_patterns() {
+        let mut policy = test_policy();
+        let mut table = ProcessTable::default();
+        table.record_exec(
+            100,
+            start_key(10),
+            ProcessLinks::from_ppid(1),
+            "/usr/local/bin/aider".to_string(),
+            vec![],
+            &policy,
+            50,
+        );
+        table.record_fork(100, 200, start_key(20), &policy, 50);
+        assert_eq!(table.ai_lineage_for_pid(200, &policy, 50), None);
+
+        policy.ai_agent_patterns.push("aider".to_string());
+        table.refresh_ai_labels(&policy, 50);
+        assert_eq!(
+            table.ai_lineage_for_pid(200, &policy, 50),
+            Some("aider".to_string())
+        );
     }
 
     #[test]
-    fn invalidate_trust_cache_for_pid_removes_all_cached_identities_for_pid() {
-        let mut cache = HashMap::new();
-        cache.insert(
-            ProcessIdentityKey {
-                pid: 123,
-                start_tvsec: 11,
-                start_tvusec: 22,
-                executable_path: "/usr/bin/git".to_string(),
-            },
-            CachedTrustedProcess {
-                decision: TrustedProcessDecision::Trusted,
-                updated_at: 100,
-            },
+    fn process_table_keeps_lineage_after_double_fork_reparent() {
+        let policy = test_policy();
+        let mut table = ProcessTable::default();
+        table.record_exec(
+            100,
+            start_key(10),
+            ProcessLinks::from_ppid(1),
+            "/usr/local/bin/codex".to_string(),
+            vec![],
+            &policy,
+            50,
         );
-        cache.insert(
-            ProcessIdentityKey {
-                pid: 123,
-                start_tvsec: 11,
-                start_tvusec: 22,
-                executable_path: "/usr/bin/xcrun".to_string(),
-            },
-            CachedTrustedProcess {
-                decision: TrustedProcessDecision::IdentityMismatch("mismatch".to_string()),
-                updated_at: 100,
+        table.record_fork(100, 200, start_key(20), &policy, 50);
+        table.record_fork(200, 300, start_key(30), &policy, 50);
+        table.remove(200);
+        table.record_exec(
+            300,
+            start_key(30),
+            ProcessLinks {
+                ppid: 1,
+                original_ppid: 200,
+                responsible_pid: 300,
             },
+            "/usr/bin/python3".to_string(),
+            vec!["python3".to_string(), "server.py".to_string()],
+            &policy,
+            60,
         );
-        cache.insert(
-            ProcessIdentityKey {
-                pid: 456,
-                start_tvsec: 33,
-                start_tvusec: 44,
-                executable_path: "/usr/bin/git".to_string(),
-            },
-            CachedTrustedProcess {
-                decision: TrustedProcessDecision::Trusted,
-                updated_at: 100,
+        assert_eq!(
+            table.ai_lineage_for_pid(300, &policy, 60),
+            Some("codex".to_string())
+        );
+
+        table.refresh_ai_labels(&policy, 50);
+        assert_eq!(
+            table.ai_lineage_for_pid(300, &policy, 60),
+            Some("codex".to_string())
+        );
+    }
+
+    #[test]
+    fn process_table_uses_responsible_process_for_reparented_spawn() {
+        let policy = test_policy();
+        let mut table = ProcessTable::default();
+        table.record_exec(
+            100,
+            start_key(10),
+            ProcessLinks::from_ppid(1),
+            "/usr/local/bin/codex".to_string(),
+            vec![],
+            &policy,
+            50,
+        );
+        table.record_exec(
+            400,
+            start_key(40),
+            ProcessLinks {
+                ppid: 1,
+                original_ppid: 1,
+                responsible_pid: 100,
             },
+            "/usr/libexec/helper".to_string(),
+            vec![],
+            &policy,
+            60,
+        );
+        assert_eq!(
+            table.ai_lineage_for_pid(400, &policy, 60),
+            Some("codex".to_string())
         );
+    }
 
-        invalidate_trust_cache_for_pid(&mut cache, 123);
+    #[test]
+    fn launcher_handoff_target_parses_helper_commands() {
+        let argv = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            launcher_handoff_target(
+                "/bin/launchctl",
+                &argv(&["launchctl", "submit", "-l", "job", "--", "/bin/cat", "/tmp/x"])
+            ),
+            Some(("launchd", Some("cat".to_string())))
+        );
+        assert_eq!(
+            launcher_handoff_target("/bin/launchctl", &argv(&["launchctl", "list"])),
+            None
+        );
+        assert_eq!(
+            launcher_handoff_target("/usr/bin/open", &argv(&["open", "-a", "Terminal"])),
+            Some(("launchd", Some("Terminal".to_string())))
+        );
+        assert_eq!(
+            launcher_handoff_target(
+                "/usr/bin/open",
+                &argv(&["open", "/System/Applications/Utilities/Terminal.app"])
+            ),
+            Some(("launchd", Some("Terminal".to_string())))
+        );
+        assert_eq!(
+            launcher_handoff_target("/usr/bin/at", &argv(&["at", "now"])),
+            Some(("atrun", None))
+        );
+        assert_eq!(
+            launcher_handoff_target("/usr/bin/at", &argv(&["at", "-l"])),
+            None
+        );
+        assert_eq!(
+            launcher_handoff_target(
+                "/opt/homebrew/bin/tmux",
+                &argv(&["tmux", "new", "-d", "make"])
+            ),
+            Some(("tmux", None))
+        );
+        assert_eq!(
+            launcher_handoff_target("/opt/homebrew/bin/tmux", &argv(&["tmux", "ls"])),
+            None
+        );
+    }
 
-        assert_eq!(cache.len(), 1);
-        assert!(cache.keys().all(|key| key.pid != 123));
+    #[test]
+    fn process_table_attributes_launchctl_submit_job_to_ai_submitter() {
+        let policy = test_policy();
+        let mut table = ProcessTable::default();
+        table.record_exec(
+            100,
+            start_key(10),
+            ProcessLinks::from_ppid(1),
+            "/usr/local/bin/codex".to_string(),
+            vec![],
+            &policy,
+            50,
+        );
+        table.record_fork(100, 150, start_key(15), &policy, 50);
+        table.record_exec(
+            150,
+            start_key(15),
+            ProcessLinks::from_ppid(100),
+            "/bin/launchctl".to_string(),
+            ["launchctl", "submit", "-l", "job", "--", "/bin/cat", "/tmp/x"]
+                .iter()
+                .map(|arg| arg.to_string())
+                .collect(),
+            &policy,
+            50,
+        );
+
+        table.record_exec(
+            500,
+            start_key(60),
+            ProcessLinks::from_ppid(1),
+            "/bin/cat".to_string(),
+            vec!["/bin/cat".to_string(), "/tmp/x".to_string()],
+            &policy,
+            60,
+        );
+        assert_eq!(
+            table.ai_lineage_for_pid(500, &policy, 60),
+            Some("codex".to_string())
+        );
+
+        table.record_exec(
+            501,
+            start_key(61),
+            ProcessLinks::from_ppid(1),
+            "/usr/bin/python3".to_string(),
+            vec![],
+            &policy,
+            61,
+        );
+        assert_eq!(table.ai_lineage_for_pid(501, &policy, 61), None);
+
+        // The hand-off is spent on the first matching exec.
+        table.record_exec(
+            502,
+            start_key(62),
+            ProcessLinks::from_ppid(1),
+            "/bin/cat".to_string(),
+            vec![],
+            &policy,
+            62,
+        );
+        assert_eq!(table.ai_lineage_for_pid(502, &policy, 62), None);
     }
 
     #[test]
-    fn trusted_process_cache_get_removes_expired_entry_without_full_prune() {
-        let key = ProcessIdentityKey {
-            pid: 321,
-            start_tvsec: 11,
-            start_tvusec: 22,
-            executable_path: "/usr/bin/git".to_string(),
+    fn process_table_open_handoff_expires_and_is_not_reused() {
+        let policy = test_policy();
+        let mut table = ProcessTable::default();
+        table.record_exec(
+            100,
+            start_key(10),
+            ProcessLinks::from_ppid(1),
+            "/usr/local/bin/codex".to_string(),
+            vec![],
+            &policy,
+            50,
+        );
+        let open_terminal = |table: &mut ProcessTable, pid: i32, now: u64| {
+            table.record_exec(
+                pid,
+                start_key(now),
+                ProcessLinks::from_ppid(100),
+                "/usr/bin/open".to_string(),
+                ["open", "-a", "Terminal"].iter().map(|arg| arg.to_string()).collect(),
+                &policy,
+                now,
+            );
         };
-        let mut cache = TrustedProcessCache {
-            entries: HashMap::from([(
-                key.clone(),
-                CachedTrustedProcess {
-                    decision: TrustedProcessDecision::Trusted,
-                    updated_at: 1_000,
-                },
-            )]),
-            last_prune_at: 1_301,
+        let terminal = |table: &mut ProcessTable, pid: i32, now: u64| {
+            table.record_exec(
+                pid,
+                start_key(now),
+                ProcessLinks::from_ppid(1),
+                "/System/Applications/Utilities/Terminal.app/Contents/MacOS/Terminal".to_string(),
+                vec![],
+                &policy,
+                now,
+            );
+            table.ai_lineage_for_pid(pid, &policy, now)
         };
 
-        let now = 1_301;
-        let decision = cache.get(&key, now);
-        assert!(decision.is_none());
-        assert!(!cache.entries.contains_key(&key));
+        open_terminal(&mut table, 150, 50);
+        assert_eq!(terminal(&mut table, 500, 51), Some("codex".to_string()));
+        // A Terminal the user opens by hand right after is not the agent's.
+        assert_eq!(terminal(&mut table, 501, 52), None);
+
+        open_terminal(&mut table, 151, 60);
+        let expired = 61 + LAUNCHER_HANDOFF_TTL_SECS;
+        assert_eq!(terminal(&mut table, 502, expired), None);
     }
 
     #[test]
-    fn trusted_process_cache_periodic_prune_compacts_other_expired_entries() {
-        let fresh_key = ProcessIdentityKey {
-            pid: 111,
-            start_tvsec: 11,
-            start_tvusec: 22,
-            executable_path: "/usr/bin/git".to_string(),
-        };
-        let stale_key = ProcessIdentityKey {
-            pid: 222,
-            start_tvsec: 33,
-            start_tvusec: 44,
-            executable_path: "/usr/bin/xcrun".to_string(),
-        };
-        let now = 2_000;
-        let mut cache = TrustedProcessCache {
-            entries: HashMap::from([
-                (
-                    fresh_key.clone(),
-                    CachedTrustedProcess {
-                        decision: TrustedProcessDecision::Trusted,
-                        updated_at: now,
-                    },
-                ),
-                (
-                    stale_key.clone(),
-                    CachedTrustedProcess {
-                        decision: TrustedProcessDecision::Trusted,
-                        updated_at: now.saturating_sub(TRUST_CACHE_TTL_SECS + 1),
-                    },
-                ),
-            ]),
-            last_prune_at: now.saturating_sub(TRUST_CACHE_PRUNE_INTERVAL_SECS),
-        };
+    fn prune_exited_processes_keeps_live_and_rerecorded_entries() {
+        let policy = test_policy();
+        let own_pid = std::process::id() as i32;
+        let table = Mutex::new(ProcessTable::default());
+        {
+            let mut table = table.lock().expect("table");
+            table.record_exec(
+                own_pid,
+                process_start_time_for_pid(own_pid),
+                ProcessLinks::from_ppid(1),
+                "/bin/sh".to_string(),
+                vec![],
+                &policy,
+                50,
+            );
+            table.record_exec(
+                900_010,
+                start_key(10),
+                ProcessLinks::from_ppid(1),
+                "/bin/sh".to_string(),
+                vec![],
+                &policy,
+                50,
+            );
+        }
+        prune_exited_processes(&table);
+        let mut table = table.into_inner().expect("table");
+        assert!(table.is_current(own_pid, process_start_time_for_pid(own_pid)));
+        assert!(!table.is_current(900_010, start_key(10)));
+
+        // An entry re-recorded after the snapshot survives the removal pass.
+        let snapshot = table.start_snapshot();
+        table.record_exec(
+            own_pid,
+            start_key(99),
+            ProcessLinks::from_ppid(1),
+            "/bin/sh".to_string(),
+            vec![],
+            &policy,
+            60,
+        );
+        for (pid, start) in snapshot {
+            table.remove_if_unchanged(pid, start);
+        }
+        assert_eq!(table.len(), 1);
+    }
 
-        let decision = cache.get(&fresh_key, now);
-        assert!(decision.is_some());
-        assert!(!cache.entries.contains_key(&stale_key));
+    #[test]
+    fn process_table_consumes_at_handoff_once() {
+        let policy = test_policy();
+        let mut table = ProcessTable::default();
+        table.record_exec(
+            100,
+            start_key(10),
+            ProcessLinks::from_ppid(1),
+            "/usr/local/bin/codex".to_string(),
+            vec![],
+            &policy,
+            50,
+        );
+        table.record_exec(
+            150,
+            start_key(15),
+            ProcessLinks::from_ppid(100),
+            "/usr/bin/at".to_string(),
+            vec!["at".to_string(), "now".to_string()],
+            &policy,
+            50,
+        );
+        table.record_exec(
+            90,
+            start_key(5),
+            ProcessLinks::from_ppid(1),
+            "/usr/libexec/atrun".to_string(),
+            vec![],
+            &policy,
+            50,
+        );
+
+        for (pid, expected) in [(600, Some("codex".to_string())), (601, None)] {
+            table.record_exec(
+                pid,
+                start_key(9000),
+                ProcessLinks::from_ppid(90),
+                "/bin/sh".to_string(),
+                vec![],
+                &policy,
+                9000,
+            );
+            assert_eq!(table.ai_lineage_for_pid(pid, &policy, 9000), expected);
+        }
     }
 
     #[test]
-    fn binary_signature_cache_get_removes_expired_entry_without_full_prune() {
-        let path = "/usr/bin/git".to_string();
-        let now: u64 = 5_000;
-        let mut cache = BinarySignatureCache {
-            entries: HashMap::from([(
-                path.clone(),
-                CachedBinarySignature {
-                    signature: Ok(BinaryCodeSignature {
-                        signing_identifier: "com.apple.git".to_string(),
-                        team_identifier: Some("APPLE123".to_string()),
-                        cdhash: Some("abcd".to_string()),
-                    }),
-                    updated_at: now.saturating_sub(SIGNATURE_CACHE_TTL_SECS + 1),
-                },
-            )]),
-            in_flight: HashSet::new(),
-            last_prune_at: now,
-        };
+    fn process_table_session_is_authoritative_for_process_tree() {
+        let policy = test_policy();
+        let mut table = ProcessTable::default();
+        table.record_exec(
+            300,
+            start_key(30),
+            ProcessLinks::from_ppid(1),
+            "/bin/bash".to_string(),
+            vec!["/bin/bash".to_string(), "/usr/local/bin/agentsmith-run".to_string()],
+            &policy,
+            50,
+        );
+        table.record_fork(300, 310, start_key(31), &policy, 50);
+        assert!(table.register_session(
+            AgentSession {
+                id: "my-agent-300-30".to_string(),
+                agent: "my-agent".to_string(),
+                pid: 300,
+                started_at: 30,
+                cwd: "/tmp".to_string(),
+                tty: None,
+                source: SESSION_SOURCE_LAUNCHER,
+            },
+            &policy,
+            50,
+        ));
+        assert_eq!(
+            table.ai_lineage_for_pid(310, &policy, 50),
+            Some("my-agent".to_string())
+        );
 
-        let signature = cache.get(path.as_str(), now);
-        assert!(signature.is_none());
-        assert!(!cache.entries.contains_key(path.as_str()));
+        // The launcher execs the agent in place; a pattern match inside the
+        // session does not replace the session label.
+        table.record_exec(
+            300,
+            start_key(30),
+            ProcessLinks::from_ppid(1),
+            "/usr/local/bin/codex".to_string(),
+            vec!["codex".to_string()],
+            &policy,
+            51,
+        );
+        table.record_fork(300, 320, start_key(40), &policy, 51);
+        table.refresh_ai_labels(&policy, 50);
+        assert_eq!(
+            table.ai_lineage_for_pid(320, &policy, 51),
+            Some("my-agent".to_string())
+        );
+        assert_eq!(
+            table.session_for_pid(320).map(|session| session.id.clone()),
+            Some("my-agent-300-30".to_string())
+        );
+        assert!(table.session_for_pid(999).is_none());
     }
 
     #[test]
-    fn binary_signature_cache_in_flight_refresh_is_debounced_and_released() {
-        let path = "/usr/bin/git";
-        let now = 100;
-        let mut cache = BinarySignatureCache::default();
+    fn process_table_detected_agent_root_owns_session_for_its_tree() {
+        let policy = test_policy();
+        let mut table = ProcessTable::default();
+        table.record_exec(
+            100,
+            start_key(10),
+            ProcessLinks::from_ppid(1),
+            "/usr/local/bin/codex".to_string(),
+            vec!["codex".to_string()],
+            &policy,
+            50,
+        );
+        let session = table.session_for_pid(100).expect("detected root session");
+        assert_eq!(session.id, "codex-100-10");
+        assert_eq!(session.source, SESSION_SOURCE_DETECTOR);
+        assert_eq!(
+            table.take_session_events(),
+            vec![SessionEvent::Start(session.clone())]
+        );
 
-        assert!(cache.should_enqueue_refresh(path));
-        assert!(!cache.should_enqueue_refresh(path));
+        // Descendants, including a nested agent, are attributed to the root session.
+        table.record_fork(100, 200, start_key(20), &policy, 50);
+        table.record_exec(
+            200,
+            start_key(20),
+            ProcessLinks::from_ppid(100),
+            "/usr/local/bin/claude".to_string(),
+            vec!["claude".to_string()],
+            &policy,
+            50,
+        );
+        assert_eq!(
+            table.ai_lineage_for_pid(200, &policy, 50),
+            Some("claude".to_string())
+        );
+        assert_eq!(table.session_for_pid(200), Some(session.clone()));
+        assert!(table.take_session_events().is_empty());
 
-        cache.cancel_in_flight_refresh(path);
-        assert!(cache.should_enqueue_refresh(path));
+        table.remove(200);
+        assert!(table.take_session_events().is_empty());
+        table.remove(100);
+        assert!(matches!(
+            table.take_session_events().as_slice(),
+            [SessionEvent::End(ended, _)] if *ended == session
+        ));
+    }
 
-        cache.insert(
-            path.to_string(),
-            Ok(BinaryCodeSignature {
-                signing_identifier: "com.apple.git".to_string(),
-                team_identifier: Some("APPLE123".to_string()),
-                cdhash: Some("abcd".to_string()),
-            }),
-            now,
+    #[test]
+    fn session_log_rotates_instead_of_truncating() {
+        let home_dir = std::env::temp_dir().join(format!("as-session-log-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&home_dir);
+        fs::create_dir_all(&home_dir).expect("temp home");
+        let home = home_dir.to_string_lossy().into_owned();
+        let log_path = ensure_guard_dirs(&home).expect("guard dir").join("sessions.jsonl");
+        let history = format!("{}\n", "x".repeat(SESSION_LOG_MAX_BYTES as usize));
+        fs::write(&log_path, &history).expect("full session log");
+
+        let session = Arc::new(AgentSession {
+            id: "codex-4300-43".to_string(),
+            agent: "codex".to_string(),
+            pid: 4300,
+            started_at: 43,
+            cwd: "/Users/jqwang/project".to_string(),
+            tty: None,
+            source: SESSION_SOURCE_DETECTOR,
+        });
+        log_session_event_sync(&home, &SessionEvent::Start(session));
+
+        let segments = log_segment_paths(&log_path);
+        assert_eq!(segments.len(), 1);
+        assert_eq!(
+            fs::read_to_string(&segments[0]).expect("rotated segment"),
+            history
         );
-        assert!(cache.should_enqueue_refresh(path));
+        let current = fs::read_to_string(&log_path).expect("current log");
+        assert_eq!(current.lines().count(), 1);
+        assert!(current.contains("\"codex-4300-43\""));
+        let _ = fs::remove_dir_all(&home_dir);
     }
 
     #[test]
-    fn binary_signature_cache_expires_error_entries_faster_than_success_entries() {
-        let now: u64 = 8_000;
-        let ok_path = "/usr/bin/git".to_string();
-        let err_path = "/usr/bin/xcrun".to_string();
-        let stale_age = SIGNATURE_ERROR_CACHE_TTL_SECS + 1;
-        let mut cache = BinarySignatureCache {
-            entries: HashMap::from([
-                (
-                    ok_path.clone(),
-                    CachedBinarySignature {
-                        signature: Ok(BinaryCodeSignature {
-                            signing_identifier: "com.apple.git".to_string(),
-                            team_identifier: Some("APPLE123".to_string()),
-                            cdhash: Some("abcd".to_string()),
-                        }),
-                        updated_at: now.saturating_sub(stale_age),
-                    },
-                ),
-                (
-                    err_path.clone(),
-                    CachedBinarySignature {
-                        signature: Err("codesign failed".to_string()),
-                        updated_at: now.saturating_sub(stale_age),
-                    },
-                ),
-            ]),
-            in_flight: HashSet::new(),
-            last_prune_at: 0,
-        };
+    fn override_use_is_charged_to_the_callers_session() {
+        let policy = test_policy();
+        let mut table = ProcessTable::default();
+        table.record_exec(
+            4100,
+            start_key(41),
+            ProcessLinks::from_ppid(1),
+            "/usr/local/bin/codex".to_string(),
+            vec!["codex".to_string()],
+            &policy,
+            50,
+        );
+        table.record_fork(4100, 4101, start_key(42), &policy, 50);
+        table.record_fork(1, 4200, start_key(43), &policy, 50);
 
-        assert!(cache.get(err_path.as_str(), now).is_none());
-        assert!(cache.entries.contains_key(ok_path.as_str()));
-        assert!(!cache.entries.contains_key(err_path.as_str()));
+        count_session_override_use(&table, 4101);
+        count_session_override_use(&table, 4100);
+        // Not part of any session: nothing to charge.
+        count_session_override_use(&table, 4200);
+
+        let mut stats = session_stats().lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        assert_eq!(stats.finish("codex-4100-41").override_uses, 2);
     }
 
     #[test]
-    fn signature_pending_decision_uses_stable_prefix_marker() {
-        let pending =
-            TrustedProcessDecision::IdentityMismatch(format!("{}: /usr/bin/git", TRUST_SIGNATURE_PENDING_PREFIX));
-        assert!(trust_decision_is_signature_pending(&pending));
-        assert!(!trust_decision_is_signature_pending(
-            &TrustedProcessDecision::IdentityMismatch("codesign failed".to_string())
-        ));
+    fn session_stats_accumulate_until_session_ends() {
+        let mut stats = SessionStats::default();
+        stats.start("codex-100-10");
+        stats.count("codex-100-10", |counters| counters.denials += 1);
+        stats.count("codex-100-10", |counters| counters.denials += 1);
+        stats.count("codex-100-10", |counters| counters.taint_marks += 1);
+        stats.count("claude-200-20", |counters| counters.override_uses += 1);
+
+        assert_eq!(
+            stats.finish("codex-100-10"),
+            SessionCounters {
+                denials: 2,
+                audit_only: 0,
+                taint_marks: 1,
+                override_uses: 0,
+            }
+        );
+        assert_eq!(stats.finish("codex-100-10"), SessionCounters::default());
+        assert_eq!(stats.finish("claude-200-20").override_uses, 1);
+
+        let session = AgentSession {
+            id: "codex-100-10".to_string(),
+            agent: "codex".to_string(),
+            pid: 100,
+            started_at: 10,
+            cwd: "/Users/jqwang/project".to_string(),
+            tty: None,
+            source: SESSION_SOURCE_DETECTOR,
+        };
+        let line = serde_json::to_value(SessionLogLine {
+            ts: 60,
+            event: "end",
+            session: &session,
+            counters: Some(SessionCounters {
+                denials: 2,
+                ..SessionCounters::default()
+            }),
+        })
+        .expect("serialize session line");
+        assert_eq!(line["session"]["source"], "detector");
+        assert_eq!(line["counters"]["denials"], 2);
+        assert!(line["session"].get("tty").is_none());
+    }
+
+    #[test]
+    fn session_agent_name_is_required_and_sanitized() {
+        assert_eq!(
+            session_agent_name(Some(" claude ")),
+            Ok("claude".to_string())
+        );
+        assert_eq!(
+            session_agent_name(Some("my agent/x")),
+            Ok("my_agent_x".to_string())
+        );
+        assert!(session_agent_name(Some("  ")).is_err());
+        assert!(session_agent_name(None).is_err());
+        assert!(session_agent_name(Some(&"a".repeat(MAX_SESSION_AGENT_NAME_LEN + 1))).is_err());
     }
 
     #[test]
     fn clear_process_state_for_pid_removes_ancestor_trust_and_taint() {
-        let mut ancestor_cache = HashMap::new();
-        ancestor_cache.insert(
+        let policy = test_policy();
+        let mut process_table = ProcessTable::default();
+        process_table.record_exec(
             123,
-            CachedAncestor {
-                ai_ancestor: Some("codex".to_string()),
-                updated_at: 100,
-            },
+            None,
+            ProcessLinks::from_ppid(1),
+            "/usr/local/bin/codex".to_string(),
+            vec![],
+            &policy,
+            100,
         );
-        ancestor_cache.insert(
+        process_table.record_exec(
             456,
-            CachedAncestor {
-                ai_ancestor: None,
-                updated_at: 100,
-            },
+            None,
+            ProcessLinks::from_ppid(1),
+            "/bin/zsh".to_string(),
+            vec![],
+            &policy,
+            100,
         );
 
         let mut trust_cache = TrustedProcessCache::default();
@@ -5329,16 +13596,52 @@ mod tests {
         taint.mark(123, 1_000);
         taint.mark(456, 1_000);
 
-        clear_process_state_for_pid(123, &mut ancestor_cache, &mut trust_cache, &mut taint);
+        clear_process_state_for_pid(123, &mut process_table, &mut trust_cache, &mut taint);
 
-        assert!(!ancestor_cache.contains_key(&123));
-        assert!(ancestor_cache.contains_key(&456));
+        assert!(!process_table.entries.contains_key(&123));
+        assert!(process_table.entries.contains_key(&456));
         assert!(trust_cache.entries.keys().all(|key| key.pid != 123));
         assert!(trust_cache.entries.keys().any(|key| key.pid == 456));
         assert!(!taint.is_tainted(123, 1_001));
         assert!(taint.is_tainted(456, 1_001));
     }
 
+    #[test]
+    fn override_request_from_reparented_helper_keeps_agent_lineage() {
+        let policy = test_policy();
+        let process_table = Mutex::new(ProcessTable::default());
+        let helper = std::process::id() as i32;
+        {
+            let mut table = process_table.lock().expect("process table");
+            table.record_exec(
+                900_001,
+                None,
+                ProcessLinks::from_ppid(1),
+                "/usr/local/bin/codex".to_string(),
+                vec![],
+                &policy,
+                50,
+            );
+            // `nohup setsid agentsmith-override ...`: the helper now hangs off launchd.
+            table.record_exec(
+                helper,
+                process_start_time_for_pid(helper),
+                ProcessLinks {
+                    ppid: 1,
+                    original_ppid: 900_001,
+                    responsible_pid: 0,
+                },
+                "/usr/local/bin/agentsmith-override".to_string(),
+                vec!["agentsmith-override".to_string()],
+                &policy,
+                50,
+            );
+        }
+        let err =
+            reject_ai_originated_request(helper, &policy, &process_table).expect_err("agent lineage must be kept");
+        assert!(err.contains("ancestor: codex"), "{}", err);
+    }
+
     #[test]
     fn validate_override_request_origin_rejects_non_helper_process() {
         let policy = test_policy();
@@ -5352,8 +13655,18 @@ mod tests {
             path: None,
             minutes: None,
             requester_pid: Some(child.id() as i32),
+            approval_id: None,
+            scope_pid: None,
+            scope_session: None,
+            ops: Vec::new(),
+            rename_to: None,
+            max_uses: None,
+            pin: false,
+            paths: Vec::new(),
+            glob: None,
         };
-        let err = validate_override_request_origin(&request, &policy).expect_err("non-helper process must be rejected");
+        let err = validate_override_request_origin(&request, &policy, &Mutex::default())
+            .expect_err("non-helper process must be rejected");
         assert!(err.contains("not agentsmith-override helper"));
         let _ = child.kill();
         let _ = child.wait();
@@ -5361,6 +13674,11 @@ mod tests {
 }
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(code) = cli::run_subcommand(&args) {
+        std::process::exit(code);
+    }
+
     let home = std::env::var("HOME").unwrap_or_else(|_| "/root".into());
     let policy_path = format!("{}/.agentsmith-rs/policy.json", home);
     let home_for_reload = home.clone();
@@ -5391,13 +13709,14 @@ fn main() {
     }
     let mut startup_policy_warning_state = None;
     log_policy_self_checks(&initial_policy, &mut startup_policy_warning_state, now_ts());
+    set_log_rotation_policy(&initial_policy.log_rotation);
 
     let initial_taint_ttl = initial_policy.taint_ttl_seconds_or_default();
     let global_policy = Arc::new(Mutex::new(initial_policy));
     init_async_log_worker(&home);
 
-    // Process ancestry cache (cleared on policy reload)
-    let ancestor_cache: Arc<Mutex<HashMap<i32, CachedAncestor>>> = Arc::new(Mutex::new(HashMap::new()));
+    // Process table (fork/exec/exit driven; AI labels recomputed on policy reload)
+    let process_table: Arc<Mutex<ProcessTable>> = Arc::new(Mutex::new(ProcessTable::default()));
     let trusted_process_cache: Arc<Mutex<TrustedProcessCache>> = Arc::new(Mutex::new(TrustedProcessCache::default()));
     let binary_signature_cache: Arc<Mutex<BinarySignatureCache>> =
         Arc::new(Mutex::new(BinarySignatureCache::default()));
@@ -5414,11 +13733,22 @@ fn main() {
             &binary_signature_cache,
             &signature_refresh_tx,
         );
+        let mut table = process_table.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        table.seed_from_snapshot(&policy_snapshot, now_ts());
+        println!(
+            "[process] seeded process table with {} entries",
+            table.len()
+        );
     }
 
-    // Policy hot-reload thread (1s polling)
+    // Control socket: requests are queued to the policy thread, which owns the overrides.
+    let (control_tx, control_rx) = mpsc::sync_channel::<ControlCommand>(CONTROL_QUEUE_BOUND);
+    spawn_control_socket_server(&home, control_tx);
+    let daemon_started_at = now_ts();
+
+    // Policy hot-reload thread (1s polling, woken early by control requests)
     let policy_clone = global_policy.clone();
-    let cache_clone = ancestor_cache.clone();
+    let process_table_clone = process_table.clone();
     let trust_cache_clone = trusted_process_cache.clone();
     let signature_cache_clone = binary_signature_cache.clone();
     let signature_refresh_tx_clone = signature_refresh_tx.clone();
@@ -5437,6 +13767,10 @@ fn main() {
             },
         };
         let mut request_window: VecDeque<u64> = VecDeque::new();
+        let mut pending_overrides = PendingOverrideQueue {
+            dirty: true,
+            ..PendingOverrideQueue::default()
+        };
         let mut last_policy_mtime = fs::metadata(&path_clone)
             .and_then(|meta| meta.modified())
             .unwrap_or(SystemTime::UNIX_EPOCH);
@@ -5444,11 +13778,25 @@ fn main() {
             .and_then(|meta| meta.modified())
             .unwrap_or(SystemTime::UNIX_EPOCH);
         let mut policy_warning_state = None;
+        let mut pending_control: Option<ControlCommand> = None;
 
         loop {
             let mut changed = false;
             let mut overrides_changed = false;
             let mut should_mirror_policy = false;
+            let mut policy_reload_failed = false;
+            let control_commands: Vec<ControlCommand> = pending_control
+                .take()
+                .into_iter()
+                .chain(control_rx.try_iter())
+                .collect();
+            if control_commands
+                .iter()
+                .any(|command| command.request.command == "reload")
+            {
+                last_policy_mtime = SystemTime::UNIX_EPOCH;
+                last_override_mtime = SystemTime::UNIX_EPOCH;
+            }
 
             if let Ok(metadata) = fs::metadata(&path_clone) {
                 if let Ok(mtime) = metadata.modified() {
@@ -5460,6 +13808,8 @@ fn main() {
                             changed = true;
                             should_mirror_policy = true;
                             println!("[policy] reloaded static config");
+                        } else {
+                            policy_reload_failed = true;
                         }
                     }
                 }
@@ -5482,17 +13832,68 @@ fn main() {
                 }
             }
 
+            process_session_requests(&home_for_reload, &static_policy, &process_table_clone);
+            let session_events = process_table_clone
+                .lock()
+                .map(|mut table| table.take_session_events())
+                .unwrap_or_default();
+            log_session_events(&home_for_reload, session_events);
+
             if process_override_requests(
                 &request_path_clone,
                 &static_policy,
                 &home_for_reload,
                 &mut runtime_overrides,
                 &mut request_window,
+                &mut pending_overrides,
+                &process_table_clone,
             ) {
                 changed = true;
                 overrides_changed = true;
             }
 
+            let mut control_replies = Vec::new();
+            let mut deferred_control = Vec::new();
+            for command in control_commands {
+                let id = control_request_id(&command.request, &command.peer);
+                let response = match command.request.command.as_str() {
+                    "reload" if policy_reload_failed => ControlResponse::error(
+                        &id,
+                        "policy.json could not be parsed; keeping the previous policy",
+                    ),
+                    "reload" => ControlResponse::ok(&id, "policy and runtime overrides reloaded", None),
+                    "status" | "cache.dump" => {
+                        deferred_control.push(command);
+                        continue;
+                    },
+                    _ => {
+                        let (command_changed, response) = handle_control_override_command(
+                            &command.request,
+                            &command.peer,
+                            &command.reply,
+                            &static_policy,
+                            &home_for_reload,
+                            &mut runtime_overrides,
+                            &mut request_window,
+                            &mut pending_overrides,
+                            &process_table_clone,
+                        );
+                        if command_changed {
+                            changed = true;
+                            overrides_changed = true;
+                        }
+                        response
+                    },
+                };
+                control_replies.push((command.reply, response));
+            }
+            expire_pending_overrides(&home_for_reload, &mut pending_overrides, now_ts());
+            write_pending_override_snapshot(&home_for_reload, &mut pending_overrides);
+            if sync_override_uses(&home_for_reload, &mut runtime_overrides, now_ts()) {
+                changed = true;
+                overrides_changed = true;
+            }
+
             let mut combined_policy = static_policy.clone();
             combined_policy.temporary_overrides = runtime_overrides.clone();
             if combined_policy.sanitize_overrides(now_ts(), &home_for_reload) {
@@ -5518,10 +13919,11 @@ fn main() {
             if changed {
                 combined_policy.temporary_overrides = runtime_overrides.clone();
                 log_policy_self_checks(&combined_policy, &mut policy_warning_state, now_ts());
+                set_log_rotation_policy(&combined_policy.log_rotation);
                 if let Ok(mut lock) = policy_clone.lock() {
                     *lock = combined_policy.clone();
-                    if let Ok(mut c) = cache_clone.lock() {
-                        c.clear();
+                    if let Ok(mut table) = process_table_clone.lock() {
+                        table.refresh_ai_labels(&combined_policy, now_ts());
                     }
                     if let Ok(mut trust_cache) = trust_cache_clone.lock() {
                         trust_cache.clear_all();
@@ -5550,20 +13952,64 @@ fn main() {
                 }
             }
 
-            thread::sleep(Duration::from_secs(1));
+            for command in deferred_control {
+                let id = control_request_id(&command.request, &command.peer);
+                let response = if command.request.command == "status" {
+                    ControlResponse::ok(
+                        &id,
+                        "daemon status",
+                        Some(control_status(
+                            &combined_policy,
+                            &runtime_overrides,
+                            daemon_started_at,
+                            &process_table_clone,
+                            &trust_cache_clone,
+                            &taint_clone,
+                        )),
+                    )
+                } else {
+                    ControlResponse::ok(
+                        &id,
+                        "cache dump",
+                        Some(control_cache_dump(
+                            &process_table_clone,
+                            &trust_cache_clone,
+                            &signature_cache_clone,
+                            &taint_clone,
+                        )),
+                    )
+                };
+                control_replies.push((command.reply, response));
+            }
+            for (reply, response) in control_replies {
+                let _ = reply.send(response);
+            }
+            pending_overrides.flush_replies();
+
+            match control_rx.recv_timeout(Duration::from_secs(1)) {
+                Ok(command) => pending_control = Some(command),
+                Err(mpsc::RecvTimeoutError::Timeout) => {},
+                Err(mpsc::RecvTimeoutError::Disconnected) => thread::sleep(Duration::from_secs(1)),
+            }
         }
     });
 
-    let cache_metrics_clone = ancestor_cache.clone();
+    let process_table_metrics_clone = process_table.clone();
     let trust_metrics_clone = trusted_process_cache.clone();
     let taint_metrics_clone = taint_state.clone();
     let callback_latency_metrics = Arc::new(CallbackLatencyMetrics::new());
     let callback_latency_metrics_clone = callback_latency_metrics.clone();
     thread::spawn(move || {
         let mut highs = CacheWatermarkHighs::default();
+        let mut last_prune_at = now_ts();
         loop {
+            let now = now_ts();
+            if now.saturating_sub(last_prune_at) >= PROCESS_TABLE_PRUNE_INTERVAL_SECS {
+                prune_exited_processes(&process_table_metrics_clone);
+                last_prune_at = now;
+            }
             emit_cache_watermark_log(
-                &cache_metrics_clone,
+                &process_table_metrics_clone,
                 &trust_metrics_clone,
                 &taint_metrics_clone,
                 &mut highs,
@@ -5579,7 +14025,7 @@ fn main() {
     });
 
     let safe_policy = AssertUnwindSafe(global_policy);
-    let safe_cache = AssertUnwindSafe(ancestor_cache);
+    let safe_process_table = AssertUnwindSafe(process_table);
     let safe_trust_cache = AssertUnwindSafe(trusted_process_cache);
     let safe_signature_cache = AssertUnwindSafe(binary_signature_cache);
     let safe_taint = AssertUnwindSafe(taint_state);
@@ -5596,6 +14042,32 @@ fn main() {
             .unwrap_or_else(|poisoned| poisoned.into_inner())
             .clone();
         let pid = message.process().audit_token().pid();
+        let process_start = message
+            .process()
+            .start_time()
+            .and_then(process_start_key_from_system_time);
+        let (session, session_events) = {
+            let mut process_table = safe_process_table
+                .0
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            if !process_table.is_current(pid, process_start) {
+                let executable_path = message.process().executable().path().to_string_lossy().into_owned();
+                process_table.observe_process(
+                    pid,
+                    process_start,
+                    ProcessLinks::from_process(&message.process()),
+                    &executable_path,
+                    &current_policy,
+                    now_ts(),
+                );
+            }
+            (
+                process_table.session_for_pid(pid),
+                process_table.take_session_events(),
+            )
+        };
+        log_session_events(&home_for_handler, session_events);
 
         match message.event() {
             Some(Event::AuthOpen(open)) => {
@@ -5610,9 +14082,68 @@ fn main() {
                 let process_name = process_name_for_pid(pid).unwrap_or_else(|| format!("pid:{}", pid));
                 let is_guard_process = pid == guard_pid;
 
+                if !is_guard_process
+                    && should_gate_protected_open_truncate(
+                        path.as_str(),
+                        fflag,
+                        open.file().stat().st_size,
+                        &current_policy,
+                        &home_for_handler,
+                    )
+                {
+                    let mut process_table = safe_process_table
+                        .0
+                        .lock()
+                        .unwrap_or_else(|poisoned| poisoned.into_inner());
+                    if let Some(decision) = should_deny_protected_zone_op(
+                        &path,
+                        pid,
+                        &home_for_handler,
+                        &current_policy,
+                        &mut process_table,
+                        &safe_trust_cache.0,
+                        &safe_signature_cache.0,
+                        &signature_refresh_tx_for_handler,
+                        GateOp::new(
+                            "truncate",
+                            Some(FileIdentity::from_stat(open.file().stat())),
+                        ),
+                        REASON_PROTECTED_ZONE_AI_TRUNCATE,
+                    ) {
+                        drop(process_table);
+                        let zone = current_policy.matched_zone(&path, &home_for_handler);
+                        if !current_policy.audit_only_mode {
+                            println!(
+                                "[DENY] open(truncate) by {} (via {}): {}",
+                                decision.process, decision.ancestor, path
+                            );
+                        }
+                        let record = DenialRecord {
+                            ts: now_ts(),
+                            op: "open".into(),
+                            path: path.clone(),
+                            dest: None,
+                            zone,
+                            process: decision.process,
+                            ancestor: decision.ancestor,
+                            reason: decision.reason.to_string(),
+                            pid: pid_for_record(pid),
+                            ppid: parent_pid_for_pid(pid),
+                            session: session.as_deref().cloned(),
+                        };
+                        if record_denial_or_audit_only(&home_for_handler, &current_policy, record) {
+                            let _ = client.respond_flags_result(&message, 0, false);
+                            return;
+                        }
+                    }
+                }
+
                 if current_policy.is_sensitive_path(path.as_str()) && is_read_intent(fflag) {
-                    let mut cache = safe_cache.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
-                    let ai_ancestor = find_ai_ancestor(pid, &current_policy, &mut cache);
+                    let mut process_table = safe_process_table
+                        .0
+                        .lock()
+                        .unwrap_or_else(|poisoned| poisoned.into_inner());
+                    let ai_ancestor = find_ai_ancestor(pid, &current_policy, &mut process_table);
                     let is_ai_context = ai_ancestor.is_some();
                     let allow_observer_read =
                         should_allow_sensitive_read_observer(&path, process_name.as_str(), &home_for_handler);
@@ -5644,6 +14175,7 @@ fn main() {
                             reason: REASON_SENSITIVE_READ_NON_AI.to_string(),
                             pid: pid_for_record(pid),
                             ppid: parent_pid_for_pid(pid),
+                            session: session.as_deref().cloned(),
                         };
                         if record_denial_or_audit_only(&home_for_handler, &current_policy, record) {
                             let _ = client.respond_flags_result(&message, 0, false);
@@ -5671,6 +14203,7 @@ fn main() {
                                 ancestor,
                                 pid,
                                 ppid: parent_pid_for_pid(pid),
+                                session_id: session.as_ref().map(|session| session.id.clone()),
                             },
                         );
                     }
@@ -5728,6 +14261,7 @@ fn main() {
                         reason: denial_reason.to_string(),
                         pid: pid_for_record(pid),
                         ppid: parent_pid_for_pid(pid),
+                        session: session.as_deref().cloned(),
                     };
                     if record_denial_or_audit_only(&home_for_handler, &current_policy, record) {
                         let _ = client.respond_flags_result(&message, 0, false);
@@ -5760,15 +14294,19 @@ fn main() {
                             ancestor: format!("inherit-from-pid:{}", parent_pid.unwrap_or_default()),
                             pid,
                             ppid: parent_pid,
+                            session_id: session.as_ref().map(|session| session.id.clone()),
                         },
                     );
                 }
-                let mut cache = safe_cache.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
-                let ai_ancestor = find_ai_ancestor(pid, &current_policy, &mut cache);
+                let mut process_table = safe_process_table
+                    .0
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                let ai_ancestor = find_ai_ancestor(pid, &current_policy, &mut process_table);
                 let is_ai_context = ai_ancestor.is_some();
                 let should_deny = should_deny_exec_in_ai_context(target_name.as_str(), is_ai_context, &current_policy);
 
-                if should_deny {
+                let denied = if should_deny {
                     let ancestor = ai_ancestor.unwrap_or_else(|| "none".to_string());
                     if !current_policy.audit_only_mode {
                         println!(
@@ -5779,7 +14317,7 @@ fn main() {
                     let record = DenialRecord {
                         ts: now_ts(),
                         op: "exec".into(),
-                        path: target_path,
+                        path: target_path.clone(),
                         dest: None,
                         zone: "exec-blocklist".to_string(),
                         process: target_name,
@@ -5787,19 +14325,61 @@ fn main() {
                         reason: REASON_EXEC_EXFIL_TOOL.to_string(),
                         pid: pid_for_record(pid),
                         ppid: parent_pid_for_pid(pid),
+                        session: session.as_deref().cloned(),
                     };
-                    if record_denial_or_audit_only(&home_for_handler, &current_policy, record) {
-                        let _ = client.respond_auth_result(&message, es_auth_result_t::ES_AUTH_RESULT_DENY, false);
-                    } else {
-                        let _ = client.respond_auth_result(&message, es_auth_result_t::ES_AUTH_RESULT_ALLOW, false);
-                    }
+                    record_denial_or_audit_only(&home_for_handler, &current_policy, record)
+                } else {
+                    false
+                };
+
+                if denied {
+                    let _ = client.respond_auth_result(&message, es_auth_result_t::ES_AUTH_RESULT_DENY, false);
                 } else {
+                    let target = exec.target();
+                    let argv: Vec<String> = exec.args().map(|arg| arg.to_string_lossy().into_owned()).collect();
+                    let env_names = current_policy.detector_env_names();
+                    let env = if env_names.is_empty() {
+                        Vec::new()
+                    } else {
+                        exec.envs()
+                            .map(|entry| entry.to_string_lossy().into_owned())
+                            .filter(|entry| env_names.contains(entry.split('=').next().unwrap_or_default()))
+                            .collect()
+                    };
+                    let evidence = ExecEvidence {
+                        env,
+                        signing_id: target.signing_id().to_string_lossy().into_owned(),
+                        team_id: target.team_id().to_string_lossy().into_owned(),
+                    };
+                    process_table.record_exec_with_evidence(
+                        pid,
+                        target.start_time().and_then(process_start_key_from_system_time),
+                        ProcessLinks::from_process(&target),
+                        target_path,
+                        argv,
+                        evidence,
+                        &current_policy,
+                        marked_at,
+                    );
+                    let session_events = process_table.take_session_events();
+                    drop(process_table);
                     let _ = client.respond_auth_result(&message, es_auth_result_t::ES_AUTH_RESULT_ALLOW, false);
+                    log_session_events(&home_for_handler, session_events);
                 }
             },
             Some(Event::NotifyFork(fork)) => {
                 let child_pid = fork.child().audit_token().pid();
                 let marked_at = now_ts();
+                let session_events = {
+                    let child_start = fork.child().start_time().and_then(process_start_key_from_system_time);
+                    let mut process_table = safe_process_table
+                        .0
+                        .lock()
+                        .unwrap_or_else(|poisoned| poisoned.into_inner());
+                    process_table.record_fork(pid, child_pid, child_start, &current_policy, marked_at);
+                    process_table.take_session_events()
+                };
+                log_session_events(&home_for_handler, session_events);
                 let inherited = {
                     let mut taint = safe_taint.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
                     taint.inherit_from_parent(pid, child_pid, marked_at)
@@ -5816,19 +14396,26 @@ fn main() {
                             ancestor: format!("inherit-from-pid:{}", pid),
                             pid: child_pid,
                             ppid: pid_for_record(pid),
+                            session_id: session.as_ref().map(|session| session.id.clone()),
                         },
                     );
                 }
             },
             Some(Event::NotifyExit(_exit)) => {
-                let mut cache = safe_cache.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                let mut process_table = safe_process_table
+                    .0
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
                 let mut trust_cache = safe_trust_cache
                     .0
                     .lock()
                     .unwrap_or_else(|poisoned| poisoned.into_inner());
                 let mut taint = safe_taint.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
-                clear_process_state_for_pid(pid, &mut cache, &mut trust_cache, &mut taint);
+                clear_process_state_for_pid(pid, &mut process_table, &mut trust_cache, &mut taint);
                 taint.prune_expired(now_ts());
+                let session_events = process_table.take_session_events();
+                drop((process_table, trust_cache, taint));
+                log_session_events(&home_for_handler, session_events);
             },
             Some(Event::AuthCreate(create)) => {
                 let dest_path = match create.destination() {
@@ -5889,6 +14476,7 @@ fn main() {
                         reason: denial_reason.to_string(),
                         pid: pid_for_record(pid),
                         ppid: parent_pid_for_pid(pid),
+                        session: session.as_deref().cloned(),
                     };
                     if record_denial_or_audit_only(&home_for_handler, &current_policy, record) {
                         let _ = client.respond_auth_result(&message, es_auth_result_t::ES_AUTH_RESULT_DENY, false);
@@ -5901,6 +14489,58 @@ fn main() {
             },
             Some(Event::AuthTruncate(truncate)) => {
                 let target_path = truncate.target().path().to_string_lossy().into_owned();
+                if should_gate_protected_truncate(
+                    &target_path,
+                    truncate.target().stat().st_size,
+                    &current_policy,
+                    &home_for_handler,
+                ) {
+                    let mut process_table = safe_process_table
+                        .0
+                        .lock()
+                        .unwrap_or_else(|poisoned| poisoned.into_inner());
+                    if let Some(decision) = should_deny_protected_zone_op(
+                        &target_path,
+                        pid,
+                        &home_for_handler,
+                        &current_policy,
+                        &mut process_table,
+                        &safe_trust_cache.0,
+                        &safe_signature_cache.0,
+                        &signature_refresh_tx_for_handler,
+                        GateOp::new(
+                            "truncate",
+                            Some(FileIdentity::from_stat(truncate.target().stat())),
+                        ),
+                        REASON_PROTECTED_ZONE_AI_TRUNCATE,
+                    ) {
+                        drop(process_table);
+                        let zone = current_policy.matched_zone(&target_path, &home_for_handler);
+                        if !current_policy.audit_only_mode {
+                            println!(
+                                "[DENY] truncate by {} (via {}): {}",
+                                decision.process, decision.ancestor, target_path
+                            );
+                        }
+                        let record = DenialRecord {
+                            ts: now_ts(),
+                            op: "truncate".into(),
+                            path: target_path.clone(),
+                            dest: None,
+                            zone,
+                            process: decision.process,
+                            ancestor: decision.ancestor,
+                            reason: decision.reason.to_string(),
+                            pid: pid_for_record(pid),
+                            ppid: parent_pid_for_pid(pid),
+                            session: session.as_deref().cloned(),
+                        };
+                        if record_denial_or_audit_only(&home_for_handler, &current_policy, record) {
+                            let _ = client.respond_auth_result(&message, es_auth_result_t::ES_AUTH_RESULT_DENY, false);
+                            return;
+                        }
+                    }
+                }
                 let process_name = process_name_for_pid(pid).unwrap_or_else(|| format!("pid:{}", pid));
                 let trusted_process = evaluate_trusted_process(
                     pid,
@@ -5951,6 +14591,7 @@ fn main() {
                         reason: denial_reason.to_string(),
                         pid: pid_for_record(pid),
                         ppid: parent_pid_for_pid(pid),
+                        session: session.as_deref().cloned(),
                     };
                     if record_denial_or_audit_only(&home_for_handler, &current_policy, record) {
                         let _ = client.respond_auth_result(&message, es_auth_result_t::ES_AUTH_RESULT_DENY, false);
@@ -5992,6 +14633,7 @@ fn main() {
                         reason: REASON_SENSITIVE_TRANSFER_OUT.to_string(),
                         pid: pid_for_record(pid),
                         ppid: parent_pid_for_pid(pid),
+                        session: session.as_deref().cloned(),
                     };
                     if record_denial_or_audit_only(&home_for_handler, &current_policy, record) {
                         let _ = client.respond_auth_result(&message, es_auth_result_t::ES_AUTH_RESULT_DENY, false);
@@ -6029,6 +14671,7 @@ fn main() {
                         reason: REASON_SENSITIVE_TRANSFER_OUT.to_string(),
                         pid: pid_for_record(pid),
                         ppid: parent_pid_for_pid(pid),
+                        session: session.as_deref().cloned(),
                     };
                     if record_denial_or_audit_only(&home_for_handler, &current_policy, record) {
                         let _ = client.respond_auth_result(&message, es_auth_result_t::ES_AUTH_RESULT_DENY, false);
@@ -6066,6 +14709,7 @@ fn main() {
                         reason: REASON_SENSITIVE_TRANSFER_OUT.to_string(),
                         pid: pid_for_record(pid),
                         ppid: parent_pid_for_pid(pid),
+                        session: session.as_deref().cloned(),
                     };
                     if record_denial_or_audit_only(&home_for_handler, &current_policy, record) {
                         let _ = client.respond_auth_result(&message, es_auth_result_t::ES_AUTH_RESULT_DENY, false);
@@ -6107,6 +14751,7 @@ fn main() {
                         reason: REASON_SENSITIVE_TRANSFER_OUT.to_string(),
                         pid: pid_for_record(pid),
                         ppid: parent_pid_for_pid(pid),
+                        session: session.as_deref().cloned(),
                     };
                     if record_denial_or_audit_only(&home_for_handler, &current_policy, record) {
                         let _ = client.respond_auth_result(&message, es_auth_result_t::ES_AUTH_RESULT_DENY, false);
@@ -6120,13 +14765,17 @@ fn main() {
             Some(Event::AuthUnlink(unlink)) => {
                 let path = unlink.target().path().to_string_lossy();
 
-                let mut cache = safe_cache.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                let mut process_table = safe_process_table
+                    .0
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
                 if let Some(decision) = should_deny(
                     &path,
+                    Some(FileIdentity::from_stat(unlink.target().stat())),
                     pid,
                     &home_for_handler,
                     &current_policy,
-                    &mut cache,
+                    &mut process_table,
                     &safe_trust_cache.0,
                     &safe_signature_cache.0,
                     &signature_refresh_tx_for_handler,
@@ -6149,6 +14798,7 @@ fn main() {
                         reason: decision.reason.to_string(),
                         pid: pid_for_record(pid),
                         ppid: parent_pid_for_pid(pid),
+                        session: session.as_deref().cloned(),
                     };
                     if record_denial_or_audit_only(&home_for_handler, &current_policy, record) {
                         let _ = client.respond_auth_result(&message, es_auth_result_t::ES_AUTH_RESULT_DENY, false);
@@ -6191,6 +14841,7 @@ fn main() {
                         reason: REASON_SENSITIVE_TRANSFER_OUT.to_string(),
                         pid: pid_for_record(pid),
                         ppid: parent_pid_for_pid(pid),
+                        session: session.as_deref().cloned(),
                     };
                     if record_denial_or_audit_only(&home_for_handler, &current_policy, record) {
                         let _ = client.respond_auth_result(&message, es_auth_result_t::ES_AUTH_RESULT_DENY, false);
@@ -6201,12 +14852,18 @@ fn main() {
                 }
 
                 // For rename: deny if moving OUT of protected zone in AI context
-                let mut cache = safe_cache.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
-                let deny_reason = if !current_policy.is_protected(source_path.as_str(), &home_for_handler)
-                    || is_system_temp(&source_path, &home_for_handler)
-                {
+                let mut process_table = safe_process_table
+                    .0
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                let deny_reason = if is_system_temp(&source_path, &home_for_handler) {
+                    None
+                } else if !current_policy.is_protected(source_path.as_str(), &home_for_handler) {
+                    if current_policy.is_in_any_zone(&source_path, &home_for_handler) {
+                        count_session_override_use(&process_table, pid);
+                    }
                     None
-                } else if let Some(ai_ancestor) = find_ai_ancestor(pid, &current_policy, &mut cache) {
+                } else if let Some(ai_ancestor) = find_ai_ancestor(pid, &current_policy, &mut process_table) {
                     let process_name = process_name_for_pid(pid).unwrap_or_else(|| format!("pid:{}", pid));
                     let trusted_process = evaluate_trusted_process(
                         pid,
@@ -6229,12 +14886,31 @@ fn main() {
                         process_name.as_str(),
                         &trusted_process,
                         &current_policy,
+                        &process_table,
                     ) {
                         None
-                    } else if current_policy.allow_trusted_tools_in_ai_context && trusted_process.is_trusted() {
+                    } else if (current_policy.allow_trusted_tools_in_ai_context && trusted_process.is_trusted())
+                        || current_policy.is_in_any_zone(&dest_path_str, &home_for_handler)
+                    {
                         None
-                    } else if !current_policy.is_in_any_zone(&dest_path_str, &home_for_handler) {
-                        let reason = if (current_policy.allow_vcs_metadata_in_ai_context
+                    } else {
+                        let claim = claim_restricted_override_for_pid(
+                            &source_path,
+                            GateOp {
+                                name: "rename",
+                                dest: Some(&dest_path_str),
+                                file: Some(FileIdentity::from_stat(rename.source().stat())),
+                            },
+                            pid,
+                            &current_policy,
+                            &mut process_table,
+                        );
+                        if claim == OverrideClaim::Allowed {
+                            count_session_override_use(&process_table, pid);
+                        }
+                        let reason = if claim == OverrideClaim::PinMismatch {
+                            REASON_OVERRIDE_PIN_MISMATCH
+                        } else if (current_policy.allow_vcs_metadata_in_ai_context
                             && is_vcs_metadata_path(source_path.as_str())
                             && is_vcs_metadata_path(dest_path_str.as_str())
                             && is_vcs_tool(process_name.as_str())
@@ -6242,7 +14918,9 @@ fn main() {
                             || (current_policy.allow_git_merge_pull_in_ai_context
                                 && process_name == "git"
                                 && trusted_process.is_identity_mismatch()
-                                && get_process_argv(pid).is_some_and(|args| is_git_merge_or_pull_invocation(&args)))
+                                && process_table
+                                    .argv_for_pid(pid)
+                                    .is_some_and(|args| is_git_merge_or_pull_invocation(&args)))
                             || (current_policy.allow_trusted_tools_in_ai_context
                                 && current_policy.is_trusted_tool(process_name.as_str())
                                 && trusted_process.is_identity_mismatch())
@@ -6251,13 +14929,11 @@ fn main() {
                         } else {
                             REASON_PROTECTED_ZONE_AI_DELETE
                         };
-                        Some(GateDenyDecision {
+                        (claim != OverrideClaim::Allowed).then_some(GateDenyDecision {
                             process: process_name,
                             ancestor: ai_ancestor,
                             reason,
                         })
-                    } else {
-                        None
                     }
                 } else {
                     None
@@ -6282,6 +14958,74 @@ fn main() {
                         reason: decision.reason.to_string(),
                         pid: pid_for_record(pid),
                         ppid: parent_pid_for_pid(pid),
+                        session: session.as_deref().cloned(),
+                    };
+                    if record_denial_or_audit_only(&home_for_handler, &current_policy, record) {
+                        let _ = client.respond_auth_result(&message, es_auth_result_t::ES_AUTH_RESULT_DENY, false);
+                    } else {
+                        let _ = client.respond_auth_result(&message, es_auth_result_t::ES_AUTH_RESULT_ALLOW, false);
+                    }
+                } else {
+                    let _ = client.respond_auth_result(&message, es_auth_result_t::ES_AUTH_RESULT_ALLOW, false);
+                }
+            },
+            Some(
+                event @ (Event::AuthSetMode(_)
+                | Event::AuthSetFlags(_)
+                | Event::AuthSetAcl(_)
+                | Event::AuthSetExtAttr(_)
+                | Event::AuthDeleteExtAttr(_)
+                | Event::AuthSetOwner(_)
+                | Event::AuthUTimes(_)),
+            ) => {
+                let change = match metadata_change_for_event(&event, &current_policy, now_ts()) {
+                    Some(change)
+                        if should_gate_protected_metadata_change(&change, &current_policy, &home_for_handler) =>
+                    {
+                        change
+                    },
+                    _ => {
+                        let _ = client.respond_auth_result(&message, es_auth_result_t::ES_AUTH_RESULT_ALLOW, false);
+                        return;
+                    },
+                };
+
+                let mut process_table = safe_process_table
+                    .0
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                if let Some(decision) = should_deny_protected_zone_op(
+                    &change.path,
+                    pid,
+                    &home_for_handler,
+                    &current_policy,
+                    &mut process_table,
+                    &safe_trust_cache.0,
+                    &safe_signature_cache.0,
+                    &signature_refresh_tx_for_handler,
+                    GateOp::new(change.op, Some(change.file)),
+                    REASON_PROTECTED_ZONE_AI_METADATA,
+                ) {
+                    drop(process_table);
+                    let zone = current_policy.matched_zone(&change.path, &home_for_handler);
+                    if !current_policy.audit_only_mode {
+                        println!(
+                            "[DENY] {} by {} (via {}): {}",
+                            change.op, decision.process, decision.ancestor, change.path
+                        );
+                    }
+                    let record = DenialRecord {
+                        ts: now_ts(),
+                        op: change.op.into(),
+                        path: change.path,
+                        dest: None,
+                        zone,
+                        process: decision.process,
+                        ancestor: decision.ancestor,
+                        reason: decision.reason.to_string(),
+                        pid: pid_for_record(pid),
+                        ppid: parent_pid_for_pid(pid),
+                        session: session.as_deref().cloned(),
                     };
                     if record_denial_or_audit_only(&home_for_handler, &current_policy, record) {
                         let _ = client.respond_auth_result(&message, es_auth_result_t::ES_AUTH_RESULT_DENY, false);
@@ -6316,6 +15060,13 @@ fn main() {
             es_event_type_t::ES_EVENT_TYPE_AUTH_EXCHANGEDATA,
             es_event_type_t::ES_EVENT_TYPE_AUTH_UNLINK,
             es_event_type_t::ES_EVENT_TYPE_AUTH_RENAME,
+            es_event_type_t::ES_EVENT_TYPE_AUTH_SETMODE,
+            es_event_type_t::ES_EVENT_TYPE_AUTH_SETFLAGS,
+            es_event_type_t::ES_EVENT_TYPE_AUTH_SETACL,
+            es_event_type_t::ES_EVENT_TYPE_AUTH_SETEXTATTR,
+            es_event_type_t::ES_EVENT_TYPE_AUTH_DELETEEXTATTR,
+            es_event_type_t::ES_EVENT_TYPE_AUTH_SETOWNER,
+            es_event_type_t::ES_EVENT_TYPE_AUTH_UTIMES,
         ])
         .expect("Failed to subscribe");
 
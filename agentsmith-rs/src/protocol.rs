@@ -0,0 +1,204 @@
+//! Override request protocol shared by the daemon and the `agentsmith-override`
+//! client. Clients talk to the daemon's control socket, one JSON line each way;
+//! when the socket is not there they fall back to JSON files dropped into
+//! `~/.agentsmith-rs/guard/override-requests/<id>.request.json`, which the
+//! daemon answers in `<id>.response.json` next to them.
+
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const OVERRIDE_DEFAULT_MINUTES: u64 = 3;
+pub const OVERRIDE_MAX_MINUTES: u64 = 30;
+pub const MAX_OVERRIDE_PATH_LEN: usize = 4096;
+pub const MAX_OVERRIDE_USES: u32 = 100;
+/// Most concrete paths one multi-path or glob request may expand to.
+pub const MAX_OVERRIDE_BATCH_PATHS: usize = 64;
+/// Operations a grant can be limited to.
+pub const OVERRIDE_OPS: &[&str] = &["unlink", "rename", "truncate"];
+pub const CONTROL_SOCKET_DIR: &str = "/var/run/agentsmith-rs";
+pub const CONTROL_PROTOCOL_VERSION: u32 = 1;
+pub const CONTROL_IO_TIMEOUT_SECS: u64 = 5;
+pub const CONTROL_MAX_RESPONSE_BYTES: u64 = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct OverrideRequest {
+    pub id: String,
+    pub action: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub minutes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requester_pid: Option<i32>,
+    /// Pending request an `approve` / `reject` action decides.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approval_id: Option<String>,
+    /// Limit a grant to this process and its descendants.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope_pid: Option<i32>,
+    /// Limit a grant to one AI session (`<agent>-<root pid>-<start time>`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope_session: Option<String>,
+    /// Limit a grant to these operations (`unlink`, `rename`, `truncate`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ops: Vec<String>,
+    /// Only allow renames to this destination (implies `rename`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rename_to: Option<String>,
+    /// Remove the grant after this many allowed operations.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_uses: Option<u32>,
+    /// Pin a grant to the target file's current device, inode, size and
+    /// content hash.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pin: bool,
+    /// More targets granted, approved or rejected together with `path`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+    /// Entries of one directory granted together, e.g. `/repo/gen/*.pb.go`;
+    /// only the last component may contain `*` or `?`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub glob: Option<String>,
+}
+
+/// `status` is `ok`, `error` when the daemon refuses the request, `invalid`
+/// when the request itself is malformed, or `pending` while a grant waits for
+/// a human; a pending response's `expires_at` is the approval deadline.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct OverrideResponse {
+    pub id: String,
+    pub status: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    /// `override_policy` rules that shaped the decision, one per line.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub explanation: Vec<String>,
+    /// Concrete paths a multi-path or glob grant expanded to.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+}
+
+/// Versioned request on the control socket: one JSON object per line, one
+/// request per connection.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ControlRequest {
+    pub version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub minutes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approval_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope_pid: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope_session: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ops: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rename_to: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_uses: Option<u32>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pin: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub glob: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ControlResponse {
+    pub version: u32,
+    pub id: String,
+    pub status: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub explanation: Vec<String>,
+}
+
+pub fn control_socket_path() -> PathBuf {
+    PathBuf::from(CONTROL_SOCKET_DIR).join("control.sock")
+}
+
+/// Client side of the control socket. A `pending` answer is passed to
+/// `on_pending` and the call keeps waiting for the final one.
+pub fn send_control_request(
+    socket_path: &Path,
+    request: &ControlRequest,
+    on_pending: impl Fn(&ControlResponse),
+) -> io::Result<ControlResponse> {
+    let mut stream = UnixStream::connect(socket_path)?;
+    let timeout = Some(Duration::from_secs(CONTROL_IO_TIMEOUT_SECS * 2));
+    stream.set_read_timeout(timeout)?;
+    stream.set_write_timeout(timeout)?;
+    let mut line = serde_json::to_vec(request).map_err(|err| io::Error::other(err.to_string()))?;
+    line.push(b'\n');
+    stream.write_all(&line)?;
+    let mut reader = io::BufReader::new(stream.try_clone()?).take(CONTROL_MAX_RESPONSE_BYTES);
+    loop {
+        let mut response = String::new();
+        reader.read_line(&mut response)?;
+        let response: ControlResponse = serde_json::from_str(&response)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+        if response.status != "pending" {
+            return Ok(response);
+        }
+        on_pending(&response);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let wait = response.expires_at.unwrap_or_default().saturating_sub(now);
+        stream.set_read_timeout(Some(Duration::from_secs(
+            wait + CONTROL_IO_TIMEOUT_SECS * 2,
+        )))?;
+    }
+}
+
+pub fn override_request_dir(home: &str) -> PathBuf {
+    PathBuf::from(home)
+        .join(".agentsmith-rs")
+        .join("guard")
+        .join("override-requests")
+}
+
+/// Lexically normalize an absolute path (`.`/`..` resolved, duplicate and
+/// trailing slashes dropped). Relative paths are rejected.
+pub fn normalize_absolute_path(path: &str) -> Option<String> {
+    if !path.starts_with('/') {
+        return None;
+    }
+
+    let mut parts: Vec<String> = Vec::new();
+    for component in Path::new(path).components() {
+        match component {
+            Component::RootDir => {},
+            Component::CurDir => {},
+            Component::ParentDir => {
+                let _ = parts.pop();
+            },
+            Component::Normal(segment) => {
+                parts.push(segment.to_string_lossy().to_string());
+            },
+            _ => return None,
+        }
+    }
+
+    if parts.is_empty() {
+        Some("/".to_string())
+    } else {
+        Some(format!("/{}", parts.join("/")))
+    }
+}
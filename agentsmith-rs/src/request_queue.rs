@@ -0,0 +1,104 @@
+//! Client side of the daemon's file queues, shared by the helper binaries:
+//! a request is `<dir>/<id>.request.json`, its answer `<dir>/<id>.response.json`.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::fs::OpenOptions;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub fn make_request_id() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(now.as_nanos());
+    format!(
+        "{}-{}-{:08x}",
+        now.as_secs(),
+        std::process::id(),
+        hasher.finish() as u32
+    )
+}
+
+/// Write the request under a dot-name and rename it into place, so the daemon
+/// never reads a half-written file.
+pub fn submit_request(request_dir: &Path, id: &str, request: &impl Serialize) -> io::Result<PathBuf> {
+    fs::DirBuilder::new().recursive(true).mode(0o700).create(request_dir)?;
+    let _ = fs::set_permissions(request_dir, fs::Permissions::from_mode(0o700));
+    let tmp_path = request_dir.join(format!(".request.{}.tmp", id));
+    let final_path = request_dir.join(format!("{}.request.json", id));
+    let content = serde_json::to_vec(request).map_err(|err| io::Error::other(err.to_string()))?;
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(&tmp_path)?;
+    let written = file.write_all(&content).and_then(|_| file.sync_data());
+    if let Err(err) = written.and_then(|_| fs::rename(&tmp_path, &final_path)) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(err);
+    }
+    Ok(final_path)
+}
+
+/// Block until something changes in `dir` or `timeout` passes.
+fn wait_for_dir_change(dir: &fs::File, timeout: Duration) {
+    let kq = unsafe { libc::kqueue() };
+    if kq < 0 {
+        std::thread::sleep(timeout.min(Duration::from_millis(100)));
+        return;
+    }
+    let change = libc::kevent {
+        ident: dir.as_raw_fd() as libc::uintptr_t,
+        filter: libc::EVFILT_VNODE,
+        flags: libc::EV_ADD | libc::EV_CLEAR,
+        fflags: libc::NOTE_WRITE,
+        data: 0,
+        udata: std::ptr::null_mut(),
+    };
+    let mut event = change;
+    let timespec = libc::timespec {
+        tv_sec: timeout.as_secs() as libc::time_t,
+        tv_nsec: timeout.subsec_nanos() as libc::c_long,
+    };
+    unsafe {
+        libc::kevent(kq, &change, 1, &mut event, 1, &timespec);
+        libc::close(kq);
+    }
+}
+
+fn read_response<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    let content = fs::read_to_string(path).ok()?;
+    // The daemon writes responses in place; retry on the next wakeup.
+    let response = serde_json::from_str(&content).ok()?;
+    let _ = fs::remove_file(path);
+    Some(response)
+}
+
+/// Wait on kqueue for the answer to request `id`, consuming it once it parses.
+pub fn wait_for_response<T: DeserializeOwned>(request_dir: &Path, id: &str, timeout: Duration) -> Option<T> {
+    let response_path = request_dir.join(format!("{}.response.json", id));
+    let dir = fs::File::open(request_dir).ok();
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(response) = read_response(&response_path) {
+            return Some(response);
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return None;
+        }
+        // Cap each wait so a missed event costs at most half a second.
+        let slice = remaining.min(Duration::from_millis(500));
+        match &dir {
+            Some(dir) => wait_for_dir_change(dir, slice),
+            None => std::thread::sleep(slice.min(Duration::from_millis(100))),
+        }
+    }
+}
@@ -0,0 +1,33 @@
+//! Session registration protocol shared by the daemon and the `agentsmith-run`
+//! launcher. Requests are JSON files dropped into
+//! `~/.agentsmith-rs/guard/session-requests/<id>.request.json`; the daemon
+//! answers in `<id>.response.json` next to them.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SessionRequest {
+    #[serde(default)]
+    pub pid: Option<i32>,
+    #[serde(default)]
+    pub agent: Option<String>,
+    #[serde(default)]
+    pub cwd: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SessionResponse {
+    pub id: String,
+    pub status: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+}
+
+pub fn session_request_dir(home: &str) -> PathBuf {
+    PathBuf::from(home)
+        .join(".agentsmith-rs")
+        .join("guard")
+        .join("session-requests")
+}
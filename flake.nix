@@ -42,9 +42,9 @@
 
         postInstall = ''
           install -Dm644 agentsmith-rs/agentsmith.plist $out/share/agentsmith-rs/agentsmith.plist
-          install -Dm755 agentsmith-rs/agentsmith-override $out/bin/agentsmith-override
           install -Dm755 agentsmith-rs/agentsmith-quarantine $out/bin/agentsmith-quarantine
           install -Dm755 agentsmith-rs/agentsmith-egress $out/bin/agentsmith-egress
+          install -Dm755 agentsmith-rs/agentsmith-feedback $out/bin/agentsmith-feedback
         '';
 
         meta = {
@@ -126,12 +126,100 @@
               description = "Enable AUTH_EXEC hardening against exfiltration tools in AI context.";
             };
 
+            metadataGateEnabled = lib.mkOption {
+              type = lib.types.bool;
+              default = true;
+              description = ''
+                Deny destructive chmod/chflags/ACL/xattr/chown/utimes changes in protected
+                zones from AI context. On by default, unlike truncateGateEnabled: only
+                destructive changes are gated, so `chmod +x`, `touch` and unpacking
+                archives keep working.
+              '';
+            };
+
+            truncateGateEnabled = lib.mkOption {
+              type = lib.types.bool;
+              default = false;
+              description = ''
+                Deny truncation and O_TRUNC overwrites of existing files in protected
+                zones from AI context (AUTH_TRUNCATE / AUTH_OPEN). Off by default
+                because most editors rewrite files with O_TRUNC.
+              '';
+            };
+
+            truncateGateZones = lib.mkOption {
+              type = lib.types.listOf lib.types.str;
+              default = [];
+              example = [ "/Users/me/projects/data" ];
+              description = "Protected-zone prefixes covered by the truncate gate (empty = all protected zones).";
+            };
+
+            truncateGateMinBytes = lib.mkOption {
+              type = lib.types.ints.unsigned;
+              default = 0;
+              description = "Only gate truncation of files whose current size is at least this many bytes.";
+            };
+
+            aiAgentProfiles = lib.mkOption {
+              type = lib.types.listOf lib.types.str;
+              default = [];
+              example = [ "claude-code" "codex" "aider" "gemini-cli" ];
+              description = "Built-in agent profiles to enable (claude-code, codex, cursor, aider, gemini-cli, opencode, goose, copilot).";
+            };
+
             taintTTLSeconds = lib.mkOption {
               type = lib.types.ints.positive;
               default = 600;
               description = "TTL in seconds for sensitive-read taint tracking.";
             };
 
+            overrideApprovalRequired = lib.mkOption {
+              type = lib.types.bool;
+              default = true;
+              description = ''
+                Park override grants as pending until a human approves them with
+                `agentsmith-override --approve ID` (or `agentsmith-rs ctl approve ID`).
+                Set to false to let validated grants take effect immediately.
+              '';
+            };
+
+            overrideApprovalTimeoutSeconds = lib.mkOption {
+              type = lib.types.ints.between 1 1800;
+              default = 300;
+              description = "How long a pending override waits for a decision before it fails.";
+            };
+
+            overridePolicy = lib.mkOption {
+              type = lib.types.attrs;
+              default = {};
+              example = {
+                max_minutes = 15;
+                never_override = [ "/Users/me/work/**/*.pem" ];
+                zones = [ { zone = "/Users/me/work"; max_minutes = 10; min_depth = 2; } ];
+                auto_approve = [ { name = "build-output"; paths = [ "/Users/me/work/*/target" ]; inside_repo = true; } ];
+              };
+              description = ''
+                Override governance rules (`override_policy` in policy.json): tighter
+                TTL and rate limits, paths that can never be overridden, per-zone
+                limits and auto-approve rules for low-risk grants.
+              '';
+            };
+
+            logRotation = lib.mkOption {
+              type = lib.types.attrs;
+              default = {};
+              example = {
+                max_segment_bytes = 4194304;
+                keep_segments = 20;
+                keep_days = 30;
+                compress = true;
+              };
+              description = ''
+                Guard log rotation (`log_rotation` in policy.json): segment size,
+                retention by count or age, and gzip of rotated segments.
+              '';
+            };
+
             autoProtectHomeDigitChildrenDefault = lib.mkOption {
               type = lib.types.bool;
               default = true;
@@ -169,7 +257,16 @@
               readGateEnabledJson = if cfg.readGateEnabled then "true" else "false";
               transferGateEnabledJson = if cfg.transferGateEnabled then "true" else "false";
               execGateEnabledJson = if cfg.execGateEnabled then "true" else "false";
+              metadataGateEnabledJson = if cfg.metadataGateEnabled then "true" else "false";
+              truncateGateEnabledJson = if cfg.truncateGateEnabled then "true" else "false";
+              truncateGateZonesJson = builtins.toJSON cfg.truncateGateZones;
+              truncateGateMinBytesJson = builtins.toJSON cfg.truncateGateMinBytes;
+              aiAgentProfilesJson = builtins.toJSON cfg.aiAgentProfiles;
               taintTTLSecondsJson = builtins.toJSON cfg.taintTTLSeconds;
+              overrideApprovalRequiredJson = if cfg.overrideApprovalRequired then "true" else "false";
+              overrideApprovalTimeoutSecondsJson = builtins.toJSON cfg.overrideApprovalTimeoutSeconds;
+              overridePolicyJson = builtins.toJSON cfg.overridePolicy;
+              logRotationJson = builtins.toJSON cfg.logRotation;
               autoProtectHomeDigitChildrenDefaultJson =
                 if cfg.autoProtectHomeDigitChildrenDefault then "true" else "false";
             in ''
@@ -199,6 +296,10 @@
                 chmod 755 /usr/local/bin/agentsmith-quarantine
                 cp -f "${cfg.package}/bin/agentsmith-egress" /usr/local/bin/agentsmith-egress
                 chmod 755 /usr/local/bin/agentsmith-egress
+                cp -f "${cfg.package}/bin/agentsmith-run" /usr/local/bin/agentsmith-run
+                chmod 755 /usr/local/bin/agentsmith-run
+                cp -f "${cfg.package}/bin/agentsmith-feedback" /usr/local/bin/agentsmith-feedback
+                chmod 755 /usr/local/bin/agentsmith-feedback
 
                 # Restart daemon so it picks up the freshly signed binary
                 /bin/launchctl kickstart -k system/${daemonLabel} 2>/dev/null || true
@@ -240,21 +341,29 @@
 
               EXISTING_TRUSTED_TOOLS="null"
               EXISTING_AI_PATTERNS="null"
+              EXISTING_AI_DETECTORS="null"
+              EXISTING_AI_MIN_CONFIDENCE="null"
               EXISTING_ALLOW_VCS_META_IN_AI="null"
               EXISTING_ALLOW_TRUSTED_IN_AI="null"
               EXISTING_AUTO_PROTECT_HOME_DIGIT_CHILDREN="null"
               EXISTING_TRUSTED_TOOL_IDENTITIES="null"
               EXISTING_TRUSTED_IDENTITY_REQUIRE_CDHASH="null"
               EXISTING_AUDIT_ONLY_MODE="null"
+              EXISTING_METADATA_GATE_OPS="null"
+              EXISTING_METADATA_GATE_XATTR_ALLOWLIST="null"
               if [ -f "$POLICY_FILE" ]; then
                 EXISTING_TRUSTED_TOOLS=$(${pkgs.jq}/bin/jq -c '.trusted_tools // null' "$POLICY_FILE" 2>/dev/null || echo "null")
                 EXISTING_AI_PATTERNS=$(${pkgs.jq}/bin/jq -c '.ai_agent_patterns // null' "$POLICY_FILE" 2>/dev/null || echo "null")
+                EXISTING_AI_DETECTORS=$(${pkgs.jq}/bin/jq -c '.ai_agent_detectors // null' "$POLICY_FILE" 2>/dev/null || echo "null")
+                EXISTING_AI_MIN_CONFIDENCE=$(${pkgs.jq}/bin/jq -c '.ai_agent_min_confidence // null' "$POLICY_FILE" 2>/dev/null || echo "null")
                 EXISTING_ALLOW_VCS_META_IN_AI=$(${pkgs.jq}/bin/jq -c '.allow_vcs_metadata_in_ai_context // null' "$POLICY_FILE" 2>/dev/null || echo "null")
                 EXISTING_ALLOW_TRUSTED_IN_AI=$(${pkgs.jq}/bin/jq -c '.allow_trusted_tools_in_ai_context // null' "$POLICY_FILE" 2>/dev/null || echo "null")
                 EXISTING_AUTO_PROTECT_HOME_DIGIT_CHILDREN=$(${pkgs.jq}/bin/jq -c '.auto_protect_home_digit_children // null' "$POLICY_FILE" 2>/dev/null || echo "null")
                 EXISTING_TRUSTED_TOOL_IDENTITIES=$(${pkgs.jq}/bin/jq -c '.trusted_tool_identities // null' "$POLICY_FILE" 2>/dev/null || echo "null")
                 EXISTING_TRUSTED_IDENTITY_REQUIRE_CDHASH=$(${pkgs.jq}/bin/jq -c '.trusted_identity_require_cdhash // null' "$POLICY_FILE" 2>/dev/null || echo "null")
                 EXISTING_AUDIT_ONLY_MODE=$(${pkgs.jq}/bin/jq -c '.audit_only_mode // null' "$POLICY_FILE" 2>/dev/null || echo "null")
+                EXISTING_METADATA_GATE_OPS=$(${pkgs.jq}/bin/jq -c '.metadata_gate_ops // null' "$POLICY_FILE" 2>/dev/null || echo "null")
+                EXISTING_METADATA_GATE_XATTR_ALLOWLIST=$(${pkgs.jq}/bin/jq -c '.metadata_gate_xattr_allowlist // null' "$POLICY_FILE" 2>/dev/null || echo "null")
               fi
 
               # Trusted identity bootstrap + top-up:
@@ -342,15 +451,28 @@ PY
                 --argjson readGateEnabled ${readGateEnabledJson} \
                 --argjson transferGateEnabled ${transferGateEnabledJson} \
                 --argjson execGateEnabled ${execGateEnabledJson} \
+                --argjson metadataGateEnabled ${metadataGateEnabledJson} \
+                --argjson truncateGateEnabled ${truncateGateEnabledJson} \
+                --argjson truncateGateZones '${truncateGateZonesJson}' \
+                --argjson truncateGateMinBytes '${truncateGateMinBytesJson}' \
                 --argjson taintTTLSeconds '${taintTTLSecondsJson}' \
+                --argjson overrideApprovalRequired ${overrideApprovalRequiredJson} \
+                --argjson overrideApprovalTimeoutSeconds '${overrideApprovalTimeoutSecondsJson}' \
+                --argjson overridePolicy '${overridePolicyJson}' \
+                --argjson logRotation '${logRotationJson}' \
                 --argjson trustedTools "$EXISTING_TRUSTED_TOOLS" \
                 --argjson aiPatterns "$EXISTING_AI_PATTERNS" \
+                --argjson aiDetectors "$EXISTING_AI_DETECTORS" \
+                --argjson aiMinConfidence "$EXISTING_AI_MIN_CONFIDENCE" \
+                --argjson aiAgentProfiles '${aiAgentProfilesJson}' \
                 --argjson allowVcsMetaInAi "$EXISTING_ALLOW_VCS_META_IN_AI" \
                 --argjson allowTrustedInAi "$EXISTING_ALLOW_TRUSTED_IN_AI" \
                 --argjson autoProtectHomeDigitChildren "$EXISTING_AUTO_PROTECT_HOME_DIGIT_CHILDREN" \
                 --argjson trustedToolIdentities "$MERGED_TRUSTED_TOOL_IDENTITIES" \
                 --argjson trustedIdentityRequireCdhash "$EXISTING_TRUSTED_IDENTITY_REQUIRE_CDHASH" \
                 --argjson auditOnlyMode "$EXISTING_AUDIT_ONLY_MODE" \
+                --argjson metadataGateOps "$EXISTING_METADATA_GATE_OPS" \
+                --argjson metadataGateXattrAllowlist "$EXISTING_METADATA_GATE_XATTR_ALLOWLIST" \
                 --argjson autoProtectHomeDigitChildrenDefault ${autoProtectHomeDigitChildrenDefaultJson} \
                 '({protected_zones: $zones, temporary_overrides: []}
                   + {sensitive_zones: $sensitiveZones}
@@ -359,7 +481,15 @@ PY
                   + {read_gate_enabled: $readGateEnabled}
                   + {transfer_gate_enabled: $transferGateEnabled}
                   + {exec_gate_enabled: $execGateEnabled}
+                  + {metadata_gate_enabled: $metadataGateEnabled}
+                  + {truncate_gate_enabled: $truncateGateEnabled}
+                  + {truncate_gate_zones: $truncateGateZones}
+                  + {truncate_gate_min_bytes: $truncateGateMinBytes}
                   + {taint_ttl_seconds: $taintTTLSeconds}
+                  + {override_approval_required: $overrideApprovalRequired}
+                  + {override_approval_timeout_seconds: $overrideApprovalTimeoutSeconds}
+                  + {override_policy: $overridePolicy}
+                  + {log_rotation: $logRotation}
                   + {auto_protect_home_digit_children: (
                       if $autoProtectHomeDigitChildren == null
                       then $autoProtectHomeDigitChildrenDefault
@@ -369,10 +499,15 @@ PY
                   + (if $trustedTools == null then {} else {trusted_tools: $trustedTools} end)
                   + (if $trustedToolIdentities == null then {} else {trusted_tool_identities: $trustedToolIdentities} end)
                   + (if $aiPatterns == null then {} else {ai_agent_patterns: $aiPatterns} end)
+                  + (if $aiDetectors == null then {} else {ai_agent_detectors: $aiDetectors} end)
+                  + (if $aiMinConfidence == null then {} else {ai_agent_min_confidence: $aiMinConfidence} end)
+                  + {ai_agent_profiles: $aiAgentProfiles}
                   + (if $allowVcsMetaInAi == null then {} else {allow_vcs_metadata_in_ai_context: $allowVcsMetaInAi} end)
                   + (if $allowTrustedInAi == null then {} else {allow_trusted_tools_in_ai_context: $allowTrustedInAi} end)
                   + (if $trustedIdentityRequireCdhash == null then {} else {trusted_identity_require_cdhash: $trustedIdentityRequireCdhash} end)
-                  + (if $auditOnlyMode == null then {} else {audit_only_mode: $auditOnlyMode} end))' \
+                  + (if $auditOnlyMode == null then {} else {audit_only_mode: $auditOnlyMode} end)
+                  + (if $metadataGateOps == null then {} else {metadata_gate_ops: $metadataGateOps} end)
+                  + (if $metadataGateXattrAllowlist == null then {} else {metadata_gate_xattr_allowlist: $metadataGateXattrAllowlist} end))' \
                 > "$POLICY_FILE"
               chown ${cfg.user}:staff "$POLICY_FILE"
               echo "agentsmith-rs: policy synced ($(echo '${protectedZonesJson}' | ${pkgs.jq}/bin/jq length) zones)"